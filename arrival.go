@@ -0,0 +1,111 @@
+package stadiacontroller
+
+import (
+	"fmt"
+	"sync"
+	"unsafe"
+
+	"golang.org/x/sys/windows"
+)
+
+var (
+	procCMRegisterNotification   = cfgmgr32.NewProc("CM_Register_Notification")
+	procCMUnregisterNotification = cfgmgr32.NewProc("CM_Unregister_Notification")
+)
+
+const (
+	cmNotifyFilterTypeDeviceInterface     = 0
+	cmNotifyActionDeviceInterfaceArrival  = 0
+	cmNotifyFilterDeviceInstanceIDLength  = 200
+	cmNotifyFilterDeviceInterfaceReserved = cmNotifyFilterDeviceInstanceIDLength*2 - 16
+)
+
+// GUID_DEVINTERFACE_HID.
+var hidInterfaceGUID = windows.GUID{0x4d1e55b2, 0xf16f, 0x11cf, [8]byte{0x88, 0xcb, 0x00, 0x11, 0x11, 0x00, 0x00, 0x30}}
+
+// cmNotifyFilter is the CM_NOTIFY_FILTER structure with its DeviceInterface
+// member, padded to the size of its largest member, DeviceInstance.
+type cmNotifyFilter struct {
+	size       uint32
+	flags      uint32
+	filterType uint32
+	reserved   uint32
+	classGUID  windows.GUID
+	_          [cmNotifyFilterDeviceInterfaceReserved]byte
+}
+
+// Functions called when a HID device arrives, by registration. Notification
+// callbacks are given the key of their function rather than a Go pointer, and
+// share a single callback since only a limited number can be created.
+var (
+	arrivalsMu       sync.Mutex
+	arrivals         map[uintptr]func()
+	nextArrival      uintptr
+	arrivalCallback  uintptr
+	arrivalsCallback sync.Once
+)
+
+// deviceArrivals calls a function whenever a HID device arrives.
+type deviceArrivals struct {
+	key          uintptr
+	notification uintptr
+}
+
+// watchDeviceArrivals calls f, on another goroutine, whenever a HID device
+// arrives, until the returned deviceArrivals is closed.
+func watchDeviceArrivals(f func()) (*deviceArrivals, error) {
+	arrivalsCallback.Do(func() {
+		arrivals = map[uintptr]func(){}
+		arrivalCallback = windows.NewCallback(onDeviceArrival)
+	})
+
+	arrivalsMu.Lock()
+	nextArrival++
+	key := nextArrival
+	arrivals[key] = f
+	arrivalsMu.Unlock()
+
+	filter := cmNotifyFilter{
+		filterType: cmNotifyFilterTypeDeviceInterface,
+		classGUID:  hidInterfaceGUID,
+	}
+	filter.size = uint32(unsafe.Sizeof(filter))
+
+	var notification uintptr
+
+	if r, _, _ := procCMRegisterNotification.Call(uintptr(unsafe.Pointer(&filter)), key, arrivalCallback, uintptr(unsafe.Pointer(&notification))); r != crSuccess {
+		arrivalsMu.Lock()
+		delete(arrivals, key)
+		arrivalsMu.Unlock()
+
+		return nil, fmt.Errorf("cannot register for device arrivals: CONFIGRET %#x", r)
+	}
+
+	return &deviceArrivals{key: key, notification: notification}, nil
+}
+
+func onDeviceArrival(notification, context, action, data, size uintptr) uintptr {
+	if action != cmNotifyActionDeviceInterfaceArrival {
+		return 0
+	}
+
+	arrivalsMu.Lock()
+	f := arrivals[context]
+	arrivalsMu.Unlock()
+
+	if f != nil {
+		go f()
+	}
+
+	return 0
+}
+
+// Close stops watching device arrivals. It waits for the pending callbacks to
+// return.
+func (a *deviceArrivals) Close() {
+	procCMUnregisterNotification.Call(a.notification)
+
+	arrivalsMu.Lock()
+	delete(arrivals, a.key)
+	arrivalsMu.Unlock()
+}
@@ -0,0 +1,33 @@
+package stadiacontroller
+
+// A Backend finds and opens HID devices on behalf of a StadiaController. The
+// default backend uses the Windows HID API, and another one can be given in
+// StadiaControllerOptions, e.g. to drive a controller with scripted reports
+// rather than a physical device.
+type Backend interface {
+	// Devices returns all connected HID devices.
+	Devices() ([]*DeviceInfo, error)
+
+	// ByPath returns the connected device with the given path.
+	ByPath(path string) (*DeviceInfo, error)
+
+	// Open opens the given device.
+	Open(info *DeviceInfo) (Device, error)
+}
+
+// HIDBackend is the Backend which uses the Windows HID API.
+var HIDBackend Backend = hidBackend{}
+
+type hidBackend struct{}
+
+func (hidBackend) Devices() ([]*DeviceInfo, error) {
+	return Devices()
+}
+
+func (hidBackend) ByPath(path string) (*DeviceInfo, error) {
+	return ByPath(path)
+}
+
+func (hidBackend) Open(info *DeviceInfo) (Device, error) {
+	return info.Open()
+}
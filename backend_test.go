@@ -0,0 +1,395 @@
+package stadiacontroller
+
+import (
+	"errors"
+	"sync"
+	"syscall"
+	"testing"
+	"time"
+)
+
+// fakeBackend is a Backend whose devices are plugged, unplugged and fed
+// reports by tests.
+type fakeBackend struct {
+	mu       sync.Mutex
+	devices  []*fakeDevice
+	openErrs map[string]error
+}
+
+func (b *fakeBackend) Devices() ([]*DeviceInfo, error) {
+	b.mu.Lock()
+	defer b.mu.Unlock()
+
+	var devices []*DeviceInfo
+
+	for _, device := range b.devices {
+		info := *device.info
+		devices = append(devices, &info)
+	}
+
+	return devices, nil
+}
+
+func (b *fakeBackend) ByPath(path string) (*DeviceInfo, error) {
+	devices, _ := b.Devices()
+
+	for _, device := range devices {
+		if device.Path == path {
+			return device, nil
+		}
+	}
+
+	return nil, &IOError{"open", syscall.Errno(2)}
+}
+
+func (b *fakeBackend) Open(info *DeviceInfo) (Device, error) {
+	b.mu.Lock()
+	defer b.mu.Unlock()
+
+	if err := b.openErrs[info.Path]; err != nil {
+		delete(b.openErrs, info.Path)
+		return nil, &IOError{"open", err}
+	}
+
+	for _, device := range b.devices {
+		if device.info.Path == info.Path {
+			return device.open(), nil
+		}
+	}
+
+	return nil, &IOError{"open", syscall.Errno(2)}
+}
+
+// plug connects the given device, and makes the controller notice it like a
+// device arrival would.
+func (b *fakeBackend) plug(c *StadiaController, device *fakeDevice) {
+	b.mu.Lock()
+	b.devices = append(b.devices, device)
+	b.mu.Unlock()
+
+	if c != nil {
+		c.retry()
+	}
+}
+
+// unplug disconnects the given device, which fails its pending reads.
+func (b *fakeBackend) unplug(device *fakeDevice) {
+	b.mu.Lock()
+	defer b.mu.Unlock()
+
+	for i, d := range b.devices {
+		if d == device {
+			b.devices = append(b.devices[:i], b.devices[i+1:]...)
+			close(device.unplugged)
+			return
+		}
+	}
+}
+
+// fakeDevice is a physical device of a fakeBackend, which may be opened
+// several times.
+type fakeDevice struct {
+	info      *DeviceInfo
+	reports   chan []byte
+	unplugged chan struct{}
+
+	// readBufs, if not zero, makes the handles of the device read reports
+	// into that many reused buffers, sent on a channel holding two less, like
+	// the handles of HIDBackend. Otherwise, reports are sent as given on an
+	// unbuffered channel.
+	readBufs int
+
+	mu     sync.Mutex
+	writes [][]byte
+	opened int
+	closed int
+}
+
+func newFakeDevice(path, serial string) *fakeDevice {
+	return &fakeDevice{
+		info: &DeviceInfo{
+			Path:              path,
+			VendorID:          stadiaControllerVid,
+			ProductID:         stadiaControllerPid,
+			SerialNumber:      serial,
+			InputReportLength: 10,
+		},
+		reports:   make(chan []byte),
+		unplugged: make(chan struct{}),
+	}
+}
+
+// send sends the given report through the open handle of the device, and
+// returns once it is read from the handle.
+func (d *fakeDevice) send(report []byte) {
+	d.reports <- report
+}
+
+func (d *fakeDevice) written() [][]byte {
+	d.mu.Lock()
+	defer d.mu.Unlock()
+
+	return append([][]byte(nil), d.writes...)
+}
+
+func (d *fakeDevice) openCount() (opened, closed int) {
+	d.mu.Lock()
+	defer d.mu.Unlock()
+
+	return d.opened, d.closed
+}
+
+func (d *fakeDevice) open() *fakeHandle {
+	d.mu.Lock()
+	d.opened++
+	d.mu.Unlock()
+
+	h := &fakeHandle{
+		device: d,
+		readCh: make(chan []byte),
+		closed: make(chan struct{}),
+	}
+
+	if d.readBufs != 0 {
+		h.readCh = make(chan []byte, d.readBufs-2)
+		h.readBufs = make([][]byte, d.readBufs)
+	}
+
+	go h.readLoop()
+
+	return h
+}
+
+// fakeHandle is an open fakeDevice, implementing Device.
+type fakeHandle struct {
+	device    *fakeDevice
+	readCh    chan []byte
+	readBufs  [][]byte
+	readErr   error
+	closeOnce sync.Once
+	closed    chan struct{}
+}
+
+func (h *fakeHandle) readLoop() {
+	defer close(h.readCh)
+
+	for i := 0; ; i++ {
+		select {
+		case report := <-h.device.reports:
+			if len(h.readBufs) != 0 {
+				buf := &h.readBufs[i%len(h.readBufs)]
+				*buf = append((*buf)[:0], report...)
+				report = *buf
+			}
+
+			select {
+			case h.readCh <- report:
+			case <-h.closed:
+				h.readErr = errors.New("hid: device closed")
+				return
+			}
+		case <-h.device.unplugged:
+			h.readErr = &IOError{"read", syscall.Errno(1167)}
+			return
+		case <-h.closed:
+			h.readErr = errors.New("hid: device closed")
+			return
+		}
+	}
+}
+
+func (h *fakeHandle) Close() {
+	h.closeOnce.Do(func() {
+		h.device.mu.Lock()
+		h.device.closed++
+		h.device.mu.Unlock()
+
+		close(h.closed)
+	})
+}
+
+func (h *fakeHandle) Write(data []byte) error {
+	select {
+	case <-h.device.unplugged:
+		return &IOError{"write", syscall.Errno(1167)}
+	default:
+	}
+
+	h.device.mu.Lock()
+	h.device.writes = append(h.device.writes, append([]byte(nil), data...))
+	h.device.mu.Unlock()
+
+	return nil
+}
+
+func (h *fakeHandle) ReadCh() <-chan []byte {
+	return h.readCh
+}
+
+func (h *fakeHandle) ReadError() error {
+	return h.readErr
+}
+
+func (h *fakeHandle) Flush() error {
+	return nil
+}
+
+// newFakeController returns a controller using the given backend, retrying
+// quickly after failures.
+func newFakeController(t *testing.T, backend *fakeBackend, options StadiaControllerOptions) *StadiaController {
+	options.Backend = backend
+	options.ReconnectInterval = 10 * time.Millisecond
+
+	c, err := NewStadiaControllerWithOptions(options)
+
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	return c
+}
+
+// waitUntil fails the test if cond is still false after a few seconds.
+func waitUntil(t *testing.T, what string, cond func() bool) {
+	t.Helper()
+
+	for deadline := time.Now().Add(5 * time.Second); !cond(); {
+		if time.Now().After(deadline) {
+			t.Fatalf("timed out waiting until %s", what)
+		}
+
+		time.Sleep(time.Millisecond)
+	}
+}
+
+// nextEvent returns the next event received on events, failing the test if
+// none is received within a few seconds.
+func nextEvent(t *testing.T, events <-chan ControllerEvent) ControllerEvent {
+	t.Helper()
+
+	select {
+	case event := <-events:
+		return event
+	case <-time.After(5 * time.Second):
+		t.Fatalf("timed out waiting for an event")
+		return nil
+	}
+}
+
+// acquired returns whether the controller acquired the given device.
+func acquired(c *StadiaController, device *fakeDevice) func() bool {
+	return func() bool {
+		info := c.Info()
+		return info != nil && info.Path == device.info.Path
+	}
+}
+
+func TestControllerOpen(t *testing.T) {
+	other := newFakeDevice(`\\?\hid#vid_045e&pid_028e#1`, "")
+	other.info.VendorID, other.info.ProductID = 0x045e, 0x028e
+	stadia := newFakeDevice(`\\?\hid#vid_18d1&pid_9400#1`, "1A2B")
+
+	backend := &fakeBackend{}
+	c := newFakeController(t, backend, StadiaControllerOptions{})
+	defer c.Close()
+
+	if _, err := c.GetReport(); !errors.Is(err, RetryError) || !errors.Is(err, ErrDeviceNotFound) {
+		t.Errorf("GetReport without device returned %v", err)
+	}
+
+	backend.plug(c, other)
+	backend.plug(c, stadia)
+	waitUntil(t, "the controller is acquired", acquired(c, stadia))
+
+	if opened, _ := other.openCount(); opened != 0 {
+		t.Errorf("device of another vendor was opened")
+	}
+	if info := c.Info(); info.SerialNumber != "1A2B" {
+		t.Errorf("Info returned serial %q", info.SerialNumber)
+	}
+
+	go stadia.send([]byte{0x03, 0x08, 0x20, 0x00, 0x80, 0x80, 0x80, 0x80, 0x00, 0xff})
+
+	report, err := c.GetReport()
+
+	if err != nil {
+		t.Fatal(err)
+	}
+	if report.Buttons != StadiaButtonMenu || report.GetRightTrigger() != 0xff {
+		t.Errorf("GetReport returned buttons %q and right trigger %d", report.Buttons, report.GetRightTrigger())
+	}
+
+	if err := c.Vibrate(0x12, 0x34); err != nil {
+		t.Fatal(err)
+	}
+	if writes := stadia.written(); len(writes) != 1 || writes[0][0] != 0x05 {
+		t.Errorf("Vibrate wrote %x", writes)
+	}
+}
+
+func TestControllerOpenFailure(t *testing.T) {
+	device := newFakeDevice(`\\?\hid#vid_18d1&pid_9400#1`, "")
+
+	backend := &fakeBackend{openErrs: map[string]error{device.info.Path: syscall.Errno(32)}}
+	c := newFakeController(t, backend, StadiaControllerOptions{})
+	defer c.Close()
+
+	events := c.Events()
+	backend.plug(c, device)
+
+	// The sharing violation is reported, and opening the device is tried
+	// again without any arrival.
+	event := nextEvent(t, events)
+
+	if err, ok := event.(ErrorEvent); !ok || !errors.Is(err.Err, ErrAccessDenied) || !errors.Is(err.Err, RetryError) {
+		t.Fatalf("received %#v, expected an ErrorEvent of ErrAccessDenied", event)
+	}
+
+	if event, ok := nextEvent(t, events).(ConnectedEvent); !ok || event.Path != device.info.Path {
+		t.Fatalf("received %#v, expected a ConnectedEvent", event)
+	}
+}
+
+func TestControllerDisconnect(t *testing.T) {
+	first := newFakeDevice(`\\?\hid#vid_18d1&pid_9400#1`, "first")
+	second := newFakeDevice(`\\?\hid#vid_18d1&pid_9400#2`, "second")
+
+	backend := &fakeBackend{}
+	backend.plug(nil, first)
+
+	c := newFakeController(t, backend, StadiaControllerOptions{})
+	defer c.Close()
+
+	waitUntil(t, "the first controller is acquired", acquired(c, first))
+	backend.unplug(first)
+
+	if _, err := c.GetReport(); !errors.Is(err, RetryError) || !errors.Is(err, ErrDeviceDisconnected) {
+		t.Errorf("GetReport after disconnection returned %v", err)
+	}
+	if opened, closed := first.openCount(); opened != 1 || closed != 1 {
+		t.Errorf("disconnected device was opened %d times and closed %d times", opened, closed)
+	}
+	if info := c.Info(); info != nil {
+		t.Errorf("disconnected device %s is still acquired", info.Path)
+	}
+	if err := c.Vibrate(0xff, 0xff); !errors.Is(err, ErrDeviceNotFound) {
+		t.Errorf("Vibrate without device returned %v", err)
+	}
+
+	backend.plug(c, second)
+	waitUntil(t, "the second controller is acquired", acquired(c, second))
+
+	go second.send([]byte{0x03, 0x04, 0x00, 0x40, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00})
+
+	if report, err := c.GetReport(); err != nil || report.Buttons != StadiaButtonA|StadiaButtonDpadDown {
+		t.Errorf("GetReport returned %q, %v", report.Buttons, err)
+	}
+
+	// Releasing the controller without disconnecting it acquires it again.
+	c.Release()
+	waitUntil(t, "the second controller is acquired again", acquired(c, second))
+
+	if opened, closed := second.openCount(); opened != 2 || closed != 1 {
+		t.Errorf("released device was opened %d times and closed %d times", opened, closed)
+	}
+}
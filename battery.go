@@ -0,0 +1,112 @@
+package stadiacontroller
+
+import (
+	"fmt"
+	"unsafe"
+
+	"golang.org/x/sys/windows"
+)
+
+// BatteryStatus is the state of the battery of a controller.
+type BatteryStatus struct {
+	// Known is false if the battery of the controller cannot be read, e.g.
+	// because it is connected in a way which does not report it. Other fields
+	// are then meaningless.
+	Known bool
+
+	// Level is the remaining charge, in percent.
+	Level uint8
+
+	// Charging is true if the battery is known to be charging.
+	Charging bool
+}
+
+// String returns a description of the status such as "85%", "85%, charging"
+// or "unknown".
+func (s BatteryStatus) String() string {
+	switch {
+	case !s.Known:
+		return "unknown"
+	case s.Charging:
+		return fmt.Sprintf("%d%%, charging", s.Level)
+	default:
+		return fmt.Sprintf("%d%%", s.Level)
+	}
+}
+
+// A batterySource reads the battery status of the device at the given path in
+// one of the ways the controller may report it, returning false if it does
+// not apply to the device.
+type batterySource func(path string) (BatteryStatus, bool)
+
+// Sources tried in order by ReadBattery. Support for other firmware or
+// transports is added here.
+var batterySources = []batterySource{
+	bluetoothBattery,
+}
+
+// ReadBattery returns the battery status of the controller whose device is at
+// the given path (as given by ConnectedEvent). The status is unknown if the
+// controller does not report it through any supported means; this is not an
+// error.
+func ReadBattery(path string) BatteryStatus {
+	for _, source := range batterySources {
+		if status, ok := source(path); ok {
+			return status
+		}
+	}
+
+	return BatteryStatus{}
+}
+
+// Battery level set by Windows on Bluetooth devices which implement the GATT
+// battery service, as displayed in the Bluetooth settings.
+var devpkeyBluetoothBatteryLevel = devPropKey{windows.GUID{0x104ea319, 0x6ee2, 0x4701, [8]byte{0xbd, 0x47, 0x8d, 0xdb, 0xf4, 0x25, 0xbb, 0xe5}}, 2}
+
+// Number of ancestors of the HID device searched for the battery level: the
+// HID device of a Bluetooth LE controller is a child of its HID over GATT
+// service, itself a child of the Bluetooth device.
+const maxBatteryAncestors = 3
+
+// bluetoothBattery reads the battery level that Windows reports for
+// controllers connected over Bluetooth. It does not apply to controllers
+// connected over USB, and does not report charging.
+func bluetoothBattery(path string) (BatteryStatus, bool) {
+	if (&DeviceInfo{Path: path}).Transport() != TransportBluetooth {
+		return BatteryStatus{}, false
+	}
+
+	devInst, err := devNode(path)
+
+	if err != nil {
+		return BatteryStatus{}, false
+	}
+
+	for i := 0; i <= maxBatteryAncestors; i++ {
+		var level byte
+		size := uint32(1)
+		var propType uint32
+
+		r, _, _ := procCMGetDevNodePropertyW.Call(
+			uintptr(devInst),
+			uintptr(unsafe.Pointer(&devpkeyBluetoothBatteryLevel)),
+			uintptr(unsafe.Pointer(&propType)),
+			uintptr(unsafe.Pointer(&level)),
+			uintptr(unsafe.Pointer(&size)),
+			0)
+
+		if r == crSuccess && propType == devPropTypeByte && level <= 100 {
+			return BatteryStatus{Known: true, Level: level}, true
+		}
+
+		var parent uint32
+
+		if r, _, _ := procCMGetParent.Call(uintptr(unsafe.Pointer(&parent)), uintptr(devInst), 0); r != crSuccess {
+			break
+		}
+
+		devInst = parent
+	}
+
+	return BatteryStatus{}, false
+}
@@ -0,0 +1,131 @@
+package stadiacontroller
+
+import (
+	"fmt"
+	"strings"
+)
+
+// StadiaButtons is a set of buttons of the Stadia controller, named after the
+// labels printed on the controller rather than after their Xbox 360
+// equivalents.
+type StadiaButtons uint32
+
+// Buttons of the Stadia controller.
+const (
+	StadiaButtonA StadiaButtons = 1 << iota
+	StadiaButtonB
+	StadiaButtonX
+	StadiaButtonY
+	StadiaButtonL1
+	StadiaButtonR1
+	StadiaButtonL3
+	StadiaButtonR3
+	StadiaButtonOptions
+	StadiaButtonMenu
+	StadiaButtonStadia
+	StadiaButtonAssistant
+	StadiaButtonCapture
+	StadiaButtonDpadUp
+	StadiaButtonDpadDown
+	StadiaButtonDpadLeft
+	StadiaButtonDpadRight
+
+	// Buttons of a headset plugged into the controller.
+	StadiaButtonVolumeUp
+	StadiaButtonVolumeDown
+)
+
+var stadiaButtonNames = [...]struct {
+	button StadiaButtons
+	name   string
+}{
+	{StadiaButtonA, "a"},
+	{StadiaButtonB, "b"},
+	{StadiaButtonX, "x"},
+	{StadiaButtonY, "y"},
+	{StadiaButtonL1, "l1"},
+	{StadiaButtonR1, "r1"},
+	{StadiaButtonL3, "l3"},
+	{StadiaButtonR3, "r3"},
+	{StadiaButtonOptions, "options"},
+	{StadiaButtonMenu, "menu"},
+	{StadiaButtonStadia, "stadia"},
+	{StadiaButtonAssistant, "assistant"},
+	{StadiaButtonCapture, "capture"},
+	{StadiaButtonDpadUp, "dpad_up"},
+	{StadiaButtonDpadDown, "dpad_down"},
+	{StadiaButtonDpadLeft, "dpad_left"},
+	{StadiaButtonDpadRight, "dpad_right"},
+	{StadiaButtonVolumeUp, "volume_up"},
+	{StadiaButtonVolumeDown, "volume_down"},
+}
+
+// Has returns whether all the given buttons are in the set.
+func (b StadiaButtons) Has(buttons StadiaButtons) bool {
+	return b&buttons == buttons
+}
+
+// String returns the names of the buttons in the set, separated by "|".
+func (b StadiaButtons) String() string {
+	var names []string
+
+	for _, button := range stadiaButtonNames {
+		if b&button.button != 0 {
+			names = append(names, button.name)
+		}
+	}
+
+	return strings.Join(names, "|")
+}
+
+// ParseStadiaButtons parses a set of buttons given by their names (as returned
+// by String) separated by "|", "," or "+". Names are case insensitive.
+func ParseStadiaButtons(s string) (StadiaButtons, error) {
+	var buttons StadiaButtons
+
+	names := strings.FieldsFunc(s, func(r rune) bool {
+		return r == '|' || r == ',' || r == '+'
+	})
+
+	for _, name := range names {
+		button, err := parseStadiaButton(strings.TrimSpace(name))
+
+		if err != nil {
+			return 0, err
+		}
+
+		buttons |= button
+	}
+
+	return buttons, nil
+}
+
+func parseStadiaButton(name string) (StadiaButtons, error) {
+	for _, button := range stadiaButtonNames {
+		if strings.EqualFold(button.name, name) {
+			return button.button, nil
+		}
+	}
+
+	return 0, fmt.Errorf("unknown Stadia button %q", name)
+}
+
+// MarshalText implements encoding.TextMarshaler, encoding the set as returned
+// by String.
+func (b StadiaButtons) MarshalText() ([]byte, error) {
+	return []byte(b.String()), nil
+}
+
+// UnmarshalText implements encoding.TextUnmarshaler, decoding the set as
+// ParseStadiaButtons.
+func (b *StadiaButtons) UnmarshalText(text []byte) error {
+	buttons, err := ParseStadiaButtons(string(text))
+
+	if err != nil {
+		return err
+	}
+
+	*b = buttons
+
+	return nil
+}
@@ -0,0 +1,138 @@
+package stadiacontroller
+
+import "testing"
+
+// Button set by each bit of the input report, by byte offset and bit.
+var reportButtonBits = map[[2]int]StadiaButtons{
+	{2, 0}:  StadiaButtonCapture,
+	{2, 1}:  StadiaButtonAssistant,
+	{2, 4}:  StadiaButtonStadia,
+	{2, 5}:  StadiaButtonMenu,
+	{2, 6}:  StadiaButtonOptions,
+	{2, 7}:  StadiaButtonR3,
+	{3, 0}:  StadiaButtonL3,
+	{3, 1}:  StadiaButtonR1,
+	{3, 2}:  StadiaButtonL1,
+	{3, 3}:  StadiaButtonY,
+	{3, 4}:  StadiaButtonX,
+	{3, 5}:  StadiaButtonB,
+	{3, 6}:  StadiaButtonA,
+	{10, 0}: StadiaButtonVolumeUp,
+	{10, 1}: StadiaButtonVolumeDown,
+}
+
+// releasedReport returns an input report with no button pressed, a released
+// D-pad, centered sticks and released triggers.
+func releasedReport() []byte {
+	return []byte{0x03, 0x08, 0x00, 0x00, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00, 0x00}
+}
+
+func TestParseReportButtonBits(t *testing.T) {
+	for offset := 1; offset < len(releasedReport()); offset++ {
+		for bit := 0; bit < 8; bit++ {
+			data := releasedReport()
+			data[offset] |= 1 << bit
+
+			var report Xbox360ControllerReport
+
+			if err := ParseReport(data, &report); err != nil {
+				t.Errorf("bit %d of offset %d: %v", bit, offset, err)
+				continue
+			}
+
+			expected := reportButtonBits[[2]int{offset, bit}]
+
+			if report.Buttons != expected {
+				t.Errorf("bit %d of offset %d sets %q, expected %q", bit, offset, report.Buttons, expected)
+			}
+			if buttons := report.GetButtons(); buttons != expected.Xbox360Buttons() {
+				t.Errorf("bit %d of offset %d sets Xbox 360 buttons %016b, expected %016b", bit, offset, buttons, expected.Xbox360Buttons())
+			}
+			if report.Capture != (expected == StadiaButtonCapture) || report.Assistant != (expected == StadiaButtonAssistant) {
+				t.Errorf("bit %d of offset %d sets Capture %v and Assistant %v", bit, offset, report.Capture, report.Assistant)
+			}
+		}
+	}
+}
+
+func TestParseReportHat(t *testing.T) {
+	directions := []StadiaButtons{
+		StadiaButtonDpadUp,
+		StadiaButtonDpadUp | StadiaButtonDpadRight,
+		StadiaButtonDpadRight,
+		StadiaButtonDpadDown | StadiaButtonDpadRight,
+		StadiaButtonDpadDown,
+		StadiaButtonDpadDown | StadiaButtonDpadLeft,
+		StadiaButtonDpadLeft,
+		StadiaButtonDpadUp | StadiaButtonDpadLeft,
+	}
+
+	for value := 0; value < 256; value++ {
+		data := releasedReport()
+		data[1] = byte(value)
+
+		var report Xbox360ControllerReport
+
+		if err := ParseReport(data, &report); err != nil {
+			t.Errorf("D-pad %d: %v", value, err)
+			continue
+		}
+
+		var expected StadiaButtons
+
+		if value < len(directions) {
+			expected = directions[value]
+		}
+
+		if report.Buttons != expected {
+			t.Errorf("D-pad %d sets %q, expected %q", value, report.Buttons, expected)
+		}
+	}
+}
+
+func TestParseReportReplacesButtons(t *testing.T) {
+	report := Xbox360ControllerReport{Buttons: StadiaButtonA, Capture: true}
+	report.SetButton(Xbox360ControllerButtonA)
+
+	if err := ParseReport(releasedReport(), &report); err != nil {
+		t.Fatal(err)
+	}
+
+	if report.Buttons != 0 || report.Capture {
+		t.Errorf("ParseReport kept buttons %q and Capture %v", report.Buttons, report.Capture)
+	}
+	if report.GetButtons() != 1<<Xbox360ControllerButtonA {
+		t.Errorf("ParseReport did not keep the Xbox 360 buttons already set")
+	}
+}
+
+func TestStadiaButtonNames(t *testing.T) {
+	var all StadiaButtons
+
+	for _, button := range stadiaButtonNames {
+		if all&button.button != 0 {
+			t.Errorf("%s is named twice", button.name)
+		}
+
+		all |= button.button
+
+		parsed, err := ParseStadiaButtons(button.button.String())
+
+		if err != nil || parsed != button.button {
+			t.Errorf("ParseStadiaButtons(%q) = %q, %v", button.button.String(), parsed, err)
+		}
+	}
+
+	if all != StadiaButtonVolumeDown<<1-1 {
+		t.Errorf("buttons %q have no name", ^all&(StadiaButtonVolumeDown<<1-1))
+	}
+
+	parsed, err := ParseStadiaButtons("A + dpad_up, Stadia")
+
+	if err != nil || parsed != StadiaButtonA|StadiaButtonDpadUp|StadiaButtonStadia {
+		t.Errorf("ParseStadiaButtons(%q) = %q, %v", "A + dpad_up, Stadia", parsed, err)
+	}
+	if _, err := ParseStadiaButtons("a|start"); err == nil {
+		t.Errorf("ParseStadiaButtons(%q) did not fail", "a|start")
+	}
+}
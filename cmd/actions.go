@@ -0,0 +1,232 @@
+package main
+
+import (
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// pressAction runs the commands and presses the keys of Capture or Assistant,
+// which follow their debounced state.
+type pressAction struct {
+	button     stadiacontroller.StadiaButtons
+	debounce   buttonDebounce
+	keys       *keyChord
+	commands   *pressCommands
+	onPressed  string
+	onReleased string
+
+	// current is the debounced state in the last report, and pressed the
+	// state of the commands and keys.
+	current, pressed bool
+}
+
+// update runs the commands and presses the keys of the button if its debounced
+// state changed.
+func (p *pressAction) update(env func(button stadiacontroller.StadiaButtons, pressed bool) []string) error {
+	if p.current == p.pressed {
+		return nil
+	}
+
+	p.pressed = p.current
+
+	if p.keys != nil {
+		p.keys.set(p.pressed)
+	}
+	if p.commands != nil {
+		return p.commands.update(p.pressed, env(p.button, p.pressed))
+	}
+
+	return runButtonPress(p.pressed, p.onPressed, p.onReleased, env(p.button, p.pressed)...)
+}
+
+// reset forgets the presses of the button when the controller is
+// disconnected, and releases its keys.
+func (p *pressAction) reset() {
+	if p.commands != nil {
+		p.commands.reset()
+	}
+	if p.keys != nil {
+		p.keys.set(false)
+	}
+
+	p.debounce.reset()
+}
+
+// buttonActions run what buttons do outside of the emulated controller, once
+// their report is forwarded: the commands and keys of Capture and Assistant,
+// the commands of profiles, gestures, volume keys and haptic clicks.
+type buttonActions struct {
+	state     *controlState
+	assistant pressAction
+	capture   pressAction
+	holdTimes buttonHoldTimes
+	gestures  []buttonGestures
+	volume    *volumeKeys
+	clicks    *hapticClicks
+
+	// Clients receiving the gestures, nil unless enabled.
+	obs       *obsClient
+	liveSplit *liveSplitClient
+}
+
+// newButtonActions returns the actions enabled by the command line. Commands
+// are given the environment of state.
+func newButtonActions(controller *stadiacontroller.StadiaController, state *controlState) (*buttonActions, error) {
+	a := &buttonActions{
+		state:     state,
+		holdTimes: buttonHoldTimes{},
+		assistant: pressAction{
+			button:     stadiacontroller.StadiaButtonAssistant,
+			debounce:   buttonDebounce{window: *buttonDebounceWindow},
+			commands:   newPressCommands(*longPressThreshold, *onAssistantPressed, *onAssistantLongPressed, *onAssistantReleased),
+			onPressed:  *onAssistantPressed,
+			onReleased: *onAssistantReleased,
+		},
+		capture: pressAction{
+			button:     stadiacontroller.StadiaButtonCapture,
+			debounce:   buttonDebounce{window: *buttonDebounceWindow},
+			commands:   newPressCommands(*longPressThreshold, *onCapturePressed, *onCaptureLongPressed, *onCaptureReleased),
+			onPressed:  *onCapturePressed,
+			onReleased: *onCaptureReleased,
+		},
+	}
+
+	if err := a.start(controller); err != nil {
+		a.Close()
+
+		return nil, err
+	}
+
+	return a, nil
+}
+
+func (a *buttonActions) start(controller *stadiacontroller.StadiaController) error {
+	var err error
+
+	if a.assistant.keys, err = parseKeyChord(*assistantKeys); err != nil {
+		return err
+	}
+	if a.capture.keys, err = parseKeyChord(*captureKeys); err != nil {
+		return err
+	}
+
+	if *volumeKeysEnabled {
+		a.volume = &volumeKeys{}
+	}
+
+	if *hapticClickButtons != "" {
+		if a.clicks, err = newHapticClicks(controller, *hapticClickButtons, *hapticClickDuration, *hapticClickIntensity); err != nil {
+			return err
+		}
+	}
+
+	if *obsURL != "" {
+		obs, detector, err := newOBSGestures(controller)
+
+		if err != nil {
+			return err
+		}
+
+		a.obs = obs
+		a.gestures = append(a.gestures, buttonGestures{stadiacontroller.StadiaButtonCapture, detector})
+	}
+
+	if *liveSplitAddr != "" {
+		liveSplit, detectors, err := newLiveSplitGestures()
+
+		if err != nil {
+			return err
+		}
+
+		a.liveSplit = liveSplit
+		a.gestures = append(a.gestures, detectors...)
+	}
+
+	return nil
+}
+
+// Close releases the keys of Capture and Assistant, which must not stay
+// pressed after exiting, and stops the clients of gestures.
+func (a *buttonActions) Close() {
+	if a.assistant.keys != nil {
+		a.assistant.keys.set(false)
+	}
+	if a.capture.keys != nil {
+		a.capture.keys.set(false)
+	}
+	if a.liveSplit != nil {
+		a.liveSplit.Close()
+	}
+	if a.obs != nil {
+		a.obs.Close()
+	}
+}
+
+// debounceStep returns the step debouncing Capture and Assistant, which must
+// come after all the steps changing the report.
+func (a *buttonActions) debounceStep() *reportStep {
+	return &reportStep{
+		apply: func(report *stadiacontroller.Xbox360ControllerReport, now time.Time) (time.Time, error) {
+			var assistantWake, captureWake time.Time
+
+			a.assistant.current, assistantWake = a.assistant.debounce.update(report.Assistant, now)
+			a.capture.current, captureWake = a.capture.debounce.update(report.Capture, now)
+
+			return earliest(assistantWake, captureWake), nil
+		},
+	}
+}
+
+// run runs the actions of the buttons of a report handled at the given time,
+// including the commands of the given hooks and shift layer.
+func (a *buttonActions) run(report *stadiacontroller.Xbox360ControllerReport, now time.Time, hooks *buttonHooks, shiftCommands []buttonCommand) error {
+	// Commands are told which button changed, and for how long it was held.
+	env := func(button stadiacontroller.StadiaButtons, pressed bool) []string {
+		return append(a.state.hookEnv(), buttonEnv(button, pressed, a.holdTimes.held(button, now), report)...)
+	}
+
+	if err := a.assistant.update(env); err != nil {
+		return err
+	}
+	if err := a.capture.update(env); err != nil {
+		return err
+	}
+
+	if hooks != nil {
+		if err := hooks.update(report.Buttons, env); err != nil {
+			return err
+		}
+	}
+	for _, c := range shiftCommands {
+		if err := runCommand(c.command, env(c.button, true)...); err != nil {
+			return err
+		}
+	}
+
+	a.holdTimes.update(report.Buttons, now)
+
+	for _, g := range a.gestures {
+		g.detector.update(report.Buttons.Has(g.button), time.Now())
+	}
+
+	if a.volume != nil {
+		a.volume.update(report.Buttons)
+	}
+	if a.clicks != nil {
+		a.clicks.update(report.Buttons)
+	}
+
+	return nil
+}
+
+// reset forgets the presses of the buttons when the controller is
+// disconnected.
+func (a *buttonActions) reset() {
+	a.assistant.reset()
+	a.capture.reset()
+
+	if a.volume != nil {
+		a.volume.update(0)
+	}
+}
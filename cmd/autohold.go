@@ -0,0 +1,210 @@
+package main
+
+import (
+	"fmt"
+	"strings"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Duration of the vibration confirming that an input will be held.
+const autoHoldPulse = 80 * time.Millisecond
+
+// States of an input with auto-hold.
+const (
+	autoHoldIdle      = iota // Released.
+	autoHoldPressed          // Pressed for less than the arming time.
+	autoHoldArmed            // Pressed for longer than the arming time.
+	autoHoldHeld             // Released after being armed, but still held.
+	autoHoldReleasing        // Pressed again to stop holding it.
+)
+
+// autoHoldInput is the state of an input with auto-hold.
+type autoHoldInput struct {
+	arm   time.Duration
+	state int
+	since time.Time
+}
+
+// update updates the state of the input, which is physically pressed or not
+// at the given time, and returns whether it must be reported as pressed.
+func (i *autoHoldInput) update(pressed bool, now time.Time) bool {
+	switch i.state {
+	case autoHoldIdle:
+		if pressed {
+			i.state, i.since = autoHoldPressed, now
+		}
+	case autoHoldPressed:
+		if !pressed {
+			i.state = autoHoldIdle
+		} else if now.Sub(i.since) >= i.arm {
+			i.state = autoHoldArmed
+		}
+	case autoHoldArmed:
+		if !pressed {
+			i.state = autoHoldHeld
+		}
+	case autoHoldHeld:
+		if pressed {
+			i.state = autoHoldReleasing
+		}
+	case autoHoldReleasing:
+		if !pressed {
+			i.state = autoHoldIdle
+		}
+	}
+
+	return pressed || i.state == autoHoldHeld
+}
+
+// autoHold implements auto-hold: an input physically held for its arming time
+// stays held after it is released, until it is pressed again. Shorter presses
+// are forwarded unchanged.
+type autoHold struct {
+	controller *stadiacontroller.StadiaController
+
+	buttons      map[stadiacontroller.StadiaButtons]*autoHoldInput
+	leftTrigger  *autoHoldInput
+	rightTrigger *autoHoldInput
+}
+
+// newAutoHold returns the auto-hold configured by a comma-separated list of
+// input=duration entries, where inputs are Stadia button names, "l2" and "r2",
+// and durations are their arming times (e.g. "l2=500ms,b=1s").
+func newAutoHold(controller *stadiacontroller.StadiaController, spec string) (*autoHold, error) {
+	h := &autoHold{
+		controller: controller,
+		buttons:    map[stadiacontroller.StadiaButtons]*autoHoldInput{},
+	}
+
+	for _, entry := range strings.Split(spec, ",") {
+		if entry = strings.TrimSpace(entry); entry == "" {
+			continue
+		}
+
+		i := strings.IndexByte(entry, '=')
+
+		if i == -1 {
+			return nil, fmt.Errorf("invalid auto-hold input %q, expected input=duration", entry)
+		}
+
+		arm, err := time.ParseDuration(entry[i+1:])
+
+		if err != nil {
+			return nil, err
+		}
+		if arm <= 0 {
+			return nil, fmt.Errorf("auto-hold arming time must be positive")
+		}
+
+		input := &autoHoldInput{arm: arm}
+
+		switch name := strings.ToLower(entry[:i]); name {
+		case "l2":
+			h.leftTrigger = input
+		case "r2":
+			h.rightTrigger = input
+		default:
+			button, err := stadiacontroller.ParseStadiaButtons(name)
+
+			if err != nil {
+				return nil, err
+			}
+
+			h.buttons[button] = input
+		}
+	}
+
+	return h, nil
+}
+
+// apply updates the inputs from the given report, received at the given time,
+// and adds the held inputs to it. If an input may be armed without another
+// report, it returns the time at which apply must be called again with the
+// same report.
+func (h *autoHold) apply(report *stadiacontroller.Xbox360ControllerReport, now time.Time) time.Time {
+	var wake time.Time
+	armed := false
+
+	update := func(input *autoHoldInput, pressed bool) bool {
+		wasArmed := input.state == autoHoldArmed
+		pressed = input.update(pressed, now)
+
+		if input.state == autoHoldArmed && !wasArmed {
+			armed = true
+		}
+		if input.state == autoHoldPressed {
+			wake = earliest(wake, input.since.Add(input.arm))
+		}
+
+		return pressed
+	}
+
+	var held stadiacontroller.StadiaButtons
+
+	for button, input := range h.buttons {
+		if update(input, report.Buttons.Has(button)) {
+			held |= button
+		}
+	}
+
+	report.Buttons |= held
+	report.Capture = report.Buttons.Has(stadiacontroller.StadiaButtonCapture)
+	report.Assistant = report.Buttons.Has(stadiacontroller.StadiaButtonAssistant)
+	report.SetButtons(report.GetButtons() | held.Xbox360Buttons())
+
+	if h.leftTrigger != nil && update(h.leftTrigger, report.GetLeftTrigger() > stickyTriggerThreshold) {
+		if h.leftTrigger.state == autoHoldHeld {
+			report.SetLeftTrigger(255)
+		}
+	}
+	if h.rightTrigger != nil && update(h.rightTrigger, report.GetRightTrigger() > stickyTriggerThreshold) {
+		if h.rightTrigger.state == autoHoldHeld {
+			report.SetRightTrigger(255)
+		}
+	}
+
+	if armed {
+		rumblePulses(h.controller, 1, autoHoldPulse)
+	}
+
+	return wake
+}
+
+// reset releases all held inputs.
+func (h *autoHold) reset() {
+	for _, input := range h.buttons {
+		input.state = autoHoldIdle
+	}
+	if h.leftTrigger != nil {
+		h.leftTrigger.state = autoHoldIdle
+	}
+	if h.rightTrigger != nil {
+		h.rightTrigger.state = autoHoldIdle
+	}
+}
+
+// earliest returns the earliest of the given times, ignoring zero times.
+func earliest(a, b time.Time) time.Time {
+	if a.IsZero() || (!b.IsZero() && b.Before(a)) {
+		return b
+	}
+
+	return a
+}
+
+// newAutoHoldStep returns the step of -auto-hold, or nil if it is not given.
+func newAutoHoldStep(controller *stadiacontroller.StadiaController) (*reportStep, error) {
+	if *autoHoldInputs == "" {
+		return nil, nil
+	}
+
+	hold, err := newAutoHold(controller, *autoHoldInputs)
+
+	if err != nil {
+		return nil, err
+	}
+
+	return timedStep(hold.apply, hold.reset), nil
+}
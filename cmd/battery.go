@@ -0,0 +1,161 @@
+package main
+
+import (
+	"fmt"
+	"log"
+	"sync/atomic"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Delay before checking the battery again when a check was skipped.
+const batteryRetryDelay = 30 * time.Second
+
+// Intensity of a vibration above which battery checks are skipped.
+const heavyRumble = 128
+
+// Intensity of the strongest motor of the controller, set by the emulator
+// callback.
+var rumbleIntensity uint32
+
+// Intensities of the motors of the controller, as LargeMotor<<8 | SmallMotor,
+// set by the emulator callback.
+var rumbleMotors uint32
+
+// setRumbleIntensity records the given vibration of the controller.
+func setRumbleIntensity(vibration stadiacontroller.Vibration) {
+	intensity := vibration.LargeMotor
+
+	if vibration.SmallMotor > intensity {
+		intensity = vibration.SmallMotor
+	}
+
+	atomic.StoreUint32(&rumbleIntensity, uint32(intensity))
+	atomic.StoreUint32(&rumbleMotors, uint32(vibration.LargeMotor)<<8|uint32(vibration.SmallMotor))
+}
+
+// batteryCheckBlocked returns whether the battery must not be checked now,
+// because the controller vibrates strongly or reports arrive too slowly.
+// Reading the battery of a Bluetooth controller uses radio time, which could
+// delay reports further when the link is already busy.
+func batteryCheckBlocked(rate *rateMonitor) bool {
+	return atomic.LoadUint32(&rumbleIntensity) >= heavyRumble || (rate != nil && rate.isDegraded())
+}
+
+// Battery levels whose crossing is logged.
+var batteryThresholds = [...]uint8{75, 50, 25, 10}
+
+// batteryMonitor caches the last battery status and logs its notable changes.
+type batteryMonitor struct {
+	status stadiacontroller.BatteryStatus
+	readAt time.Time
+}
+
+// update sets the battery status read at the given time, and returns whether
+// it changed.
+func (m *batteryMonitor) update(status stadiacontroller.BatteryStatus, readAt time.Time) bool {
+	previous := m.status
+	m.status = status
+	m.readAt = readAt
+
+	if status == previous {
+		return false
+	}
+
+	switch {
+	case !status.Known:
+		log.Printf("battery: unknown")
+
+	case !previous.Known:
+		log.Printf("battery: %v", status)
+
+	case status.Charging && !previous.Charging:
+		log.Printf("battery charging (%v)", status)
+
+	case !status.Charging && previous.Charging:
+		log.Printf("battery no longer charging (%v)", status)
+
+	default:
+		for _, threshold := range batteryThresholds {
+			if previous.Level > threshold && status.Level <= threshold {
+				log.Printf("battery at or below %d%% (%v)", threshold, status)
+				break
+			}
+			if previous.Level <= threshold && status.Level > threshold {
+				log.Printf("battery above %d%% (%v)", threshold, status)
+				break
+			}
+		}
+	}
+
+	return true
+}
+
+// batteryJSON is the JSON representation of a known battery status, along
+// with the time it was read at. Unknown statuses are represented by null.
+type batteryJSON struct {
+	Level    uint8     `json:"level"`
+	Charging bool      `json:"charging"`
+	ReadAt   time.Time `json:"read_at"`
+}
+
+func newBatteryJSON(status stadiacontroller.BatteryStatus, readAt time.Time) *batteryJSON {
+	if !status.Known {
+		return nil
+	}
+
+	return &batteryJSON{status.Level, status.Charging, readAt}
+}
+
+// Number of percents the battery level must rise above the low battery
+// threshold for the warning to be given again, so that readings bouncing
+// around the threshold do not repeat it.
+const lowBatteryHysteresis = 5
+
+// lowBatteryAlarm decides when to warn that the battery is low: once when its
+// level drops to or below the threshold while not charging, and again only
+// after it charged or rose back above the threshold.
+type lowBatteryAlarm struct {
+	threshold uint8
+	fired     bool
+}
+
+// update sets the current battery status, and returns whether the warning
+// must be given.
+func (a *lowBatteryAlarm) update(status stadiacontroller.BatteryStatus) bool {
+	if !status.Known {
+		return false
+	}
+
+	if status.Charging || int(status.Level) > int(a.threshold)+lowBatteryHysteresis {
+		a.fired = false
+		return false
+	}
+
+	if a.fired || status.Level > a.threshold {
+		return false
+	}
+
+	a.fired = true
+
+	return true
+}
+
+// warnLowBattery logs a warning, vibrates the controller with three short
+// pulses, and runs the command given by -on-low-battery, if any, with the
+// battery level in the STADIA_BATTERY_LEVEL environment variable in addition
+// to the given ones.
+func warnLowBattery(controller *stadiacontroller.StadiaController, status stadiacontroller.BatteryStatus, env ...string) {
+	log.Printf("warning: battery low (%v)", status)
+
+	rumblePulses(controller, 3, 150*time.Millisecond)
+
+	if *onLowBattery != "" {
+		env = append(env, fmt.Sprintf("STADIA_BATTERY_LEVEL=%d", status.Level))
+
+		if err := runCommand(*onLowBattery, env...); err != nil {
+			log.Printf("warning: cannot run low battery command: %v", err)
+		}
+	}
+}
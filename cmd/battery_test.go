@@ -0,0 +1,92 @@
+package main
+
+import (
+	"testing"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+func TestLowBatteryAlarm(t *testing.T) {
+	unknown := stadiacontroller.BatteryStatus{}
+	level := func(level uint8) stadiacontroller.BatteryStatus {
+		return stadiacontroller.BatteryStatus{Known: true, Level: level}
+	}
+	charging := func(level uint8) stadiacontroller.BatteryStatus {
+		return stadiacontroller.BatteryStatus{Known: true, Level: level, Charging: true}
+	}
+
+	// Readings of a controller slowly discharging around a threshold of 20%,
+	// and whether each one must give the warning.
+	readings := []struct {
+		status stadiacontroller.BatteryStatus
+		warn   bool
+	}{
+		{unknown, false},
+		{level(60), false},
+		{level(21), false},
+		{level(20), true},
+		{level(19), false},
+		{unknown, false},
+		{level(18), false},
+
+		// Readings bouncing around the threshold do not repeat the warning.
+		{level(22), false},
+		{level(25), false},
+		{level(19), false},
+
+		// Rising past the hysteresis does.
+		{level(26), false},
+		{level(20), true},
+
+		// So does charging, even below the threshold.
+		{charging(15), false},
+		{charging(18), false},
+		{level(18), true},
+		{level(17), false},
+		{level(0), false},
+	}
+
+	alarm := lowBatteryAlarm{threshold: 20}
+
+	for i, reading := range readings {
+		if warn := alarm.update(reading.status); warn != reading.warn {
+			t.Errorf("reading %d (%v): warn is %v, expected %v", i, reading.status, warn, reading.warn)
+		}
+	}
+
+	// The warning is also given if the first known level is already low.
+	fresh := lowBatteryAlarm{threshold: 20}
+
+	if !fresh.update(level(3)) {
+		t.Errorf("first reading of 3%% did not warn")
+	}
+}
+
+func TestBatteryMonitorUpdate(t *testing.T) {
+	var m batteryMonitor
+	now := time.Now()
+
+	changes := []struct {
+		status  stadiacontroller.BatteryStatus
+		changed bool
+	}{
+		{stadiacontroller.BatteryStatus{}, false},
+		{stadiacontroller.BatteryStatus{Known: true, Level: 80}, true},
+		{stadiacontroller.BatteryStatus{Known: true, Level: 80}, false},
+		{stadiacontroller.BatteryStatus{Known: true, Level: 74}, true},
+		{stadiacontroller.BatteryStatus{Known: true, Level: 74, Charging: true}, true},
+		{stadiacontroller.BatteryStatus{}, true},
+	}
+
+	for i, change := range changes {
+		readAt := now.Add(time.Duration(i) * time.Minute)
+
+		if changed := m.update(change.status, readAt); changed != change.changed {
+			t.Errorf("update %d (%v): changed is %v, expected %v", i, change.status, changed, change.changed)
+		}
+		if m.status != change.status || !m.readAt.Equal(readAt) {
+			t.Errorf("update %d: status %v read at %v was not cached", i, change.status, readAt)
+		}
+	}
+}
@@ -0,0 +1,134 @@
+package main
+
+import (
+	"fmt"
+	"strings"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Buttons of the emulated controller which Stadia buttons can be mapped to,
+// by name. "none" drops a button.
+var xbox360ButtonNames = map[string]uint16{
+	"a":             1 << stadiacontroller.Xbox360ControllerButtonA,
+	"b":             1 << stadiacontroller.Xbox360ControllerButtonB,
+	"x":             1 << stadiacontroller.Xbox360ControllerButtonX,
+	"y":             1 << stadiacontroller.Xbox360ControllerButtonY,
+	"leftshoulder":  1 << stadiacontroller.Xbox360ControllerButtonLeftShoulder,
+	"rightshoulder": 1 << stadiacontroller.Xbox360ControllerButtonRightShoulder,
+	"leftthumb":     1 << stadiacontroller.Xbox360ControllerButtonLeftThumb,
+	"rightthumb":    1 << stadiacontroller.Xbox360ControllerButtonRightThumb,
+	"back":          1 << stadiacontroller.Xbox360ControllerButtonBack,
+	"start":         1 << stadiacontroller.Xbox360ControllerButtonStart,
+	"guide":         1 << stadiacontroller.Xbox360ControllerButtonGuide,
+	"up":            1 << stadiacontroller.Xbox360ControllerButtonUp,
+	"down":          1 << stadiacontroller.Xbox360ControllerButtonDown,
+	"left":          1 << stadiacontroller.Xbox360ControllerButtonLeft,
+	"right":         1 << stadiacontroller.Xbox360ControllerButtonRight,
+	"none":          0,
+}
+
+// parseXbox360Button parses the name of a button of the emulated controller,
+// ignoring case and underscores (e.g. "LeftShoulder" or "left_shoulder").
+func parseXbox360Button(name string) (uint16, error) {
+	key := strings.ToLower(strings.Replace(strings.TrimSpace(name), "_", "", -1))
+
+	if button, ok := xbox360ButtonNames[key]; ok {
+		return button, nil
+	}
+
+	return 0, fmt.Errorf("unknown Xbox 360 button %q", name)
+}
+
+// buttonMap maps Stadia buttons to buttons of the emulated controller other
+// than their Xbox 360 equivalents. Unmapped buttons keep their equivalents.
+type buttonMap map[stadiacontroller.StadiaButtons]uint16
+
+// newButtonMap returns the mapping given by the [buttons] section of the
+// configuration file, by the buttons section of the given profile if any, and
+// by -capture-button and -assistant-button, or nil if it is empty.
+func newButtonMap(config *configFile, profile *configProfile) (buttonMap, error) {
+	m := buttonMap{}
+
+	if config != nil {
+		if err := m.setEntries(config.path, config.buttons); err != nil {
+			return nil, err
+		}
+	}
+	if profile != nil {
+		if err := m.setEntries(config.path, profile.buttons); err != nil {
+			return nil, err
+		}
+	}
+
+	for _, option := range []struct {
+		button stadiacontroller.StadiaButtons
+		value  string
+	}{
+		{stadiacontroller.StadiaButtonCapture, *captureButton},
+		{stadiacontroller.StadiaButtonAssistant, *assistantButton},
+	} {
+		if option.value == "" {
+			continue
+		}
+
+		to, err := parseXbox360Button(option.value)
+
+		if err != nil {
+			return nil, err
+		}
+
+		m[option.button] = to
+	}
+
+	if len(m) == 0 {
+		return nil, nil
+	}
+
+	return m, nil
+}
+
+// setEntries adds the given entries of a buttons section of the configuration
+// file at the given path.
+func (m buttonMap) setEntries(path string, entries []configEntry) error {
+	for _, entry := range entries {
+		from, err := stadiacontroller.ParseStadiaButtons(entry.key)
+
+		if err != nil || from == 0 || from&(from-1) != 0 {
+			return fmt.Errorf("%s:%d: invalid Stadia button %q", path, entry.line, entry.key)
+		}
+
+		to, err := parseXbox360Button(entry.value)
+
+		if err != nil {
+			return fmt.Errorf("%s:%d: %v", path, entry.line, err)
+		}
+
+		m[from] = to
+	}
+
+	return nil
+}
+
+// apply replaces the Xbox 360 equivalents of the pressed Stadia buttons by
+// their mapped buttons in the given report.
+func (m buttonMap) apply(report *stadiacontroller.Xbox360ControllerReport) {
+	var mapped, defaults uint16
+
+	for from, to := range m {
+		if report.Buttons.Has(from) {
+			defaults |= from.Xbox360Buttons()
+			mapped |= to
+		}
+	}
+
+	// Keep the equivalents of unmapped Stadia buttons, e.g. B when A is mapped
+	// to X and B is not mapped.
+	unmapped := report.Buttons
+
+	for from := range m {
+		unmapped &^= from
+	}
+
+	report.SetButtons(report.GetButtons()&^(defaults&^unmapped.Xbox360Buttons()) | mapped)
+}
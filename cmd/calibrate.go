@@ -0,0 +1,489 @@
+package main
+
+import (
+	"bufio"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"io/ioutil"
+	"log"
+	"os"
+	"path/filepath"
+	"sort"
+	"text/tabwriter"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Name of the calibration file, next to the configuration file or to the
+// executable.
+const calibrationFileName = "calibration.json"
+
+// Duration for which the centers of the sticks are measured by calibrate.
+const calibrationCenterDuration = 2 * time.Second
+
+// Minimum ranges measured by calibrate: an axis or trigger moving less was
+// most likely not moved at all.
+const (
+	minCalibratedAxisRange    = 8000
+	minCalibratedTriggerRange = 64
+)
+
+// axisCalibration is the range of a stick axis measured by calibrate.
+type axisCalibration struct {
+	Min    int16 `json:"min"`
+	Center int16 `json:"center"`
+	Max    int16 `json:"max"`
+}
+
+// triggerCalibration is the range of a trigger measured by calibrate.
+type triggerCalibration struct {
+	Min byte `json:"min"`
+	Max byte `json:"max"`
+}
+
+// controllerCalibration holds the ranges of the inputs of a controller.
+type controllerCalibration struct {
+	LeftX        axisCalibration    `json:"left_x"`
+	LeftY        axisCalibration    `json:"left_y"`
+	RightX       axisCalibration    `json:"right_x"`
+	RightY       axisCalibration    `json:"right_y"`
+	LeftTrigger  triggerCalibration `json:"left_trigger"`
+	RightTrigger triggerCalibration `json:"right_trigger"`
+}
+
+// calibrations scales the inputs of each controller from the ranges measured
+// by calibrate, keyed by serial number, to their full ranges.
+type calibrations struct {
+	controllers map[string]*controllerCalibration
+	active      *controllerCalibration
+}
+
+// calibrationPath returns the path of the calibration file given to
+// -calibration, or its default path next to the configuration file or to the
+// executable.
+func calibrationPath() (string, error) {
+	if *calibrationFile != "" {
+		return *calibrationFile, nil
+	}
+	if *configPath != "" {
+		return filepath.Join(filepath.Dir(*configPath), calibrationFileName), nil
+	}
+
+	executable, err := os.Executable()
+
+	if err != nil {
+		return "", err
+	}
+
+	return filepath.Join(filepath.Dir(executable), calibrationFileName), nil
+}
+
+// readCalibrationFile reads the calibrations at the given path, keyed by
+// serial number. A missing file has no calibrations.
+func readCalibrationFile(path string) (map[string]*controllerCalibration, error) {
+	data, err := ioutil.ReadFile(path)
+
+	if os.IsNotExist(err) {
+		return map[string]*controllerCalibration{}, nil
+	}
+	if err != nil {
+		return nil, err
+	}
+
+	return parseCalibrations(path, data)
+}
+
+// parseCalibrations parses the content of the calibration file at the given
+// path.
+func parseCalibrations(path string, data []byte) (map[string]*controllerCalibration, error) {
+	controllers := map[string]*controllerCalibration{}
+
+	if err := json.Unmarshal(data, &controllers); err != nil {
+		return nil, fmt.Errorf("invalid calibration file %s: %w", path, err)
+	}
+
+	return controllers, nil
+}
+
+// loadCalibrations returns the calibrations of the calibration file, or nil if
+// no controller was calibrated.
+func loadCalibrations() (*calibrations, error) {
+	path, err := calibrationPath()
+
+	if err != nil {
+		return nil, err
+	}
+
+	controllers, err := readCalibrationFile(path)
+
+	if err != nil || len(controllers) == 0 {
+		return nil, err
+	}
+
+	return &calibrations{controllers: controllers}, nil
+}
+
+// setController selects the calibration of the controller with the given
+// serial number, if any.
+func (c *calibrations) setController(serial string) {
+	c.active = c.controllers[serial]
+
+	if c.active != nil {
+		log.Printf("using the calibration of controller %s", serial)
+	} else {
+		log.Printf("controller %s is not calibrated, using its raw ranges", serial)
+	}
+}
+
+// apply scales the sticks and triggers of the given report from their
+// calibrated ranges to their full ranges.
+func (c *calibrations) apply(report *stadiacontroller.Xbox360ControllerReport) {
+	if c.active == nil {
+		return
+	}
+
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	report.SetLeftThumb(c.active.LeftX.apply(lx), c.active.LeftY.apply(ly))
+	report.SetRightThumb(c.active.RightX.apply(rx), c.active.RightY.apply(ry))
+	report.SetLeftTrigger(c.active.LeftTrigger.apply(report.GetLeftTrigger()))
+	report.SetRightTrigger(c.active.RightTrigger.apply(report.GetRightTrigger()))
+}
+
+// apply scales each side of the axis from its center to its measured end to
+// the full range, clamping values beyond the ends.
+func (a axisCalibration) apply(value int16) int16 {
+	v, c := int32(value), int32(a.Center)
+	var scaled int32
+
+	if v >= c {
+		scaled = (v - c) * 32767 / max32(int32(a.Max)-c, 1)
+	} else {
+		scaled = (v - c) * 32768 / max32(c-int32(a.Min), 1)
+	}
+
+	switch {
+	case scaled > 32767:
+		return 32767
+	case scaled < -32768:
+		return -32768
+	default:
+		return int16(scaled)
+	}
+}
+
+// apply scales the trigger from its measured range to 0 to 255, clamping
+// values beyond its ends.
+func (t triggerCalibration) apply(value byte) byte {
+	switch {
+	case value <= t.Min:
+		return 0
+	case value >= t.Max:
+		return 255
+	default:
+		return byte(int(value-t.Min) * 255 / int(t.Max-t.Min))
+	}
+}
+
+func max32(a, b int32) int32 {
+	if a > b {
+		return a
+	}
+
+	return b
+}
+
+// runCalibrate guides the user through the measure of the centers and ranges
+// of the sticks and triggers of the controller, and saves them to the
+// calibration file.
+func runCalibrate(args []string) error {
+	if len(args) != 0 {
+		return errors.New("usage: calibrate")
+	}
+
+	path, err := calibrationPath()
+
+	if err != nil {
+		return err
+	}
+
+	controllers, err := readCalibrationFile(path)
+
+	if err != nil {
+		return err
+	}
+
+	controller, err := newController()
+
+	if err != nil {
+		return err
+	}
+
+	defer controller.Close()
+
+	if err := waitForController(controller); err != nil {
+		return err
+	}
+
+	info := controller.Info()
+
+	if info == nil || info.Serial == "" {
+		return errors.New("the controller has no serial number to save its calibration")
+	}
+
+	reports := controller.Events()
+	enter := make(chan struct{})
+
+	go func() {
+		scanner := bufio.NewScanner(os.Stdin)
+
+		for scanner.Scan() {
+			enter <- struct{}{}
+		}
+	}()
+
+	// Every report updates the calibration measured by the current step.
+	var calibration controllerCalibration
+	var sums [4]int64
+	samples := int64(0)
+
+	axes := func() []*axisCalibration {
+		return []*axisCalibration{&calibration.LeftX, &calibration.LeftY, &calibration.RightX, &calibration.RightY}
+	}
+
+	step := func(instructions string, done <-chan struct{}, update func(report *stadiacontroller.Xbox360ControllerReport)) error {
+		fmt.Println(instructions)
+
+		for {
+			select {
+			case event, ok := <-reports:
+				if !ok {
+					return errors.New("the controller was closed")
+				}
+
+				switch event := event.(type) {
+				case stadiacontroller.ReportEvent:
+					update(&event.Report)
+				case stadiacontroller.DisconnectedEvent:
+					return errors.New("the controller was disconnected")
+				}
+
+			case <-done:
+				return nil
+			}
+		}
+	}
+
+	centered := make(chan struct{})
+	time.AfterFunc(calibrationCenterDuration, func() { close(centered) })
+
+	err = step("leave both sticks untouched...", centered, func(report *stadiacontroller.Xbox360ControllerReport) {
+		lx, ly := report.GetLeftThumb()
+		rx, ry := report.GetRightThumb()
+
+		for i, value := range [4]int16{lx, ly, rx, ry} {
+			sums[i] += int64(value)
+		}
+
+		samples++
+	})
+
+	if err != nil {
+		return err
+	}
+	if samples == 0 {
+		return errors.New("no report received")
+	}
+
+	for i, axis := range axes() {
+		center := int16(sums[i] / samples)
+
+		if abs16(center) > maxRecenterOffset {
+			return errors.New("the sticks were moved, run calibrate again without touching them at first")
+		}
+
+		*axis = axisCalibration{Min: center, Center: center, Max: center}
+	}
+
+	rotated := make(chan struct{})
+	go func() { <-enter; close(rotated) }()
+
+	err = step("rotate both sticks fully several times, then press Enter", rotated, func(report *stadiacontroller.Xbox360ControllerReport) {
+		lx, ly := report.GetLeftThumb()
+		rx, ry := report.GetRightThumb()
+		values := [4]int16{lx, ly, rx, ry}
+
+		for i, axis := range axes() {
+			value := values[i]
+
+			if value < axis.Min {
+				axis.Min = value
+			}
+			if value > axis.Max {
+				axis.Max = value
+			}
+		}
+	})
+
+	if err != nil {
+		return err
+	}
+
+	for i, axis := range axes() {
+		if int32(axis.Max)-int32(axis.Center) < minCalibratedAxisRange || int32(axis.Center)-int32(axis.Min) < minCalibratedAxisRange {
+			return fmt.Errorf("the %s axis of the %s stick barely moved, run calibrate again", [...]string{"X", "Y"}[i%2], [...]string{"left", "right"}[i/2])
+		}
+	}
+
+	calibration.LeftTrigger = triggerCalibration{Min: 255}
+	calibration.RightTrigger = triggerCalibration{Min: 255}
+
+	pressed := make(chan struct{})
+	go func() { <-enter; close(pressed) }()
+
+	err = step("fully press and release both triggers, then press Enter", pressed, func(report *stadiacontroller.Xbox360ControllerReport) {
+		for _, trigger := range []struct {
+			calibration *triggerCalibration
+			value       byte
+		}{
+			{&calibration.LeftTrigger, report.GetLeftTrigger()},
+			{&calibration.RightTrigger, report.GetRightTrigger()},
+		} {
+			if trigger.value < trigger.calibration.Min {
+				trigger.calibration.Min = trigger.value
+			}
+			if trigger.value > trigger.calibration.Max {
+				trigger.calibration.Max = trigger.value
+			}
+		}
+	})
+
+	if err != nil {
+		return err
+	}
+
+	for i, trigger := range []triggerCalibration{calibration.LeftTrigger, calibration.RightTrigger} {
+		if int(trigger.Max)-int(trigger.Min) < minCalibratedTriggerRange {
+			return fmt.Errorf("the %s trigger barely moved, run calibrate again", [...]string{"left", "right"}[i])
+		}
+	}
+
+	controllers[info.Serial] = &calibration
+
+	data, err := encodeCalibrations(controllers)
+
+	if err != nil {
+		return err
+	}
+	if err := ioutil.WriteFile(path, data, 0644); err != nil {
+		return err
+	}
+
+	fmt.Printf("calibration of controller %s saved to %s\n", info.Serial, path)
+
+	return nil
+}
+
+// encodeCalibrations returns the content of a calibration file with the given
+// calibrations.
+func encodeCalibrations(controllers map[string]*controllerCalibration) ([]byte, error) {
+	data, err := json.MarshalIndent(controllers, "", "  ")
+
+	if err != nil {
+		return nil, err
+	}
+
+	return append(data, '\n'), nil
+}
+
+// runCalibration lists the controllers of the calibration file, or deletes
+// the calibration of a controller which is no longer used.
+func runCalibration(args []string) error {
+	path, err := calibrationPath()
+
+	if err != nil {
+		return err
+	}
+
+	controllers, err := readCalibrationFile(path)
+
+	if err != nil {
+		return err
+	}
+
+	switch {
+	case len(args) == 1 && args[0] == "list":
+		if len(controllers) == 0 {
+			fmt.Printf("no controller is calibrated in %s\n", path)
+			return nil
+		}
+
+		serials := make([]string, 0, len(controllers))
+
+		for serial := range controllers {
+			serials = append(serials, serial)
+		}
+
+		sort.Strings(serials)
+		w := tabwriter.NewWriter(os.Stdout, 0, 0, 2, ' ', 0)
+
+		fmt.Fprintln(w, "SERIAL\tLEFT STICK\tRIGHT STICK\tTRIGGERS")
+
+		for _, serial := range serials {
+			c := controllers[serial]
+			fmt.Fprintf(w, "%s\tx %v, y %v\tx %v, y %v\t%v, %v\n", serial, c.LeftX, c.LeftY, c.RightX, c.RightY, c.LeftTrigger, c.RightTrigger)
+		}
+
+		return w.Flush()
+
+	case len(args) == 2 && args[0] == "delete":
+		return deleteCalibration(path, controllers, args[1])
+
+	default:
+		return errors.New("usage: calibration <list|delete serial>")
+	}
+}
+
+func (a axisCalibration) String() string {
+	return fmt.Sprintf("%d..%d..%d", a.Min, a.Center, a.Max)
+}
+
+func (t triggerCalibration) String() string {
+	return fmt.Sprintf("%d..%d", t.Min, t.Max)
+}
+
+// deleteCalibration removes the calibration of the controller with the given
+// serial number from the given calibrations of the file at path.
+func deleteCalibration(path string, controllers map[string]*controllerCalibration, serial string) error {
+	if _, ok := controllers[serial]; !ok {
+		return fmt.Errorf("controller %q is not calibrated, see 'calibration list'", serial)
+	}
+
+	delete(controllers, serial)
+
+	data, err := encodeCalibrations(controllers)
+
+	if err != nil {
+		return err
+	}
+	if err := replaceFile(path, data); err != nil {
+		return err
+	}
+
+	fmt.Printf("calibration of controller %s deleted from %s (previous file saved to %s.bak)\n", serial, path, path)
+
+	return nil
+}
+
+// step returns the step applying the calibration of the connected controller,
+// or nil if c is nil.
+func (c *calibrations) step() *reportStep {
+	if c == nil {
+		return nil
+	}
+
+	return transformStep(c.apply, nil)
+}
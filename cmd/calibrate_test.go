@@ -0,0 +1,48 @@
+package main
+
+import (
+	"io/ioutil"
+	"os"
+	"path/filepath"
+	"testing"
+)
+
+func TestDeleteCalibration(t *testing.T) {
+	dir, err := ioutil.TempDir("", "stadiacontroller")
+
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	defer os.RemoveAll(dir)
+
+	path := filepath.Join(dir, calibrationFileName)
+	original := `{"A1": {"left_x": {"min": -30000, "center": 100, "max": 31000}}, "B2": {"left_trigger": {"min": 3, "max": 250}}}`
+
+	if err := ioutil.WriteFile(path, []byte(original), 0644); err != nil {
+		t.Fatal(err)
+	}
+
+	controllers, err := readCalibrationFile(path)
+
+	if err != nil {
+		t.Fatal(err)
+	}
+	if err := deleteCalibration(path, controllers, "C3"); err == nil {
+		t.Errorf("deleting the calibration of an unknown controller succeeded")
+	}
+	if err := deleteCalibration(path, controllers, "A1"); err != nil {
+		t.Fatal(err)
+	}
+
+	if data, _ := ioutil.ReadFile(path + ".bak"); string(data) != original {
+		t.Errorf("backup is\n%s\nexpected\n%s", data, original)
+	}
+
+	if controllers, err = readCalibrationFile(path); err != nil {
+		t.Fatal(err)
+	}
+	if len(controllers) != 1 || controllers["B2"] == nil || controllers["B2"].LeftTrigger != (triggerCalibration{Min: 3, Max: 250}) {
+		t.Errorf("%d calibrations left, expected only that of B2", len(controllers))
+	}
+}
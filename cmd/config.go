@@ -0,0 +1,405 @@
+package main
+
+import (
+	"bufio"
+	"bytes"
+	"flag"
+	"fmt"
+	"io/ioutil"
+	"os"
+	"strconv"
+	"strings"
+)
+
+// A configFile is a configuration file, written in a subset of TOML:
+//
+//     # Comment.
+//     sticky = "l1,l2"
+//     low-battery = 20
+//
+//     [buttons]
+//     a = "B"
+//     capture = "Back"
+//
+//     [shift]
+//     a = "Guide"
+//     dpad_up = "run:obs-replay.bat"
+//
+//     [profile.racing]
+//     match = ["acc.exe", "dirtrally2.exe"]
+//     left-deadzone = "15%"
+//
+//     [profile.racing.buttons]
+//     r1 = "A"
+//
+// Top-level keys are command line flags, which the command line overrides.
+// Other sections are described by the fields below.
+type configFile struct {
+	path  string
+	flags []configEntry
+
+	// Targets of the Stadia buttons on the emulated controller, by button
+	// name (see newButtonMap).
+	buttons []configEntry
+
+	// Targets of the Stadia buttons pressed while Assistant is held (see
+	// newShiftLayer).
+	shift []configEntry
+
+	// Profiles given by [profile.NAME], [profile.NAME.buttons] and
+	// [profile.NAME.shift] sections, in the order of their first section (see
+	// loadProfiles).
+	profiles []*configProfile
+}
+
+// A configProfile is a named set of flags and button targets, replacing the
+// top-level ones while it is active.
+type configProfile struct {
+	name    string
+	line    int
+	flags   []configEntry
+	buttons []configEntry
+	shift   []configEntry
+
+	// Executables activating the profile when in the foreground.
+	match []string
+}
+
+type configEntry struct {
+	key   string
+	value string
+	line  int
+}
+
+// loadConfig reads the configuration file at the given path.
+func loadConfig(path string) (*configFile, error) {
+	data, err := ioutil.ReadFile(path)
+
+	if err != nil {
+		return nil, err
+	}
+
+	config, err := parseConfig(data)
+
+	if err != nil {
+		return nil, fmt.Errorf("%s:%w", path, err)
+	}
+
+	config.path = path
+
+	return config, nil
+}
+
+func parseConfig(data []byte) (*configFile, error) {
+	config := &configFile{}
+	section := ""
+	var profile *configProfile
+	scanner := bufio.NewScanner(bytes.NewReader(data))
+
+	for line := 1; scanner.Scan(); line++ {
+		text := strings.TrimSpace(stripConfigComment(scanner.Text()))
+
+		if text == "" {
+			continue
+		}
+
+		if strings.HasPrefix(text, "[") {
+			if !strings.HasSuffix(text, "]") {
+				return nil, fmt.Errorf("%d: invalid section header %q", line, text)
+			}
+
+			section = strings.TrimSpace(text[1 : len(text)-1])
+
+			switch {
+			case section == "buttons", section == "shift":
+
+			case strings.HasPrefix(section, "profile."):
+				name, suffix, _ := profileSection(section)
+				section = "profile" + suffix
+
+				if !validProfileName(name) {
+					return nil, fmt.Errorf("%d: invalid profile name %q", line, name)
+				}
+
+				profile = config.profile(name, line)
+
+			default:
+				return nil, fmt.Errorf("%d: unknown section %q", line, section)
+			}
+
+			continue
+		}
+
+		i := strings.IndexByte(text, '=')
+
+		if i == -1 {
+			return nil, fmt.Errorf("%d: expected key = value", line)
+		}
+
+		key := strings.TrimSpace(text[:i])
+		value, err := parseConfigValue(strings.TrimSpace(text[i+1:]))
+
+		if err != nil {
+			return nil, fmt.Errorf("%d: %s: %w", line, key, err)
+		}
+
+		entry := configEntry{key, value, line}
+
+		switch section {
+		case "":
+			config.flags = append(config.flags, entry)
+		case "buttons":
+			config.buttons = append(config.buttons, entry)
+		case "shift":
+			config.shift = append(config.shift, entry)
+		case "profile":
+			if key == "match" {
+				profile.match = append(profile.match, strings.Split(value, ",")...)
+			} else {
+				profile.flags = append(profile.flags, entry)
+			}
+		case "profile.buttons":
+			profile.buttons = append(profile.buttons, entry)
+		case "profile.shift":
+			profile.shift = append(profile.shift, entry)
+		}
+	}
+
+	return config, scanner.Err()
+}
+
+// profile returns the profile with the given name, adding it if its first
+// section is at the given line.
+func (c *configFile) profile(name string, line int) *configProfile {
+	for _, p := range c.profiles {
+		if p.name == name {
+			return p
+		}
+	}
+
+	p := &configProfile{name: name, line: line}
+	c.profiles = append(c.profiles, p)
+
+	return p
+}
+
+// profileSection returns the name of the profile of the given section, and
+// the part of the section following it, e.g. "racing" and ".buttons" for
+// "profile.racing.buttons".
+func profileSection(section string) (name, suffix string, ok bool) {
+	if !strings.HasPrefix(section, "profile.") {
+		return "", "", false
+	}
+
+	name = strings.TrimPrefix(section, "profile.")
+
+	for _, suffix := range []string{".buttons", ".shift"} {
+		if strings.HasSuffix(name, suffix) {
+			return strings.TrimSuffix(name, suffix), suffix, true
+		}
+	}
+
+	return name, "", true
+}
+
+func validProfileName(name string) bool {
+	return name != "" && !strings.ContainsAny(name, ". \t\"")
+}
+
+// stripConfigComment removes the comment at the end of the given line, if
+// any, ignoring "#" in strings.
+func stripConfigComment(line string) string {
+	inString := false
+
+	for i := 0; i < len(line); i++ {
+		switch line[i] {
+		case '\\':
+			i++
+		case '"':
+			inString = !inString
+		case '#':
+			if !inString {
+				return line[:i]
+			}
+		}
+	}
+
+	return line
+}
+
+// parseConfigValue parses a string, boolean, number or array of strings into
+// the text given to flags. Strings of arrays are separated by commas.
+func parseConfigValue(value string) (string, error) {
+	if strings.HasPrefix(value, `"`) {
+		return strconv.Unquote(value)
+	}
+	if strings.HasPrefix(value, "[") && strings.HasSuffix(value, "]") {
+		return parseConfigArray(value[1 : len(value)-1])
+	}
+	if value == "true" || value == "false" {
+		return value, nil
+	}
+	if _, err := strconv.ParseFloat(value, 64); err == nil {
+		return value, nil
+	}
+
+	return "", fmt.Errorf("invalid value %s, expected a string, a boolean, a number or an array of strings", value)
+}
+
+// parseConfigArray parses the comma-separated strings of an array, and joins
+// them with commas.
+func parseConfigArray(items string) (string, error) {
+	var values []string
+
+	for items = strings.TrimSpace(items); items != ""; {
+		end := 1
+
+		for end < len(items) && items[end] != '"' {
+			if items[end] == '\\' {
+				end++
+			}
+
+			end++
+		}
+
+		if items[0] != '"' || end >= len(items) {
+			return "", fmt.Errorf("invalid array, expected strings")
+		}
+
+		value, err := strconv.Unquote(items[:end+1])
+
+		if err != nil {
+			return "", err
+		}
+
+		values = append(values, value)
+		items = strings.TrimSpace(items[end+1:])
+
+		if strings.HasPrefix(items, ",") {
+			items = strings.TrimSpace(items[1:])
+		} else if items != "" {
+			return "", fmt.Errorf("invalid array, expected a comma after %q", value)
+		}
+	}
+
+	return strings.Join(values, ","), nil
+}
+
+// explicitFlags are the flags given on the command line, which the
+// configuration file does not override, including when it is reloaded.
+var explicitFlags = map[string]bool{}
+
+// apply sets the flags of the configuration which were not given on the
+// command line.
+func (c *configFile) apply() error {
+	flag.Visit(func(f *flag.Flag) { explicitFlags[f.Name] = true })
+
+	return c.setFlags(explicitFlags)
+}
+
+// setFlags sets the flags given by the top-level keys, except the skipped
+// ones.
+func (c *configFile) setFlags(skipped map[string]bool) error {
+	for _, entry := range c.flags {
+		if flag.Lookup(entry.key) == nil || entry.key == "config" {
+			return fmt.Errorf("%s:%d: unknown flag %q", c.path, entry.line, entry.key)
+		}
+		if skipped[entry.key] {
+			continue
+		}
+		if err := flag.Set(entry.key, entry.value); err != nil {
+			return fmt.Errorf("%s:%d: %v", c.path, entry.line, err)
+		}
+	}
+
+	return nil
+}
+
+// A configSection is a range of lines of a configuration file, from the
+// comments preceding the header of a section to the next section. Top-level
+// keys are in the first section, which has no name nor header.
+type configSection struct {
+	name               string
+	start, header, end int
+}
+
+// readConfigLines reads the lines of the configuration file at the given path
+// along with its sections, so that they can be edited without changing the
+// other lines.
+func readConfigLines(path string) ([]string, []configSection, error) {
+	data, err := ioutil.ReadFile(path)
+
+	if err != nil {
+		return nil, nil, err
+	}
+
+	lines, sections := splitConfigLines(data)
+
+	return lines, sections, nil
+}
+
+// splitConfigLines returns the lines of the given configuration along with its
+// sections.
+func splitConfigLines(data []byte) ([]string, []configSection) {
+	lines := strings.Split(string(data), "\n")
+	sections := []configSection{{header: -1}}
+
+	for i, line := range lines {
+		text := strings.TrimSpace(stripConfigComment(line))
+
+		if !strings.HasPrefix(text, "[") || !strings.HasSuffix(text, "]") {
+			continue
+		}
+
+		previous := &sections[len(sections)-1]
+		start := i
+
+		for start > previous.header+1 && strings.HasPrefix(strings.TrimSpace(lines[start-1]), "#") {
+			start--
+		}
+
+		previous.end = start
+		sections = append(sections, configSection{name: strings.TrimSpace(text[1 : len(text)-1]), start: start, header: i})
+	}
+
+	sections[len(sections)-1].end = len(lines)
+
+	return lines, sections
+}
+
+// saveConfig replaces the configuration file at the given path by the given
+// lines, after copying it to path.bak. The lines are only saved if they are a
+// valid configuration.
+func saveConfig(path string, lines []string) error {
+	data := []byte(strings.Join(lines, "\n"))
+
+	if _, err := parseConfig(data); err != nil {
+		return fmt.Errorf("edited configuration is invalid, %s left unchanged: %v", path, err)
+	}
+
+	return replaceFile(path, data)
+}
+
+// replaceFile replaces the file at the given path by data, after copying it to
+// path.bak if it exists.
+func replaceFile(path string, data []byte) error {
+	previous, err := ioutil.ReadFile(path)
+
+	if err == nil {
+		err = ioutil.WriteFile(path+".bak", previous, 0644)
+	} else if os.IsNotExist(err) {
+		err = nil
+	}
+	if err != nil {
+		return err
+	}
+
+	// The file is replaced at once, so that it is never left half written.
+	temporary := path + ".tmp"
+
+	if err := ioutil.WriteFile(temporary, data, 0644); err != nil {
+		return err
+	}
+
+	return os.Rename(temporary, path)
+}
@@ -0,0 +1,198 @@
+package main
+
+import (
+	"flag"
+	"fmt"
+	"log"
+	"os"
+	"time"
+)
+
+// Interval at which the configuration file is checked for changes.
+const configPollInterval = 1 * time.Second
+
+// configWatcher reports changes of the configuration file, found by checking
+// its modification time and size every configPollInterval rather than by
+// keeping it open. A change is only reported once the file stayed the same
+// for an interval, so that a file saved in several writes is not read while
+// half-written.
+type configWatcher struct {
+	// Receives a value when the file changed.
+	changes chan struct{}
+
+	path string
+	done chan struct{}
+}
+
+func newConfigWatcher(path string) *configWatcher {
+	w := &configWatcher{
+		changes: make(chan struct{}, 1),
+		path:    path,
+		done:    make(chan struct{}),
+	}
+
+	go w.run()
+
+	return w
+}
+
+func (w *configWatcher) run() {
+	ticker := time.NewTicker(configPollInterval)
+	defer ticker.Stop()
+
+	last, _ := os.Stat(w.path)
+	reported := last
+
+	for {
+		select {
+		case <-ticker.C:
+		case <-w.done:
+			return
+		}
+
+		info, err := os.Stat(w.path)
+
+		if err != nil {
+			// E.g. the file is being replaced by an editor.
+			continue
+		}
+
+		if last == nil || !sameFileInfo(info, last) {
+			last = info
+			continue
+		}
+		if reported != nil && sameFileInfo(info, reported) {
+			continue
+		}
+
+		reported = info
+
+		select {
+		case w.changes <- struct{}{}:
+		default:
+		}
+	}
+}
+
+func (w *configWatcher) Close() {
+	close(w.done)
+}
+
+func sameFileInfo(a, b os.FileInfo) bool {
+	return a.Size() == b.Size() && a.ModTime().Equal(b.ModTime())
+}
+
+// reloadConfig reads the configuration file at the given path again, and
+// returns it with its profiles. The top-level keys which profiles may set are
+// applied again, and set back to their default value when removed; other
+// top-level keys are only read on startup, so their changes are only logged.
+// The flags are left unchanged on errors.
+func reloadConfig(previous *configFile, path string) (*configFile, []*profile, error) {
+	reloaded, err := loadConfig(path)
+
+	if err != nil {
+		return nil, nil, err
+	}
+
+	for _, entry := range reloaded.flags {
+		if flag.Lookup(entry.key) == nil || entry.key == "config" {
+			return nil, nil, fmt.Errorf("%s:%d: unknown flag %q", path, entry.line, entry.key)
+		}
+	}
+
+	values := map[string]string{}
+	commands := map[string]buttonCommandsFlag{}
+	reset := map[string]bool{}
+
+	for name := range resetFlags {
+		reset[name] = true
+	}
+
+	// save saves the value of the given flag before it is first changed, and
+	// clears repeated flags such as -on-pressed.
+	save := func(name string) {
+		f := flag.Lookup(name)
+
+		if c, ok := f.Value.(*buttonCommandsFlag); ok {
+			if _, saved := commands[name]; !saved {
+				commands[name] = *c
+				*c = nil
+			}
+		} else if _, saved := values[name]; !saved {
+			values[name] = f.Value.String()
+		}
+	}
+
+	restore := func() {
+		for name, value := range values {
+			flag.Set(name, value)
+		}
+		for name, value := range commands {
+			*flag.Lookup(name).Value.(*buttonCommandsFlag) = value
+		}
+
+		resetFlags = reset
+	}
+
+	before, after := configValues(previous), configValues(reloaded)
+
+	for name := range before {
+		if _, kept := after[name]; kept || explicitFlags[name] {
+			continue
+		}
+		if !profileFlags[name] {
+			log.Printf("warning: %s: removing %s only applies after a restart", path, name)
+			continue
+		}
+
+		save(name)
+
+		if _, ok := flag.Lookup(name).Value.(*buttonCommandsFlag); !ok {
+			flag.Set(name, flag.Lookup(name).DefValue)
+		}
+
+		resetFlags[name] = true
+	}
+
+	for _, entry := range reloaded.flags {
+		if explicitFlags[entry.key] {
+			continue
+		}
+		if !profileFlags[entry.key] {
+			if old, ok := before[entry.key]; !ok || old.value != after[entry.key].value {
+				log.Printf("warning: %s:%d: changing %s only applies after a restart", path, entry.line, entry.key)
+			}
+
+			continue
+		}
+
+		save(entry.key)
+		delete(resetFlags, entry.key)
+
+		if err := flag.Set(entry.key, entry.value); err != nil {
+			restore()
+			return nil, nil, fmt.Errorf("%s:%d: %v", path, entry.line, err)
+		}
+	}
+
+	profiles, err := loadProfiles(reloaded)
+
+	if err != nil {
+		restore()
+		return nil, nil, err
+	}
+
+	return reloaded, profiles, nil
+}
+
+// configValues returns the last entry of each top-level key of the given
+// configuration file.
+func configValues(config *configFile) map[string]configEntry {
+	values := map[string]configEntry{}
+
+	for _, entry := range config.flags {
+		values[entry.key] = entry
+	}
+
+	return values
+}
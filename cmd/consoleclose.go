@@ -0,0 +1,59 @@
+package main
+
+import (
+	"log"
+	"sync"
+	"time"
+
+	"golang.org/x/sys/windows"
+)
+
+var procSetConsoleCtrlHandler = kernel32.NewProc("SetConsoleCtrlHandler")
+
+// Console control events ending the process once handled.
+const (
+	ctrlCloseEvent    = 2
+	ctrlLogoffEvent   = 5
+	ctrlShutdownEvent = 6
+)
+
+// Time given to the shutdown when the console is closed. Windows ends the
+// process 5 seconds after the event anyway.
+const consoleCloseTimeout = 4 * time.Second
+
+// onConsoleClose calls f in the background when the console window is closed,
+// the user logs off or Windows shuts down, which end the process as soon as
+// the handler returns. The handler then waits until done is closed, or for
+// consoleCloseTimeout, so that the shutdown started by f can complete.
+//
+// f should stop the motors of the controller first, since the emulated
+// controller may not be removed in time.
+func onConsoleClose(f func(), done <-chan struct{}) {
+	var once sync.Once
+
+	handler := windows.NewCallback(func(event uintptr) uintptr {
+		switch event {
+		case ctrlCloseEvent, ctrlLogoffEvent, ctrlShutdownEvent:
+		default:
+			// Let Ctrl-C reach onInterrupt.
+			return 0
+		}
+
+		once.Do(func() {
+			log.Printf("console closed, shutting down")
+			go f()
+		})
+
+		select {
+		case <-done:
+		case <-time.After(consoleCloseTimeout):
+			log.Printf("warning: shutdown did not complete in %v", consoleCloseTimeout)
+		}
+
+		return 1
+	})
+
+	if r, _, err := procSetConsoleCtrlHandler.Call(handler, 1); r == 0 {
+		log.Printf("warning: cannot handle the console being closed: %v", err)
+	}
+}
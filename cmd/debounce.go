@@ -0,0 +1,36 @@
+package main
+
+import "time"
+
+// buttonDebounce ignores the changes of a button which do not last for its
+// window, e.g. the bounces of a worn button. A change is only acted on once
+// the button stayed in its new state for the window.
+type buttonDebounce struct {
+	window  time.Duration
+	stable  bool
+	current bool
+	since   time.Time
+}
+
+// update records the state of the button at now, and returns its debounced
+// state, along with when to update it again while it is changing.
+func (d *buttonDebounce) update(pressed bool, now time.Time) (bool, time.Time) {
+	if pressed != d.current {
+		d.current, d.since = pressed, now
+	}
+	if d.current == d.stable {
+		return d.stable, time.Time{}
+	}
+	if now.Sub(d.since) < d.window {
+		return d.stable, d.since.Add(d.window)
+	}
+
+	d.stable = d.current
+
+	return d.stable, time.Time{}
+}
+
+// reset releases the button.
+func (d *buttonDebounce) reset() {
+	d.stable, d.current = false, false
+}
@@ -0,0 +1,115 @@
+package main
+
+import (
+	"fmt"
+	"log"
+	"sync/atomic"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Maximum delay added by -added-delay, and maximum number of reports kept
+// meanwhile; the oldest reports are dropped beyond that.
+const (
+	maxAddedDelay     = 1 * time.Second
+	maxDelayedReports = 1024
+)
+
+type delayedReport struct {
+	report stadiacontroller.Xbox360ControllerReport
+	due    time.Time
+}
+
+// delayQueue delays the reports forwarded to the emulated controller by a
+// fixed time after they were received, preserving their order and spacing.
+type delayQueue struct {
+	delay   time.Duration
+	enabled bool
+	reports []delayedReport
+}
+
+// newDelayQueue returns a delayQueue adding the given delay, or nil if it is
+// zero.
+func newDelayQueue(delay time.Duration) (*delayQueue, error) {
+	if delay == 0 {
+		return nil, nil
+	}
+	if delay < 0 || delay > maxAddedDelay {
+		return nil, fmt.Errorf("added delay must be between 0 and %v", maxAddedDelay)
+	}
+
+	q := &delayQueue{delay: delay}
+	q.setEnabled(true)
+
+	return q, nil
+}
+
+// setEnabled starts or stops delaying reports. Reports which are still
+// delayed when disabled must be flushed by the caller.
+func (q *delayQueue) setEnabled(enabled bool) {
+	q.enabled = enabled
+
+	if enabled {
+		atomic.StoreInt64(&stats.addedDelayNanos, int64(q.delay))
+		log.Printf("delaying inputs by %v", q.delay)
+	} else {
+		atomic.StoreInt64(&stats.addedDelayNanos, 0)
+		log.Printf("no longer delaying inputs")
+	}
+}
+
+// push delays the given report, received at the given time.
+func (q *delayQueue) push(report stadiacontroller.Xbox360ControllerReport, received time.Time) {
+	if len(q.reports) == maxDelayedReports {
+		q.reports = append(q.reports[:0], q.reports[1:]...)
+	}
+
+	q.reports = append(q.reports, delayedReport{report, received.Add(q.delay)})
+}
+
+// next returns when the next report is due, or the zero time if no report is
+// delayed.
+func (q *delayQueue) next() time.Time {
+	if len(q.reports) == 0 {
+		return time.Time{}
+	}
+
+	return q.reports[0].due
+}
+
+// pop removes the reports due at the given time, and returns them in order.
+func (q *delayQueue) pop(now time.Time) []stadiacontroller.Xbox360ControllerReport {
+	var due []stadiacontroller.Xbox360ControllerReport
+
+	for len(q.reports) > 0 && !q.reports[0].due.After(now) {
+		due = append(due, q.reports[0].report)
+		q.reports = q.reports[1:]
+	}
+
+	return due
+}
+
+// clear drops the delayed reports.
+func (q *delayQueue) clear() {
+	q.reports = nil
+}
+
+// flush forwards the latest delayed report right away, then releases all
+// inputs, and drops the other reports.
+func (q *delayQueue) flush(pad stadiacontroller.EmulatedController) error {
+	if len(q.reports) == 0 {
+		return nil
+	}
+
+	latest := q.reports[len(q.reports)-1].report
+	neutral := stadiacontroller.NewXbox360ControllerReport()
+
+	q.clear()
+
+	if err := pad.Send(&latest); err != nil {
+		return err
+	}
+
+	return pad.Send(&neutral)
+}
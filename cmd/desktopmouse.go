@@ -0,0 +1,304 @@
+package main
+
+import (
+	"errors"
+	"log"
+	"math"
+	"time"
+	"unsafe"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Constants used to send mouse inputs with SendInput.
+const (
+	inputMouse          = 0
+	mouseEventMove      = 0x0001
+	mouseEventLeftDown  = 0x0002
+	mouseEventLeftUp    = 0x0004
+	mouseEventRightDown = 0x0008
+	mouseEventRightUp   = 0x0010
+	mouseEventWheel     = 0x0800
+	mouseEventHWheel    = 0x1000
+	mouseWheelDelta     = 120
+)
+
+// Deflection of the sticks, as a fraction of their range, ignored by the
+// desktop mouse.
+const desktopMouseDeadzone = 0.15
+
+// Interval at which the cursor moves while a stick is deflected, since reports
+// are only received when the controller changes, and longest interval taken
+// into account, so that the cursor does not jump after a stall.
+const (
+	desktopMouseTick     = 10 * time.Millisecond
+	desktopMouseMaxDelta = 100 * time.Millisecond
+)
+
+// Scrolling speed at full deflection of the left stick, in notches per second.
+const desktopMouseScrollSpeed = 10
+
+// mouseInputData is the MOUSEINPUT structure. Its pointer-sized field aligns
+// it within mouseInput like the union of INPUT.
+type mouseInputData struct {
+	dx        int32
+	dy        int32
+	mouseData uint32
+	flags     uint32
+	time      uint32
+	extraInfo uintptr
+}
+
+// mouseInput is the INPUT structure with its MOUSEINPUT member.
+type mouseInput struct {
+	typ   uint32
+	mouse mouseInputData
+}
+
+// sendMouseInputs sends the given mouse inputs.
+func sendMouseInputs(inputs []mouseInput) {
+	if len(inputs) == 0 {
+		return
+	}
+
+	r, _, err := procSendInput.Call(uintptr(len(inputs)), uintptr(unsafe.Pointer(&inputs[0])), unsafe.Sizeof(inputs[0]))
+
+	if r != uintptr(len(inputs)) {
+		log.Printf("cannot send mouse input: %v", err)
+	}
+}
+
+// desktopMouse turns the controller into a mouse while active, e.g. to use
+// the launcher of a game from the couch: the right stick moves the cursor, the
+// left stick scrolls, A clicks and B right-clicks. The emulated controller
+// is released and no longer updated meanwhile.
+//
+// Buttons held while toggling the mode are ignored until released, so that
+// neither the game nor the desktop receive the end of a press they did not
+// receive the start of.
+type desktopMouse struct {
+	controller *stadiacontroller.StadiaController
+	combo      buttonCombo
+	speed      float64
+	curve      float64
+
+	active bool
+	ticker *time.Ticker
+
+	clicks   stadiacontroller.StadiaButtons
+	held     stadiacontroller.StadiaButtons
+	lastMove time.Time
+
+	// Fractions of pixels and notches not sent yet.
+	x, y, wheel, hwheel float64
+}
+
+// newDesktopMouse returns the mouse mode toggled by the given combination of
+// buttons, or nil if the combination is empty. The cursor moves at speed
+// pixels per second when the stick is fully deflected, and curve is the
+// exponent applied to the deflection.
+func newDesktopMouse(controller *stadiacontroller.StadiaController, combo string, speed, curve float64) (*desktopMouse, error) {
+	buttons, err := stadiacontroller.ParseStadiaButtons(combo)
+
+	if err != nil || buttons == 0 {
+		return nil, err
+	}
+	if speed <= 0 {
+		return nil, errors.New("desktop mouse speed must be positive")
+	}
+	if curve <= 0 {
+		return nil, errors.New("desktop mouse curve must be positive")
+	}
+
+	return &desktopMouse{controller: controller, combo: buttonCombo{buttons: buttons}, speed: speed, curve: curve}, nil
+}
+
+// setActive enables or disables the mode while the given buttons are held,
+// confirming the change with a vibration.
+func (d *desktopMouse) setActive(active bool, buttons stadiacontroller.StadiaButtons, now time.Time) {
+	if active == d.active {
+		return
+	}
+
+	d.active = active
+	d.held = buttons
+	d.release()
+
+	if active {
+		log.Printf("desktop mouse enabled")
+		d.lastMove = now
+		d.x, d.y, d.wheel, d.hwheel = 0, 0, 0, 0
+		d.ticker = time.NewTicker(desktopMouseTick)
+		rumblePulses(d.controller, 1, 100*time.Millisecond)
+	} else {
+		log.Printf("desktop mouse disabled")
+		d.ticker.Stop()
+		d.ticker = nil
+		rumblePulses(d.controller, 2, 100*time.Millisecond)
+	}
+}
+
+// ticks returns the channel on which the cursor should be moved again, or nil
+// while the mode is disabled.
+func (d *desktopMouse) ticks() <-chan time.Time {
+	if d.ticker == nil {
+		return nil
+	}
+
+	return d.ticker.C
+}
+
+// apply hides the buttons held since the last toggle from the given report,
+// and returns whether the report was consumed by the mode, in which case it
+// must not be forwarded.
+func (d *desktopMouse) apply(report *stadiacontroller.Xbox360ControllerReport, now time.Time) bool {
+	d.held &= report.Buttons
+
+	if d.held != 0 {
+		report.Buttons &^= d.held
+		report.Capture = report.Buttons.Has(stadiacontroller.StadiaButtonCapture)
+		report.Assistant = report.Buttons.Has(stadiacontroller.StadiaButtonAssistant)
+		report.SetButtons(report.GetButtons() &^ d.held.Xbox360Buttons())
+	}
+
+	if !d.active {
+		return false
+	}
+
+	d.click(report.Buttons)
+
+	dt := now.Sub(d.lastMove)
+	d.lastMove = now
+
+	if dt > desktopMouseMaxDelta {
+		dt = desktopMouseMaxDelta
+	}
+
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+	var inputs []mouseInput
+
+	// Stick Y axes point up, screen Y axes point down.
+	mx, my := d.deflection(rx, ry)
+	d.x += mx * d.speed * dt.Seconds()
+	d.y -= my * d.speed * dt.Seconds()
+
+	if dx, dy := takeWhole(&d.x), takeWhole(&d.y); dx != 0 || dy != 0 {
+		inputs = append(inputs, mouseInput{typ: inputMouse, mouse: mouseInputData{dx: dx, dy: dy, flags: mouseEventMove}})
+	}
+
+	sx, sy := d.deflection(lx, ly)
+	d.hwheel += sx * desktopMouseScrollSpeed * mouseWheelDelta * dt.Seconds()
+	d.wheel += sy * desktopMouseScrollSpeed * mouseWheelDelta * dt.Seconds()
+
+	if wheel := takeWhole(&d.wheel); wheel != 0 {
+		inputs = append(inputs, mouseInput{typ: inputMouse, mouse: mouseInputData{mouseData: uint32(wheel), flags: mouseEventWheel}})
+	}
+	if hwheel := takeWhole(&d.hwheel); hwheel != 0 {
+		inputs = append(inputs, mouseInput{typ: inputMouse, mouse: mouseInputData{mouseData: uint32(hwheel), flags: mouseEventHWheel}})
+	}
+
+	sendMouseInputs(inputs)
+
+	return true
+}
+
+// deflection returns the deflection of a stick between -1 and 1 on each axis,
+// past a radial deadzone and through the curve.
+func (d *desktopMouse) deflection(x, y int16) (float64, float64) {
+	fx, fy := float64(x)/32768, float64(y)/32768
+	magnitude := math.Hypot(fx, fy)
+
+	if magnitude <= desktopMouseDeadzone {
+		return 0, 0
+	}
+
+	scaled := math.Pow(math.Min((magnitude-desktopMouseDeadzone)/(1-desktopMouseDeadzone), 1), d.curve)
+
+	return fx / magnitude * scaled, fy / magnitude * scaled
+}
+
+// takeWhole removes the whole part of the given value, and returns it.
+func takeWhole(value *float64) int32 {
+	whole := math.Trunc(*value)
+	*value -= whole
+
+	return int32(whole)
+}
+
+// click presses and releases the mouse buttons from the given buttons.
+func (d *desktopMouse) click(buttons stadiacontroller.StadiaButtons) {
+	pressed := buttons & (stadiacontroller.StadiaButtonA | stadiacontroller.StadiaButtonB)
+	changed := pressed ^ d.clicks
+	var inputs []mouseInput
+
+	for _, button := range []struct {
+		button   stadiacontroller.StadiaButtons
+		down, up uint32
+	}{
+		{stadiacontroller.StadiaButtonA, mouseEventLeftDown, mouseEventLeftUp},
+		{stadiacontroller.StadiaButtonB, mouseEventRightDown, mouseEventRightUp},
+	} {
+		if changed&button.button == 0 {
+			continue
+		}
+
+		flags := button.up
+
+		if pressed&button.button != 0 {
+			flags = button.down
+		}
+
+		inputs = append(inputs, mouseInput{typ: inputMouse, mouse: mouseInputData{flags: flags}})
+	}
+
+	d.clicks = pressed
+	sendMouseInputs(inputs)
+}
+
+// release releases the mouse buttons held by the controller.
+func (d *desktopMouse) release() {
+	d.click(0)
+}
+
+// Close releases the mouse buttons and stops moving the cursor.
+func (d *desktopMouse) Close() {
+	d.release()
+
+	if d.ticker != nil {
+		d.ticker.Stop()
+	}
+}
+
+// step returns the step of the desktop mouse, which takes the reports from the
+// emulated controller of the given loop while active, or nil if d is nil.
+func (d *desktopMouse) step(l *inputLoop) *reportStep {
+	if d == nil {
+		return nil
+	}
+
+	apply := func(report *stadiacontroller.Xbox360ControllerReport, now time.Time) (time.Time, error) {
+		if d.combo.update(report.Buttons) {
+			d.setActive(!d.active, report.Buttons, now)
+			l.desktopTicks = d.ticks()
+
+			// Release everything on the emulated controller, which is no
+			// longer updated until the desktop mouse is disabled.
+			if d.active && l.state.paused {
+				l.clearDelayed()
+				l.state.report = stadiacontroller.NewXbox360ControllerReport()
+			} else if d.active {
+				if err := l.release(); err != nil {
+					return time.Time{}, err
+				}
+			}
+		}
+		if d.apply(report, now) {
+			return time.Time{}, errSkipReport
+		}
+
+		return time.Time{}, nil
+	}
+
+	return &reportStep{apply: apply, reset: d.release}
+}
@@ -0,0 +1,78 @@
+package main
+
+import (
+	"fmt"
+	"strconv"
+	"strings"
+)
+
+// parseDeviceID parses a vendor or product ID, in hexadecimal with or without
+// a leading "0x".
+func parseDeviceID(s string) (uint16, error) {
+	s = strings.TrimSpace(s)
+	digits := strings.TrimPrefix(strings.TrimPrefix(s, "0x"), "0X")
+
+	id, err := strconv.ParseUint(digits, 16, 16)
+
+	if err != nil || digits == "" {
+		return 0, fmt.Errorf("invalid ID %q, expected a hexadecimal number from 0x0001 to 0xffff", s)
+	}
+	if id == 0 {
+		return 0, fmt.Errorf("invalid ID %q, which cannot be zero", s)
+	}
+
+	return uint16(id), nil
+}
+
+// vendorIDFlag is a flag given as a hexadecimal vendor ID.
+type vendorIDFlag uint16
+
+func (f *vendorIDFlag) String() string {
+	if f == nil || *f == 0 {
+		return ""
+	}
+
+	return fmt.Sprintf("0x%04x", uint16(*f))
+}
+
+func (f *vendorIDFlag) Set(value string) error {
+	id, err := parseDeviceID(value)
+
+	if err != nil {
+		return err
+	}
+
+	*f = vendorIDFlag(id)
+
+	return nil
+}
+
+// productIDsFlag is a flag given as comma-separated hexadecimal product IDs,
+// which can be given several times.
+type productIDsFlag []uint16
+
+func (f *productIDsFlag) String() string {
+	var ids []string
+
+	if f != nil {
+		for _, id := range *f {
+			ids = append(ids, fmt.Sprintf("0x%04x", id))
+		}
+	}
+
+	return strings.Join(ids, ",")
+}
+
+func (f *productIDsFlag) Set(value string) error {
+	for _, entry := range strings.Split(value, ",") {
+		id, err := parseDeviceID(entry)
+
+		if err != nil {
+			return err
+		}
+
+		*f = append(*f, id)
+	}
+
+	return nil
+}
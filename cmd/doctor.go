@@ -0,0 +1,232 @@
+package main
+
+import (
+	"errors"
+	"fmt"
+	"os"
+	"text/tabwriter"
+	"time"
+
+	"github.com/71/stadiacontroller"
+	"golang.org/x/sys/windows"
+)
+
+// Name of the service of the ViGEmBus driver, maximum time to wait for a
+// report of the controller, and duration of the test vibration.
+const (
+	vigemBusService    = "ViGEmBus"
+	doctorReadTimeout  = 2 * time.Second
+	doctorRumbleLength = 200 * time.Millisecond
+)
+
+// Hint given when ViGEm is missing.
+const vigemInstallHint = "install ViGEmBus from https://github.com/ViGEm/ViGEmBus/releases"
+
+// runDoctor checks each piece needed to emulate the controller, from the HID
+// device to the ViGEm bus, and prints a summary with a hint for each failure.
+// With "no-rumble", the motors are not checked. It returns an error if a check
+// failed, so that the exit code reflects the result.
+func runDoctor(args []string) error {
+	rumble := true
+
+	switch {
+	case len(args) == 0:
+	case len(args) == 1 && args[0] == "no-rumble":
+		rumble = false
+	default:
+		return fmt.Errorf("usage: doctor [no-rumble]")
+	}
+
+	var results []selfTestResult
+
+	results = append(results, doctorController(rumble)...)
+	results = append(results, doctorVigem()...)
+
+	w := tabwriter.NewWriter(os.Stdout, 0, 0, 2, ' ', 0)
+
+	for _, result := range results {
+		fmt.Fprintf(w, "%s\t%s\t%s\n", result.outcome, result.check, result.detail)
+	}
+
+	w.Flush()
+
+	for _, result := range results {
+		if result.outcome == selfTestFail {
+			return errors.New("some checks failed")
+		}
+	}
+
+	return nil
+}
+
+// doctorController checks that the controller is found, opened and read from,
+// and optionally that it vibrates.
+func doctorController(rumble bool) []selfTestResult {
+	options, err := controllerOptions()
+
+	if err != nil {
+		return []selfTestResult{{"controller", selfTestFail, err.Error()}}
+	}
+
+	devices, err := options.FindDevices()
+
+	switch {
+	case err != nil:
+		return []selfTestResult{{"HID devices", selfTestFail, fmt.Sprintf("cannot list devices (%v)", err)}}
+	case len(devices) == 0:
+		return []selfTestResult{{"HID devices", selfTestFail, "no controller found: plug it in over USB or pair it over Bluetooth, and see list-devices all"}}
+	}
+
+	results := []selfTestResult{{"HID devices", selfTestPass, fmt.Sprintf("%d controller(s) found, e.g. %s", len(devices), devices[0].Path)}}
+
+	controller, err := stadiacontroller.NewStadiaControllerWithOptions(options)
+
+	if err != nil {
+		return append(results, selfTestResult{"controller", selfTestFail, err.Error()})
+	}
+
+	defer controller.Close()
+
+	events := controller.Events()
+	timeout := time.After(selfTestConnectTimeout)
+
+	for connected := false; !connected; {
+		select {
+		case event, ok := <-events:
+			if !ok {
+				return append(results, selfTestResult{"open", selfTestFail, "the controller could not be opened"})
+			}
+
+			switch event := event.(type) {
+			case stadiacontroller.ConnectedEvent:
+				connected = true
+				results = append(results, selfTestResult{"open", selfTestPass, fmt.Sprintf("over %v", event.Transport)})
+			case stadiacontroller.ErrorEvent:
+				if errors.Is(event.Err, stadiacontroller.ErrAccessDenied) {
+					return append(results, selfTestResult{"open", selfTestFail, "access denied: close Steam or other programs using the controller"})
+				}
+			}
+
+		case <-timeout:
+			return append(results, selfTestResult{"open", selfTestFail, "the controller could not be opened: see list-devices"})
+		}
+	}
+
+	timeout = time.After(doctorReadTimeout)
+
+read:
+	for {
+		select {
+		case event, ok := <-events:
+			if !ok {
+				return append(results, selfTestResult{"read", selfTestFail, "the controller was closed"})
+			}
+
+			switch event := event.(type) {
+			case stadiacontroller.ReportEvent:
+				results = append(results, selfTestResult{"read", selfTestPass, ""})
+				break read
+			case stadiacontroller.ErrorEvent:
+				if errors.Is(event.Err, stadiacontroller.ErrMalformedReport) {
+					return append(results, selfTestResult{"read", selfTestFail, "unknown report format: see report-map, and file an issue with the output of controller-info"})
+				}
+			case stadiacontroller.DisconnectedEvent:
+				return append(results, selfTestResult{"read", selfTestFail, "the controller was disconnected: check the cable or the Bluetooth connection"})
+			}
+
+		case <-timeout:
+			return append(results, selfTestResult{"read", selfTestFail, "no report received: reconnect the controller"})
+		}
+	}
+
+	if !rumble {
+		return results
+	}
+
+	err = controller.Vibrate(0, 255)
+
+	if err == nil {
+		time.Sleep(doctorRumbleLength)
+		err = controller.Vibrate(0, 0)
+	}
+
+	if err != nil {
+		return append(results, selfTestResult{"rumble", selfTestFail, fmt.Sprintf("vibration rejected (%s): see test-rumble", describeWriteError(err))})
+	}
+
+	return append(results, selfTestResult{"rumble", selfTestPass, "the controller should have vibrated briefly"})
+}
+
+// doctorVigem checks that the ViGEmBus driver is installed and running, and
+// that an emulated controller can be plugged into it.
+func doctorVigem() []selfTestResult {
+	var results []selfTestResult
+
+	if running, err := serviceRunning(vigemBusService); err != nil {
+		results = append(results, selfTestResult{"ViGEmBus driver", selfTestFail, fmt.Sprintf("not found (%v): %s", err, vigemInstallHint)})
+	} else if !running {
+		results = append(results, selfTestResult{"ViGEmBus driver", selfTestFail, "installed but not running: restart the computer, or reinstall ViGEmBus"})
+	} else {
+		results = append(results, selfTestResult{"ViGEmBus driver", selfTestPass, ""})
+	}
+
+	emulator, err := stadiacontroller.NewEmulator(func(stadiacontroller.Vibration) {})
+
+	if err != nil {
+		return append(results, selfTestResult{"ViGEm client", selfTestFail, fmt.Sprintf("cannot connect to the bus (%v): %s, and keep ViGEmClient.dll next to the program", err, vigemInstallHint)})
+	}
+
+	defer emulator.Close()
+
+	results = append(results, selfTestResult{"ViGEm client", selfTestPass, ""})
+
+	pad, err := emulator.CreateXbox360Controller()
+
+	if err == nil {
+		defer pad.Close()
+
+		if err = pad.Connect(); err == nil {
+			err = pad.Disconnect()
+		}
+	}
+
+	if err != nil {
+		return append(results, selfTestResult{"emulated controller", selfTestFail, fmt.Sprintf("cannot plug in a controller (%v): reinstall ViGEmBus", err)})
+	}
+
+	return append(results, selfTestResult{"emulated controller", selfTestPass, "plugged in and removed a test controller"})
+}
+
+// serviceRunning returns whether the service with the given name is running,
+// or an error if it is not installed.
+func serviceRunning(name string) (bool, error) {
+	manager, err := windows.OpenSCManager(nil, nil, windows.SC_MANAGER_CONNECT)
+
+	if err != nil {
+		return false, err
+	}
+
+	defer windows.CloseServiceHandle(manager)
+
+	serviceName, err := windows.UTF16PtrFromString(name)
+
+	if err != nil {
+		return false, err
+	}
+
+	service, err := windows.OpenService(manager, serviceName, windows.SERVICE_QUERY_STATUS)
+
+	if err != nil {
+		return false, err
+	}
+
+	defer windows.CloseServiceHandle(service)
+
+	var status windows.SERVICE_STATUS
+
+	if err := windows.QueryServiceStatus(service, &status); err != nil {
+		return false, err
+	}
+
+	return status.CurrentState == windows.SERVICE_RUNNING, nil
+}
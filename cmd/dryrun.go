@@ -0,0 +1,83 @@
+package main
+
+import (
+	"fmt"
+	"strings"
+
+	"github.com/71/stadiacontroller"
+)
+
+// flagButtons are printed separately from the other buttons by reportPrinter,
+// like the D-pad.
+const flagButtons = stadiacontroller.StadiaButtonAssistant | stadiacontroller.StadiaButtonCapture
+
+// reportPrinter replaces the emulated controller with -dry-run: it prints the
+// reports which would be sent to games when they change, so that the
+// controller can be checked without ViGEm.
+type reportPrinter struct {
+	last    stadiacontroller.Xbox360ControllerReport
+	printed bool
+}
+
+func (p *reportPrinter) Connect() error    { return nil }
+func (p *reportPrinter) Disconnect() error { return nil }
+func (p *reportPrinter) Close() error      { return nil }
+
+// Send prints the given report, unless it is the same as the previous one.
+func (p *reportPrinter) Send(report *stadiacontroller.Xbox360ControllerReport) error {
+	if p.printed && *report == p.last {
+		return nil
+	}
+
+	p.last, p.printed = *report, true
+
+	buttons := (report.Buttons &^ (dpadButtons | flagButtons)).String()
+
+	if buttons == "" {
+		buttons = "none"
+	}
+
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	fmt.Printf("buttons=%s dpad=%s left=(%d,%d) right=(%d,%d) l2=%d r2=%d assistant=%v capture=%v\n",
+		buttons, dpadDirection(report.Buttons), lx, ly, rx, ry,
+		report.GetLeftTrigger(), report.GetRightTrigger(), report.Assistant, report.Capture)
+
+	return nil
+}
+
+// discardController replaces the emulated controller with -dry-run and
+// -output json, which writes the reports itself.
+type discardController struct{}
+
+func (discardController) Connect() error                                       { return nil }
+func (discardController) Disconnect() error                                    { return nil }
+func (discardController) Close() error                                         { return nil }
+func (discardController) Send(*stadiacontroller.Xbox360ControllerReport) error { return nil }
+
+// dpadDirection returns the direction of the D-pad in the given buttons, e.g.
+// "up-left", or "none".
+func dpadDirection(buttons stadiacontroller.StadiaButtons) string {
+	var directions []string
+
+	for _, direction := range []struct {
+		button stadiacontroller.StadiaButtons
+		name   string
+	}{
+		{stadiacontroller.StadiaButtonDpadUp, "up"},
+		{stadiacontroller.StadiaButtonDpadDown, "down"},
+		{stadiacontroller.StadiaButtonDpadLeft, "left"},
+		{stadiacontroller.StadiaButtonDpadRight, "right"},
+	} {
+		if buttons.Has(direction.button) {
+			directions = append(directions, direction.name)
+		}
+	}
+
+	if len(directions) == 0 {
+		return "none"
+	}
+
+	return strings.Join(directions, "-")
+}
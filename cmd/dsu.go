@@ -0,0 +1,349 @@
+package main
+
+import (
+	"encoding/binary"
+	"hash/crc32"
+	"log"
+	"math/rand"
+	"net"
+	"sync"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Constants of the DSU (cemuhook) protocol, as described in
+// https://v1993.github.io/cemuhook-protocol/.
+const (
+	dsuProtocolVersion = 1001
+	dsuHeaderSize      = 16
+
+	dsuMessageVersion  = 0x100000
+	dsuMessagePorts    = 0x100001
+	dsuMessagePadData  = 0x100002
+	dsuClientTimeout   = 5 * time.Second
+	dsuMaxPacketSize   = 1024
+	dsuSlotConnected   = 2
+	dsuModelNoMotion   = 0
+	dsuConnectionUSB   = 1
+	dsuBatteryFull     = 0x05
+	dsuRegisterBySlot  = 1
+	dsuRegisterByMAC   = 2
+	dsuPadDataSize     = 100
+	dsuPortInfoSize    = 32
+	dsuVersionInfoSize = 22
+)
+
+// The controller is always exposed in the first slot, with this MAC address.
+var dsuMAC = [6]byte{0x00, 0x00, 0x5D, 0x1A, 0x00, 0x01}
+
+// DSU buttons, in the order of the two button bytes of a pad data message,
+// from the most significant bit of the first byte to the least significant
+// bit of the second byte. Face buttons are named after their position.
+var dsuButtons = [16]int{
+	stadiacontroller.Xbox360ControllerButtonLeft,
+	stadiacontroller.Xbox360ControllerButtonDown,
+	stadiacontroller.Xbox360ControllerButtonRight,
+	stadiacontroller.Xbox360ControllerButtonUp,
+	stadiacontroller.Xbox360ControllerButtonStart,
+	stadiacontroller.Xbox360ControllerButtonRightThumb,
+	stadiacontroller.Xbox360ControllerButtonLeftThumb,
+	stadiacontroller.Xbox360ControllerButtonBack,
+	stadiacontroller.Xbox360ControllerButtonX, // Left.
+	stadiacontroller.Xbox360ControllerButtonA, // Bottom.
+	stadiacontroller.Xbox360ControllerButtonB, // Right.
+	stadiacontroller.Xbox360ControllerButtonY, // Top.
+	stadiacontroller.Xbox360ControllerButtonRightShoulder,
+	stadiacontroller.Xbox360ControllerButtonLeftShoulder,
+	-1, // R2, set from the trigger.
+	-1, // L2, set from the trigger.
+}
+
+// dsuServer streams the reports of the controller to DSU clients, such as
+// Dolphin, Cemu or yuzu.
+type dsuServer struct {
+	conn *net.UDPConn
+	id   uint32
+
+	mu        sync.Mutex
+	clients   map[string]dsuClient
+	connected bool
+	packet    uint32
+}
+
+type dsuClient struct {
+	addr     *net.UDPAddr
+	lastSeen time.Time
+}
+
+// newDSUServer starts a DSU server listening on the given port.
+func newDSUServer(port int) (*dsuServer, error) {
+	conn, err := net.ListenUDP("udp", &net.UDPAddr{IP: net.IPv4(127, 0, 0, 1), Port: port})
+
+	if err != nil {
+		return nil, err
+	}
+
+	s := &dsuServer{
+		conn:    conn,
+		id:      rand.New(rand.NewSource(time.Now().UnixNano())).Uint32(),
+		clients: map[string]dsuClient{},
+	}
+
+	go s.serve()
+
+	log.Printf("DSU server listening on %s", conn.LocalAddr())
+
+	return s, nil
+}
+
+// Close stops the server and closes its socket.
+func (s *dsuServer) Close() error {
+	return s.conn.Close()
+}
+
+// setConnected sets whether the controller is connected, as reported to
+// clients.
+func (s *dsuServer) setConnected(connected bool) {
+	s.mu.Lock()
+	s.connected = connected
+	s.mu.Unlock()
+}
+
+// serve answers requests of clients until the server is closed.
+func (s *dsuServer) serve() {
+	buf := make([]byte, dsuMaxPacketSize)
+
+	for {
+		n, addr, err := s.conn.ReadFromUDP(buf)
+
+		if err != nil {
+			if ne, ok := err.(net.Error); ok && ne.Temporary() {
+				continue
+			}
+			return
+		}
+
+		message, payload, ok := decodeDSUPacket(buf[:n])
+
+		if !ok {
+			continue
+		}
+
+		switch message {
+		case dsuMessageVersion:
+			s.sendTo(addr, s.versionInfo())
+
+		case dsuMessagePorts:
+			if len(payload) < 4 {
+				continue
+			}
+
+			count := int(binary.LittleEndian.Uint32(payload))
+
+			if count < 0 || count > 4 || len(payload) < 4+count {
+				continue
+			}
+
+			for _, slot := range payload[4 : 4+count] {
+				s.sendTo(addr, s.portInfo(slot))
+			}
+
+		case dsuMessagePadData:
+			if len(payload) < 8 {
+				continue
+			}
+
+			flags, slot, mac := payload[0], payload[1], payload[2:8]
+
+			if flags&dsuRegisterBySlot != 0 && slot != 0 {
+				continue
+			}
+			if flags&dsuRegisterByMAC != 0 && string(mac) != string(dsuMAC[:]) {
+				continue
+			}
+
+			s.mu.Lock()
+			s.clients[addr.String()] = dsuClient{addr: addr, lastSeen: time.Now()}
+			s.mu.Unlock()
+		}
+	}
+}
+
+// send sends the given report to all registered clients.
+func (s *dsuServer) send(report *stadiacontroller.Xbox360ControllerReport) {
+	s.mu.Lock()
+	defer s.mu.Unlock()
+
+	if len(s.clients) == 0 {
+		return
+	}
+
+	s.packet++
+
+	packet := s.padData(report, s.packet)
+	now := time.Now()
+
+	for key, client := range s.clients {
+		if now.Sub(client.lastSeen) > dsuClientTimeout {
+			delete(s.clients, key)
+			continue
+		}
+
+		s.sendTo(client.addr, packet)
+	}
+}
+
+func (s *dsuServer) sendTo(addr *net.UDPAddr, packet []byte) {
+	if _, err := s.conn.WriteToUDP(packet, addr); err != nil {
+		log.Printf("cannot send DSU packet to %s: %v", addr, err)
+	}
+}
+
+func (s *dsuServer) versionInfo() []byte {
+	packet := make([]byte, dsuVersionInfoSize)
+	binary.LittleEndian.PutUint16(packet[20:], dsuProtocolVersion)
+
+	return s.encode(packet, dsuMessageVersion)
+}
+
+func (s *dsuServer) portInfo(slot byte) []byte {
+	packet := make([]byte, dsuPortInfoSize)
+	packet[20] = slot
+
+	if slot == 0 {
+		s.mu.Lock()
+		s.putSlotInfo(packet[20:])
+		s.mu.Unlock()
+	}
+
+	return s.encode(packet, dsuMessagePorts)
+}
+
+// putSlotInfo writes the information about the first slot shared by port info
+// and pad data messages.
+func (s *dsuServer) putSlotInfo(b []byte) {
+	b[0] = 0
+
+	if s.connected {
+		b[1] = dsuSlotConnected
+	}
+
+	b[2] = dsuModelNoMotion
+	b[3] = dsuConnectionUSB
+	copy(b[4:10], dsuMAC[:])
+	b[10] = dsuBatteryFull
+}
+
+func (s *dsuServer) padData(report *stadiacontroller.Xbox360ControllerReport, number uint32) []byte {
+	packet := make([]byte, dsuPadDataSize)
+	b := packet[20:]
+
+	s.putSlotInfo(b)
+
+	if s.connected {
+		b[11] = 1
+	}
+
+	binary.LittleEndian.PutUint32(b[12:], number)
+
+	buttons := report.GetButtons()
+	leftTrigger, rightTrigger := report.GetLeftTrigger(), report.GetRightTrigger()
+	var dsuButtonBits uint16
+
+	for i, button := range dsuButtons {
+		if button >= 0 && buttons&(1<<button) != 0 {
+			dsuButtonBits |= 0x8000 >> i
+		}
+	}
+	if rightTrigger > 0 {
+		dsuButtonBits |= 0x0002
+	}
+	if leftTrigger > 0 {
+		dsuButtonBits |= 0x0001
+	}
+
+	binary.BigEndian.PutUint16(b[16:], dsuButtonBits)
+
+	if buttons&(1<<stadiacontroller.Xbox360ControllerButtonGuide) != 0 {
+		b[18] = 1
+	}
+
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	b[20] = dsuAxis(lx)
+	b[21] = dsuAxis(ly)
+	b[22] = dsuAxis(rx)
+	b[23] = dsuAxis(ry)
+
+	// Analog D-pad and face buttons, in the same order as the button bits
+	// above.
+	for i, button := range dsuButtons[:4] {
+		b[24+i] = dsuAnalog(buttons, button)
+	}
+	for i, button := range dsuButtons[8:14] {
+		b[28+i] = dsuAnalog(buttons, button)
+	}
+
+	b[34] = rightTrigger
+	b[35] = leftTrigger
+
+	// Touch and motion data are left zeroed.
+
+	return s.encode(packet, dsuMessagePadData)
+}
+
+func dsuAxis(value int16) byte {
+	return byte((int32(value) + 0x8000) >> 8)
+}
+
+func dsuAnalog(buttons uint16, button int) byte {
+	if buttons&(1<<button) != 0 {
+		return 0xFF
+	}
+	return 0
+}
+
+// encode fills the header of the given server packet, whose payload starts
+// after the message type at offset 20.
+func (s *dsuServer) encode(packet []byte, message uint32) []byte {
+	copy(packet, "DSUS")
+	binary.LittleEndian.PutUint16(packet[4:], dsuProtocolVersion)
+	binary.LittleEndian.PutUint16(packet[6:], uint16(len(packet)-dsuHeaderSize))
+	binary.LittleEndian.PutUint32(packet[12:], s.id)
+	binary.LittleEndian.PutUint32(packet[16:], message)
+	binary.LittleEndian.PutUint32(packet[8:], crc32.ChecksumIEEE(packet))
+
+	return packet
+}
+
+// decodeDSUPacket validates the header of the given client packet, and returns
+// its message type and payload.
+func decodeDSUPacket(packet []byte) (message uint32, payload []byte, ok bool) {
+	if len(packet) < dsuHeaderSize+4 || string(packet[:4]) != "DSUC" {
+		return 0, nil, false
+	}
+	if binary.LittleEndian.Uint16(packet[4:]) > dsuProtocolVersion {
+		return 0, nil, false
+	}
+
+	length := int(binary.LittleEndian.Uint16(packet[6:]))
+
+	if length < 4 || dsuHeaderSize+length > len(packet) {
+		return 0, nil, false
+	}
+
+	packet = packet[:dsuHeaderSize+length]
+	checksum := binary.LittleEndian.Uint32(packet[8:])
+
+	copied := make([]byte, len(packet))
+	copy(copied, packet)
+	binary.LittleEndian.PutUint32(copied[8:], 0)
+
+	if crc32.ChecksumIEEE(copied) != checksum {
+		return 0, nil, false
+	}
+
+	return binary.LittleEndian.Uint32(packet[16:]), packet[20:], true
+}
@@ -0,0 +1,233 @@
+package main
+
+import (
+	"encoding/binary"
+	"hash/crc32"
+	"net"
+	"testing"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// dsuClientPacket encodes a client message as described by the protocol.
+func dsuClientPacket(message uint32, payload []byte) []byte {
+	packet := make([]byte, 20+len(payload))
+	copy(packet, "DSUC")
+	binary.LittleEndian.PutUint16(packet[4:], 1001)
+	binary.LittleEndian.PutUint16(packet[6:], uint16(4+len(payload)))
+	binary.LittleEndian.PutUint32(packet[12:], 0xC11E17)
+	binary.LittleEndian.PutUint32(packet[16:], message)
+	copy(packet[20:], payload)
+	binary.LittleEndian.PutUint32(packet[8:], crc32.ChecksumIEEE(packet))
+
+	return packet
+}
+
+// checkDSUServerPacket checks the header of a packet sent by the server, and
+// returns its payload.
+func checkDSUServerPacket(t *testing.T, packet []byte, message uint32, size int) []byte {
+	t.Helper()
+
+	if len(packet) != size {
+		t.Fatalf("packet is %d bytes long, expected %d", len(packet), size)
+	}
+	if string(packet[:4]) != "DSUS" || binary.LittleEndian.Uint16(packet[4:]) != 1001 {
+		t.Errorf("packet starts with %q and version %d", packet[:4], binary.LittleEndian.Uint16(packet[4:]))
+	}
+	if length := binary.LittleEndian.Uint16(packet[6:]); int(length) != size-16 {
+		t.Errorf("packet has length %d, expected %d", length, size-16)
+	}
+
+	zeroed := append([]byte(nil), packet...)
+	binary.LittleEndian.PutUint32(zeroed[8:], 0)
+
+	if checksum := binary.LittleEndian.Uint32(packet[8:]); checksum != crc32.ChecksumIEEE(zeroed) {
+		t.Errorf("packet has checksum %08x, expected %08x", checksum, crc32.ChecksumIEEE(zeroed))
+	}
+	if m := binary.LittleEndian.Uint32(packet[16:]); m != message {
+		t.Errorf("packet has message type %#x, expected %#x", m, message)
+	}
+
+	return packet[20:]
+}
+
+func TestDecodeDSUPacket(t *testing.T) {
+	valid := dsuClientPacket(0x100001, []byte{1, 0, 0, 0, 0})
+
+	message, payload, ok := decodeDSUPacket(valid)
+
+	if !ok || message != 0x100001 || string(payload) != "\x01\x00\x00\x00\x00" {
+		t.Errorf("decodeDSUPacket of a port request returned %#x, %x, %v", message, payload, ok)
+	}
+
+	// Bytes after the length given in the header are ignored.
+	if _, payload, ok := decodeDSUPacket(append(valid, 0xff, 0xff)); !ok || len(payload) != 5 {
+		t.Errorf("decodeDSUPacket of a padded packet returned %x, %v", payload, ok)
+	}
+
+	invalid := map[string]func(packet []byte) []byte{
+		"server magic": func(p []byte) []byte {
+			p[3] = 'S'
+			return p
+		},
+		"newer version": func(p []byte) []byte {
+			binary.LittleEndian.PutUint16(p[4:], 1002)
+			return p
+		},
+		"wrong checksum": func(p []byte) []byte {
+			p[20]++
+			return p
+		},
+		"length past the end": func(p []byte) []byte {
+			binary.LittleEndian.PutUint16(p[6:], 10)
+			return p
+		},
+		"length without message type": func(p []byte) []byte {
+			binary.LittleEndian.PutUint16(p[6:], 3)
+			return p
+		},
+		"truncated header": func(p []byte) []byte {
+			return p[:19]
+		},
+	}
+
+	for name, corrupt := range invalid {
+		packet := corrupt(append([]byte(nil), valid...))
+
+		if _, _, ok := decodeDSUPacket(packet); ok {
+			t.Errorf("decodeDSUPacket accepted a packet with %s", name)
+		}
+	}
+}
+
+func TestDSUPadData(t *testing.T) {
+	s := &dsuServer{id: 0x12345678, connected: true}
+
+	var report stadiacontroller.Xbox360ControllerReport
+	report.SetButton(stadiacontroller.Xbox360ControllerButtonLeft)
+	report.SetButton(stadiacontroller.Xbox360ControllerButtonStart)
+	report.SetButton(stadiacontroller.Xbox360ControllerButtonA)
+	report.SetButton(stadiacontroller.Xbox360ControllerButtonY)
+	report.SetButton(stadiacontroller.Xbox360ControllerButtonLeftShoulder)
+	report.SetButton(stadiacontroller.Xbox360ControllerButtonGuide)
+	report.SetLeftTrigger(0x80)
+	report.SetLeftThumb(-32768, 32767)
+	report.SetRightThumb(0, -256)
+
+	b := checkDSUServerPacket(t, s.padData(&report, 7), 0x100002, 100)
+
+	// Bytes not listed, e.g. the right trigger, touch and motion data, are
+	// zero.
+	expected := map[int]byte{
+		0:  0x00, // Slot.
+		1:  0x02, // Connected.
+		3:  0x01, // USB.
+		6:  0x5D, // MAC address.
+		7:  0x1A,
+		9:  0x01,
+		10: 0x05, // Battery full.
+		11: 0x01, // Active.
+		12: 0x07, // Packet number.
+		16: 0x88, // D-pad left and Options (Start).
+		17: 0x55, // Bottom and top face buttons, L1 and L2.
+		18: 0x01, // Home (Guide).
+		21: 0xFF, // Left stick, whose X is 0.
+		22: 0x80, // Right stick.
+		23: 0x7F,
+		24: 0xFF, // Analog D-pad left.
+		29: 0xFF, // Analog bottom face button.
+		31: 0xFF, // Analog top face button.
+		33: 0xFF, // Analog L1.
+		35: 0x80, // Analog L2.
+	}
+
+	for i := range b {
+		if b[i] != expected[i] {
+			t.Errorf("byte %d of pad data is %#04x, expected %#04x", i, b[i], expected[i])
+		}
+	}
+
+	if id := binary.LittleEndian.Uint32(s.padData(&report, 7)[12:]); id != 0x12345678 {
+		t.Errorf("pad data was sent by server %08x", id)
+	}
+}
+
+func TestDSUServer(t *testing.T) {
+	s, err := newDSUServer(0)
+
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer s.Close()
+
+	client, err := net.DialUDP("udp", nil, s.conn.LocalAddr().(*net.UDPAddr))
+
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer client.Close()
+
+	receive := func() []byte {
+		t.Helper()
+
+		buf := make([]byte, dsuMaxPacketSize)
+		client.SetReadDeadline(time.Now().Add(5 * time.Second))
+		n, err := client.Read(buf)
+
+		if err != nil {
+			t.Fatal(err)
+		}
+
+		return buf[:n]
+	}
+
+	client.Write(dsuClientPacket(0x100000, nil))
+
+	if version := checkDSUServerPacket(t, receive(), 0x100000, 22); binary.LittleEndian.Uint16(version) != 1001 {
+		t.Errorf("server reported protocol version %d", binary.LittleEndian.Uint16(version))
+	}
+
+	// Only the first slot has a controller, which is not connected yet.
+	client.Write(dsuClientPacket(0x100001, []byte{2, 0, 0, 0, 0, 1}))
+
+	for slot := byte(0); slot < 2; slot++ {
+		info := checkDSUServerPacket(t, receive(), 0x100001, 32)
+
+		if info[0] != slot || info[1] != 0 {
+			t.Errorf("slot %d was reported as slot %d in state %d", slot, info[0], info[1])
+		}
+	}
+
+	s.setConnected(true)
+
+	// Pad data is only sent once requested, here by MAC address.
+	var report stadiacontroller.Xbox360ControllerReport
+	report.SetButton(stadiacontroller.Xbox360ControllerButtonB)
+
+	client.Write(dsuClientPacket(0x100002, append([]byte{2, 0}, dsuMAC[:]...)))
+	waitForDSUClient(t, s)
+	s.send(&report)
+
+	data := checkDSUServerPacket(t, receive(), 0x100002, 100)
+
+	if data[1] != 2 || data[17] != 0b0010_0000 || data[30] != 0xff {
+		t.Errorf("pad data of a connected controller with B pressed is %x", data)
+	}
+}
+
+func waitForDSUClient(t *testing.T, s *dsuServer) {
+	t.Helper()
+
+	for deadline := time.Now().Add(5 * time.Second); time.Now().Before(deadline); time.Sleep(time.Millisecond) {
+		s.mu.Lock()
+		registered := len(s.clients) != 0
+		s.mu.Unlock()
+
+		if registered {
+			return
+		}
+	}
+
+	t.Fatalf("client did not register")
+}
@@ -0,0 +1,126 @@
+package main
+
+import (
+	"strings"
+	"time"
+	"unsafe"
+
+	"golang.org/x/sys/windows"
+)
+
+var procGetWindowTextW = user32.NewProc("GetWindowTextW")
+
+// Interval at which the foreground window is checked by -only-when-focused.
+const focusPollInterval = 250 * time.Millisecond
+
+// stringsFlag is a flag which can be repeated, keeping every value.
+type stringsFlag []string
+
+func (f *stringsFlag) String() string {
+	if f == nil {
+		return ""
+	}
+
+	return strings.Join(*f, ",")
+}
+
+func (f *stringsFlag) Set(value string) error {
+	*f = append(*f, value)
+
+	return nil
+}
+
+// focusWatcher reports whether the foreground window belongs to one of the
+// applications given to -only-when-focused, matched by the name of their
+// executable or by a part of the title of their window, ignoring case.
+type focusWatcher struct {
+	// Receives whether a matching window is in the foreground when it
+	// changes, starting with the current state.
+	changes chan bool
+
+	targets []string
+	done    chan struct{}
+}
+
+// newFocusWatcher starts watching the foreground window, if targets is not
+// empty.
+func newFocusWatcher(targets []string) *focusWatcher {
+	if len(targets) == 0 {
+		return nil
+	}
+
+	w := &focusWatcher{
+		changes: make(chan bool, 1),
+		done:    make(chan struct{}),
+	}
+
+	for _, target := range targets {
+		w.targets = append(w.targets, strings.ToLower(target))
+	}
+
+	go w.run()
+
+	return w
+}
+
+func (w *focusWatcher) run() {
+	ticker := time.NewTicker(focusPollInterval)
+	defer ticker.Stop()
+
+	var focused, reported bool
+
+	for {
+		executable, _ := foregroundExecutable()
+		matching := w.matches(strings.ToLower(executable), strings.ToLower(foregroundTitle()))
+
+		if matching != focused || !reported {
+			focused, reported = matching, true
+
+			// Only the latest state matters if the input loop is behind.
+			select {
+			case <-w.changes:
+			default:
+			}
+
+			w.changes <- focused
+		}
+
+		select {
+		case <-ticker.C:
+		case <-w.done:
+			return
+		}
+	}
+}
+
+// matches returns whether the given executable name or window title, both in
+// lower case, match a target.
+func (w *focusWatcher) matches(executable, title string) bool {
+	for _, target := range w.targets {
+		if executable == target || (title != "" && strings.Contains(title, target)) {
+			return true
+		}
+	}
+
+	return false
+}
+
+func (w *focusWatcher) Close() {
+	close(w.done)
+}
+
+// foregroundTitle returns the title of the foreground window, or an empty
+// string if there is none.
+func foregroundTitle() string {
+	hwnd, _, _ := procGetForegroundWindow.Call()
+
+	if hwnd == 0 {
+		return ""
+	}
+
+	var title [256]uint16
+
+	n, _, _ := procGetWindowTextW.Call(hwnd, uintptr(unsafe.Pointer(&title[0])), uintptr(len(title)))
+
+	return windows.UTF16ToString(title[:n])
+}
@@ -0,0 +1,154 @@
+package main
+
+import (
+	"errors"
+	"path/filepath"
+	"strings"
+	"time"
+	"unsafe"
+
+	"golang.org/x/sys/windows"
+)
+
+var (
+	procGetForegroundWindow        = user32.NewProc("GetForegroundWindow")
+	procGetWindowThreadProcessId   = user32.NewProc("GetWindowThreadProcessId")
+	procQueryFullProcessImageNameW = kernel32.NewProc("QueryFullProcessImageNameW")
+)
+
+const processQueryLimitedInformation = 0x1000
+
+// Interval at which the foreground window is checked.
+const foregroundPollInterval = 1 * time.Second
+
+// foregroundWatcher selects the profile matching the executable of the
+// foreground window, or the default profile if none matches. A profile is only
+// selected once it stayed selected for the switch delay, so that briefly
+// switching to another window does not switch profiles.
+type foregroundWatcher struct {
+	// Receives the index of the profile to activate.
+	changes chan int
+
+	profiles []*profile
+	delay    time.Duration
+	done     chan struct{}
+}
+
+// newForegroundWatcher starts watching the foreground window while the profile
+// at the given index is active, if a profile has executables to match.
+func newForegroundWatcher(profiles []*profile, active int, delay time.Duration) *foregroundWatcher {
+	matched := false
+
+	for _, p := range profiles {
+		matched = matched || len(p.match) > 0
+	}
+
+	if !matched {
+		return nil
+	}
+
+	w := &foregroundWatcher{
+		changes:  make(chan int, 1),
+		profiles: profiles,
+		delay:    delay,
+		done:     make(chan struct{}),
+	}
+
+	go w.run(active)
+
+	return w
+}
+
+func (w *foregroundWatcher) run(selected int) {
+	ticker := time.NewTicker(foregroundPollInterval)
+	defer ticker.Stop()
+
+	pending := selected
+	var since time.Time
+
+	for {
+		select {
+		case <-ticker.C:
+		case <-w.done:
+			return
+		}
+
+		executable, err := foregroundExecutable()
+
+		if err != nil {
+			// E.g. the window of an elevated process, or the desktop.
+			continue
+		}
+
+		matching := w.matchingProfile(executable)
+
+		if matching != pending {
+			pending, since = matching, time.Now()
+		}
+		if pending == selected || time.Since(since) < w.delay {
+			continue
+		}
+
+		selected = pending
+		debugf("%s is in the foreground, selecting profile %s", executable, w.profiles[selected].name)
+
+		// Only the latest selection matters if the input loop is behind.
+		select {
+		case <-w.changes:
+		default:
+		}
+
+		w.changes <- selected
+	}
+}
+
+// matchingProfile returns the index of the first profile matching the given
+// executable, or of the default profile.
+func (w *foregroundWatcher) matchingProfile(executable string) int {
+	for i, p := range w.profiles {
+		for _, match := range p.match {
+			if strings.EqualFold(match, executable) {
+				return i
+			}
+		}
+	}
+
+	return 0
+}
+
+func (w *foregroundWatcher) Close() {
+	close(w.done)
+}
+
+// foregroundExecutable returns the name of the executable of the foreground
+// window, e.g. "acc.exe".
+func foregroundExecutable() (string, error) {
+	hwnd, _, _ := procGetForegroundWindow.Call()
+
+	if hwnd == 0 {
+		return "", errors.New("no foreground window")
+	}
+
+	var pid uint32
+
+	procGetWindowThreadProcessId.Call(hwnd, uintptr(unsafe.Pointer(&pid)))
+
+	process, err := windows.OpenProcess(processQueryLimitedInformation, false, pid)
+
+	if err != nil {
+		return "", err
+	}
+
+	defer windows.CloseHandle(process)
+
+	var path [windows.MAX_PATH]uint16
+	size := uint32(len(path))
+
+	r, _, err := procQueryFullProcessImageNameW.Call(uintptr(process), 0, uintptr(unsafe.Pointer(&path[0])), uintptr(unsafe.Pointer(&size)))
+
+	if r == 0 {
+		return "", err
+	}
+
+	return filepath.Base(windows.UTF16ToString(path[:size])), nil
+}
@@ -0,0 +1,398 @@
+package main
+
+import (
+	"crypto/hmac"
+	"crypto/sha256"
+	"encoding/binary"
+	"errors"
+	"fmt"
+	"log"
+	"net"
+	"sync"
+	"sync/atomic"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Forwarded packets start with a header made of a magic number, a version, a
+// packet kind and a sequence number, followed by a payload depending on the
+// kind and, if a key is given, a truncated HMAC-SHA256 of all of the above.
+const (
+	forwardMagic      = "SCFW"
+	forwardVersion    = 1
+	forwardHeaderSize = 14
+	forwardMACSize    = 16
+	forwardReportSize = 17
+	forwardRumbleSize = 2
+	forwardMaxPacket  = 256
+)
+
+// Kinds of forwarded packets.
+const (
+	forwardHello  = 1 // Sender to receiver, starts a session.
+	forwardAck    = 2 // Receiver to sender, accepts a session.
+	forwardReport = 3 // Sender to receiver, carries a report.
+	forwardRumble = 4 // Receiver to sender, carries a vibration.
+)
+
+// Interval at which the sender says hello until it is acknowledged, and
+// resends the last report when the controller is idle so that the receiver
+// does not consider the link lost.
+const forwardKeepAlive = 250 * time.Millisecond
+
+// forwardLink encodes and decodes forwarded packets.
+type forwardLink struct {
+	seq uint64 // First for 64-bit alignment of atomic operations.
+	key []byte
+}
+
+func (l *forwardLink) encode(kind byte, payload []byte) []byte {
+	seq := atomic.AddUint64(&l.seq, 1)
+	packet := make([]byte, forwardHeaderSize, forwardHeaderSize+len(payload)+forwardMACSize)
+
+	copy(packet, forwardMagic)
+	packet[4] = forwardVersion
+	packet[5] = kind
+	binary.LittleEndian.PutUint64(packet[6:], seq)
+	packet = append(packet, payload...)
+
+	if len(l.key) > 0 {
+		mac := hmac.New(sha256.New, l.key)
+		mac.Write(packet)
+		packet = append(packet, mac.Sum(nil)[:forwardMACSize]...)
+	}
+
+	return packet
+}
+
+func (l *forwardLink) decode(packet []byte) (kind byte, seq uint64, payload []byte, ok bool) {
+	if len(l.key) > 0 {
+		if len(packet) < forwardHeaderSize+forwardMACSize {
+			return 0, 0, nil, false
+		}
+
+		signed := packet[:len(packet)-forwardMACSize]
+		mac := hmac.New(sha256.New, l.key)
+		mac.Write(signed)
+
+		if !hmac.Equal(mac.Sum(nil)[:forwardMACSize], packet[len(signed):]) {
+			return 0, 0, nil, false
+		}
+
+		packet = signed
+	}
+
+	if len(packet) < forwardHeaderSize || string(packet[:4]) != forwardMagic || packet[4] != forwardVersion {
+		return 0, 0, nil, false
+	}
+
+	return packet[5], binary.LittleEndian.Uint64(packet[6:]), packet[forwardHeaderSize:], true
+}
+
+func encodeForwardedReport(report *stadiacontroller.Xbox360ControllerReport) []byte {
+	payload := make([]byte, forwardReportSize)
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	binary.LittleEndian.PutUint16(payload[0:], report.GetButtons())
+	payload[2] = report.GetLeftTrigger()
+	payload[3] = report.GetRightTrigger()
+	binary.LittleEndian.PutUint16(payload[4:], uint16(lx))
+	binary.LittleEndian.PutUint16(payload[6:], uint16(ly))
+	binary.LittleEndian.PutUint16(payload[8:], uint16(rx))
+	binary.LittleEndian.PutUint16(payload[10:], uint16(ry))
+	binary.LittleEndian.PutUint32(payload[12:], uint32(report.Buttons))
+
+	if report.Capture {
+		payload[16] |= 1
+	}
+	if report.Assistant {
+		payload[16] |= 2
+	}
+
+	return payload
+}
+
+func decodeForwardedReport(payload []byte) (stadiacontroller.Xbox360ControllerReport, bool) {
+	report := stadiacontroller.NewXbox360ControllerReport()
+
+	if len(payload) != forwardReportSize {
+		return report, false
+	}
+
+	report.SetButtons(binary.LittleEndian.Uint16(payload[0:]))
+	report.SetLeftTrigger(payload[2])
+	report.SetRightTrigger(payload[3])
+	report.SetLeftThumb(int16(binary.LittleEndian.Uint16(payload[4:])), int16(binary.LittleEndian.Uint16(payload[6:])))
+	report.SetRightThumb(int16(binary.LittleEndian.Uint16(payload[8:])), int16(binary.LittleEndian.Uint16(payload[10:])))
+	report.Buttons = stadiacontroller.StadiaButtons(binary.LittleEndian.Uint32(payload[12:]))
+	report.Capture = payload[16]&1 != 0
+	report.Assistant = payload[16]&2 != 0
+
+	return report, true
+}
+
+// runForwarder sends the reports of the local controller to a receiver at the
+// given address, and applies the vibrations it sends back.
+func runForwarder(addr string, key []byte) error {
+	unlock, err := lockInstance(*devicePath)
+
+	if err != nil {
+		return err
+	}
+
+	defer unlock()
+
+	remote, err := net.ResolveUDPAddr("udp", addr)
+
+	if err != nil {
+		return err
+	}
+
+	conn, err := net.DialUDP("udp", nil, remote)
+
+	if err != nil {
+		return err
+	}
+
+	defer conn.Close()
+
+	link := &forwardLink{key: key}
+	controller, err := newController()
+
+	if err != nil {
+		return err
+	}
+
+	defer controller.Close()
+
+	onInterrupt(controller.Close)
+
+	var acknowledged int32
+
+	go func() {
+		var lastRumble uint64
+		buf := make([]byte, forwardMaxPacket)
+
+		for {
+			n, err := conn.Read(buf)
+
+			if err != nil {
+				var netErr net.Error
+				if errors.As(err, &netErr) && netErr.Temporary() {
+					continue
+				}
+				return
+			}
+
+			kind, seq, payload, ok := link.decode(buf[:n])
+
+			if !ok {
+				continue
+			}
+
+			switch kind {
+			case forwardAck:
+				if atomic.SwapInt32(&acknowledged, 1) == 0 {
+					log.Printf("forwarding to %s", remote)
+				}
+				lastRumble = seq
+
+			case forwardRumble:
+				if seq <= lastRumble || len(payload) != forwardRumbleSize {
+					continue
+				}
+
+				lastRumble = seq
+				controller.VibrateAsync(payload[0], payload[1])
+			}
+		}
+	}()
+
+	send := func(packet []byte) {
+		if _, err := conn.Write(packet); err != nil {
+			log.Printf("cannot send packet to %s: %v", remote, err)
+		}
+	}
+
+	keepAlive := time.NewTicker(forwardKeepAlive)
+	defer keepAlive.Stop()
+
+	var lastReport []byte
+	lastSent := time.Now()
+	events := controller.Events()
+
+	send(link.encode(forwardHello, nil))
+
+	for {
+		select {
+		case <-keepAlive.C:
+			if atomic.LoadInt32(&acknowledged) == 0 {
+				send(link.encode(forwardHello, nil))
+			} else if lastReport != nil && time.Since(lastSent) >= forwardKeepAlive {
+				send(link.encode(forwardReport, lastReport))
+				lastSent = time.Now()
+			}
+
+		case event, ok := <-events:
+			if !ok {
+				return nil
+			}
+
+			switch event := event.(type) {
+			case stadiacontroller.ErrorEvent:
+				if !errors.Is(event.Err, stadiacontroller.RetryError) {
+					return event.Err
+				}
+
+			case stadiacontroller.DisconnectedEvent:
+				// Let the receiver release all inputs.
+				neutral := stadiacontroller.NewXbox360ControllerReport()
+				lastReport = encodeForwardedReport(&neutral)
+				send(link.encode(forwardReport, lastReport))
+				lastSent = time.Now()
+
+			case stadiacontroller.ReportEvent:
+				lastReport = encodeForwardedReport(&event.Report)
+
+				if atomic.LoadInt32(&acknowledged) != 0 {
+					send(link.encode(forwardReport, lastReport))
+					lastSent = time.Now()
+				}
+			}
+		}
+	}
+}
+
+// runReceiver emulates a controller driven by the reports sent by a forwarder
+// to the given UDP port, and sends it the vibrations of the emulated
+// controller. All inputs are released when no packet is received for
+// linkTimeout.
+func runReceiver(port int, key []byte, linkTimeout time.Duration) error {
+	conn, err := net.ListenUDP("udp", &net.UDPAddr{Port: port})
+
+	if err != nil {
+		return err
+	}
+
+	defer conn.Close()
+
+	link := &forwardLink{key: key}
+
+	var peerMu sync.Mutex
+	var peer *net.UDPAddr
+
+	emulator, err := stadiacontroller.NewEmulator(func(vibration stadiacontroller.Vibration) {
+		peerMu.Lock()
+		addr := peer
+		peerMu.Unlock()
+
+		if addr != nil {
+			conn.WriteToUDP(link.encode(forwardRumble, []byte{vibration.LargeMotor, vibration.SmallMotor}), addr)
+		}
+	})
+
+	if err != nil {
+		return fmt.Errorf("unable to start ViGEm client: %w", err)
+	}
+
+	defer emulator.Close()
+
+	pad, err := createEmulatedController(emulator)
+
+	if err != nil {
+		return fmt.Errorf("unable to create emulated controller: %w", err)
+	}
+
+	defer pad.Close()
+
+	if err = pad.Connect(); err != nil {
+		return fmt.Errorf("unable to connect to emulated controller: %w", err)
+	}
+
+	defer pad.Disconnect()
+
+	// Closing the socket makes the loop below return.
+	onInterrupt(func() { conn.Close() })
+
+	log.Printf("waiting for a forwarded controller on %s", conn.LocalAddr())
+
+	var lastReport uint64
+	buf := make([]byte, forwardMaxPacket)
+	neutral := true
+
+	for {
+		conn.SetReadDeadline(time.Now().Add(linkTimeout))
+
+		n, addr, err := conn.ReadFromUDP(buf)
+
+		if err != nil {
+			var netErr net.Error
+
+			if errors.As(err, &netErr) && netErr.Timeout() {
+				if !neutral {
+					log.Printf("link lost, releasing all inputs")
+
+					report := stadiacontroller.NewXbox360ControllerReport()
+
+					if err := pad.Send(&report); err != nil {
+						return err
+					}
+
+					neutral = true
+				}
+				continue
+			}
+			if errors.As(err, &netErr) && netErr.Temporary() {
+				continue
+			}
+			return nil
+		}
+
+		kind, seq, payload, ok := link.decode(buf[:n])
+
+		if !ok {
+			continue
+		}
+
+		peerMu.Lock()
+		current := peer
+		peerMu.Unlock()
+
+		switch kind {
+		case forwardHello:
+			if current == nil || current.String() != addr.String() {
+				log.Printf("receiving controller from %s", addr)
+			}
+
+			peerMu.Lock()
+			peer = addr
+			peerMu.Unlock()
+
+			lastReport = seq
+			conn.WriteToUDP(link.encode(forwardAck, nil), addr)
+
+		case forwardReport:
+			// Only accept reports of the current session, and drop those
+			// older than the last one applied.
+			if current == nil || current.String() != addr.String() || seq <= lastReport {
+				continue
+			}
+
+			report, ok := decodeForwardedReport(payload)
+
+			if !ok {
+				continue
+			}
+
+			lastReport = seq
+
+			if err := pad.Send(&report); err != nil {
+				return err
+			}
+
+			neutral = false
+		}
+	}
+}
@@ -0,0 +1,129 @@
+package main
+
+import (
+	"fmt"
+	"sync"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// A gesture is a way of pressing a button.
+type gesture int
+
+const (
+	// gestureTap is a short press which is not followed by another one.
+	gestureTap gesture = iota
+	// gestureDoublePress is two short presses in a row.
+	gestureDoublePress
+	// gestureLongPress is a press held for at least longPressDuration.
+	gestureLongPress
+)
+
+var gestureNames = [...]string{
+	gestureTap:         "tap",
+	gestureDoublePress: "double-press",
+	gestureLongPress:   "long-press",
+}
+
+func (g gesture) String() string {
+	return gestureNames[g]
+}
+
+// Minimum duration of a long press.
+const longPressDuration = 500 * time.Millisecond
+
+// Maximum time between the release of a tap and the next press for both to
+// form a double press.
+const doublePressWindow = 300 * time.Millisecond
+
+// gestureDetector recognizes gestures from the successive states of a button.
+// Long presses are recognized on release.
+type gestureDetector struct {
+	onGesture func(gesture)
+	double    bool
+
+	mu         sync.Mutex
+	pressed    bool
+	pressedAt  time.Time
+	pendingTap *time.Timer
+}
+
+// newGestureDetector returns a detector which calls onGesture for every
+// recognized gesture, possibly from another goroutine. If double is false,
+// double presses are not recognized, and taps are reported immediately rather
+// than after doublePressWindow.
+func newGestureDetector(double bool, onGesture func(gesture)) *gestureDetector {
+	return &gestureDetector{onGesture: onGesture, double: double}
+}
+
+// update sets the state of the button at the given time.
+func (d *gestureDetector) update(pressed bool, now time.Time) {
+	d.mu.Lock()
+	defer d.mu.Unlock()
+
+	if pressed == d.pressed {
+		return
+	}
+
+	d.pressed = pressed
+
+	if pressed {
+		d.pressedAt = now
+		return
+	}
+
+	if now.Sub(d.pressedAt) >= longPressDuration {
+		go d.onGesture(gestureLongPress)
+		return
+	}
+
+	if !d.double {
+		go d.onGesture(gestureTap)
+		return
+	}
+
+	if d.pendingTap != nil && d.pendingTap.Stop() {
+		d.pendingTap = nil
+		go d.onGesture(gestureDoublePress)
+		return
+	}
+
+	d.pendingTap = time.AfterFunc(doublePressWindow, func() {
+		d.onGesture(gestureTap)
+	})
+}
+
+// parseGesture returns the gesture with the given name.
+func parseGesture(name string) (gesture, error) {
+	for g, gestureName := range gestureNames {
+		if gestureName == name {
+			return gesture(g), nil
+		}
+	}
+
+	return 0, fmt.Errorf("unknown gesture %q", name)
+}
+
+// buttonGestures recognizes the gestures of a single button.
+type buttonGestures struct {
+	button   stadiacontroller.StadiaButtons
+	detector *gestureDetector
+}
+
+// buttonCombo detects presses of a combination of buttons.
+type buttonCombo struct {
+	buttons stadiacontroller.StadiaButtons
+	pressed bool
+}
+
+// update returns whether the combination was pressed since the last update.
+// An empty combination is never pressed.
+func (c *buttonCombo) update(buttons stadiacontroller.StadiaButtons) bool {
+	pressed := c.buttons != 0 && buttons.Has(c.buttons)
+	toggled := pressed && !c.pressed
+
+	c.pressed = pressed
+
+	return toggled
+}
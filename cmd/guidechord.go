@@ -0,0 +1,96 @@
+package main
+
+import (
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// States of a guideChord.
+const (
+	// guideChordIdle is when the Stadia button is released.
+	guideChordIdle = iota
+	// guideChordPressed is when Back and Start are pressed in place of Guide.
+	guideChordPressed
+	// guideChordIgnored is when the Stadia button was pressed while Back or
+	// Start already was, until it is released.
+	guideChordIgnored
+)
+
+// guideChord presses Back and Start on the emulated controller instead of
+// Guide while the Stadia button is pressed, for games which only know Back and
+// Start as their menu combination. The chord lasts at least minDuration, so
+// that short taps register.
+//
+// Only the buttons of the emulated controller change: hooks and gestures still
+// see the Stadia button.
+type guideChord struct {
+	minDuration time.Duration
+
+	state     int
+	pressedAt time.Time
+}
+
+// apply replaces Guide by the chord in the given report, received at the given
+// time. It returns when the chord must be released if the Stadia button was
+// released early, or the zero time.
+func (g *guideChord) apply(report *stadiacontroller.Xbox360ControllerReport, now time.Time) time.Time {
+	stadia := report.Buttons.Has(stadiacontroller.StadiaButtonStadia)
+	releaseAt := g.pressedAt.Add(g.minDuration)
+
+	switch g.state {
+	case guideChordIdle:
+		// Pressing Back and Start while either is held would release it
+		// along with the Stadia button.
+		if stadia && report.Buttons&(stadiacontroller.StadiaButtonOptions|stadiacontroller.StadiaButtonMenu) != 0 {
+			g.state = guideChordIgnored
+		} else if stadia {
+			g.state = guideChordPressed
+			g.pressedAt = now
+			releaseAt = now.Add(g.minDuration)
+		}
+
+	case guideChordPressed:
+		if !stadia && !now.Before(releaseAt) {
+			g.state = guideChordIdle
+		}
+
+	case guideChordIgnored:
+		if !stadia {
+			g.state = guideChordIdle
+		}
+	}
+
+	buttons := report.GetButtons() &^ (1 << stadiacontroller.Xbox360ControllerButtonGuide)
+
+	if g.state != guideChordPressed {
+		report.SetButtons(buttons)
+
+		return time.Time{}
+	}
+
+	report.SetButtons(buttons | 1<<stadiacontroller.Xbox360ControllerButtonBack | 1<<stadiacontroller.Xbox360ControllerButtonStart)
+
+	if !stadia {
+		return releaseAt
+	}
+
+	return time.Time{}
+}
+
+// reset releases the chord.
+func (g *guideChord) reset() {
+	g.state = guideChordIdle
+}
+
+// newGuideChordStep returns the step of -guide-as-chord, or nil if it is not
+// given.
+func newGuideChordStep() *reportStep {
+	if !*guideAsChord {
+		return nil
+	}
+
+	chord := &guideChord{minDuration: *guideChordMinimum}
+
+	return timedStep(chord.apply, chord.reset)
+}
@@ -0,0 +1,79 @@
+package main
+
+import (
+	"fmt"
+	"sync/atomic"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Intensity of a vibration of the game above which clicks are not played.
+const hapticClickRumbleThreshold = 16
+
+// Longest haptic click, so that clicks never feel like a vibration.
+const maxHapticClick = 50 * time.Millisecond
+
+// hapticClicks plays a short, weak pulse of the small motor when one of the
+// given buttons is pressed, confirming the press. Vibrations of the game
+// always win: clicks are skipped while the game makes the controller vibrate.
+type hapticClicks struct {
+	controller *stadiacontroller.StadiaController
+
+	buttons   stadiacontroller.StadiaButtons
+	duration  time.Duration
+	intensity byte
+
+	previous stadiacontroller.StadiaButtons
+}
+
+func newHapticClicks(controller *stadiacontroller.StadiaController, buttons string, duration time.Duration, intensity uint) (*hapticClicks, error) {
+	clickButtons, err := stadiacontroller.ParseStadiaButtons(buttons)
+
+	if err != nil {
+		return nil, err
+	}
+	if duration <= 0 || duration > maxHapticClick {
+		return nil, fmt.Errorf("haptic click duration must be between 0 (excluded) and %v", maxHapticClick)
+	}
+	if intensity == 0 || intensity > 255 {
+		return nil, fmt.Errorf("haptic click intensity must be between 1 and 255")
+	}
+
+	return &hapticClicks{
+		controller: controller,
+		buttons:    clickButtons,
+		duration:   duration,
+		intensity:  byte(intensity),
+	}, nil
+}
+
+// update plays a click if one of the buttons was pressed since the last
+// update.
+func (c *hapticClicks) update(buttons stadiacontroller.StadiaButtons) {
+	pressed := buttons & c.buttons
+	clicked := pressed&^c.previous != 0
+
+	c.previous = pressed
+
+	if !clicked || atomic.LoadUint32(&rumbleIntensity) > hapticClickRumbleThreshold {
+		return
+	}
+
+	motors := atomic.LoadUint32(&rumbleMotors)
+	large, small := byte(motors>>8), byte(motors)
+
+	if c.intensity > small {
+		small = c.intensity
+	}
+
+	c.controller.VibrateAsync(large, small)
+
+	// Restore the vibration of the game afterwards, which may have changed
+	// during the click.
+	time.AfterFunc(c.duration, func() {
+		motors := atomic.LoadUint32(&rumbleMotors)
+
+		c.controller.VibrateAsync(byte(motors>>8), byte(motors))
+	})
+}
@@ -0,0 +1,230 @@
+package main
+
+import (
+	"errors"
+	"fmt"
+	"log"
+	"os"
+	"path/filepath"
+	"strings"
+	"unicode/utf16"
+	"unsafe"
+
+	"github.com/71/stadiacontroller"
+	"golang.org/x/sys/windows"
+)
+
+// Control device of HidHide, and codes of its IOCTLs, i.e.
+// CTL_CODE(0x8001, function, METHOD_BUFFERED, FILE_READ_DATA).
+const (
+	hidHideControlDevice = `\\.\HidHide`
+	hidHideGetWhitelist  = 0x8001<<16 | 1<<14 | 2048<<2
+	hidHideSetWhitelist  = 0x8001<<16 | 1<<14 | 2049<<2
+	hidHideGetBlacklist  = 0x8001<<16 | 1<<14 | 2050<<2
+	hidHideSetBlacklist  = 0x8001<<16 | 1<<14 | 2051<<2
+	hidHideGetActive     = 0x8001<<16 | 1<<14 | 2052<<2
+)
+
+// hidHide hides controllers from games and Steam with HidHide, so that they
+// only see the emulated controller. This program is added to the programs
+// which may still see hidden devices, and every change is undone by Close.
+type hidHide struct {
+	handle windows.Handle
+
+	// Entries added to the lists of HidHide.
+	hidden      []string
+	whitelisted string
+}
+
+// openHidHide connects to the control device of HidHide.
+func openHidHide() (*hidHide, error) {
+	name, err := windows.UTF16PtrFromString(hidHideControlDevice)
+
+	if err != nil {
+		return nil, err
+	}
+
+	handle, err := windows.CreateFile(name, windows.GENERIC_READ, windows.FILE_SHARE_READ|windows.FILE_SHARE_WRITE, nil, windows.OPEN_EXISTING, 0, 0)
+
+	if errors.Is(err, windows.ERROR_FILE_NOT_FOUND) {
+		return nil, errors.New("HidHide is not installed, see https://github.com/ViGEm/HidHide/releases")
+	}
+	if err != nil {
+		return nil, fmt.Errorf("cannot open HidHide: %w", err)
+	}
+
+	h := &hidHide{handle: handle}
+
+	if active, err := h.active(); err == nil && !active {
+		log.Printf("warning: HidHide is disabled, enable device hiding in the HidHide configuration client")
+	}
+
+	return h, nil
+}
+
+// hide hides the controller at the given path from every program but this
+// one.
+func (h *hidHide) hide(path string) error {
+	id, err := (&stadiacontroller.DeviceInfo{Path: path}).InstanceID()
+
+	if err != nil {
+		return err
+	}
+
+	if h.whitelisted == "" {
+		image, err := executableDevicePath()
+
+		if err != nil {
+			return err
+		}
+
+		added, err := h.add(hidHideGetWhitelist, hidHideSetWhitelist, image)
+
+		if err != nil {
+			return fmt.Errorf("cannot allow this program in HidHide: %w", err)
+		}
+		if added {
+			h.whitelisted = image
+		}
+	}
+
+	added, err := h.add(hidHideGetBlacklist, hidHideSetBlacklist, id)
+
+	if err != nil {
+		return fmt.Errorf("cannot hide %s with HidHide: %w", id, err)
+	}
+	if added {
+		h.hidden = append(h.hidden, id)
+		log.Printf("hiding %s from other programs", id)
+	}
+
+	return nil
+}
+
+// Close removes the entries added to the lists of HidHide, making the hidden
+// controllers visible again.
+func (h *hidHide) Close() {
+	for _, id := range h.hidden {
+		if err := h.remove(hidHideGetBlacklist, hidHideSetBlacklist, id); err != nil {
+			log.Printf("warning: cannot unhide %s: %v", id, err)
+		}
+	}
+	if h.whitelisted != "" {
+		if err := h.remove(hidHideGetWhitelist, hidHideSetWhitelist, h.whitelisted); err != nil {
+			log.Printf("warning: cannot remove this program from HidHide: %v", err)
+		}
+	}
+
+	windows.CloseHandle(h.handle)
+}
+
+// add adds the given entry to a list of HidHide, and returns whether it was
+// not in the list already.
+func (h *hidHide) add(get, set uint32, entry string) (bool, error) {
+	list, err := h.list(get)
+
+	if err != nil {
+		return false, err
+	}
+
+	for _, e := range list {
+		if strings.EqualFold(e, entry) {
+			return false, nil
+		}
+	}
+
+	return true, h.setList(set, append(list, entry))
+}
+
+// remove removes the given entry from a list of HidHide.
+func (h *hidHide) remove(get, set uint32, entry string) error {
+	list, err := h.list(get)
+
+	if err != nil {
+		return err
+	}
+
+	kept := list[:0]
+
+	for _, e := range list {
+		if !strings.EqualFold(e, entry) {
+			kept = append(kept, e)
+		}
+	}
+
+	return h.setList(set, kept)
+}
+
+// list returns a list of HidHide, given as a multi-string.
+func (h *hidHide) list(code uint32) ([]string, error) {
+	var size uint32
+
+	if err := windows.DeviceIoControl(h.handle, code, nil, 0, nil, 0, &size, nil); err != nil {
+		return nil, err
+	}
+	if size < 2 {
+		return nil, nil
+	}
+
+	buf := make([]uint16, size/2)
+
+	if err := windows.DeviceIoControl(h.handle, code, nil, 0, (*byte)(unsafe.Pointer(&buf[0])), size, &size, nil); err != nil {
+		return nil, err
+	}
+
+	var list []string
+
+	for _, s := range strings.Split(string(utf16.Decode(buf)), "\x00") {
+		if s != "" {
+			list = append(list, s)
+		}
+	}
+
+	return list, nil
+}
+
+// setList replaces a list of HidHide.
+func (h *hidHide) setList(code uint32, list []string) error {
+	multiString := utf16.Encode([]rune(strings.Join(list, "\x00") + "\x00\x00"))
+
+	var size uint32
+
+	return windows.DeviceIoControl(h.handle, code, (*byte)(unsafe.Pointer(&multiString[0])), uint32(len(multiString)*2), nil, 0, &size, nil)
+}
+
+// active returns whether HidHide hides the devices of its list.
+func (h *hidHide) active() (bool, error) {
+	var active byte
+	var size uint32
+
+	if err := windows.DeviceIoControl(h.handle, hidHideGetActive, nil, 0, &active, 1, &size, nil); err != nil {
+		return false, err
+	}
+
+	return active != 0, nil
+}
+
+// executableDevicePath returns the path of this program in the form expected
+// by HidHide, e.g. `\Device\HarddiskVolume3\Tools\stadiacontroller.exe`.
+func executableDevicePath() (string, error) {
+	path, err := os.Executable()
+
+	if err != nil {
+		return "", err
+	}
+
+	volume := filepath.VolumeName(path)
+	volumePtr, err := windows.UTF16PtrFromString(volume)
+
+	if err != nil {
+		return "", err
+	}
+
+	var device [windows.MAX_PATH]uint16
+
+	if _, err := windows.QueryDosDevice(volumePtr, &device[0], uint32(len(device))); err != nil {
+		return "", fmt.Errorf("cannot find the device of %s: %w", volume, err)
+	}
+
+	return windows.UTF16ToString(device[:]) + path[len(volume):], nil
+}
@@ -0,0 +1,172 @@
+package main
+
+import (
+	"fmt"
+	"strconv"
+	"strings"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// buttonCommand is a command run when a button is pressed or released.
+type buttonCommand struct {
+	button  stadiacontroller.StadiaButtons
+	command string
+}
+
+// buttonCommandsFlag is a flag which can be given several times, each time as
+// button=command.
+type buttonCommandsFlag []buttonCommand
+
+func (f *buttonCommandsFlag) String() string {
+	var entries []string
+
+	if f != nil {
+		for _, c := range *f {
+			entries = append(entries, c.button.String()+"="+c.command)
+		}
+	}
+
+	return strings.Join(entries, " ")
+}
+
+func (f *buttonCommandsFlag) Set(value string) error {
+	i := strings.IndexByte(value, '=')
+
+	if i == -1 {
+		return fmt.Errorf("invalid button command %q, expected button=command", value)
+	}
+
+	button, err := stadiacontroller.ParseStadiaButtons(value[:i])
+
+	if err != nil {
+		return err
+	}
+	if button == 0 || button&(button-1) != 0 {
+		return fmt.Errorf("invalid button command %q, expected a single button", value)
+	}
+
+	*f = append(*f, buttonCommand{button, value[i+1:]})
+
+	return nil
+}
+
+// buttonHooks runs the commands given to -on-pressed and -on-released, and
+// hides the buttons given to -consume from the emulated controller.
+type buttonHooks struct {
+	pressed  []buttonCommand
+	released []buttonCommand
+	consumed stadiacontroller.StadiaButtons
+	previous stadiacontroller.StadiaButtons
+}
+
+// newButtonHooks returns the hooks configured by the command line, or nil if
+// there are none.
+func newButtonHooks() (*buttonHooks, error) {
+	consumed, err := stadiacontroller.ParseStadiaButtons(*consumeButtons)
+
+	if err != nil {
+		return nil, err
+	}
+
+	if len(onPressed) == 0 && len(onReleased) == 0 && consumed == 0 {
+		return nil, nil
+	}
+
+	return &buttonHooks{pressed: onPressed, released: onReleased, consumed: consumed}, nil
+}
+
+// consume releases the consumed buttons in the given report.
+func (h *buttonHooks) consume(report *stadiacontroller.Xbox360ControllerReport) {
+	consumed := report.Buttons & h.consumed
+
+	if consumed == 0 {
+		return
+	}
+
+	report.Buttons &^= consumed
+	report.Capture = report.Buttons.Has(stadiacontroller.StadiaButtonCapture)
+	report.Assistant = report.Buttons.Has(stadiacontroller.StadiaButtonAssistant)
+	report.SetButtons(report.GetButtons() &^ consumed.Xbox360Buttons())
+}
+
+// update runs the commands of the buttons pressed or released since the last
+// update, with the variables returned by env added to their environment.
+func (h *buttonHooks) update(buttons stadiacontroller.StadiaButtons, env func(button stadiacontroller.StadiaButtons, pressed bool) []string) error {
+	pressed := buttons &^ h.previous
+	released := h.previous &^ buttons
+
+	h.previous = buttons
+
+	for _, c := range h.pressed {
+		if pressed&c.button != 0 {
+			if err := runCommand(c.command, env(c.button, true)...); err != nil {
+				return err
+			}
+		}
+	}
+	for _, c := range h.released {
+		if released&c.button != 0 {
+			if err := runCommand(c.command, env(c.button, false)...); err != nil {
+				return err
+			}
+		}
+	}
+
+	return nil
+}
+
+// buttonHoldTimes records when the held buttons were pressed.
+type buttonHoldTimes map[stadiacontroller.StadiaButtons]time.Time
+
+// held returns for how long the given button has been held at now, or 0 if it
+// was not held at the last update.
+func (t buttonHoldTimes) held(button stadiacontroller.StadiaButtons, now time.Time) time.Duration {
+	if since, ok := t[button]; ok {
+		return now.Sub(since)
+	}
+
+	return 0
+}
+
+// update records when the given buttons were pressed, if they were not held
+// at the last update, and forgets the released buttons.
+func (t buttonHoldTimes) update(buttons stadiacontroller.StadiaButtons, now time.Time) {
+	for button := stadiacontroller.StadiaButtons(1); button <= stadiacontroller.StadiaButtonVolumeDown; button <<= 1 {
+		if !buttons.Has(button) {
+			delete(t, button)
+		} else if _, ok := t[button]; !ok {
+			t[button] = now
+		}
+	}
+}
+
+// buttonEnv returns the environment variables describing a press or release of
+// a button, and the state of the sticks and triggers at that time, given to its
+// commands. Releases also give for how long the button was held.
+func buttonEnv(button stadiacontroller.StadiaButtons, pressed bool, held time.Duration, report *stadiacontroller.Xbox360ControllerReport) []string {
+	state := "released"
+
+	if pressed {
+		state = "pressed"
+	}
+
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	env := []string{
+		"STADIA_BUTTON=" + button.String(),
+		"STADIA_STATE=" + state,
+		fmt.Sprintf("STADIA_LEFT_STICK=%d,%d", lx, ly),
+		fmt.Sprintf("STADIA_RIGHT_STICK=%d,%d", rx, ry),
+		"STADIA_LEFT_TRIGGER=" + strconv.Itoa(int(report.GetLeftTrigger())),
+		"STADIA_RIGHT_TRIGGER=" + strconv.Itoa(int(report.GetRightTrigger())),
+	}
+
+	if !pressed {
+		env = append(env, "STADIA_HOLD_MS="+strconv.FormatInt(int64(held/time.Millisecond), 10))
+	}
+
+	return env
+}
@@ -0,0 +1,29 @@
+package main
+
+import (
+	"github.com/71/stadiacontroller"
+)
+
+// Deflection of the sticks and triggers, as a fraction of their range, below
+// which -idle-timeout considers them at rest, so that a drifting stick does not
+// keep the controller awake.
+const idleDeadzone = 0.25
+
+// hasInput returns whether a button is pressed, or a stick or trigger deflected
+// past idleDeadzone, in the given report.
+func hasInput(report *stadiacontroller.Xbox360ControllerReport) bool {
+	if report.Buttons != 0 {
+		return true
+	}
+
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	for _, axis := range []int16{lx, ly, rx, ry} {
+		if float64(axis) > idleDeadzone*32767 || float64(axis) < -idleDeadzone*32768 {
+			return true
+		}
+	}
+
+	return float64(report.GetLeftTrigger()) > idleDeadzone*255 || float64(report.GetRightTrigger()) > idleDeadzone*255
+}
@@ -0,0 +1,177 @@
+package main
+
+import (
+	"encoding/json"
+	"flag"
+	"fmt"
+	"os"
+	"strings"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// controllerInfo is everything known about the connected controller, as
+// printed by the controller-info subcommand.
+type controllerInfo struct {
+	Path        string   `json:"path"`
+	HardwareIDs []string `json:"hardware_ids,omitempty"`
+	Transport   string   `json:"transport"`
+
+	VendorID     uint16 `json:"vendor_id"`
+	ProductID    uint16 `json:"product_id"`
+	Firmware     uint16 `json:"firmware_revision"`
+	Manufacturer string `json:"manufacturer"`
+	Product      string `json:"product"`
+	Serial       string `json:"serial"`
+
+	UsagePage           uint16 `json:"usage_page"`
+	Usage               uint16 `json:"usage"`
+	InputReportLength   uint16 `json:"input_report_length"`
+	OutputReportLength  uint16 `json:"output_report_length"`
+	FeatureReportLength uint16 `json:"feature_report_length"`
+
+	Battery *batteryJSON `json:"battery"`
+
+	// Errors of the optional queries which failed, by query.
+	Errors map[string]string `json:"errors,omitempty"`
+}
+
+// runControllerInfo prints what can be learned about the controller that
+// would be acquired, without emulating it.
+func runControllerInfo(args []string) error {
+	flags := flag.NewFlagSet("controller-info", flag.ExitOnError)
+	jsonOutput := flags.Bool("json", false, "print the information as JSON")
+
+	flags.Parse(args)
+
+	options, err := controllerOptions()
+
+	if err != nil {
+		return err
+	}
+
+	device, err := options.FindDevice()
+
+	if err != nil {
+		return fmt.Errorf("cannot find controller: %w", err)
+	}
+
+	battery := stadiacontroller.ReadBattery(device.Path)
+	info := controllerInfo{
+		Path:      device.Path,
+		Transport: device.Transport().String(),
+
+		VendorID:     device.VendorID,
+		ProductID:    device.ProductID,
+		Firmware:     device.VersionNumber,
+		Manufacturer: device.Manufacturer,
+		Product:      device.Product,
+		Serial:       device.SerialNumber,
+
+		UsagePage:           device.UsagePage,
+		Usage:               device.Usage,
+		InputReportLength:   device.InputReportLength,
+		OutputReportLength:  device.OutputReportLength,
+		FeatureReportLength: device.FeatureReportLength,
+
+		Battery: newBatteryJSON(battery, time.Now()),
+		Errors:  map[string]string{},
+	}
+
+	if info.HardwareIDs, err = device.HardwareIDs(); err != nil {
+		info.Errors["hardware_ids"] = err.Error()
+	}
+
+	if *jsonOutput {
+		encoder := json.NewEncoder(os.Stdout)
+		encoder.SetIndent("", "  ")
+
+		return encoder.Encode(info)
+	}
+
+	fmt.Printf("path:            %s\n", info.Path)
+	fmt.Printf("hardware IDs:    %s\n", strings.Join(info.HardwareIDs, ", "))
+	fmt.Printf("transport:       %s\n", info.Transport)
+	fmt.Printf("vendor ID:       %04x\n", info.VendorID)
+	fmt.Printf("product ID:      %04x\n", info.ProductID)
+	fmt.Printf("firmware:        %04x\n", info.Firmware)
+	fmt.Printf("manufacturer:    %s\n", info.Manufacturer)
+	fmt.Printf("product:         %s\n", info.Product)
+	fmt.Printf("serial:          %s\n", info.Serial)
+	fmt.Printf("usage:           %04x:%04x\n", info.UsagePage, info.Usage)
+	fmt.Printf("report lengths:  input %d, output %d, feature %d\n",
+		info.InputReportLength, info.OutputReportLength, info.FeatureReportLength)
+	fmt.Printf("battery:         %s\n", battery)
+
+	for query, err := range info.Errors {
+		fmt.Printf("failed query:    %s: %s\n", query, err)
+	}
+
+	return nil
+}
+
+// runListDevices prints the HID devices matching the controller options, or
+// all HID devices with "all", and whether each of them can be opened.
+func runListDevices(args []string) error {
+	var devices []*stadiacontroller.DeviceInfo
+	var err error
+
+	switch {
+	case len(args) == 0:
+		options, optionsErr := controllerOptions()
+
+		if optionsErr != nil {
+			return optionsErr
+		}
+
+		// List every controller, not only the selected one.
+		options.DevicePath, options.Serial, options.HandOffIdle = "", "", 0
+
+		devices, err = options.FindDevices()
+
+	case len(args) == 1 && args[0] == "all":
+		devices, err = stadiacontroller.HIDBackend.Devices()
+
+	default:
+		return fmt.Errorf("usage: list-devices [all]")
+	}
+
+	if err != nil {
+		return fmt.Errorf("cannot list devices: %w", err)
+	}
+	if len(devices) == 0 {
+		fmt.Println("no device found: check that the controller is plugged in over USB or paired over Bluetooth")
+		return nil
+	}
+
+	for i, device := range devices {
+		if i > 0 {
+			fmt.Println()
+		}
+
+		hardwareIDs, err := device.HardwareIDs()
+		hardwareIDsText := strings.Join(hardwareIDs, ", ")
+
+		if err != nil {
+			hardwareIDsText = "unknown (" + err.Error() + ")"
+		}
+
+		opened := "yes"
+
+		if d, err := device.Open(); err != nil {
+			opened = "no (" + err.Error() + ")"
+		} else {
+			d.Close()
+		}
+
+		fmt.Printf("path:            %s\n", device.Path)
+		fmt.Printf("hardware IDs:    %s\n", hardwareIDsText)
+		fmt.Printf("name:            %s %s\n", device.Manufacturer, device.Product)
+		fmt.Printf("IDs:             %04x:%04x\n", device.VendorID, device.ProductID)
+		fmt.Printf("transport:       %s\n", device.Transport())
+		fmt.Printf("can be opened:   %s\n", opened)
+	}
+
+	return nil
+}
@@ -0,0 +1,61 @@
+package main
+
+import (
+	"errors"
+	"fmt"
+	"strings"
+	"unsafe"
+
+	"golang.org/x/sys/windows"
+)
+
+var procCreateMutexW = kernel32.NewProc("CreateMutexW")
+
+// Prefix of the names of the mutexes held by running instances, followed by
+// the device path of their controller, or by instanceLockAll.
+const (
+	instanceLockPrefix = `Local\stadiacontroller `
+	instanceLockAll    = "all"
+)
+
+// errInstanceRunning is returned by lockInstance when another instance holds
+// the lock.
+var errInstanceRunning = errors.New("another instance is already running for this controller (use -force to run anyway)")
+
+// lockInstance creates the mutex held while a controller is used by this
+// instance, keyed on its device path, or shared by all controllers if path is
+// empty. It returns errInstanceRunning if another instance holds it, unless
+// -force is given. The returned function releases the lock; Windows also
+// releases it when the process exits, including after a panic or log.Fatal.
+func lockInstance(path string) (func(), error) {
+	if *force {
+		return func() {}, nil
+	}
+
+	key := instanceLockAll
+
+	if path != "" {
+		// Backslashes are not allowed in the names of kernel objects, and device
+		// paths are not case-sensitive.
+		key = strings.ReplaceAll(strings.ToLower(path), `\`, "#")
+	}
+
+	name, err := windows.UTF16PtrFromString(instanceLockPrefix + key)
+
+	if err != nil {
+		return nil, err
+	}
+
+	r, _, err := procCreateMutexW.Call(0, 0, uintptr(unsafe.Pointer(name)))
+	handle := windows.Handle(r)
+
+	if handle == 0 {
+		return nil, fmt.Errorf("cannot create instance lock: %w", err)
+	}
+	if err == windows.ERROR_ALREADY_EXISTS {
+		windows.CloseHandle(handle)
+		return nil, errInstanceRunning
+	}
+
+	return func() { windows.CloseHandle(handle) }, nil
+}
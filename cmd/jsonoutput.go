@@ -0,0 +1,193 @@
+package main
+
+import (
+	"encoding/json"
+	"fmt"
+	"io"
+	"log"
+	"sync"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Buttons of the emulated controller in the events of -output json, in the
+// order they are listed.
+var jsonButtonNames = []struct {
+	bit  uint
+	name string
+}{
+	{stadiacontroller.Xbox360ControllerButtonA, "a"},
+	{stadiacontroller.Xbox360ControllerButtonB, "b"},
+	{stadiacontroller.Xbox360ControllerButtonX, "x"},
+	{stadiacontroller.Xbox360ControllerButtonY, "y"},
+	{stadiacontroller.Xbox360ControllerButtonLeftShoulder, "left_shoulder"},
+	{stadiacontroller.Xbox360ControllerButtonRightShoulder, "right_shoulder"},
+	{stadiacontroller.Xbox360ControllerButtonLeftThumb, "left_thumb"},
+	{stadiacontroller.Xbox360ControllerButtonRightThumb, "right_thumb"},
+	{stadiacontroller.Xbox360ControllerButtonBack, "back"},
+	{stadiacontroller.Xbox360ControllerButtonStart, "start"},
+	{stadiacontroller.Xbox360ControllerButtonGuide, "guide"},
+	{stadiacontroller.Xbox360ControllerButtonUp, "up"},
+	{stadiacontroller.Xbox360ControllerButtonDown, "down"},
+	{stadiacontroller.Xbox360ControllerButtonLeft, "left"},
+	{stadiacontroller.Xbox360ControllerButtonRight, "right"},
+}
+
+// A jsonEvent is a line written by a jsonOutput. Fields which do not apply to
+// the type of the event are omitted.
+type jsonEvent struct {
+	Type string `json:"type"`
+
+	// Set for "connected" events.
+	Transport string `json:"transport,omitempty"`
+
+	// Set for "report" events.
+	Buttons   *[]string `json:"buttons,omitempty"`
+	LX        *int16    `json:"lx,omitempty"`
+	LY        *int16    `json:"ly,omitempty"`
+	RX        *int16    `json:"rx,omitempty"`
+	RY        *int16    `json:"ry,omitempty"`
+	LT        *byte     `json:"lt,omitempty"`
+	RT        *byte     `json:"rt,omitempty"`
+	Assistant *bool     `json:"assistant,omitempty"`
+	Capture   *bool     `json:"capture,omitempty"`
+
+	// Set for "vibration" events.
+	Large *byte `json:"large,omitempty"`
+	Small *byte `json:"small,omitempty"`
+}
+
+// jsonOutput writes the state changes of the controller with -output json and
+// to the clients subscribed to the control pipe, one JSON object per line, so
+// that other programs can follow them without reading the controller
+// themselves. Reports are only written when they differ from the previous one.
+type jsonOutput struct {
+	mu      sync.Mutex
+	writers []io.Writer
+	last    stadiacontroller.Xbox360ControllerReport
+	written bool
+}
+
+// An eventListener is a writer of a jsonOutput which may not be read, in which
+// case reports are not encoded for it.
+type eventListener interface {
+	listening() bool
+}
+
+// parseOutputFormat checks the format given to -output, and returns whether
+// it is JSON.
+func parseOutputFormat(format string) (bool, error) {
+	switch format {
+	case "text":
+		return false, nil
+	case "json":
+		return true, nil
+	default:
+		return false, fmt.Errorf("unknown output format %q, expected text or json", format)
+	}
+}
+
+func newJSONOutput(writers ...io.Writer) *jsonOutput {
+	return &jsonOutput{writers: writers}
+}
+
+// connected writes that the controller was connected over the given transport.
+func (o *jsonOutput) connected(transport stadiacontroller.Transport) {
+	o.write(jsonEvent{Type: "connected", Transport: transport.String()})
+}
+
+// disconnected writes that the controller was disconnected. The next report is
+// written even if it is the same as the last one.
+func (o *jsonOutput) disconnected() {
+	o.mu.Lock()
+	o.written = false
+	o.mu.Unlock()
+
+	o.write(jsonEvent{Type: "disconnected"})
+}
+
+// report writes the given report, unless it is the same as the previous one.
+func (o *jsonOutput) report(report *stadiacontroller.Xbox360ControllerReport) {
+	o.mu.Lock()
+	changed := !o.written || *report != o.last
+	o.last, o.written = *report, true
+	o.mu.Unlock()
+
+	if !changed || !o.listened() {
+		return
+	}
+
+	buttons := []string{}
+	pressed := report.GetButtons()
+
+	for _, button := range jsonButtonNames {
+		if pressed&(1<<button.bit) != 0 {
+			buttons = append(buttons, button.name)
+		}
+	}
+
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+	lt, rt := report.GetLeftTrigger(), report.GetRightTrigger()
+
+	o.write(jsonEvent{
+		Type:      "report",
+		Buttons:   &buttons,
+		LX:        &lx,
+		LY:        &ly,
+		RX:        &rx,
+		RY:        &ry,
+		LT:        &lt,
+		RT:        &rt,
+		Assistant: &report.Assistant,
+		Capture:   &report.Capture,
+	})
+}
+
+// vibration writes the given vibration, as requested by a game.
+func (o *jsonOutput) vibration(vibration stadiacontroller.Vibration) {
+	o.write(jsonEvent{Type: "vibration", Large: &vibration.LargeMotor, Small: &vibration.SmallMotor})
+}
+
+// listened returns whether one of the writers may be read.
+func (o *jsonOutput) listened() bool {
+	o.mu.Lock()
+	defer o.mu.Unlock()
+
+	for _, w := range o.writers {
+		if listener, ok := w.(eventListener); !ok || listener.listening() {
+			return true
+		}
+	}
+
+	return false
+}
+
+// write writes the given event on its own line to each writer. Writers are
+// dropped once writing to them fails, e.g. when the reading program exits.
+func (o *jsonOutput) write(event jsonEvent) {
+	line, err := json.Marshal(event)
+
+	if err != nil {
+		log.Printf("warning: cannot encode JSON event: %v", err)
+		return
+	}
+
+	line = append(line, '\n')
+
+	o.mu.Lock()
+	defer o.mu.Unlock()
+
+	writers := o.writers[:0]
+
+	for _, w := range o.writers {
+		if _, err := w.Write(line); err != nil {
+			log.Printf("warning: cannot write JSON events, no longer writing them: %v", err)
+			continue
+		}
+
+		writers = append(writers, w)
+	}
+
+	o.writers = writers
+}
@@ -0,0 +1,442 @@
+package main
+
+import (
+	"fmt"
+	"runtime"
+	"strconv"
+	"strings"
+	"sync"
+	"unsafe"
+
+	"github.com/71/stadiacontroller"
+	"golang.org/x/sys/windows"
+)
+
+var (
+	procSetWindowsHookExW   = user32.NewProc("SetWindowsHookExW")
+	procUnhookWindowsHookEx = user32.NewProc("UnhookWindowsHookEx")
+	procCallNextHookEx      = user32.NewProc("CallNextHookEx")
+	procPostThreadMessageW  = user32.NewProc("PostThreadMessageW")
+	procGetCurrentThreadId  = kernel32.NewProc("GetCurrentThreadId")
+)
+
+// Constants used to install the low-level keyboard hook.
+const (
+	whKeyboardLL    = 13
+	wmQuit          = 0x0012
+	wmKeyDown       = 0x0100
+	wmKeyUp         = 0x0101
+	wmSysKeyDown    = 0x0104
+	wmSysKeyUp      = 0x0105
+	llkhfInjected   = 0x00000010
+	hookActionCode  = 0
+	stickMaxForward = 32767
+	stickMaxBack    = -32768
+)
+
+// kbdllHookStruct is the KBDLLHOOKSTRUCT structure.
+type kbdllHookStruct struct {
+	vkCode    uint32
+	scanCode  uint32
+	flags     uint32
+	time      uint32
+	extraInfo uintptr
+}
+
+// Virtual-key codes of the keys which can be named in key bindings, in
+// addition to letters, digits, F1-F24 and hexadecimal codes (e.g. "0x41").
+var keyNames = map[string]uint32{
+	"backspace":   0x08,
+	"tab":         0x09,
+	"enter":       0x0D,
+	"shift":       0x10,
+	"ctrl":        0x11,
+	"alt":         0x12,
+	"escape":      0x1B,
+	"space":       0x20,
+	"pageup":      0x21,
+	"pagedown":    0x22,
+	"end":         0x23,
+	"home":        0x24,
+	"left":        0x25,
+	"up":          0x26,
+	"right":       0x27,
+	"down":        0x28,
+	"printscreen": 0x2C,
+	"insert":      0x2D,
+	"delete":      0x2E,
+	"win":         0x5B,
+}
+
+// Sticks moved by key bindings.
+const (
+	keyNoStick = iota
+	keyLeftStick
+	keyRightStick
+)
+
+// A keyBinding is the input of the emulated controller pressed by a key: a
+// button, a fully pulled trigger, or a stick fully pushed in one direction.
+type keyBinding struct {
+	buttons      stadiacontroller.StadiaButtons
+	leftTrigger  bool
+	rightTrigger bool
+	stick        int
+	dx, dy       int16
+}
+
+// Stick directions which can be bound to keys.
+var keyStickDirections = map[string]keyBinding{
+	"ls_up":    {stick: keyLeftStick, dy: stickMaxForward},
+	"ls_down":  {stick: keyLeftStick, dy: stickMaxBack},
+	"ls_left":  {stick: keyLeftStick, dx: stickMaxBack},
+	"ls_right": {stick: keyLeftStick, dx: stickMaxForward},
+	"rs_up":    {stick: keyRightStick, dy: stickMaxForward},
+	"rs_down":  {stick: keyRightStick, dy: stickMaxBack},
+	"rs_left":  {stick: keyRightStick, dx: stickMaxBack},
+	"rs_right": {stick: keyRightStick, dx: stickMaxForward},
+}
+
+// keyboardInput adds the inputs bound to keys of the keyboard to the reports
+// of the controller, using a low-level keyboard hook. Buttons and triggers
+// are combined with those of the controller, and keys bound to a stick
+// direction override the controller on that axis while pressed.
+//
+// Keys are passed on to other programs unless swallow is set.
+type keyboardInput struct {
+	bindings map[uint32]keyBinding
+	swallow  bool
+	changes  chan struct{}
+	callback uintptr
+
+	mu       sync.Mutex
+	pressed  map[uint32]bool
+	threadID uintptr
+	done     chan struct{}
+}
+
+// newKeyboardInput returns the keyboard input configured by a comma-separated
+// list of key=input entries, e.g. "f13=stadia,up=dpad_up,w=ls_up,q=l2". Inputs
+// are Stadia button names, "l2", "r2" and stick directions such as "ls_up" or
+// "rs_left". The hook is not installed yet.
+func newKeyboardInput(spec string, swallow bool) (*keyboardInput, error) {
+	k := &keyboardInput{
+		bindings: map[uint32]keyBinding{},
+		swallow:  swallow,
+		changes:  make(chan struct{}, 1),
+		pressed:  map[uint32]bool{},
+	}
+
+	for _, entry := range strings.Split(spec, ",") {
+		if entry = strings.TrimSpace(entry); entry == "" {
+			continue
+		}
+
+		i := strings.IndexByte(entry, '=')
+
+		if i == -1 {
+			return nil, fmt.Errorf("invalid key binding %q, expected key=input", entry)
+		}
+
+		key, err := parseKey(entry[:i])
+
+		if err != nil {
+			return nil, err
+		}
+
+		binding, err := parseKeyBinding(entry[i+1:])
+
+		if err != nil {
+			return nil, err
+		}
+
+		k.bindings[key] = binding
+	}
+
+	if len(k.bindings) == 0 {
+		return nil, fmt.Errorf("no key bindings given")
+	}
+
+	// Only a limited number of callbacks can be created, so the callback is
+	// reused by all installations of the hook.
+	k.callback = windows.NewCallback(k.hookProc)
+
+	return k, nil
+}
+
+func parseKey(name string) (uint32, error) {
+	name = strings.ToLower(strings.TrimSpace(name))
+
+	if vk, ok := keyNames[name]; ok {
+		return vk, nil
+	}
+	if len(name) == 1 && (name[0] >= 'a' && name[0] <= 'z' || name[0] >= '0' && name[0] <= '9') {
+		return uint32(strings.ToUpper(name)[0]), nil
+	}
+	if strings.HasPrefix(name, "f") {
+		if n, err := strconv.Atoi(name[1:]); err == nil && n >= 1 && n <= 24 {
+			return 0x70 + uint32(n-1), nil
+		}
+	}
+	if strings.HasPrefix(name, "0x") {
+		if vk, err := strconv.ParseUint(name[2:], 16, 8); err == nil {
+			return uint32(vk), nil
+		}
+	}
+
+	return 0, fmt.Errorf("unknown key %q", name)
+}
+
+func parseKeyBinding(name string) (keyBinding, error) {
+	name = strings.ToLower(strings.TrimSpace(name))
+
+	switch name {
+	case "l2":
+		return keyBinding{leftTrigger: true}, nil
+	case "r2":
+		return keyBinding{rightTrigger: true}, nil
+	}
+
+	if binding, ok := keyStickDirections[name]; ok {
+		return binding, nil
+	}
+
+	buttons, err := stadiacontroller.ParseStadiaButtons(name)
+
+	if err != nil {
+		return keyBinding{}, err
+	}
+
+	return keyBinding{buttons: buttons}, nil
+}
+
+// isInstalled returns whether the hook is installed.
+func (k *keyboardInput) isInstalled() bool {
+	k.mu.Lock()
+	defer k.mu.Unlock()
+
+	return k.done != nil
+}
+
+// install installs the keyboard hook, unless it already is.
+func (k *keyboardInput) install() error {
+	k.mu.Lock()
+	defer k.mu.Unlock()
+
+	if k.done != nil {
+		return nil
+	}
+
+	ready := make(chan error, 1)
+	done := make(chan struct{})
+
+	go k.run(ready, done)
+
+	if err := <-ready; err != nil {
+		return err
+	}
+
+	k.done = done
+
+	return nil
+}
+
+// uninstall uninstalls the keyboard hook if it is installed, and releases the
+// inputs of all keys.
+func (k *keyboardInput) uninstall() {
+	k.mu.Lock()
+	done := k.done
+	threadID := k.threadID
+	k.done = nil
+	k.mu.Unlock()
+
+	if done == nil {
+		return
+	}
+
+	procPostThreadMessageW.Call(threadID, wmQuit, 0, 0)
+	<-done
+
+	k.mu.Lock()
+	k.pressed = map[uint32]bool{}
+	k.mu.Unlock()
+
+	k.notify()
+}
+
+// run installs the hook and dispatches messages until it receives wmQuit.
+// Low-level hooks are called on the thread which installed them, which must
+// therefore dispatch messages.
+func (k *keyboardInput) run(ready chan<- error, done chan<- struct{}) {
+	runtime.LockOSThread()
+	defer close(done)
+
+	instance, _, _ := procGetModuleHandleW.Call(0)
+	hook, _, err := procSetWindowsHookExW.Call(whKeyboardLL, k.callback, instance, 0)
+
+	if hook == 0 {
+		ready <- fmt.Errorf("cannot install keyboard hook: %w", err)
+		return
+	}
+
+	defer procUnhookWindowsHookEx.Call(hook)
+
+	threadID, _, _ := procGetCurrentThreadId.Call()
+
+	// install holds the lock until ready is sent to.
+	k.threadID = threadID
+	ready <- nil
+
+	var msg winMsg
+
+	for {
+		r, _, _ := procGetMessageW.Call(uintptr(unsafe.Pointer(&msg)), 0, 0, 0)
+
+		if int32(r) <= 0 {
+			return
+		}
+
+		procDispatchMessageW.Call(uintptr(unsafe.Pointer(&msg)))
+	}
+}
+
+func (k *keyboardInput) hookProc(code, wParam, lParam uintptr) uintptr {
+	if int32(code) == hookActionCode {
+		info := (*kbdllHookStruct)(unsafe.Pointer(lParam))
+
+		// Ignore keys sent by programs, including -volume-keys.
+		if _, ok := k.bindings[info.vkCode]; ok && info.flags&llkhfInjected == 0 {
+			switch wParam {
+			case wmKeyDown, wmSysKeyDown:
+				k.setPressed(info.vkCode, true)
+			case wmKeyUp, wmSysKeyUp:
+				k.setPressed(info.vkCode, false)
+			}
+
+			if k.swallow {
+				return 1
+			}
+		}
+	}
+
+	r, _, _ := procCallNextHookEx.Call(0, code, wParam, lParam)
+
+	return r
+}
+
+func (k *keyboardInput) setPressed(vk uint32, pressed bool) {
+	k.mu.Lock()
+	changed := k.pressed[vk] != pressed
+	k.pressed[vk] = pressed
+	k.mu.Unlock()
+
+	// Held keys repeat: only notify actual changes.
+	if changed {
+		k.notify()
+	}
+}
+
+// notify tells the input loop that the keyboard inputs changed, without
+// blocking the hook.
+func (k *keyboardInput) notify() {
+	select {
+	case k.changes <- struct{}{}:
+	default:
+	}
+}
+
+// apply adds the inputs of the pressed keys to the given report.
+func (k *keyboardInput) apply(report *stadiacontroller.Xbox360ControllerReport) {
+	k.mu.Lock()
+	defer k.mu.Unlock()
+
+	var buttons stadiacontroller.StadiaButtons
+	var sticks [3]struct {
+		x, y       int32
+		setX, setY bool
+	}
+
+	for vk, pressed := range k.pressed {
+		if !pressed {
+			continue
+		}
+
+		binding := k.bindings[vk]
+		buttons |= binding.buttons
+
+		if binding.leftTrigger {
+			report.SetLeftTrigger(255)
+		}
+		if binding.rightTrigger {
+			report.SetRightTrigger(255)
+		}
+
+		stick := &sticks[binding.stick]
+
+		if binding.dx != 0 {
+			stick.x += int32(binding.dx)
+			stick.setX = true
+		}
+		if binding.dy != 0 {
+			stick.y += int32(binding.dy)
+			stick.setY = true
+		}
+	}
+
+	report.Buttons |= buttons
+	report.Capture = report.Buttons.Has(stadiacontroller.StadiaButtonCapture)
+	report.Assistant = report.Buttons.Has(stadiacontroller.StadiaButtonAssistant)
+	report.SetButtons(report.GetButtons() | buttons.Xbox360Buttons())
+
+	// Opposite directions cancel out.
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	report.SetLeftThumb(keyAxis(lx, sticks[keyLeftStick].x, sticks[keyLeftStick].setX), keyAxis(ly, sticks[keyLeftStick].y, sticks[keyLeftStick].setY))
+	report.SetRightThumb(keyAxis(rx, sticks[keyRightStick].x, sticks[keyRightStick].setX), keyAxis(ry, sticks[keyRightStick].y, sticks[keyRightStick].setY))
+}
+
+// keyAxis returns the value of an axis of a stick, overridden by the given
+// value of the keys if set.
+func keyAxis(value int16, keys int32, set bool) int16 {
+	if !set {
+		return value
+	}
+	if keys > stickMaxForward {
+		return stickMaxForward
+	}
+	if keys < stickMaxBack {
+		return stickMaxBack
+	}
+
+	return int16(keys)
+}
+
+// startKeyboardInput installs the hook of the keys bound by -keys, or returns
+// nil if no key is bound.
+func startKeyboardInput() (*keyboardInput, error) {
+	if *keyBindings == "" {
+		return nil, nil
+	}
+
+	k, err := newKeyboardInput(*keyBindings, *keySwallow)
+
+	if err != nil {
+		return nil, err
+	}
+	if err := k.install(); err != nil {
+		return nil, err
+	}
+
+	return k, nil
+}
+
+// step returns the step pressing the inputs of the held keys, or nil if k is
+// nil.
+func (k *keyboardInput) step() *reportStep {
+	if k == nil {
+		return nil
+	}
+
+	return transformStep(k.apply, nil)
+}
@@ -0,0 +1,117 @@
+package main
+
+import (
+	"fmt"
+	"log"
+	"strings"
+	"unsafe"
+)
+
+var procMapVirtualKeyW = user32.NewProc("MapVirtualKeyW")
+
+// MAPVK_VK_TO_VSC, used to find the scan codes of keys.
+const mapvkVkToVsc = 0
+
+// Modifiers which can start a key chord.
+var modifierKeys = map[string]uint16{
+	"ctrl":  0x11,
+	"shift": 0x10,
+	"alt":   0x12,
+	"win":   0x5B,
+}
+
+// Keys which must be sent with KEYEVENTF_EXTENDEDKEY, as they are distinct
+// from keys of the numeric keypad sharing their scan codes.
+var extendedKeys = map[uint16]bool{
+	0x21: true, // Page Up
+	0x22: true, // Page Down
+	0x23: true, // End
+	0x24: true, // Home
+	0x25: true, // Left
+	0x26: true, // Up
+	0x27: true, // Right
+	0x28: true, // Down
+	0x2C: true, // Print Screen
+	0x2D: true, // Insert
+	0x2E: true, // Delete
+	0x5B: true, // Left Windows
+	0x5C: true, // Right Windows
+	0x5D: true, // Applications
+	0x6F: true, // Numeric keypad divide
+	0x90: true, // Num Lock
+}
+
+// keyChord is a combination of keys, e.g. "win+alt+printscreen", pressed while
+// a button is held.
+type keyChord struct {
+	keys []uint16
+	held bool
+}
+
+// parseKeyChord parses a key chord given as keys separated by '+': modifiers
+// (ctrl, shift, alt and win) followed by a single other key, named as in key
+// bindings. It returns nil if spec is empty.
+func parseKeyChord(spec string) (*keyChord, error) {
+	if strings.TrimSpace(spec) == "" {
+		return nil, nil
+	}
+
+	names := strings.Split(spec, "+")
+	chord := &keyChord{}
+
+	for i, name := range names {
+		name = strings.ToLower(strings.TrimSpace(name))
+
+		if vk, ok := modifierKeys[name]; ok && i < len(names)-1 {
+			chord.keys = append(chord.keys, vk)
+			continue
+		}
+		if i < len(names)-1 {
+			return nil, fmt.Errorf("invalid key chord %q, expected modifiers followed by a key", spec)
+		}
+
+		vk, err := parseKey(name)
+
+		if err != nil {
+			return nil, err
+		}
+
+		chord.keys = append(chord.keys, uint16(vk))
+	}
+
+	return chord, nil
+}
+
+// set presses the keys of the chord in order, or releases them in reverse
+// order, unless they already are.
+func (c *keyChord) set(pressed bool) {
+	if c.held == pressed {
+		return
+	}
+
+	c.held = pressed
+
+	inputs := make([]keyInput, len(c.keys))
+
+	for i, vk := range c.keys {
+		scan, _, _ := procMapVirtualKeyW.Call(uintptr(vk), mapvkVkToVsc)
+		input := keyInput{typ: inputKeyboard, vk: vk, scan: uint16(scan)}
+
+		if extendedKeys[vk] {
+			input.flags |= keyEventExtendedKey
+		}
+
+		if pressed {
+			inputs[i] = input
+		} else {
+			input.flags |= keyEventKeyUp
+			inputs[len(inputs)-1-i] = input
+		}
+	}
+
+	r, _, err := procSendInput.Call(uintptr(len(inputs)), uintptr(unsafe.Pointer(&inputs[0])), unsafe.Sizeof(inputs[0]))
+
+	if r != uintptr(len(inputs)) {
+		log.Printf("cannot send keys: %v", err)
+	}
+}
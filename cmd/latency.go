@@ -0,0 +1,135 @@
+package main
+
+import (
+	"fmt"
+	"log"
+	"strings"
+	"sync"
+	"sync/atomic"
+	"time"
+)
+
+// Upper bounds of the histogram buckets; a last, unbounded bucket holds
+// everything above.
+var latencyBuckets = [...]time.Duration{
+	50 * time.Microsecond,
+	100 * time.Microsecond,
+	250 * time.Microsecond,
+	500 * time.Microsecond,
+	1 * time.Millisecond,
+	2 * time.Millisecond,
+	4 * time.Millisecond,
+	8 * time.Millisecond,
+	16 * time.Millisecond,
+	32 * time.Millisecond,
+}
+
+type histogram struct {
+	counts [len(latencyBuckets) + 1]uint64
+	count  uint64
+	sum    time.Duration
+	min    time.Duration
+	max    time.Duration
+}
+
+func (h *histogram) add(d time.Duration) {
+	i := 0
+	for i < len(latencyBuckets) && d > latencyBuckets[i] {
+		i++
+	}
+
+	h.counts[i]++
+
+	if h.count == 0 || d < h.min {
+		h.min = d
+	}
+	if d > h.max {
+		h.max = d
+	}
+
+	h.count++
+	h.sum += d
+}
+
+func (h *histogram) String() string {
+	if h.count == 0 {
+		return "no samples"
+	}
+
+	var b strings.Builder
+
+	fmt.Fprintf(&b, "n=%d min=%v avg=%v max=%v |", h.count, h.min, h.sum/time.Duration(h.count), h.max)
+
+	for i, n := range h.counts {
+		if n == 0 {
+			continue
+		}
+		if i < len(latencyBuckets) {
+			fmt.Fprintf(&b, " <=%v:%d", latencyBuckets[i], n)
+		} else {
+			fmt.Fprintf(&b, " >%v:%d", latencyBuckets[len(latencyBuckets)-1], n)
+		}
+	}
+
+	return b.String()
+}
+
+// latencyStats measures how long passes between reading a report from the
+// controller and sending it to ViGEm, and how much time passes between two
+// consecutive reports sent.
+type latencyStats struct {
+	mu         sync.Mutex
+	processing histogram
+	interval   histogram
+	last       time.Time
+	lowLatency bool
+}
+
+// newLatencyStats returns a latencyStats that prints a summary every given
+// interval, or never if it is zero. lowLatency indicates whether the
+// low-latency mode is active, which is mentioned in the summary so that
+// measurements can be compared.
+func newLatencyStats(every time.Duration, lowLatency bool) *latencyStats {
+	stats := &latencyStats{lowLatency: lowLatency}
+
+	if every > 0 {
+		go func() {
+			for range time.Tick(every) {
+				stats.print()
+			}
+		}()
+	}
+
+	return stats
+}
+
+// record adds a report read from the controller at the given time, and sent
+// to ViGEm at the other given time.
+func (s *latencyStats) record(received, forwarded time.Time) {
+	s.mu.Lock()
+	defer s.mu.Unlock()
+
+	s.processing.add(forwarded.Sub(received))
+
+	if !s.last.IsZero() {
+		s.interval.add(received.Sub(s.last))
+	}
+
+	s.last = received
+}
+
+func (s *latencyStats) print() {
+	s.mu.Lock()
+	defer s.mu.Unlock()
+
+	if s.lowLatency {
+		log.Printf("measured with low-latency mode active")
+	}
+
+	if delay := atomic.LoadInt64(&stats.addedDelayNanos); delay != 0 {
+		log.Printf("added delay (excluded from processing latency): %v", time.Duration(delay))
+	}
+
+	log.Printf("processing latency: %v", &s.processing)
+	log.Printf("report interval: %v", &s.interval)
+}
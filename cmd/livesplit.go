@@ -0,0 +1,195 @@
+package main
+
+import (
+	"fmt"
+	"log"
+	"net"
+	"strings"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Delay between two attempts to connect to LiveSplit.
+const liveSplitRetryDelay = 2 * time.Second
+
+// liveSplitClient sends commands to a LiveSplit Server, reconnecting when
+// LiveSplit is restarted.
+type liveSplitClient struct {
+	addr     string
+	commands chan string
+	done     chan struct{}
+}
+
+// newLiveSplitClient returns a client which connects to the LiveSplit Server
+// at the given address in the background.
+func newLiveSplitClient(addr string) *liveSplitClient {
+	c := &liveSplitClient{
+		addr:     addr,
+		commands: make(chan string, 16),
+		done:     make(chan struct{}),
+	}
+
+	go c.run()
+
+	return c
+}
+
+// Close disconnects from LiveSplit.
+func (c *liveSplitClient) Close() {
+	close(c.done)
+}
+
+// send queues the given command without blocking. It is dropped if too many
+// commands are pending.
+func (c *liveSplitClient) send(command string) {
+	select {
+	case c.commands <- command:
+	default:
+	}
+}
+
+func (c *liveSplitClient) run() {
+	var conn net.Conn
+
+	defer func() {
+		if conn != nil {
+			conn.Close()
+		}
+	}()
+
+	// Failures are only logged once per outage.
+	down := false
+
+	connect := func() bool {
+		var err error
+		conn, err = net.DialTimeout("tcp", c.addr, liveSplitRetryDelay)
+
+		if err != nil {
+			conn = nil
+
+			if !down {
+				log.Printf("cannot connect to LiveSplit at %s, retrying in the background: %v", c.addr, err)
+				down = true
+			}
+
+			return false
+		}
+
+		if down {
+			log.Printf("reconnected to LiveSplit at %s", c.addr)
+			down = false
+		}
+
+		return true
+	}
+
+	connect()
+
+	retry := time.NewTicker(liveSplitRetryDelay)
+	defer retry.Stop()
+
+	for {
+		select {
+		case command := <-c.commands:
+			if conn == nil && !connect() {
+				continue
+			}
+
+			if _, err := conn.Write([]byte(command + "\r\n")); err != nil {
+				// LiveSplit may have been restarted since the last command, in
+				// which case a new connection succeeds.
+				conn.Close()
+
+				if !connect() {
+					continue
+				}
+				if _, err := conn.Write([]byte(command + "\r\n")); err != nil {
+					conn.Close()
+					conn = nil
+					log.Printf("cannot send command to LiveSplit: %v", err)
+					down = true
+				}
+			}
+
+		case <-retry.C:
+			if conn == nil {
+				connect()
+			}
+
+		case <-c.done:
+			return
+		}
+	}
+}
+
+// parseLiveSplitMap parses a comma-separated list of
+// "button:gesture=command" mappings, e.g. "capture:tap=startorsplit".
+func parseLiveSplitMap(s string) (map[stadiacontroller.StadiaButtons]*[len(gestureNames)]string, error) {
+	mapping := map[stadiacontroller.StadiaButtons]*[len(gestureNames)]string{}
+
+	for _, entry := range strings.Split(s, ",") {
+		entry = strings.TrimSpace(entry)
+
+		if entry == "" {
+			continue
+		}
+
+		i, j := strings.IndexByte(entry, ':'), strings.IndexByte(entry, '=')
+
+		if i == -1 || j < i {
+			return nil, fmt.Errorf("invalid LiveSplit mapping %q, expected button:gesture=command", entry)
+		}
+
+		button, err := stadiacontroller.ParseStadiaButtons(entry[:i])
+
+		if err != nil {
+			return nil, err
+		}
+		if button == 0 {
+			return nil, fmt.Errorf("invalid LiveSplit mapping %q, expected a button", entry)
+		}
+
+		g, err := parseGesture(entry[i+1 : j])
+
+		if err != nil {
+			return nil, err
+		}
+
+		if mapping[button] == nil {
+			mapping[button] = new([len(gestureNames)]string)
+		}
+
+		mapping[button][g] = strings.TrimSpace(entry[j+1:])
+	}
+
+	return mapping, nil
+}
+
+// newLiveSplitGestures connects to LiveSplit as configured by the command
+// line, and returns detectors sending the configured commands for the
+// gestures of each mapped button.
+func newLiveSplitGestures() (*liveSplitClient, []buttonGestures, error) {
+	mapping, err := parseLiveSplitMap(*liveSplitMap)
+
+	if err != nil {
+		return nil, nil, err
+	}
+
+	client := newLiveSplitClient(*liveSplitAddr)
+
+	var gestures []buttonGestures
+
+	for button, commands := range mapping {
+		commands := commands
+		detector := newGestureDetector(commands[gestureDoublePress] != "", func(g gesture) {
+			if command := commands[g]; command != "" {
+				client.send(command)
+			}
+		})
+
+		gestures = append(gestures, buttonGestures{button, detector})
+	}
+
+	return client, gestures, nil
+}
@@ -0,0 +1,156 @@
+package main
+
+import (
+	"bytes"
+	"fmt"
+	"io"
+	"log"
+	"strings"
+	"sync"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// A logLevel is the most detailed kind of messages logged. The level of a
+// message is given by its prefix, e.g. "warning: ", and messages without
+// prefix are info messages.
+type logLevel int
+
+const (
+	logLevelError logLevel = iota
+	logLevelWarning
+	logLevelInfo
+	logLevelDebug
+	logLevelTrace
+)
+
+var logLevelNames = [...]string{"error", "warning", "info", "debug", "trace"}
+
+// Name of the log file next to the executable with -background, unless
+// -log-file is given.
+const backgroundLogFileName = "stadiacontroller.log"
+
+// Maximum number of reports dumped per second with -dump-reports.
+const maxDumpedReportsPerSecond = 50
+
+// currentLogLevel is the level selected by -log-level. It is only set on
+// startup.
+var currentLogLevel = logLevelInfo
+
+// reportDump logs the reports read with -dump-reports.
+var reportDump reportDumper
+
+func parseLogLevel(s string) (logLevel, error) {
+	for level, name := range logLevelNames {
+		if strings.EqualFold(s, name) {
+			return logLevel(level), nil
+		}
+	}
+
+	return 0, fmt.Errorf("unknown log level %q, expected error, warning, info, debug or trace", s)
+}
+
+// messageLevel returns the level of the given message.
+func messageLevel(message []byte) logLevel {
+	for level, name := range logLevelNames {
+		if len(message) > len(name)+1 && string(message[:len(name)]) == name && message[len(name)] == ':' {
+			return logLevel(level)
+		}
+	}
+
+	return logLevelInfo
+}
+
+// levelWriter writes the messages of the standard logger up to a level,
+// prefixed by their time to the microsecond.
+type levelWriter struct {
+	w     io.Writer
+	level logLevel
+}
+
+func (w *levelWriter) Write(p []byte) (int, error) {
+	if messageLevel(p) > w.level {
+		return len(p), nil
+	}
+
+	line := append([]byte(time.Now().Format("2006/01/02 15:04:05.000000 ")), p...)
+
+	if _, err := w.w.Write(line); err != nil {
+		return 0, err
+	}
+
+	return len(p), nil
+}
+
+// setLogLevel only logs the messages up to the given level from now on.
+func setLogLevel(level logLevel) {
+	currentLogLevel = level
+
+	log.SetFlags(0)
+	log.SetOutput(&levelWriter{log.Writer(), level})
+}
+
+// debugf logs a debug message, e.g. for each command run and vibration.
+func debugf(format string, args ...interface{}) {
+	if currentLogLevel >= logLevelDebug {
+		log.Printf("debug: "+format, args...)
+	}
+}
+
+// reportDumper logs the reports read with -dump-reports. Only reports which
+// differ from the previous one are logged, and at most
+// maxDumpedReportsPerSecond of them every second.
+type reportDumper struct {
+	mu      sync.Mutex
+	last    []byte
+	second  time.Time
+	dumped  int
+	skipped int
+}
+
+// dump logs the given report, as given to OnRawReport.
+func (d *reportDumper) dump(data []byte, report *stadiacontroller.Xbox360ControllerReport, err error) {
+	d.mu.Lock()
+
+	if bytes.Equal(data, d.last) {
+		d.mu.Unlock()
+		return
+	}
+
+	d.last = append(d.last[:0], data...)
+
+	if now := time.Now(); now.Sub(d.second) >= time.Second {
+		if d.skipped > 0 {
+			log.Printf("trace: %d changed reports were not dumped in the last second", d.skipped)
+		}
+
+		d.second, d.dumped, d.skipped = now, 0, 0
+	}
+
+	if d.dumped == maxDumpedReportsPerSecond {
+		d.skipped++
+		d.mu.Unlock()
+		return
+	}
+
+	d.dumped++
+	d.mu.Unlock()
+
+	if err != nil {
+		log.Printf("trace: report % x: %v", data, err)
+		return
+	}
+
+	buttons := report.Buttons.String()
+
+	if buttons == "" {
+		buttons = "none"
+	}
+
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	log.Printf("trace: report % x: buttons=%s lx=%d ly=%d rx=%d ry=%d lt=%d rt=%d",
+		data, buttons, lx, ly, rx, ry, report.GetLeftTrigger(), report.GetRightTrigger())
+}
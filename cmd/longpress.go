@@ -0,0 +1,102 @@
+package main
+
+import (
+	"log"
+	"strconv"
+	"sync"
+	"time"
+)
+
+// pressCommands runs different commands for short and long presses of a
+// button. The long press command runs as soon as the button has been held for
+// threshold, and the short press command runs on release if the button was
+// released earlier, so that a press only runs one of them. The release
+// command runs on every release.
+type pressCommands struct {
+	threshold time.Duration
+	short     string
+	long      string
+	released  string
+
+	mu      sync.Mutex
+	pressed bool
+	fired   bool
+	timer   *time.Timer
+}
+
+// newPressCommands returns the commands of a button, or nil if it has no long
+// press command, in which case the pressed command runs on press.
+func newPressCommands(threshold time.Duration, short, long, released string) *pressCommands {
+	if long == "" {
+		return nil
+	}
+
+	return &pressCommands{threshold: threshold, short: short, long: long, released: released}
+}
+
+// update sets the state of the button, running the commands with the given
+// variables added to their environment. STADIA_STATE is short-pressed or
+// long-pressed for the commands of short and long presses.
+func (p *pressCommands) update(pressed bool, env []string) error {
+	p.mu.Lock()
+	defer p.mu.Unlock()
+
+	if pressed == p.pressed {
+		return nil
+	}
+
+	p.pressed = pressed
+
+	if pressed {
+		p.fired = false
+		p.timer = time.AfterFunc(p.threshold, func() {
+			p.mu.Lock()
+			defer p.mu.Unlock()
+
+			if !p.pressed || p.fired {
+				return
+			}
+
+			p.fired = true
+
+			env := append(env[:len(env):len(env)], "STADIA_STATE=long-pressed", "STADIA_HOLD_MS="+strconv.FormatInt(int64(p.threshold/time.Millisecond), 10))
+
+			if err := runCommand(p.long, env...); err != nil {
+				log.Printf("cannot run long press command: %v", err)
+			}
+		})
+
+		return nil
+	}
+
+	p.timer.Stop()
+
+	if !p.fired {
+		p.fired = true
+
+		if p.short != "" {
+			if err := runCommand(p.short, append(env[:len(env):len(env)], "STADIA_STATE=short-pressed")...); err != nil {
+				return err
+			}
+		}
+	}
+
+	if p.released != "" {
+		return runCommand(p.released, env...)
+	}
+
+	return nil
+}
+
+// reset forgets the current press without running its commands, e.g. when the
+// controller is disconnected while the button is held.
+func (p *pressCommands) reset() {
+	p.mu.Lock()
+	defer p.mu.Unlock()
+
+	if p.timer != nil {
+		p.timer.Stop()
+	}
+
+	p.fired = true
+}
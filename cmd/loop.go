@@ -0,0 +1,929 @@
+package main
+
+import (
+	"errors"
+	"fmt"
+	"log"
+	"strings"
+	"sync/atomic"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// A reportStep is a part of the input loop, which changes the reports read
+// from the controller before they are forwarded.
+type reportStep struct {
+	// apply changes a report handled at the given time. It returns the time at
+	// which the last report must be handled again, if the step may change it
+	// without another report, and errSkipReport if the report must not be
+	// forwarded.
+	apply func(report *stadiacontroller.Xbox360ControllerReport, now time.Time) (time.Time, error)
+
+	// reset, if not nil, forgets the state of the step when the controller is
+	// disconnected.
+	reset func()
+}
+
+// errSkipReport is returned by steps which used a report for something else
+// than the emulated controller.
+var errSkipReport = errors.New("report not forwarded")
+
+// transformStep returns a step which only changes the report.
+func transformStep(apply func(report *stadiacontroller.Xbox360ControllerReport), reset func()) *reportStep {
+	return &reportStep{
+		apply: func(report *stadiacontroller.Xbox360ControllerReport, now time.Time) (time.Time, error) {
+			apply(report)
+			return time.Time{}, nil
+		},
+		reset: reset,
+	}
+}
+
+// timedStep returns a step which may change the last report once some time
+// passed.
+func timedStep(apply func(report *stadiacontroller.Xbox360ControllerReport, now time.Time) time.Time, reset func()) *reportStep {
+	return &reportStep{
+		apply: func(report *stadiacontroller.Xbox360ControllerReport, now time.Time) (time.Time, error) {
+			return apply(report, now), nil
+		},
+		reset: reset,
+	}
+}
+
+// inputLoop forwards the reports of the controller to the emulated controller,
+// and handles the other events of the controller, the commands and the timers
+// of the features meanwhile. Its fields are only used by the goroutine of
+// run, except those used by vibrate.
+type inputLoop struct {
+	controller *stadiacontroller.StadiaController
+	pad        stadiacontroller.EmulatedController
+	out        *outputs
+	measures   measures
+	dog        *watchdog
+	state      controlState
+	commands   <-chan controlCommand
+	events     <-chan stadiacontroller.ControllerEvent
+
+	// Features handled by the loop itself rather than by steps, each nil
+	// unless enabled.
+	hider       *hidHide
+	calibration *calibrations
+	recenter    *stickRecenter
+	desktop     *desktopMouse
+	delayed     *delayQueue
+	actions     *buttonActions
+
+	// Vibrations requested by games, and the pattern played when the
+	// controller connects.
+	vibrations     *vibrationResponse
+	rumbleWatchdog *rumbleWatchdog
+	connectRumble  []rumbleSegment
+
+	// pipeline returns the steps applied to the reports while the given
+	// profile is active, and steps are those of the active profile. Disabled
+	// steps are nil.
+	pipeline func(p *profile) []*reportStep
+	steps    []*reportStep
+
+	// Commands of the shift layer, run once the current report is forwarded.
+	shiftCommands []buttonCommand
+
+	profiles []*profile
+	active   int
+
+	// Profiles matching games are activated when the games are in the
+	// foreground.
+	foreground     *foregroundWatcher
+	profileChanges <-chan int
+
+	// The profiles are loaded again when the configuration file changes.
+	configChanges <-chan struct{}
+
+	// Set while the emulated controller is unplugged by -idle-timeout.
+	idle int32
+
+	// Set while forwarding is paused, or while the application given to
+	// -only-when-focused is not in the foreground, during which the vibrations
+	// of games are not forwarded either.
+	paused, unfocused int32
+
+	// The player number assigned to the emulated controller is handled by the
+	// input loop, once per change.
+	assignedPlayer int32
+	playerChanges  chan struct{}
+
+	// Everything is released for one report after switching profiles, so
+	// that no input of the previous profile stays pressed.
+	releaseInputs bool
+
+	// The program holding the controller is only looked for once, until the
+	// controller is opened.
+	busyWarned bool
+
+	lowBattery   lowBatteryAlarm
+	batteryCheck <-chan time.Time
+
+	// Filtered presses may become long enough, auto-held inputs may be armed
+	// and keys may be pressed without another report: the last report is then
+	// handled again.
+	reportCheck  <-chan time.Time
+	lastReport   stadiacontroller.Xbox360ControllerReport
+	keyChanges   <-chan struct{}
+	mouseChanges <-chan struct{}
+
+	// The cursor of the desktop mouse keeps moving while a stick is held.
+	desktopTicks <-chan time.Time
+
+	// Reports delayed by -added-delay are forwarded when due.
+	delayCheck <-chan time.Time
+
+	// The emulated controller is released once when reports stop for
+	// -stale-input while the controller is connected.
+	staleCheck   <-chan time.Time
+	lastReceived time.Time
+	stale        bool
+
+	// Reports are only forwarded while the application given to
+	// -only-when-focused is in the foreground.
+	focused      bool
+	focusChanges <-chan bool
+
+	// Unless -persistent-target is given, the emulated controller is unplugged
+	// once the controller stayed disconnected for -unplug-delay.
+	unplugCheck <-chan time.Time
+
+	// The emulated controller is unplugged when no input was given for
+	// -idle-timeout, until the next input. The controller is kept open, since
+	// reading it is the only way to notice that input.
+	idleCheck <-chan time.Time
+	lastInput time.Time
+
+	// The controllers which could be selected are listed if the selected one
+	// is not found.
+	selectionCheck <-chan time.Time
+}
+
+// run forwards the reports of the controller until it is closed.
+func (l *inputLoop) run() error {
+	l.steps = l.pipeline(l.activeProfile())
+	l.events = l.controller.Events()
+	l.focused = true
+	l.lastInput = time.Now()
+
+	if len(l.profiles) > 1 {
+		log.Printf("profile %s active", l.activeProfile().name)
+		l.out.setProfile(l.activeProfile().name, false)
+	}
+
+	l.watchForeground()
+
+	defer func() {
+		if l.foreground != nil {
+			l.foreground.Close()
+		}
+	}()
+
+	if config != nil && !*noWatch {
+		watcher := newConfigWatcher(config.path)
+		defer watcher.Close()
+
+		l.configChanges = watcher.changes
+	}
+
+	if *staleInput > 0 {
+		ticker := time.NewTicker(*staleInput / 4)
+		defer ticker.Stop()
+
+		l.staleCheck = ticker.C
+	}
+
+	if focus := newFocusWatcher(onlyWhenFocused); focus != nil {
+		defer focus.Close()
+
+		l.focusChanges = focus.changes
+	}
+
+	if *idleTimeout > 0 {
+		ticker := time.NewTicker(*idleTimeout / 4)
+		defer ticker.Stop()
+
+		l.idleCheck = ticker.C
+	}
+
+	if *devicePath != "" || *serialNumber != "" {
+		l.selectionCheck = time.After(selectedControllerTimeout)
+	}
+
+	if l.state.keyboard != nil {
+		l.keyChanges = l.state.keyboard.changes
+	}
+	if l.state.mouse != nil {
+		l.mouseChanges = l.state.mouse.changes
+	}
+
+	l.dog.enter(stepWaiting)
+
+	for {
+		var err error
+
+		select {
+		case <-l.reportCheck:
+			l.reportCheck = nil
+			err = l.handleReport(l.lastReport, time.Time{}, true)
+
+		case <-l.keyChanges:
+			err = l.handleReport(l.lastReport, time.Time{}, true)
+
+		case <-l.mouseChanges:
+			err = l.handleReport(l.lastReport, time.Time{}, true)
+
+		case <-l.desktopTicks:
+			err = l.handleReport(l.lastReport, time.Time{}, true)
+
+		case <-l.selectionCheck:
+			l.selectionCheck = nil
+
+			if !l.state.connected {
+				if options, err := controllerOptions(); err == nil {
+					go logCandidateControllers(options)
+				}
+			}
+
+		case <-l.staleCheck:
+			err = l.checkStale()
+
+		case focused := <-l.focusChanges:
+			err = l.setFocused(focused)
+
+		case <-l.unplugCheck:
+			l.unplugCheck = nil
+			l.unplug()
+
+		case <-l.idleCheck:
+			err = l.checkIdle()
+
+		case <-l.delayCheck:
+			l.delayCheck = nil
+			err = l.forwardDelayed()
+
+		case i := <-l.profileChanges:
+			if i != l.active {
+				l.switchProfile(i, l.lastReport.Buttons)
+			}
+
+		case <-l.configChanges:
+			l.configChanged()
+
+		case <-l.playerChanges:
+			l.playerChanged()
+
+		case <-l.batteryCheck:
+			l.checkBattery()
+
+		case command := <-l.commands:
+			err = l.executeCommand(command)
+
+		case event, ok := <-l.events:
+			if !ok {
+				return nil
+			}
+
+			err = l.handleEvent(event)
+		}
+
+		if err != nil {
+			return err
+		}
+	}
+}
+
+// activeProfile returns the profile whose steps are applied.
+func (l *inputLoop) activeProfile() *profile {
+	return l.profiles[l.active]
+}
+
+func (l *inputLoop) handleEvent(event stadiacontroller.ControllerEvent) error {
+	switch event := event.(type) {
+	case stadiacontroller.ConnectedEvent:
+		return l.connected(event)
+
+	case stadiacontroller.HandOffEvent:
+		l.handedOff(event)
+
+	case stadiacontroller.DisconnectedEvent:
+		return l.disconnected()
+
+	case stadiacontroller.ErrorEvent:
+		return l.failed(event.Err)
+
+	case stadiacontroller.ReportEvent:
+		return l.handleReport(event.Report, event.Received, false)
+	}
+
+	return nil
+}
+
+func (l *inputLoop) connected(event stadiacontroller.ConnectedEvent) error {
+	log.Printf("controller connected over %v (firmware %04x)", event.Transport, event.Firmware)
+	l.state.connected = true
+	l.state.transport = event.Transport
+	l.state.devicePath = event.Path
+	l.lastReceived = time.Now()
+	l.lastInput = time.Now()
+	l.stale = false
+	l.busyWarned = false
+
+	// The emulated controller is plugged in once the controller is connected,
+	// unless it is idle.
+	l.unplugCheck = nil
+
+	if !*persistentTarget && atomic.LoadInt32(&l.idle) == 0 {
+		if err := l.pad.Connect(); err != nil {
+			return err
+		}
+	}
+
+	l.state.patterns.play(l.connectRumble)
+	l.selectController(event.Serial, event.Path)
+	l.out.connected(event.Transport, l.state.player)
+
+	if *onConnect != "" {
+		if err := runCommand(*onConnect, l.state.hookEnv()...); err != nil {
+			log.Printf("warning: cannot run connection command: %v", err)
+		}
+	}
+
+	return nil
+}
+
+func (l *inputLoop) handedOff(event stadiacontroller.HandOffEvent) {
+	log.Printf("controller %s handed off to %s over %v", event.From, event.To.Serial, event.To.Transport)
+	l.state.transport = event.To.Transport
+	l.state.devicePath = event.To.Path
+	l.lastReceived = time.Now()
+	l.stale = false
+
+	l.selectController(event.To.Serial, event.To.Path)
+	l.out.handedOff(event.To.Transport)
+
+	// The game does not know that the controller changed, so keep the new one
+	// vibrating like the previous one.
+	if motors := atomic.LoadUint32(&rumbleMotors); motors != 0 {
+		l.controller.VibrateAsync(byte(motors>>8), byte(motors))
+	}
+
+	if *onHandOff != "" {
+		env := append(l.state.hookEnv(), "STADIA_HANDOFF_FROM="+event.From, "STADIA_HANDOFF_TO="+event.To.Serial)
+
+		if err := runCommand(*onHandOff, env...); err != nil {
+			log.Printf("warning: cannot run hand-off command: %v", err)
+		}
+	}
+}
+
+// selectController applies the calibration, centers and hiding of the
+// controller with the given serial number and path, which now sends the
+// reports.
+func (l *inputLoop) selectController(serial, path string) {
+	if l.calibration != nil {
+		l.calibration.setController(serial)
+	}
+	if l.recenter != nil {
+		l.recenter.setController(serial)
+	}
+	if l.hider != nil {
+		if err := l.hider.hide(path); err != nil {
+			log.Printf("warning: %v", err)
+		}
+	}
+
+	// Check the battery right away, since it may differ from the last
+	// controller, or from the last transport of the same one.
+	if *batteryInterval > 0 {
+		l.batteryCheck = time.After(0)
+	}
+}
+
+func (l *inputLoop) disconnected() error {
+	log.Printf("controller disconnected")
+	l.state.connected = false
+	l.state.report = stadiacontroller.NewXbox360ControllerReport()
+	l.batteryCheck = nil
+	l.lastReport = stadiacontroller.NewXbox360ControllerReport()
+	l.reportCheck = nil
+
+	for _, step := range l.steps {
+		if step != nil && step.reset != nil {
+			step.reset()
+		}
+	}
+
+	if l.delayed != nil {
+		if err := l.delayed.flush(l.pad); err != nil {
+			return err
+		}
+
+		l.delayCheck = nil
+	}
+
+	// Release everything on the emulated controller, which would otherwise
+	// keep the last inputs until the controller reconnects. This also happens
+	// when shutting down, since closing the controller disconnects it.
+	neutral := stadiacontroller.NewXbox360ControllerReport()
+
+	if err := l.pad.Send(&neutral); err != nil {
+		return err
+	}
+
+	l.actions.reset()
+
+	if !*persistentTarget {
+		l.unplugCheck = time.After(*unplugDelay)
+	}
+
+	l.setBattery(stadiacontroller.BatteryStatus{}, time.Now())
+
+	atomic.AddUint64(&stats.disconnects, 1)
+	l.out.disconnected(l.state.transport, l.state.player)
+
+	if *onDisconnect != "" {
+		if err := runCommand(*onDisconnect, l.state.hookEnv()...); err != nil {
+			log.Printf("warning: cannot run disconnection command: %v", err)
+		}
+	}
+
+	return nil
+}
+
+// failed handles an error of the controller, which ends the loop unless the
+// controller is opened again.
+func (l *inputLoop) failed(err error) error {
+	if errors.Is(err, stadiacontroller.ErrMalformedReport) {
+		atomic.AddUint64(&stats.parseFailures, 1)
+	}
+	if errors.Is(err, stadiacontroller.ErrAccessDenied) {
+		if *failOnBusy {
+			return fmt.Errorf("the controller is used by another program: %w", err)
+		}
+		if !l.busyWarned {
+			l.busyWarned = true
+			processes, _ := runningProcesses()
+
+			if isSteamConflict(err, processes) {
+				log.Print(steamWarning)
+			} else if mappers := runningMappers(processes); len(mappers) > 0 {
+				log.Printf("warning: the controller is used by another program, probably %s; waiting until it is released", strings.Join(mappers, " or "))
+			} else {
+				log.Printf("warning: the controller is used by another program; waiting until it is released")
+			}
+		}
+	}
+	if errors.Is(err, stadiacontroller.RetryError) {
+		return nil
+	}
+
+	return err
+}
+
+// handleReport applies the steps to a report read from the controller at the
+// given time, or handled again if repeated, forwards it and runs the actions
+// of its buttons.
+func (l *inputLoop) handleReport(report stadiacontroller.Xbox360ControllerReport, received time.Time, repeated bool) error {
+	if !repeated {
+		atomic.AddUint64(&stats.reportsReceived, 1)
+		l.lastReceived = time.Now()
+		l.stale = false
+		l.out.received(&report)
+	}
+
+	l.lastReport = report
+	now := time.Now()
+
+	if !repeated && hasInput(&report) {
+		l.lastInput = now
+
+		if atomic.CompareAndSwapInt32(&l.idle, 1, 0) {
+			log.Printf("input received, plugging the emulated controller in again")
+
+			if err := l.pad.Connect(); err != nil {
+				return err
+			}
+		}
+	}
+	if atomic.LoadInt32(&l.idle) != 0 {
+		return nil
+	}
+
+	var wake time.Time
+	l.shiftCommands = nil
+
+	// Steps may switch profiles, in which case the steps of the new profile
+	// apply to the rest of the report.
+	for i := 0; i < len(l.steps); i++ {
+		step := l.steps[i]
+
+		if step == nil {
+			continue
+		}
+
+		next, err := step.apply(&report, now)
+
+		if err == errSkipReport {
+			return nil
+		}
+		if err != nil {
+			return err
+		}
+
+		wake = earliest(wake, next)
+	}
+
+	l.reportCheck = nil
+
+	if !wake.IsZero() {
+		l.reportCheck = time.After(time.Until(wake))
+	}
+
+	if l.measures.rate != nil && !repeated {
+		l.measures.rate.record(received)
+	}
+
+	l.dog.enter(stepForwarding)
+
+	if err := l.forward(report, received, now, repeated); err != nil {
+		return err
+	}
+
+	l.out.report(&report, l.state.paused)
+	l.dog.enter(stepRunningCommands)
+
+	if err := l.actions.run(&report, now, l.activeProfile().hooks, l.shiftCommands); err != nil {
+		return err
+	}
+
+	l.dog.enter(stepWaiting)
+
+	return nil
+}
+
+// forward sends a report handled at the given time to the emulated
+// controller, unless forwarding is paused or the focused application is not
+// in the foreground. The buttons consumed by commands are not forwarded.
+func (l *inputLoop) forward(report stadiacontroller.Xbox360ControllerReport, received, now time.Time, repeated bool) error {
+	if hooks := l.activeProfile().hooks; hooks != nil {
+		hooks.consume(&report)
+	}
+	if l.releaseInputs {
+		report = stadiacontroller.NewXbox360ControllerReport()
+		l.releaseInputs = false
+	}
+
+	switch {
+	case l.state.paused || !l.focused:
+
+	case l.delayed != nil && l.delayed.enabled:
+		l.delayed.push(report, now)
+
+		if l.delayCheck == nil {
+			l.delayCheck = time.After(time.Until(l.delayed.next()))
+		}
+
+	default:
+		if err := l.pad.Send(&report); err != nil {
+			return err
+		}
+
+		// Only reports read from the controller are measured, from the time
+		// at which they were read.
+		if !repeated {
+			l.measures.record(received, time.Now())
+		}
+
+		atomic.AddUint64(&stats.reportsForwarded, 1)
+	}
+
+	l.state.report = report
+
+	return nil
+}
+
+// release drops the delayed reports and releases everything on the emulated
+// controller.
+func (l *inputLoop) release() error {
+	l.clearDelayed()
+	l.state.report = stadiacontroller.NewXbox360ControllerReport()
+
+	return l.pad.Send(&l.state.report)
+}
+
+// clearDelayed drops the reports delayed by -added-delay.
+func (l *inputLoop) clearDelayed() {
+	if l.delayed != nil {
+		l.delayed.clear()
+		l.delayCheck = nil
+	}
+}
+
+func (l *inputLoop) forwardDelayed() error {
+	l.dog.enter(stepForwarding)
+
+	for _, report := range l.delayed.pop(time.Now()) {
+		if err := l.pad.Send(&report); err != nil {
+			return err
+		}
+
+		atomic.AddUint64(&stats.reportsForwarded, 1)
+	}
+
+	if next := l.delayed.next(); !next.IsZero() {
+		l.delayCheck = time.After(time.Until(next))
+	}
+
+	l.dog.enter(stepWaiting)
+
+	return nil
+}
+
+func (l *inputLoop) checkStale() error {
+	if !l.state.connected || l.stale || time.Since(l.lastReceived) < *staleInput {
+		return nil
+	}
+
+	l.stale = true
+	log.Printf("warning: no report received for %v, releasing the emulated controller", *staleInput)
+
+	l.lastReport = stadiacontroller.NewXbox360ControllerReport()
+	l.reportCheck = nil
+	l.clearDelayed()
+
+	if !l.state.paused {
+		if err := l.release(); err != nil {
+			return err
+		}
+	}
+	if *staleInputReacquire {
+		log.Printf("reopening the controller")
+
+		// Release waits for the disconnection event to be received.
+		go l.controller.Release()
+	}
+
+	return nil
+}
+
+func (l *inputLoop) setFocused(focused bool) error {
+	if focused == l.focused {
+		return nil
+	}
+
+	l.focused = focused
+
+	if focused {
+		log.Printf("focused application in the foreground, forwarding the controller")
+		atomic.StoreInt32(&l.unfocused, 0)
+
+		// Forward the current state right away, including the inputs held
+		// while not focused.
+		l.reportCheck = time.After(0)
+
+		return nil
+	}
+
+	log.Printf("focused application no longer in the foreground, releasing the emulated controller")
+	atomic.StoreInt32(&l.unfocused, 1)
+	l.controller.VibrateAsync(0, 0)
+
+	return l.release()
+}
+
+// unplug unplugs the emulated controller if the controller is still
+// disconnected.
+func (l *inputLoop) unplug() {
+	if l.state.connected {
+		return
+	}
+
+	log.Printf("controller disconnected for %v, unplugging the emulated controller", *unplugDelay)
+
+	if err := l.pad.Disconnect(); err != nil {
+		log.Printf("warning: cannot unplug the emulated controller: %v", err)
+	}
+}
+
+func (l *inputLoop) checkIdle() error {
+	if atomic.LoadInt32(&l.idle) != 0 || time.Since(l.lastInput) < *idleTimeout {
+		return nil
+	}
+
+	log.Printf("no input for %v, unplugging the emulated controller until the next input", *idleTimeout)
+	atomic.StoreInt32(&l.idle, 1)
+
+	l.lastReport = stadiacontroller.NewXbox360ControllerReport()
+	l.reportCheck = nil
+
+	if err := l.release(); err != nil {
+		return err
+	}
+	if err := l.pad.Disconnect(); err != nil {
+		log.Printf("warning: cannot unplug the emulated controller: %v", err)
+	}
+
+	return nil
+}
+
+// switchProfile activates the profile at the given index while the given
+// buttons are held.
+func (l *inputLoop) switchProfile(i int, buttons stadiacontroller.StadiaButtons) {
+	l.active = i
+	l.steps = l.pipeline(l.activeProfile())
+	l.releaseInputs = true
+
+	// Commands do not run for the buttons held while switching.
+	if hooks := l.activeProfile().hooks; hooks != nil {
+		hooks.previous = buttons
+	}
+
+	log.Printf("switched to profile %s", l.activeProfile().name)
+	l.out.setProfile(l.activeProfile().name, true)
+}
+
+// watchForeground activates the profiles matching games when they are in the
+// foreground, replacing the previous watcher.
+func (l *inputLoop) watchForeground() {
+	if l.foreground != nil {
+		l.foreground.Close()
+	}
+
+	l.foreground, l.profileChanges = newForegroundWatcher(l.profiles, l.active, *profileDelay), nil
+
+	if l.foreground != nil {
+		l.profileChanges = l.foreground.changes
+	}
+}
+
+func (l *inputLoop) configChanged() {
+	reloaded, profiles, err := reloadConfig(config, config.path)
+
+	if err != nil {
+		log.Printf("error: cannot reload the configuration, keeping the previous one: %v", err)
+		return
+	}
+
+	log.Printf("reloaded %s", reloaded.path)
+
+	name := l.activeProfile().name
+	config, l.profiles = reloaded, profiles
+	i, err := findProfile(l.profiles, name)
+
+	if err != nil {
+		log.Printf("warning: profile %s was removed", name)
+	}
+
+	// Switching releases everything for one report, so that no input mapped
+	// by the previous configuration stays pressed.
+	l.switchProfile(i, l.lastReport.Buttons)
+	l.watchForeground()
+}
+
+func (l *inputLoop) playerChanged() {
+	player := int(atomic.LoadInt32(&l.assignedPlayer))
+
+	if player == l.state.player {
+		return
+	}
+
+	l.state.player = player
+	log.Printf("emulated controller assigned to player %d", player)
+
+	if *onPlayerAssigned != "" {
+		if err := runCommand(*onPlayerAssigned, l.state.hookEnv()...); err != nil {
+			log.Printf("warning: cannot run player assignment command: %v", err)
+		}
+	}
+}
+
+func (l *inputLoop) checkBattery() {
+	// Reports come first: check the battery once they are handled.
+	if len(l.events) > 0 {
+		l.batteryCheck = time.After(10 * time.Millisecond)
+		return
+	}
+	if batteryCheckBlocked(l.measures.rate) {
+		l.batteryCheck = time.After(batteryRetryDelay)
+		return
+	}
+
+	l.batteryCheck = time.After(*batteryInterval)
+	status := stadiacontroller.ReadBattery(l.state.devicePath)
+	l.setBattery(status, time.Now())
+
+	if *lowBatteryLevel > 0 && l.lowBattery.update(status) {
+		warnLowBattery(l.controller, status, l.state.hookEnv()...)
+		l.out.lowBattery(status)
+	}
+}
+
+// setBattery shows the battery status read at the given time, if it changed,
+// including on the emulated controller if it can show it.
+func (l *inputLoop) setBattery(status stadiacontroller.BatteryStatus, readAt time.Time) {
+	if !l.state.battery.update(status, readAt) {
+		return
+	}
+
+	l.out.setBattery(status, readAt)
+
+	if reporter, ok := l.pad.(stadiacontroller.BatteryReporter); ok {
+		if err := reporter.SetBattery(status); err != nil {
+			log.Printf("warning: cannot show the battery on the emulated controller: %v", err)
+		}
+	}
+}
+
+func (l *inputLoop) executeCommand(command controlCommand) error {
+	wasPaused := l.state.paused
+	command.reply <- l.state.execute(command.request)
+
+	if l.delayed != nil && (l.state.paused || !l.delayed.enabled) {
+		// Delayed reports must not be forwarded once paused, and are flushed
+		// once no longer delayed.
+		if l.state.paused {
+			l.delayed.clear()
+		} else if err := l.delayed.flush(l.pad); err != nil {
+			return err
+		}
+
+		l.delayCheck = nil
+	}
+	if l.state.paused != wasPaused {
+		if err := l.pauseChanged(); err != nil {
+			return err
+		}
+	}
+	if l.state.shutdown {
+		log.Printf("shutting down")
+		l.controller.Close()
+	}
+
+	return nil
+}
+
+// pauseChanged applies a change of state.paused: while paused, everything is
+// released on the emulated controller and the motors are stopped.
+func (l *inputLoop) pauseChanged() error {
+	if !l.state.paused {
+		log.Printf("forwarding resumed")
+		atomic.StoreInt32(&l.paused, 0)
+		l.out.setPaused(false)
+
+		return nil
+	}
+
+	log.Printf("forwarding paused")
+	atomic.StoreInt32(&l.paused, 1)
+	l.out.setPaused(true)
+	l.controller.VibrateAsync(0, 0)
+
+	return l.release()
+}
+
+// vibrate forwards a vibration requested by a game to the controller. It is
+// called by the ViGEm notification thread, which it must not block while the
+// controller handles the write.
+func (l *inputLoop) vibrate(vibration stadiacontroller.Vibration) {
+	if player := int32(vibration.Player); player != 0 && atomic.SwapInt32(&l.assignedPlayer, player) != player {
+		select {
+		case l.playerChanges <- struct{}{}:
+		default:
+		}
+	}
+
+	if atomic.LoadInt32(&l.idle) != 0 || atomic.LoadInt32(&l.paused) != 0 || atomic.LoadInt32(&l.unfocused) != 0 {
+		return
+	}
+
+	l.out.vibrationRequested(vibration)
+
+	if vibrationsMuted() {
+		return
+	}
+	if l.vibrations != nil {
+		if l.vibrations.disabled() {
+			return
+		}
+
+		vibration = l.vibrations.apply(vibration)
+	}
+
+	debugf("vibrating at %d, %d", vibration.LargeMotor, vibration.SmallMotor)
+	l.state.patterns.vibrate(vibration.LargeMotor, vibration.SmallMotor)
+	atomic.AddUint64(&stats.vibrations, 1)
+
+	setRumbleIntensity(vibration)
+
+	if l.rumbleWatchdog != nil {
+		l.rumbleWatchdog.update(vibration)
+	}
+
+	l.out.rumble(vibration)
+}
@@ -0,0 +1,256 @@
+package main
+
+import (
+	"encoding/hex"
+	"flag"
+	"io/ioutil"
+	"strings"
+	"testing"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// pipelineBudget is the time the input loop may spend on each report, from
+// its raw bytes to the report sent to the emulated controller. The controller
+// sends a report every 4ms over USB, and most of that time is left to the
+// emulated controller and to games.
+const pipelineBudget = 20 * time.Microsecond
+
+// Serial number of the controller calibrated by testdata/bench-calibration.json.
+const benchSerial = "9B13ZA0XW2"
+
+// noDevices is a Backend without any device, so that the controller given to
+// the input loop never connects.
+type noDevices struct{}
+
+func (noDevices) Devices() ([]*stadiacontroller.DeviceInfo, error) {
+	return nil, nil
+}
+
+func (noDevices) ByPath(path string) (*stadiacontroller.DeviceInfo, error) {
+	return nil, stadiacontroller.ErrDeviceNotFound
+}
+
+func (noDevices) Open(info *stadiacontroller.DeviceInfo) (stadiacontroller.Device, error) {
+	return nil, stadiacontroller.ErrDeviceNotFound
+}
+
+// readReportFixtures returns the raw reports of the file at the given path,
+// written one per line as hexadecimal bytes. Empty lines and lines starting
+// with # are ignored.
+func readReportFixtures(tb testing.TB, path string) [][]byte {
+	tb.Helper()
+
+	data, err := ioutil.ReadFile(path)
+
+	if err != nil {
+		tb.Fatal(err)
+	}
+
+	var reports [][]byte
+
+	for i, line := range strings.Split(string(data), "\n") {
+		if line = strings.TrimSpace(line); line == "" || strings.HasPrefix(line, "#") {
+			continue
+		}
+
+		report, err := hex.DecodeString(strings.Replace(line, " ", "", -1))
+
+		if err != nil {
+			tb.Fatalf("%s:%d: %v", path, i+1, err)
+		}
+
+		reports = append(reports, report)
+	}
+
+	if len(reports) == 0 {
+		tb.Fatalf("%s has no report", path)
+	}
+
+	return reports
+}
+
+// newBenchLoop returns an input loop sending its reports nowhere, with the
+// steps given by the configuration file at the given path, if any. The flags
+// set by the file are restored once the benchmark ends.
+func newBenchLoop(b *testing.B, configPath string) *inputLoop {
+	values := map[string]string{}
+	flag.VisitAll(func(f *flag.Flag) { values[f.Name] = f.Value.String() })
+
+	b.Cleanup(func() {
+		for name, value := range values {
+			flag.Set(name, value)
+		}
+	})
+
+	defer func(saved map[string]bool) { explicitFlags = saved }(explicitFlags)
+	explicitFlags = map[string]bool{}
+
+	var cfg *configFile
+	var err error
+
+	if configPath != "" {
+		if cfg, err = loadConfig(configPath); err != nil {
+			b.Fatal(err)
+		}
+		if err := cfg.apply(); err != nil {
+			b.Fatal(err)
+		}
+	}
+
+	// Features which rumble do so on a closed controller, which drops the
+	// vibrations.
+	controller, err := stadiacontroller.NewStadiaControllerWithOptions(stadiacontroller.StadiaControllerOptions{Backend: noDevices{}})
+
+	if err != nil {
+		b.Fatal(err)
+	}
+
+	controller.Close()
+
+	l := &inputLoop{controller: controller, pad: discardController{}, out: &outputs{}, focused: true}
+	l.state = controlState{controller: controller}
+
+	if l.actions, err = newButtonActions(controller, &l.state); err != nil {
+		b.Fatal(err)
+	}
+
+	b.Cleanup(l.actions.Close)
+
+	if l.calibration, err = loadCalibrations(); err != nil {
+		b.Fatal(err)
+	}
+	if l.calibration != nil {
+		l.calibration.setController(benchSerial)
+	}
+
+	recenterButtons, err := stadiacontroller.ParseStadiaButtons(*recenterCombo)
+
+	if err != nil {
+		b.Fatal(err)
+	}
+
+	l.recenter = newStickRecenter(controller, recenterButtons, *recenterHold, *autoCenter)
+
+	toggles, err := newToggles(l)
+
+	if err != nil {
+		b.Fatal(err)
+	}
+
+	presses, err := newPressFilterStep()
+
+	if err != nil {
+		b.Fatal(err)
+	}
+
+	turbo, err := newTurboStep()
+
+	if err != nil {
+		b.Fatal(err)
+	}
+
+	layout, err := newOneHandedStep()
+
+	if err != nil {
+		b.Fatal(err)
+	}
+
+	hold, err := newAutoHoldStep(controller)
+
+	if err != nil {
+		b.Fatal(err)
+	}
+
+	sticky, err := newStickyStep(controller)
+
+	if err != nil {
+		b.Fatal(err)
+	}
+
+	if l.profiles, err = loadProfiles(cfg); err != nil {
+		b.Fatal(err)
+	}
+
+	// The steps of run, except those of the desktop mouse, of the keyboard and
+	// of mouse aim, which need Windows hooks.
+	l.pipeline = func(p *profile) []*reportStep {
+		return []*reportStep{
+			toggles.systemStep(),
+			l.calibration.step(),
+			presses,
+			l.recenter.step(),
+			p.sticksStep(),
+			p.triggersStep(),
+			toggles.featuresStep(),
+			p.shiftStep(&l.shiftCommands),
+			turbo,
+			p.remapStep(),
+			layout,
+			newDpadAsStickStep(),
+			hold,
+			sticky,
+			p.mappingStep(),
+			newGuideChordStep(),
+			toggles.slowStep(),
+			l.actions.debounceStep(),
+		}
+	}
+	l.steps = l.pipeline(l.activeProfile())
+
+	return l
+}
+
+// benchmarkPipeline parses the reports of testdata/reports.hex and handles
+// them with the given loop, and fails if a report takes longer than
+// pipelineBudget on average.
+func benchmarkPipeline(b *testing.B, l *inputLoop) {
+	reports := readReportFixtures(b, "../testdata/reports.hex")
+
+	b.ReportAllocs()
+	b.ResetTimer()
+
+	start := time.Now()
+
+	for i := 0; i < b.N; i++ {
+		report := stadiacontroller.NewXbox360ControllerReport()
+
+		if err := stadiacontroller.ParseReport(reports[i%len(reports)], &report); err != nil {
+			b.Fatal(err)
+		}
+		if err := l.handleReport(report, time.Now(), false); err != nil {
+			b.Fatal(err)
+		}
+	}
+
+	elapsed := time.Since(start)
+
+	b.StopTimer()
+
+	// Too few iterations are dominated by the first reports, which allocate
+	// the state of the steps.
+	if perReport := elapsed / time.Duration(b.N); b.N >= 1000 && perReport > pipelineBudget {
+		b.Errorf("%v per report, expected at most %v", perReport, pipelineBudget)
+	}
+}
+
+func BenchmarkHandleReport(b *testing.B) {
+	benchmarkPipeline(b, newBenchLoop(b, ""))
+}
+
+// BenchmarkHandleReportAllTransforms enables every transform in
+// testdata/bench.toml, with slow mode on.
+func BenchmarkHandleReportAllTransforms(b *testing.B) {
+	l := newBenchLoop(b, "testdata/bench.toml")
+
+	for i, step := range l.steps {
+		if step == nil {
+			b.Fatalf("step %d is disabled", i)
+		}
+	}
+
+	l.state.slow = true
+
+	benchmarkPipeline(b, l)
+}
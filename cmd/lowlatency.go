@@ -0,0 +1,87 @@
+package main
+
+import (
+	"log"
+	"runtime"
+
+	"golang.org/x/sys/windows"
+)
+
+var (
+	kernel32 = windows.NewLazySystemDLL("kernel32.dll")
+	winmm    = windows.NewLazySystemDLL("winmm.dll")
+
+	procGetPriorityClass  = kernel32.NewProc("GetPriorityClass")
+	procSetPriorityClass  = kernel32.NewProc("SetPriorityClass")
+	procGetCurrentThread  = kernel32.NewProc("GetCurrentThread")
+	procSetThreadPriority = kernel32.NewProc("SetThreadPriority")
+	procTimeBeginPeriod   = winmm.NewProc("timeBeginPeriod")
+	procTimeEndPeriod     = winmm.NewProc("timeEndPeriod")
+)
+
+const (
+	aboveNormalPriorityClass = 0x00008000
+	highPriorityClass        = 0x00000080
+
+	threadPriorityNormal  = 0
+	threadPriorityHighest = 2
+)
+
+// enableLowLatency raises the priority class of the process and the priority
+// of the calling thread, and requests a 1ms timer resolution. Failures are
+// logged and otherwise ignored.
+//
+// The calling goroutine is locked to its thread until the returned function,
+// which reverts all these changes, is called from the same goroutine.
+func enableLowLatency() (restore func()) {
+	runtime.LockOSThread()
+
+	process, _ := windows.GetCurrentProcess()
+	previousClass, _, _ := procGetPriorityClass.Call(uintptr(process))
+
+	if r, _, err := procSetPriorityClass.Call(uintptr(process), highPriorityClass); r != 0 {
+		log.Printf("low-latency: raised process priority to high")
+	} else if r, _, _ := procSetPriorityClass.Call(uintptr(process), aboveNormalPriorityClass); r != 0 {
+		log.Printf("low-latency: cannot raise process priority to high (%v), using above normal", err)
+	} else {
+		log.Printf("low-latency: cannot raise process priority: %v", err)
+
+		previousClass = 0
+	}
+
+	thread, _, _ := procGetCurrentThread.Call()
+
+	if r, _, err := procSetThreadPriority.Call(thread, threadPriorityHighest); r != 0 {
+		log.Printf("low-latency: raised input thread priority")
+	} else {
+		log.Printf("low-latency: cannot raise input thread priority: %v", err)
+	}
+
+	timerSet := false
+
+	if procTimeBeginPeriod.Find() != nil {
+		log.Printf("low-latency: winmm.dll is unavailable, keeping the default timer resolution")
+	} else if r, _, _ := procTimeBeginPeriod.Call(1); r == 0 {
+		log.Printf("low-latency: set timer resolution to 1ms")
+
+		timerSet = true
+	} else {
+		log.Printf("low-latency: cannot set timer resolution to 1ms")
+	}
+
+	return func() {
+		if timerSet {
+			procTimeEndPeriod.Call(1)
+		}
+
+		procSetThreadPriority.Call(thread, threadPriorityNormal)
+
+		if previousClass != 0 {
+			procSetPriorityClass.Call(uintptr(process), previousClass)
+		}
+
+		runtime.UnlockOSThread()
+
+		log.Printf("low-latency: restored default priorities and timer resolution")
+	}
+}
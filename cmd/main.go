@@ -1,122 +1,805 @@
 package main
 
 import (
-	"errors"
 	"flag"
 	"fmt"
 	"log"
+	"os"
 	"os/exec"
+	"os/signal"
+	"path/filepath"
+	"sync"
+	"sync/atomic"
+	"syscall"
 	"time"
 
 	"github.com/71/stadiacontroller"
 )
 
 var (
-	shell = flag.String("shell", "pwsh", "a path to the shell to execute for commands")
+	shell          = flag.String("shell", "pwsh", "a path to the shell to execute for commands, or none to run commands as programs followed by their arguments")
+	shellArgs      = flag.String("shell-args", "/C {}", "the arguments given to -shell, where {} is the command, e.g. '-NoProfile -Command {}'")
+	commandTimeout = flag.Duration("command-timeout", 0, "kill commands still running after this long (0 disables)")
+
+	transport    = flag.String("transport", "any", "only acquire a controller connected over this transport: usb, bt or any")
+	devicePath   = flag.String("device-path", "", "only acquire the controller with this HID device path, as printed by controller-info")
+	serialNumber = flag.String("serial", "", "only acquire the controller with this serial number, as printed by controller-info")
+	emulate      = flag.String("emulate", "x360", "the emulated controller: x360 (Xbox 360) or ds4 (DualShock 4)")
+	multi        = flag.Bool("multi", false, "emulate one controller for each connected Stadia controller, forwarding their reports unchanged")
+	dryRun       = flag.Bool("dry-run", false, "print the reports which would be sent to games instead of emulating a controller, to check the controller without ViGEm")
+	tuiEnabled   = flag.Bool("tui", false, "show the buttons, sticks, triggers, connection, report rate and vibrations of the controller in the console, along with the last log messages, e.g. with -dry-run")
+	outputFormat = flag.String("output", "text", "write state changes to stdout as text or as json, one object per line, with the log and command results on stderr (with -dry-run, only the JSON is written)")
+	noVigemRetry = flag.Bool("no-vigem-retry", false, "exit when the ViGEm bus becomes unavailable instead of connecting to it again")
+	force        = flag.Bool("force", false, "run even if another instance is already running for the same controller")
+	failOnBusy   = flag.Bool("fail-on-busy", false, "exit when the controller is used by another program, e.g. Steam, instead of waiting until it is released")
+	hideDevice   = flag.Bool("hide-device", false, "hide the controller from games and Steam with HidHide until exit, so that they only see the emulated controller")
+
+	persistentTarget = flag.Bool("persistent-target", false, "keep the emulated controller plugged in while no controller is connected, instead of only while one is")
+	unplugDelay      = flag.Duration("unplug-delay", 5*time.Second, "how long the emulated controller stays plugged in after the controller disconnected, so that reconnecting right away, e.g. after bumping the cable, does not unplug it")
+
+	reportMaps = flag.String("report-map", "", "comma-separated JSON files describing the reports of controller clones or firmwares, see the report-map subcommand")
+
+	handOffIdle = flag.Duration("hand-off", 0, "keep a second controller on standby, taking over when the active one is disconnected or when it is pressed while the active one was idle for this long (0 disables)")
+	onHandOff   = flag.String("on-hand-off", "", "the command to run when the standby controller takes over, with the serial numbers of the controllers in %STADIA_HANDOFF_FROM% and %STADIA_HANDOFF_TO%")
+
+	onConnect    = flag.String("on-connect", "", "the command to run when a controller is connected and opened, with its path in %STADIA_DEVICE_PATH%")
+	onDisconnect = flag.String("on-disconnect", "", "the command to run once when the controller is disconnected, with its path in %STADIA_DEVICE_PATH%")
+
+	onPlayerAssigned = flag.String("on-player-assigned", "", "the command to run when the emulated controller is assigned a player number, given in %STADIA_PLAYER%")
 
 	onCapturePressed    = flag.String("capture-pressed", "", "a command to run when the Capture button is pressed")
 	onCaptureReleased   = flag.String("capture-released", "", "a command to run when the Capture button is released")
 	onAssistantPressed  = flag.String("assistant-pressed", "", "a command to run when the Assistant button is pressed")
 	onAssistantReleased = flag.String("assistant-released", "", "a command to run when the Assistant button is released")
+
+	onCaptureLongPressed   = flag.String("capture-long-pressed", "", "a command to run when the Capture button is held for -long-press, in which case -capture-pressed runs on release of shorter presses")
+	onAssistantLongPressed = flag.String("assistant-long-pressed", "", "a command to run when the Assistant button is held for -long-press, in which case -assistant-pressed runs on release of shorter presses")
+	longPressThreshold     = flag.Duration("long-press", 800*time.Millisecond, "how long Capture or Assistant must be held to run -capture-long-pressed or -assistant-long-pressed")
+	buttonDebounceWindow   = flag.Duration("button-debounce", 0, "ignore presses and releases of Capture and Assistant shorter than this for their commands and keys, e.g. 30ms for worn buttons, without changing what games receive (0 disables)")
+
+	consumeButtons = flag.String("consume", "", "comma-separated buttons which are not forwarded to the emulated controller, e.g. to only run their -on-pressed commands")
+
+	captureButton   = flag.String("capture-button", "", "the button of the emulated controller held while Capture is held, e.g. 'back' (overrides the [buttons] section of -config)")
+	assistantButton = flag.String("assistant-button", "", "the button of the emulated controller held while Assistant is held, e.g. 'guide' (overrides the [buttons] section of -config)")
+	captureKeys     = flag.String("capture-key", "", "the keys held while Capture is held, e.g. 'win+alt+printscreen' (in addition to -capture-pressed)")
+	assistantKeys   = flag.String("assistant-key", "", "the keys held while Assistant is held, e.g. 'win+g' (in addition to -assistant-pressed)")
+
+	assistantIsShift = flag.Bool("assistant-is-shift", false, "only use Assistant as the shift key of the [shift] section of -config, without forwarding it or running -assistant-pressed")
+
+	measureLatency = flag.Bool("measure-latency", false, "periodically print histograms of the time between reading a report and sending it to the emulated controller, and of the interval between reports")
+	minReportRate  = flag.Float64("min-report-rate", 0, "warn when fewer reports than this are received per second (0 disables)")
+	statsInterval  = flag.Duration("stats", 0, "print the report rate, the forwarding latency (min, avg, p99), the reports not forwarded or not parsed and the vibrations per second every this long, e.g. 5s (0 disables)")
+	reportGap      = flag.Duration("report-gap", 0, "count gaps between reports longer than this as suspected drops (0 disables)")
+	lowLatency     = flag.Bool("low-latency", false, "raise the process and input thread priority and the timer resolution while running")
+	addedDelay     = flag.Duration("added-delay", 0, "forward reports to the emulated controller this long after they were received, e.g. to practice with streaming latency, toggled by the delay command (0 disables, at most 1s)")
+	maxUpdateRate  = flag.Float64("max-update-rate", 0, "update the sticks of the emulated controller at most this many times per second, sending button and trigger changes right away, e.g. 120 to save CPU (0 disables)")
+
+	wsAddr        = flag.String("ws-server", "", "serve a web UI, push controller events to WebSocket clients at /events and serve Prometheus metrics at /metrics on this address, e.g. localhost:9000")
+	wsSampleRate  = flag.Float64("ws-sample-rate", 30, "the maximum number of times per second the controller state is pushed to WebSocket clients")
+	wsAllowRemote = flag.Bool("ws-allow-remote", false, "allow the WebSocket server to listen on non-loopback addresses")
+
+	overlayEnabled  = flag.Bool("overlay", false, "show the inputs of the controller in a small always-on-top window, toggled by -overlay-combo or the overlay command")
+	overlayPosition = flag.String("overlay-position", "20,20", "the position of the overlay on the screen, as x,y in pixels")
+	overlayFPS      = flag.Int("overlay-fps", 30, "the maximum number of times per second the overlay is redrawn")
+	overlayCombo    = flag.String("overlay-combo", "", "the buttons showing or hiding the overlay when pressed together, e.g. 'stadia+l1' (empty disables)")
+
+	sharedStateEnabled = flag.Bool("shared-state", true, "publish the controller state to the "+sharedStateName+" shared-memory section for overlays")
+
+	dsuPort = flag.Int("dsu-server", 0, "serve the controller to DSU (cemuhook) clients on this UDP port, usually 26760 (0 disables)")
+
+	obsURL                = flag.String("obs-url", "", "control OBS through obs-websocket (v5) at this URL, e.g. ws://localhost:4455")
+	obsPassword           = flag.String("obs-password", "", "the obs-websocket password")
+	obsCaptureTap         = flag.String("obs-capture-tap", "SaveReplayBuffer", "the OBS request sent when Capture is tapped, optionally followed by its JSON data")
+	obsCaptureDoublePress = flag.String("obs-capture-double-press", "", "the OBS request sent when Capture is pressed twice, e.g. 'SetCurrentProgramScene {\"sceneName\": \"Gaming\"}'")
+	obsCaptureLongPress   = flag.String("obs-capture-long-press", "ToggleRecord", "the OBS request sent when Capture is held")
+	obsErrorRumble        = flag.Bool("obs-error-rumble", false, "vibrate the controller briefly when an OBS request fails")
+
+	liveSplitAddr = flag.String("livesplit", "", "send commands to the LiveSplit Server at this address, e.g. localhost:16834")
+	liveSplitMap  = flag.String("livesplit-map", "capture:tap=startorsplit,capture:long-press=undo,assistant:double-press=reset", "the LiveSplit commands sent for button gestures, as comma-separated button:gesture=command entries")
+
+	forwardAddr = flag.String("forward", "", "send the controller to an instance running with -listen at this address instead of emulating it locally")
+	listenPort  = flag.Int("listen", 0, "emulate a controller forwarded with -forward to this UDP port instead of a local one (0 disables)")
+	forwardKey  = flag.String("forward-key", "", "a key shared by -forward and -listen to authenticate forwarded packets")
+	linkTimeout = flag.Duration("link-timeout", 1*time.Second, "release all inputs when no packet was received for this long with -listen")
+
+	controlPipeName = flag.String("control-pipe", defaultControlPipe, "the named pipe accepting commands from other programs and the ctl subcommand (empty disables)")
+
+	trayEnabled   = flag.Bool("tray", false, "close the console and show the state of the controller in the notification area, with a menu to reconnect it, disable vibrations or exit")
+	background    = flag.Bool("background", false, "close the console on startup, e.g. when started by Steam, logging to -log-file (stadiacontroller.log next to the executable by default) until stopped by -exit-combo, the menu of -tray or the stop subcommand")
+	exitCombo     = flag.String("exit-combo", "", "the buttons exiting when pressed together, e.g. 'stadia+options+menu' (empty disables)")
+	logFile       = flag.String("log-file", "", "append the log to this file instead of printing it, e.g. with -tray")
+	logLevelName  = flag.String("log-level", "info", "the most detailed messages logged: error, warning, info, debug (also commands run and vibrations) or trace (also -dump-reports)")
+	recordPath    = flag.String("record", "", "record the reports read from the controller to this file, to replay them with -replay")
+	replayPath    = flag.String("replay", "", "replay the reports recorded to this file by -record instead of reading a controller, then exit")
+	replaySpeed   = flag.Float64("replay-speed", 1, "the speed at which -replay replays reports, e.g. 2 for twice as fast")
+	replayLoop    = flag.Bool("replay-loop", false, "replay the recording of -replay in a loop until Ctrl-C")
+	dumpReports   = flag.Bool("dump-reports", false, "log the raw bytes and decoded fields of each report differing from the previous one, at most 50 per second (implies -log-level trace)")
+	notifyEnabled = flag.Bool("notify", false, "show notifications when the controller is connected or disconnected, when the ViGEm bus fails and when the battery is low (adds an icon to the notification area)")
+
+	stickyInputsList = flag.String("sticky", "", "comma-separated buttons (and l2, r2) which stay pressed until pressed again, e.g. 'l1,l2'")
+	stickyRelease    = flag.String("sticky-release", "options+menu", "the buttons which release all inputs kept pressed by -sticky when pressed together")
+
+	configPath   = flag.String("config", "", "a configuration file setting flags, which the command line overrides, and mapping buttons (see the README)")
+	noWatch      = flag.Bool("no-watch", false, "do not reload -config when it changes")
+	presetName   = flag.String("preset", "", "apply the flags of a built-in preset (see 'preset list'), which flags given on the command line override")
+	profileName  = flag.String("profile", defaultProfileName, "the profile of -config active on startup, or default for the top-level keys")
+	profileCombo = flag.String("profile-combo", "", "the buttons switching to the next profile of -config when pressed together, e.g. 'assistant+right' (empty disables)")
+	profileDelay = flag.Duration("profile-switch-delay", 3*time.Second, "how long a game must stay in the foreground before switching to the profile of -config matching it")
+
+	leftHanded   = flag.Bool("left-handed", false, "swap the sticks, the D-pad and face buttons, and the bumpers and triggers (overridden by -remap, -swap-sticks and -swap-triggers)")
+	remapButtons = flag.String("remap", "", "comma-separated button=button entries replacing buttons by others, e.g. 'a=b,b=a'")
+	swapSticks   = flag.Bool("swap-sticks", false, "swap the left and right sticks (use -remap l3=r3,r3=l3 to swap their buttons)")
+	swapTriggers = flag.Bool("swap-triggers", false, "swap the left and right triggers")
+	swapABXY     = flag.Bool("swap-ab-xy", false, "swap A with B and X with Y by position, as on Nintendo layouts (applied before -remap and the [buttons] section of -config)")
+
+	oneHanded         = flag.String("one-handed", "", "mirror the given half of the controller (left or right) to the other one while -one-handed-modifier is held")
+	oneHandedModifier = flag.String("one-handed-modifier", "", "the buttons switching to the mirrored layout with -one-handed (l3 or r3 by default)")
+	oneHandedMap      = flag.String("one-handed-map", "", "comma-separated button=button entries overriding the mirroring of -one-handed, e.g. 'dpad_up=x,dpad_left=y'")
+
+	slowModeFactor = flag.Float64("slow-mode-factor", 0.5, "the factor applied to the sticks and triggers in slow mode, toggled by -slow-mode-combo or the slow command")
+	slowModeCombo  = flag.String("slow-mode-combo", "", "the buttons toggling slow mode when pressed together, e.g. 'l3+r3' (empty disables)")
+
+	leftDeadzone  = flag.String("left-deadzone", "", "the radial deadzone of the left stick, as a percentage (e.g. '8%') or a value up to 32767 (empty disables)")
+	rightDeadzone = flag.String("right-deadzone", "", "the radial deadzone of the right stick, as a percentage (e.g. '8%') or a value up to 32767 (empty disables)")
+	leftCurve     = flag.String("left-curve", "linear", "the response curve of the left stick: linear, squared, cubed, or pow:exponent (e.g. 'pow:1.5')")
+	rightCurve    = flag.String("right-curve", "linear", "the response curve of the right stick: linear, squared, cubed, or pow:exponent (e.g. 'pow:1.5')")
+	dpadAsStick   = flag.Bool("dpad-as-stick", false, "move the left stick with the D-pad instead of pressing the D-pad buttons")
+
+	triggerMode      = flag.String("trigger-mode", "analog", "how the triggers are forwarded: analog (unchanged), digital (released or fully pulled), or hybrid (released below the threshold, analog above it)")
+	triggerThreshold = flag.Int("trigger-threshold", 0, "the value from 0 to 255 at which the triggers are pressed with -trigger-mode digital or hybrid (0 uses 128 and 10 respectively)")
+
+	recenterCombo   = flag.String("recenter-combo", "", "the buttons which, held together while the sticks rest, make the current positions of the sticks their centers, e.g. 'l3+r3' (empty disables)")
+	recenterHold    = flag.Duration("recenter-hold", 2*time.Second, "how long -recenter-combo must be held")
+	calibrationFile = flag.String("calibration", "", "the file saved by the calibrate subcommand, scaling the sticks and triggers of each controller to their full range (calibration.json next to -config or the executable by default)")
+	autoCenter      = flag.Bool("auto-center", false, "make the average positions of the sticks during the first 500ms after each connection their centers, to compensate drift")
+
+	guideAsChord      = flag.Bool("guide-as-chord", false, "press Back and Start on the emulated controller instead of Guide, for games which do not know Guide")
+	guideChordMinimum = flag.Duration("guide-chord-min", 100*time.Millisecond, "the minimum duration of the Back and Start presses of -guide-as-chord")
+
+	turboButtonsList = flag.String("turbo", "", "comma-separated Stadia buttons which are repeatedly pressed and released while held, e.g. 'a,x'")
+	turboRate        = flag.Float64("turbo-rate", 15, "the number of presses per second of -turbo buttons (at most 30)")
+
+	autoHoldInputs = flag.String("auto-hold", "", "comma-separated input=duration entries (Stadia buttons, l2 and r2) which stay held when released after being held for this long, until pressed again, e.g. 'l2=500ms'")
+
+	minPress    = flag.String("min-press", "", "ignore presses shorter than this, delaying the others by as much, as comma-separated button=duration entries, e.g. 'all=60ms,a=100ms'")
+	minPressGap = flag.Duration("min-press-gap", 0, "ignore presses of a button this soon after its last release, unless held until the end of the gap (0 disables)")
+
+	hapticClickButtons   = flag.String("haptic-click", "", "the buttons confirmed by a short vibration when pressed, e.g. 'a,b,x,y,dpad_up,dpad_down,dpad_left,dpad_right'")
+	hapticClickDuration  = flag.Duration("haptic-click-duration", 8*time.Millisecond, "the duration of the vibrations of -haptic-click")
+	hapticClickIntensity = flag.Uint("haptic-click-intensity", 64, "the intensity (1-255) of the vibrations of -haptic-click")
+
+	vibrationStrengthLarge = flag.Uint("vibration-strength", 100, "the strength (0-400) of the vibrations of the game, as a percentage of the intensity requested by the game")
+	vibrationStrengthSmall = flag.Uint("vibration-strength-small", 100, "the strength (0-400) of the vibrations of the small motor, overriding -vibration-strength")
+	noVibration            = flag.Bool("no-vibration", false, "never vibrate the controller when the game requests it")
+	maxRumble              = flag.Duration("max-rumble", 0, "stop the motors when the game did not request a vibration for this long while they vibrate, e.g. after the game crashed (0 disables)")
+	rumbleMap              = flag.String("rumble-map", "default", "how the motors requested by the game drive the motors of the controller: default, swap (large and small exchanged) or merge (both driven by the strongest)")
+
+	onConnectRumble        = flag.String("on-connect-rumble", "", "the vibration pattern played when a controller is connected, as comma-separated intensity@ms segments where the intensity (0-255) may be given as large/small, e.g. '200@100,0@100,200@100'")
+	onCommandSuccessRumble = flag.String("on-command-success-rumble", "", "the vibration pattern played when a command run by a button or hook succeeds, see -on-connect-rumble")
+	onCommandFailureRumble = flag.String("on-command-failure-rumble", "", "the vibration pattern played when a command run by a button or hook fails, see -on-connect-rumble")
+
+	keyBindings = flag.String("keyboard", "", "add keys of the keyboard to the emulated controller with a global keyboard hook, as comma-separated key=input entries, e.g. 'f13=stadia,up=dpad_up,w=ls_up,q=l2'")
+	keySwallow  = flag.Bool("keyboard-swallow", false, "hide the keys given to -keyboard from other programs")
+
+	mouseAimEnabled     = flag.Bool("mouse-aim", false, "listen to the mouse, which drives the right stick while enabled by -mouse-aim-combo or the mouse command")
+	mouseAimSensitivity = flag.Float64("mouse-aim-sensitivity", 1, "the sensitivity of -mouse-aim (1 fully deflects the stick at 4000 mouse counts per second)")
+	mouseAimCurve       = flag.Float64("mouse-aim-curve", 1, "the exponent applied to the deflection of -mouse-aim (below 1 is more responsive, above 1 more precise)")
+	mouseAimDecay       = flag.Duration("mouse-aim-decay", 50*time.Millisecond, "how fast the stick returns to its center when the mouse stops with -mouse-aim")
+	mouseAimCombo       = flag.String("mouse-aim-combo", "", "the buttons enabling or disabling -mouse-aim when pressed together, e.g. 'stadia+r3' (empty disables)")
+
+	desktopMouseCombo = flag.String("desktop-mouse-combo", "", "the buttons toggling the desktop mouse when pressed together, e.g. 'assistant+r3': the right stick then moves the cursor, the left stick scrolls, A clicks and B right-clicks instead of driving the emulated controller (empty disables)")
+	desktopMouseSpeed = flag.Float64("desktop-mouse-speed", 1500, "the speed of the cursor of the desktop mouse, in pixels per second with the stick fully deflected")
+	desktopMouseCurve = flag.Float64("desktop-mouse-curve", 2, "the exponent applied to the deflection of the sticks by the desktop mouse (above 1 is more precise near the center)")
+
+	volumeKeysEnabled = flag.Bool("volume-keys", false, "press the volume keys of the keyboard with the volume buttons of a headset plugged into the controller")
+
+	batteryInterval = flag.Duration("battery-interval", 5*time.Minute, "how often the battery level of the controller is read, skipping reads while it vibrates or reports are slow (0 disables)")
+	lowBatteryLevel = flag.Uint("low-battery", 15, "warn once when the battery level drops to this percentage while not charging (0 disables)")
+	onLowBattery    = flag.String("on-low-battery", "", "the command to run when the battery is low, with the level in %STADIA_BATTERY_LEVEL%")
+
+	selfTestEnabled = flag.Bool("self-test", false, "check the reports, resting sticks and triggers, and motors of the controller before emulating it, like the self-test subcommand")
+	watchdogTimeout = flag.Duration("watchdog", 10*time.Second, "log a diagnostic when the input loop is stuck for this long (0 disables)")
+
+	staleInput          = flag.Duration("stale-input", 0, "release the emulated controller when no report was received from the connected controller for this long, e.g. 2s (0 disables)")
+	staleInputReacquire = flag.Bool("stale-input-reacquire", false, "also reopen the controller when -stale-input releases the emulated controller")
+	idleTimeout         = flag.Duration("idle-timeout", 0, "unplug the emulated controller when no input was given for this long, e.g. 15m, until the next input (0 disables)")
+
+	pauseHoldDuration = flag.Duration("pause-hold", 0, "pause forwarding when the Stadia button is held for this long, e.g. 2s, and resume it when held again, e.g. to type in a chat without the sticks reaching the game; the Stadia button is then only forwarded as a short Guide press when released earlier (0 disables)")
+	pauseRumble       = flag.Bool("pause-rumble", true, "vibrate once when -pause-hold pauses forwarding, and twice when it resumes")
 )
 
+// Commands run when any button is pressed or released, given as
+// button=command and repeatable.
+var onPressed, onReleased buttonCommandsFlag
+
+// IDs of the devices to acquire instead of those of the Stadia controller.
+var (
+	vendorID   vendorIDFlag
+	productIDs productIDsFlag
+
+	onlyWhenFocused stringsFlag
+)
+
+func init() {
+	flag.Var(&vendorID, "vid", "the vendor ID of the controller to acquire, in hexadecimal, e.g. '0x18d1' (the Stadia controller by default)")
+	flag.Var(&productIDs, "pid", "the accepted product IDs of the controller to acquire, in hexadecimal, e.g. '0x9400,0x9401' (can be repeated, the Stadia controller by default)")
+	flag.Var(&onPressed, "on-pressed", "a command to run when a button is pressed, as button=command, e.g. 'stadia=nircmd.exe monitor off' (can be repeated)")
+	flag.Var(&onReleased, "on-released", "a command to run when a button is released, as button=command (can be repeated)")
+	flag.Var(&onlyWhenFocused, "only-when-focused", "only forward the controller to games while the foreground window belongs to this application, given as the name of its executable or a part of the title of its window, e.g. 'eldenring.exe' (can be repeated)")
+}
+
+// config is the configuration file given to -config, if any.
+var config *configFile
+
 func main() {
 	flag.Parse()
 
-	err := run()
+	var err error
+
+	if *configPath != "" {
+		config, err = loadConfig(*configPath)
+
+		// import-settings may create the configuration file.
+		if os.IsNotExist(err) && flag.Arg(0) == "import-settings" {
+			err = nil
+		} else if err == nil {
+			err = config.apply()
+		}
+		if err != nil {
+			log.Fatalf("error: %v", err)
+		}
+	}
+
+	if *presetName != "" {
+		if err := applyPreset(*presetName); err != nil {
+			log.Fatalf("error: %v", err)
+		}
+	}
+
+	// Subcommands, e.g. install-startup, keep the console.
+	runInBackground := *background && flag.NArg() == 0
+
+	if runInBackground && *logFile == "" {
+		executable, err := os.Executable()
+
+		if err != nil {
+			log.Fatalf("error: %v", err)
+		}
+
+		*logFile = filepath.Join(filepath.Dir(executable), backgroundLogFileName)
+	}
+
+	if *logFile != "" {
+		f, err := os.OpenFile(*logFile, os.O_WRONLY|os.O_CREATE|os.O_APPEND, 0644)
+
+		if err != nil {
+			log.Fatalf("error: %v", err)
+		}
+
+		defer f.Close()
+
+		log.SetOutput(f)
+	}
+	if runInBackground {
+		detachConsole()
+	}
+
+	level, err := parseLogLevel(*logLevelName)
 
 	if err != nil {
-		log.Fatal(err)
+		log.Fatalf("error: %v", err)
+	}
+	if *dumpReports {
+		level = logLevelTrace
+	}
+
+	setLogLevel(level)
+
+	if err := openRecording(); err != nil {
+		log.Fatalf("error: %v", err)
+	}
+	if recorder != nil {
+		defer recorder.Close()
+	}
+
+	switch {
+	case flag.Arg(0) == "ctl":
+		err = runCtl(*controlPipeName, flag.Args()[1:])
+	case flag.Arg(0) == "verify":
+		err = runVerify()
+	case flag.Arg(0) == "preset":
+		err = runPreset(flag.Args()[1:])
+	case flag.Arg(0) == "profile":
+		err = runProfile(flag.Args()[1:])
+	case flag.Arg(0) == "self-test":
+		err = runSelfTest()
+	case flag.Arg(0) == "test-rumble":
+		err = runTestRumble(flag.Args()[1:])
+	case flag.Arg(0) == "doctor":
+		err = runDoctor(flag.Args()[1:])
+	case flag.Arg(0) == "report-map":
+		err = runReportMap(flag.Args()[1:])
+	case flag.Arg(0) == "controller-info":
+		err = runControllerInfo(flag.Args()[1:])
+	case flag.Arg(0) == "list-devices":
+		err = runListDevices(flag.Args()[1:])
+	case flag.Arg(0) == "calibrate":
+		err = runCalibrate(flag.Args()[1:])
+	case flag.Arg(0) == "calibration":
+		err = runCalibration(flag.Args()[1:])
+	case flag.Arg(0) == "export-settings":
+		err = runExportSettings(flag.Args()[1:])
+	case flag.Arg(0) == "import-settings":
+		err = runImportSettings(flag.Args()[1:])
+	case flag.Arg(0) == "stop":
+		err = runStop(flag.Args()[1:])
+	case flag.Arg(0) == "install-startup":
+		err = runInstallStartup(flag.Args()[1:])
+	case flag.Arg(0) == "uninstall-startup":
+		err = runUninstallStartup(flag.Args()[1:])
+	case *forwardAddr != "":
+		err = runForwarder(*forwardAddr, []byte(*forwardKey))
+	case *listenPort != 0:
+		err = runReceiver(*listenPort, []byte(*forwardKey), *linkTimeout)
+	case *multi:
+		err = runMulti()
+	default:
+		err = run()
+	}
+
+	if err != nil {
+		log.Fatalf("error: %v", err)
 	}
 }
 
-func run() error {
-	controller := stadiacontroller.NewStadiaController()
+// controllerOptions returns the options selecting the controller, as given on
+// the command line.
+func controllerOptions() (stadiacontroller.StadiaControllerOptions, error) {
+	t, err := stadiacontroller.ParseTransport(*transport)
 
-	defer controller.Close()
+	if err != nil {
+		return stadiacontroller.StadiaControllerOptions{}, err
+	}
 
-	emulator, err := stadiacontroller.NewEmulator(func(vibration stadiacontroller.Vibration) {
-		controller.Vibrate(vibration.LargeMotor, vibration.SmallMotor)
-	})
+	maps, err := loadReportMaps(*reportMaps)
 
 	if err != nil {
-		return fmt.Errorf("unable to start ViGEm client: %w", err)
+		return stadiacontroller.StadiaControllerOptions{}, err
+	}
+
+	options := stadiacontroller.StadiaControllerOptions{
+		VendorID:    uint16(vendorID),
+		ProductIDs:  productIDs,
+		DevicePath:  *devicePath,
+		Serial:      *serialNumber,
+		Transport:   t,
+		HandOffIdle: *handOffIdle,
+		ReportMaps:  maps,
 	}
 
-	defer emulator.Close()
+	if *dumpReports {
+		options.OnRawReport = reportDump.dump
+	}
+
+	switch {
+	case replay != nil:
+		options.Backend = replay
+	case recorder != nil:
+		options.Backend = recordingBackend{stadiacontroller.HIDBackend, recorder}
+	}
+
+	return options, nil
+}
+
+// Time after which the available controllers are logged if the one selected
+// by -device-path or -serial was not found.
+const selectedControllerTimeout = 10 * time.Second
 
-	x360, err := emulator.CreateXbox360Controller()
+// logCandidateControllers logs the controllers which could be selected with
+// -device-path or -serial.
+func logCandidateControllers(options stadiacontroller.StadiaControllerOptions) {
+	options.DevicePath, options.Serial, options.HandOffIdle = "", "", 0
+
+	devices, err := options.FindDevices()
 
 	if err != nil {
-		return fmt.Errorf("unable to create emulated Xbox 360 controller: %w", err)
+		log.Printf("warning: cannot list controllers: %v", err)
+		return
+	}
+	if len(devices) == 0 {
+		log.Printf("warning: the selected controller was not found, and no other controller is connected")
+		return
 	}
 
-	defer x360.Close()
+	log.Printf("warning: the selected controller was not found; connected controllers:")
 
-	if err = x360.Connect(); err != nil {
-		return fmt.Errorf("unable to connect to emulated Xbox 360 controller: %w", err)
+	for _, device := range devices {
+		log.Printf("  -device-path %s -serial %s (%v)", device.Path, device.SerialNumber, device.Transport())
 	}
+}
 
-	assistantPressed, capturePressed := false, false
+// createEmulatedController creates the controller selected by -emulate.
+func createEmulatedController(emulator *stadiacontroller.Emulator) (stadiacontroller.EmulatedController, error) {
+	switch *emulate {
+	case "x360":
+		return emulator.CreateXbox360Controller()
+	case "ds4":
+		return emulator.CreateDS4Controller()
+	default:
+		return nil, fmt.Errorf("unknown emulated controller %q, expected x360 or ds4", *emulate)
+	}
+}
 
-	for {
-		report, err := controller.GetReport()
+// newController returns the controller selected on the command line.
+func newController() (*stadiacontroller.StadiaController, error) {
+	options, err := controllerOptions()
 
-		if err != nil {
-			if errors.Is(err, stadiacontroller.RetryError) {
-				time.Sleep(1 * time.Second)
-				continue
-			}
-			return err
-		}
+	if err != nil {
+		return nil, err
+	}
 
-		err = x360.Send(&report)
+	return stadiacontroller.NewStadiaControllerWithOptions(options)
+}
 
-		if err != nil {
+func run() error {
+	unlock, err := lockInstance(*devicePath)
+
+	if err != nil {
+		return err
+	}
+
+	defer unlock()
+
+	if *selfTestEnabled {
+		if err := runSelfTest(); err != nil {
 			return err
 		}
+	}
+
+	controller, err := newController()
+
+	if err != nil {
+		return err
+	}
+
+	defer controller.Close()
+
+	m := newMeasures()
+	defer m.print()
+
+	// Commands from the control pipe, the web UI, the tray icon and stdin are
+	// all executed by the input loop.
+	commands := make(chan controlCommand)
+	stopCommands := make(chan struct{})
+
+	defer close(stopCommands)
+
+	out, err := newOutputs(commands, stopCommands, m)
+
+	if err != nil {
+		return err
+	}
 
-		if report.Assistant != assistantPressed {
-			assistantPressed = report.Assistant
+	defer out.Close()
 
-			if err := runButtonPress(assistantPressed, *onAssistantPressed, *onAssistantReleased); err != nil {
-				return err
-			}
+	patterns, connectRumble, err := newFeedbackRumbles(controller)
+
+	if err != nil {
+		return err
+	}
+
+	vibrations, err := newVibrationResponse()
+
+	if err != nil {
+		return err
+	}
+
+	rumbleWatchdog, err := newRumbleWatchdog(controller, *maxRumble)
+
+	if err != nil {
+		return err
+	}
+
+	l := &inputLoop{
+		controller:     controller,
+		out:            out,
+		measures:       m,
+		commands:       commands,
+		vibrations:     vibrations,
+		rumbleWatchdog: rumbleWatchdog,
+		connectRumble:  connectRumble,
+		playerChanges:  make(chan struct{}, 1),
+		lowBattery:     lowBatteryAlarm{threshold: uint8(*lowBatteryLevel)},
+	}
+
+	// Closed once the deferred calls below removed the emulated controller.
+	cleanedUp := make(chan struct{})
+	defer close(cleanedUp)
+
+	if l.pad, err = newEmulatedPad(l.vibrate, out); err != nil {
+		return err
+	}
+
+	defer l.pad.Close()
+
+	if *hideDevice {
+		if hider, err := openHidHide(); err != nil {
+			log.Printf("warning: %v, the controller will not be hidden", err)
+		} else {
+			l.hider = hider
+			defer hider.Close()
 		}
+	}
+
+	// Close the controller on Ctrl-C, which closes its event channel and makes
+	// the deferred calls above remove the emulated controller.
+	onInterrupt(controller.Close)
+
+	// Closing the console ends the process once the handler returns, so it
+	// waits for the emulated controller to be removed. Closing the controller
+	// stops its motors first.
+	onConsoleClose(controller.Close, cleanedUp)
+	closeWhenReplayed(controller)
+
+	if *lowLatency {
+		defer enableLowLatency()()
+	}
+
+	if *watchdogTimeout > 0 {
+		l.dog = newWatchdog(*watchdogTimeout)
+	}
+
+	// Keep stdout for JSON events.
+	if out.stdoutJSON {
+		go readStdinCommands(commands, stopCommands, os.Stderr)
+	} else {
+		go readStdinCommands(commands, stopCommands, os.Stdout)
+	}
+
+	if l.profiles, err = loadProfiles(config); err != nil {
+		return err
+	}
+	if l.active, err = findProfile(l.profiles, *profileName); err != nil {
+		return err
+	}
+
+	keyboard, err := startKeyboardInput()
+
+	if err != nil {
+		return err
+	}
+	if keyboard != nil {
+		// Release the inputs of the keys before the emulated controller is
+		// unplugged.
+		defer keyboard.uninstall()
+	}
+
+	mouse, err := startMouseAim()
+
+	if err != nil {
+		return err
+	}
+	if mouse != nil {
+		defer mouse.Close()
+	}
+
+	if l.desktop, err = newDesktopMouse(controller, *desktopMouseCombo, *desktopMouseSpeed, *desktopMouseCurve); err != nil {
+		return err
+	}
+	if l.desktop != nil {
+		// The mouse buttons must not stay pressed after exiting.
+		defer l.desktop.Close()
+	}
+
+	if l.delayed, err = newDelayQueue(*addedDelay); err != nil {
+		return err
+	}
+
+	l.state = controlState{controller: controller, overlay: out.overlay, keyboard: keyboard, mouse: mouse, delayed: l.delayed, patterns: patterns}
+
+	if l.actions, err = newButtonActions(controller, &l.state); err != nil {
+		return err
+	}
+
+	defer l.actions.Close()
+
+	if l.calibration, err = loadCalibrations(); err != nil {
+		return err
+	}
+
+	recenterButtons, err := stadiacontroller.ParseStadiaButtons(*recenterCombo)
+
+	if err != nil {
+		return err
+	}
+
+	l.recenter = newStickRecenter(controller, recenterButtons, *recenterHold, *autoCenter)
+
+	toggles, err := newToggles(l)
+
+	if err != nil {
+		return err
+	}
+
+	presses, err := newPressFilterStep()
+
+	if err != nil {
+		return err
+	}
+
+	turbo, err := newTurboStep()
+
+	if err != nil {
+		return err
+	}
+
+	layout, err := newOneHandedStep()
+
+	if err != nil {
+		return err
+	}
+
+	hold, err := newAutoHoldStep(controller)
+
+	if err != nil {
+		return err
+	}
+
+	sticky, err := newStickyStep(controller)
+
+	if err != nil {
+		return err
+	}
 
-		if report.Capture != capturePressed {
-			capturePressed = report.Capture
+	dpadStick := newDpadAsStickStep()
+	chord := newGuideChordStep()
 
-			if err := runButtonPress(capturePressed, *onCapturePressed, *onCaptureReleased); err != nil {
-				return err
-			}
+	// Steps apply in this order. Those of the profile are built again when it
+	// is switched, while the others keep their state.
+	l.pipeline = func(p *profile) []*reportStep {
+		return []*reportStep{
+			toggles.systemStep(),
+			l.calibration.step(),
+			l.desktop.step(l),
+			presses,
+			l.recenter.step(),
+			p.sticksStep(),
+			p.triggersStep(),
+			toggles.featuresStep(),
+			p.shiftStep(&l.shiftCommands),
+			turbo,
+			p.remapStep(),
+			layout,
+			dpadStick,
+			hold,
+			sticky,
+			keyboard.step(),
+			mouse.step(),
+			p.mappingStep(),
+			chord,
+			toggles.slowStep(),
+			l.actions.debounceStep(),
 		}
 	}
+
+	return l.run()
 }
 
-func runButtonPress(pressed bool, ifPressed, ifReleased string) error {
+// onInterrupt calls f in the background on the first Ctrl-C. A second Ctrl-C
+// kills the process as usual.
+func onInterrupt(f func()) {
+	go func() {
+		// Closing the console, logging off and shutting down are received as
+		// SIGTERM.
+		interrupt := make(chan os.Signal, 1)
+		signal.Notify(interrupt, os.Interrupt, syscall.SIGTERM)
+		<-interrupt
+		signal.Stop(interrupt)
+
+		log.Printf("shutting down")
+		f()
+	}()
+}
+
+func runButtonPress(pressed bool, ifPressed, ifReleased string, env ...string) error {
 	if pressed && ifPressed != "" {
-		return runCommand(ifPressed)
+		return runCommand(ifPressed, env...)
 	}
 	if !pressed && ifReleased != "" {
-		return runCommand(ifReleased)
+		return runCommand(ifReleased, env...)
 	}
 	return nil
 }
 
-func runCommand(cmd string) error {
-	command := exec.Command(*shell, "/C", cmd)
+// rumblePulses vibrates the small motor of the controller count times for the
+// given duration, with pauses of the same duration in between.
+func rumblePulses(controller *stadiacontroller.StadiaController, count int, duration time.Duration) {
+	for i := 0; i < count; i++ {
+		start := time.Duration(2*i) * duration
+
+		time.AfterFunc(start, func() {
+			controller.VibrateAsync(0, 255)
+		})
+		time.AfterFunc(start+duration, func() {
+			controller.VibrateAsync(0, 0)
+		})
+	}
+}
+
+// commandFinished is called with the result of each command run by
+// runCommand, nil if it succeeded.
+var commandFinished = func(err error) {}
+
+// Commands started by runCommand which are still running.
+var (
+	runningCommandsMu sync.Mutex
+	runningCommands   = map[string]bool{}
+)
+
+// runCommand starts the given command in the background, with the given
+// variables added to its environment, and never waits for it. A command which
+// is still running from a previous press is not started again, and commands
+// running for longer than -command-timeout are killed.
+func runCommand(cmd string, env ...string) error {
+	runningCommandsMu.Lock()
+	defer runningCommandsMu.Unlock()
+
+	if runningCommands[cmd] {
+		log.Printf("command '%s' is still running, ignoring", cmd)
+		return nil
+	}
+
+	name, args, err := shellCommand(cmd)
+
+	if err != nil {
+		atomic.AddUint64(&stats.commandFailures, 1)
+		commandFinished(err)
+		return err
+	}
+
+	command := exec.Command(name, args...)
+
+	if len(env) > 0 {
+		command.Env = append(os.Environ(), env...)
+	}
+
+	atomic.AddUint64(&stats.commandsRun, 1)
+	debugf("running command '%s'", cmd)
 
 	if err := command.Start(); err != nil {
+		atomic.AddUint64(&stats.commandFailures, 1)
+		commandFinished(err)
 		return err
 	}
 
+	runningCommands[cmd] = true
+
+	var timeout *time.Timer
+
+	if *commandTimeout > 0 {
+		timeout = time.AfterFunc(*commandTimeout, func() {
+			log.Printf("command '%s' timed out, killing it", cmd)
+			command.Process.Kill()
+		})
+	}
+
 	go func() {
 		err := command.Wait()
 
+		if timeout != nil {
+			timeout.Stop()
+		}
+
+		runningCommandsMu.Lock()
+		delete(runningCommands, cmd)
+		runningCommandsMu.Unlock()
+
 		if err != nil {
+			atomic.AddUint64(&stats.commandFailures, 1)
 			log.Printf("command '%s' failed: %v", cmd, err)
 		}
+
+		commandFinished(err)
 	}()
 
 	return nil
@@ -0,0 +1,95 @@
+package main
+
+import (
+	"fmt"
+	"io"
+	"net/http"
+	"sync/atomic"
+	"time"
+)
+
+// counters counts the events of the input loop. Its fields are updated
+// atomically.
+type counters struct {
+	reportsReceived  uint64
+	reportsForwarded uint64
+	parseFailures    uint64
+	disconnects      uint64
+	vibrations       uint64
+	commandsRun      uint64
+	commandFailures  uint64
+
+	// Time spent in slow mode until it was last disabled, and the time at
+	// which it was enabled (zero if it is not active), in nanoseconds.
+	slowModeNanos int64
+	slowModeSince int64
+
+	// Delay added to the reports by -added-delay, in nanoseconds (zero if it
+	// is disabled).
+	addedDelayNanos int64
+}
+
+var stats counters
+
+// metrics serves the counters and statistics of the input loop in the
+// Prometheus text format.
+type metrics struct {
+	latency *latencyStats
+	rate    *rateMonitor
+}
+
+func (m *metrics) ServeHTTP(w http.ResponseWriter, r *http.Request) {
+	w.Header().Set("Content-Type", "text/plain; version=0.0.4; charset=utf-8")
+
+	writeCounter(w, "reports_received_total", "Reports received from the controller.", &stats.reportsReceived)
+	writeCounter(w, "reports_forwarded_total", "Reports forwarded to the emulated controller.", &stats.reportsForwarded)
+	writeCounter(w, "parse_failures_total", "Reports that could not be parsed.", &stats.parseFailures)
+	writeCounter(w, "disconnects_total", "Times the controller was disconnected.", &stats.disconnects)
+	writeCounter(w, "vibrations_total", "Vibrations forwarded to the controller.", &stats.vibrations)
+	writeCounter(w, "commands_total", "Button commands started.", &stats.commandsRun)
+	writeCounter(w, "command_failures_total", "Button commands which could not be started or failed.", &stats.commandFailures)
+
+	fmt.Fprintf(w, "# HELP stadiacontroller_slow_mode_seconds_total Time spent in slow mode.\n")
+	fmt.Fprintf(w, "# TYPE stadiacontroller_slow_mode_seconds_total counter\n")
+	fmt.Fprintf(w, "stadiacontroller_slow_mode_seconds_total %g\n", slowModeDuration().Seconds())
+
+	fmt.Fprintf(w, "# HELP stadiacontroller_added_delay_seconds Delay added to the reports on purpose, excluded from the processing latency.\n")
+	fmt.Fprintf(w, "# TYPE stadiacontroller_added_delay_seconds gauge\n")
+	fmt.Fprintf(w, "stadiacontroller_added_delay_seconds %g\n", time.Duration(atomic.LoadInt64(&stats.addedDelayNanos)).Seconds())
+
+	m.rate.mu.Lock()
+	rate := m.rate.rate
+	m.rate.mu.Unlock()
+
+	fmt.Fprintf(w, "# HELP stadiacontroller_report_rate_hertz Reports received per second, over the last second.\n")
+	fmt.Fprintf(w, "# TYPE stadiacontroller_report_rate_hertz gauge\n")
+	fmt.Fprintf(w, "stadiacontroller_report_rate_hertz %g\n", rate)
+
+	m.latency.mu.Lock()
+	processing := m.latency.processing
+	m.latency.mu.Unlock()
+
+	writeHistogram(w, "processing_latency_seconds", "Time between reading a report from the controller and sending it to the emulated controller.", &processing)
+}
+
+func writeCounter(w io.Writer, name, help string, value *uint64) {
+	fmt.Fprintf(w, "# HELP stadiacontroller_%s %s\n", name, help)
+	fmt.Fprintf(w, "# TYPE stadiacontroller_%s counter\n", name)
+	fmt.Fprintf(w, "stadiacontroller_%s %d\n", name, atomic.LoadUint64(value))
+}
+
+func writeHistogram(w io.Writer, name, help string, h *histogram) {
+	fmt.Fprintf(w, "# HELP stadiacontroller_%s %s\n", name, help)
+	fmt.Fprintf(w, "# TYPE stadiacontroller_%s histogram\n", name)
+
+	var cumulative uint64
+
+	for i, bound := range latencyBuckets {
+		cumulative += h.counts[i]
+		fmt.Fprintf(w, "stadiacontroller_%s_bucket{le=\"%g\"} %d\n", name, bound.Seconds(), cumulative)
+	}
+
+	fmt.Fprintf(w, "stadiacontroller_%s_bucket{le=\"+Inf\"} %d\n", name, h.count)
+	fmt.Fprintf(w, "stadiacontroller_%s_sum %g\n", name, h.sum.Seconds())
+	fmt.Fprintf(w, "stadiacontroller_%s_count %d\n", name, h.count)
+}
@@ -0,0 +1,133 @@
+package main
+
+import (
+	"bufio"
+	"math"
+	"net/http/httptest"
+	"regexp"
+	"strconv"
+	"strings"
+	"testing"
+	"time"
+)
+
+// Lines of the Prometheus text exposition format.
+var (
+	metricCommentLine = regexp.MustCompile(`^# (HELP|TYPE) (stadiacontroller_[a-z_]+) (.+)$`)
+	metricSampleLine  = regexp.MustCompile(`^(stadiacontroller_[a-z_]+)(\{le="([^"]+)"\})? (\S+)$`)
+)
+
+func TestMetricsExposition(t *testing.T) {
+	defer func(saved counters) { stats = saved }(stats)
+
+	stats = counters{reportsReceived: 1000, reportsForwarded: 998, parseFailures: 2, disconnects: 1}
+	stats.addedDelayNanos = int64(8 * time.Millisecond)
+
+	latency := newLatencyStats(0, false)
+	start := time.Now()
+
+	for i, d := range []time.Duration{20 * time.Microsecond, 80 * time.Microsecond, 80 * time.Microsecond, 3 * time.Millisecond, time.Second} {
+		received := start.Add(time.Duration(i) * 4 * time.Millisecond)
+		latency.record(received, received.Add(d))
+	}
+
+	rate := newRateMonitor(0, 0)
+
+	// The rate is measured over windows of a second.
+	for i := 0; i <= 500; i++ {
+		rate.record(start.Add(time.Duration(i) * 4 * time.Millisecond))
+	}
+
+	recorder := httptest.NewRecorder()
+	(&metrics{latency: latency, rate: rate}).ServeHTTP(recorder, httptest.NewRequest("GET", "/metrics", nil))
+
+	if contentType := recorder.Header().Get("Content-Type"); !strings.HasPrefix(contentType, "text/plain; version=0.0.4") {
+		t.Errorf("metrics are served as %q", contentType)
+	}
+
+	types := map[string]string{}
+	helped := map[string]bool{}
+	samples := map[string]float64{}
+	var buckets []float64
+
+	scanner := bufio.NewScanner(recorder.Body)
+
+	for scanner.Scan() {
+		line := scanner.Text()
+
+		if m := metricCommentLine.FindStringSubmatch(line); m != nil {
+			if m[1] == "HELP" {
+				helped[m[2]] = true
+			} else {
+				types[m[2]] = m[3]
+			}
+			continue
+		}
+
+		m := metricSampleLine.FindStringSubmatch(line)
+
+		if m == nil {
+			t.Errorf("invalid line %q", line)
+			continue
+		}
+
+		name := strings.TrimSuffix(strings.TrimSuffix(strings.TrimSuffix(m[1], "_bucket"), "_sum"), "_count")
+
+		if types[name] == "" || !helped[name] {
+			t.Errorf("%s is not preceded by its HELP and TYPE", m[1])
+		}
+		if types[name] != "histogram" && name != m[1] {
+			t.Errorf("%s is not a histogram, but has a sample %s", name, m[1])
+		}
+
+		value, err := strconv.ParseFloat(m[4], 64)
+
+		if err != nil {
+			t.Errorf("invalid value in line %q", line)
+		}
+
+		if m[2] != "" {
+			if len(buckets) > 0 && value < buckets[len(buckets)-1] {
+				t.Errorf("bucket %s of %s is not cumulative", m[3], name)
+			}
+
+			buckets = append(buckets, value)
+			samples[m[1]+"{"+m[3]+"}"] = value
+		} else {
+			samples[m[1]] = value
+		}
+	}
+
+	expected := map[string]float64{
+		"stadiacontroller_reports_received_total":                    1000,
+		"stadiacontroller_reports_forwarded_total":                   998,
+		"stadiacontroller_parse_failures_total":                      2,
+		"stadiacontroller_disconnects_total":                         1,
+		"stadiacontroller_vibrations_total":                          0,
+		"stadiacontroller_slow_mode_seconds_total":                   0,
+		"stadiacontroller_added_delay_seconds":                       0.008,
+		"stadiacontroller_report_rate_hertz":                         250,
+		"stadiacontroller_processing_latency_seconds_bucket{5e-05}":  1,
+		"stadiacontroller_processing_latency_seconds_bucket{0.0001}": 3,
+		"stadiacontroller_processing_latency_seconds_bucket{0.004}":  4,
+		"stadiacontroller_processing_latency_seconds_bucket{0.032}":  4,
+		"stadiacontroller_processing_latency_seconds_bucket{+Inf}":   5,
+		"stadiacontroller_processing_latency_seconds_count":          5,
+		"stadiacontroller_processing_latency_seconds_sum":            1.00318,
+	}
+
+	for name, value := range expected {
+		if sample, ok := samples[name]; !ok {
+			t.Errorf("%s is missing", name)
+		} else if math.Abs(sample-value) > 1e-9 {
+			t.Errorf("%s is %g, expected %g", name, sample, value)
+		}
+	}
+
+	if len(buckets) != len(latencyBuckets)+1 {
+		t.Errorf("processing latency has %d buckets, expected %d", len(buckets), len(latencyBuckets)+1)
+	}
+	if types["stadiacontroller_reports_received_total"] != "counter" || types["stadiacontroller_report_rate_hertz"] != "gauge" {
+		t.Errorf("metrics have types %v", types)
+	}
+}
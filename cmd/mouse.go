@@ -0,0 +1,362 @@
+package main
+
+import (
+	"fmt"
+	"log"
+	"math"
+	"runtime"
+	"sync"
+	"time"
+	"unsafe"
+
+	"github.com/71/stadiacontroller"
+	"golang.org/x/sys/windows"
+)
+
+var (
+	procRegisterRawInputDevices = user32.NewProc("RegisterRawInputDevices")
+	procGetRawInputData         = user32.NewProc("GetRawInputData")
+)
+
+// Constants used to receive the raw input of mice.
+const (
+	hwndMessage       = ^uintptr(2) // HWND_MESSAGE, i.e. (HWND)-3.
+	wmInput           = 0x00FF
+	ridInput          = 0x10000003
+	ridevInputSink    = 0x00000100
+	rimTypeMouse      = 0
+	mouseMoveAbsolute = 0x01
+	usagePageDesktop  = 0x01
+	usageMouse        = 0x02
+	mouseAimTimerID   = 1
+	mouseAimClassName = "StadiaControllerMouseAim"
+)
+
+// Interval at which the deflection of the stick is updated, and mouse speed
+// (in counts per second) deflecting the stick fully at sensitivity 1.
+const (
+	mouseAimTick      = 8 * time.Millisecond
+	mouseAimFullSpeed = 4000
+)
+
+// rawInputDevice is the RAWINPUTDEVICE structure.
+type rawInputDevice struct {
+	usagePage uint16
+	usage     uint16
+	flags     uint32
+	target    uintptr
+}
+
+// rawMouseInput is the RAWINPUT structure with its RAWMOUSE member.
+type rawMouseInput struct {
+	typ        uint32
+	size       uint32
+	device     uintptr
+	wParam     uintptr
+	flags      uint16
+	_          uint16
+	buttons    uint32
+	rawButtons uint32
+	lastX      int32
+	lastY      int32
+	extra      uint32
+}
+
+// mouseAim drives the right stick with the relative motion of the mouse, read
+// through raw input. The speed of the mouse is converted to a deflection of
+// the stick, which decays back to the center when the mouse stops.
+//
+// While active, it overrides the right stick of the controller whenever the
+// mouse deflects it.
+type mouseAim struct {
+	sensitivity float64
+	curve       float64
+	decay       time.Duration
+	changes     chan struct{}
+
+	hwnd uintptr
+	done chan struct{}
+
+	mu       sync.Mutex
+	active   bool
+	dx, dy   int32
+	x, y     float64
+	lastTick time.Time
+}
+
+// newMouseAim starts listening to the mouse. The returned mouseAim is
+// inactive until setActive is called.
+func newMouseAim(sensitivity, curve float64, decay time.Duration) (*mouseAim, error) {
+	if sensitivity <= 0 {
+		return nil, fmt.Errorf("mouse aim sensitivity must be positive")
+	}
+	if curve <= 0 {
+		return nil, fmt.Errorf("mouse aim curve must be positive")
+	}
+	if decay < 0 {
+		return nil, fmt.Errorf("mouse aim decay cannot be negative")
+	}
+
+	m := &mouseAim{
+		sensitivity: sensitivity,
+		curve:       curve,
+		decay:       decay,
+		changes:     make(chan struct{}, 1),
+		done:        make(chan struct{}),
+	}
+
+	ready := make(chan error, 1)
+
+	go m.run(ready)
+
+	if err := <-ready; err != nil {
+		return nil, err
+	}
+
+	return m, nil
+}
+
+// run creates a message-only window receiving the raw input of mice, even in
+// the background, and dispatches its messages until it is destroyed.
+func (m *mouseAim) run(ready chan<- error) {
+	runtime.LockOSThread()
+	defer close(m.done)
+
+	instance, _, _ := procGetModuleHandleW.Call(0)
+	className, _ := windows.UTF16PtrFromString(mouseAimClassName)
+
+	class := wndClassEx{
+		size:      uint32(unsafe.Sizeof(wndClassEx{})),
+		wndProc:   windows.NewCallback(m.windowProc),
+		instance:  instance,
+		className: className,
+	}
+
+	if r, _, err := procRegisterClassExW.Call(uintptr(unsafe.Pointer(&class))); r == 0 {
+		ready <- fmt.Errorf("cannot register mouse window class: %w", err)
+		return
+	}
+
+	hwnd, _, err := procCreateWindowExW.Call(
+		0, uintptr(unsafe.Pointer(className)), uintptr(unsafe.Pointer(className)), 0,
+		0, 0, 0, 0, hwndMessage, 0, instance, 0)
+
+	if hwnd == 0 {
+		ready <- fmt.Errorf("cannot create mouse window: %w", err)
+		return
+	}
+
+	device := rawInputDevice{
+		usagePage: usagePageDesktop,
+		usage:     usageMouse,
+		flags:     ridevInputSink,
+		target:    hwnd,
+	}
+
+	if r, _, err := procRegisterRawInputDevices.Call(uintptr(unsafe.Pointer(&device)), 1, unsafe.Sizeof(device)); r == 0 {
+		ready <- fmt.Errorf("cannot listen to the mouse: %w", err)
+		return
+	}
+
+	m.hwnd = hwnd
+	m.lastTick = time.Now()
+
+	procSetTimer.Call(hwnd, mouseAimTimerID, uintptr(mouseAimTick/time.Millisecond), 0)
+
+	ready <- nil
+
+	var msg winMsg
+
+	for {
+		r, _, _ := procGetMessageW.Call(uintptr(unsafe.Pointer(&msg)), 0, 0, 0)
+
+		if int32(r) <= 0 {
+			return
+		}
+
+		procDispatchMessageW.Call(uintptr(unsafe.Pointer(&msg)))
+	}
+}
+
+func (m *mouseAim) windowProc(hwnd, msg, wParam, lParam uintptr) uintptr {
+	switch msg {
+	case wmInput:
+		var input rawMouseInput
+		size := uint32(unsafe.Sizeof(input))
+
+		r, _, _ := procGetRawInputData.Call(lParam, ridInput, uintptr(unsafe.Pointer(&input)), uintptr(unsafe.Pointer(&size)), unsafe.Offsetof(input.flags))
+
+		// Absolute positions come from tablets and remote desktops, and are
+		// not motion.
+		if int32(r) > 0 && input.typ == rimTypeMouse && input.flags&mouseMoveAbsolute == 0 {
+			m.mu.Lock()
+			m.dx += input.lastX
+			m.dy += input.lastY
+			m.mu.Unlock()
+		}
+
+	case wmTimer:
+		m.tick(time.Now())
+		return 0
+
+	case wmDestroy:
+		procKillTimer.Call(hwnd, mouseAimTimerID)
+		procPostQuitMessage.Call(0)
+		return 0
+	}
+
+	r, _, _ := procDefWindowProcW.Call(hwnd, msg, wParam, lParam)
+
+	return r
+}
+
+// tick converts the motion of the mouse since the last tick to a deflection
+// of the stick.
+func (m *mouseAim) tick(now time.Time) {
+	m.mu.Lock()
+	defer m.mu.Unlock()
+
+	dt := now.Sub(m.lastTick).Seconds()
+	dx, dy := m.dx, m.dy
+
+	m.lastTick = now
+	m.dx, m.dy = 0, 0
+
+	if !m.active || dt <= 0 {
+		return
+	}
+
+	// The Y axis of the mouse grows downwards, and that of the stick upwards.
+	x := m.follow(m.x, stickDeflection(float64(dx)/dt, m.sensitivity, m.curve), dt)
+	y := m.follow(m.y, -stickDeflection(float64(dy)/dt, m.sensitivity, m.curve), dt)
+
+	if x != m.x || y != m.y {
+		m.x, m.y = x, y
+		m.notify()
+	}
+}
+
+// stickDeflection converts a speed of the mouse along an axis, in counts per
+// second, to a deflection of the stick from -1 to 1. The magnitude is raised
+// to the power curve: curves below 1 make slow motion more responsive, and
+// curves above 1 make it more precise.
+func stickDeflection(speed, sensitivity, curve float64) float64 {
+	magnitude := math.Abs(speed) * sensitivity / mouseAimFullSpeed
+
+	if magnitude > 1 {
+		magnitude = 1
+	}
+
+	return math.Copysign(math.Pow(magnitude, curve), speed)
+}
+
+// follow returns the deflection following current towards target after dt
+// seconds: it jumps to stronger deflections, and decays exponentially towards
+// weaker ones.
+func (m *mouseAim) follow(current, target, dt float64) float64 {
+	if math.Abs(target) >= math.Abs(current) || (target != 0 && (target > 0) != (current > 0)) || m.decay == 0 {
+		return target
+	}
+
+	decayed := current * math.Exp(-dt/m.decay.Seconds())
+
+	if math.Abs(decayed) < math.Abs(target) {
+		return target
+	}
+	if math.Abs(decayed) < 0.01 {
+		return 0
+	}
+
+	return decayed
+}
+
+func (m *mouseAim) notify() {
+	select {
+	case m.changes <- struct{}{}:
+	default:
+	}
+}
+
+// isActive returns whether the mouse drives the stick.
+func (m *mouseAim) isActive() bool {
+	m.mu.Lock()
+	defer m.mu.Unlock()
+
+	return m.active
+}
+
+// setActive starts or stops driving the stick with the mouse.
+func (m *mouseAim) setActive(active bool) {
+	m.mu.Lock()
+	m.active = active
+	m.mu.Unlock()
+
+	m.reset()
+}
+
+// setMouseAim enables or disables mouse aim, confirming the change with a
+// vibration.
+func (s *controlState) setMouseAim(active bool) {
+	if active == s.mouse.isActive() {
+		return
+	}
+
+	s.mouse.setActive(active)
+
+	if active {
+		log.Printf("mouse aim enabled")
+		rumblePulses(s.controller, 1, 100*time.Millisecond)
+	} else {
+		log.Printf("mouse aim disabled")
+		rumblePulses(s.controller, 2, 100*time.Millisecond)
+	}
+}
+
+// reset centers the stick.
+func (m *mouseAim) reset() {
+	m.mu.Lock()
+	m.x, m.y = 0, 0
+	m.dx, m.dy = 0, 0
+	m.mu.Unlock()
+
+	m.notify()
+}
+
+// apply sets the right stick of the given report to the deflection of the
+// mouse, if any.
+func (m *mouseAim) apply(report *stadiacontroller.Xbox360ControllerReport) {
+	m.mu.Lock()
+	defer m.mu.Unlock()
+
+	if !m.active || (m.x == 0 && m.y == 0) {
+		return
+	}
+
+	report.SetRightThumb(int16(m.x*32767), int16(m.y*32767))
+}
+
+// Close stops listening to the mouse.
+func (m *mouseAim) Close() {
+	procPostMessageW.Call(m.hwnd, wmClose, 0, 0)
+	<-m.done
+}
+
+// startMouseAim starts listening to the mouse if -mouse-aim is given, or
+// returns nil.
+func startMouseAim() (*mouseAim, error) {
+	if !*mouseAimEnabled {
+		return nil, nil
+	}
+
+	return newMouseAim(*mouseAimSensitivity, *mouseAimCurve, *mouseAimDecay)
+}
+
+// step returns the step moving the right stick with the mouse, or nil if m is
+// nil.
+func (m *mouseAim) step() *reportStep {
+	if m == nil {
+		return nil
+	}
+
+	return transformStep(m.apply, m.reset)
+}
@@ -0,0 +1,235 @@
+package main
+
+import (
+	"errors"
+	"fmt"
+	"log"
+	"sync"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Interval at which new controllers are looked for with -multi.
+const multiScanInterval = 1 * time.Second
+
+// multiController is one of the controllers emulated with -multi. It has its
+// own ViGEm client, so that the vibrations of its emulated controller are
+// routed back to it.
+type multiController struct {
+	path       string
+	controller *stadiacontroller.StadiaController
+	emulator   *stadiacontroller.Emulator
+	unlock     func()
+
+	// calibration, nil if no controller is calibrated, applies the
+	// calibration of the connected controller.
+	calibration *calibrations
+}
+
+// runMulti emulates one controller for each connected Stadia controller,
+// adding an emulated controller when a Stadia controller is connected and
+// removing it when it is disconnected. Reports are only scaled by the
+// calibration of each controller.
+// Controllers used by another instance started with -device-path are skipped.
+func runMulti() error {
+	unlock, err := lockInstance("")
+
+	if err != nil {
+		return err
+	}
+
+	defer unlock()
+
+	options, err := controllerOptions()
+
+	if err != nil {
+		return err
+	}
+	if options.HandOffIdle != 0 {
+		return errors.New("-hand-off cannot be used with -multi")
+	}
+	if *dryRun {
+		return errors.New("-dry-run cannot be used with -multi")
+	}
+	if *outputFormat != "text" {
+		return errors.New("-output cannot be used with -multi")
+	}
+
+	calibration, err := loadCalibrations()
+
+	if err != nil {
+		return err
+	}
+
+	stop := make(chan struct{})
+	onInterrupt(func() { close(stop) })
+
+	var wg sync.WaitGroup
+	controllers := map[string]*multiController{}
+	skipped := map[string]bool{}
+
+	defer func() {
+		for _, c := range controllers {
+			c.controller.Close()
+		}
+
+		wg.Wait()
+
+		for _, c := range controllers {
+			c.unlock()
+		}
+	}()
+
+	ticker := time.NewTicker(multiScanInterval)
+	defer ticker.Stop()
+
+	log.Printf("waiting for controllers")
+
+	for {
+		devices, err := options.FindDevices()
+
+		if err != nil {
+			return err
+		}
+
+		// Controllers keep their emulated controller until exit, since their
+		// path is the same when they are plugged back in.
+		for _, device := range devices {
+			if _, ok := controllers[device.Path]; ok {
+				continue
+			}
+
+			unlock, err := lockInstance(device.Path)
+
+			if errors.Is(err, errInstanceRunning) {
+				if !skipped[device.Path] {
+					log.Printf("warning: skipping controller %s: %v", device.Path, err)
+					skipped[device.Path] = true
+				}
+
+				continue
+			}
+			if err != nil {
+				return err
+			}
+
+			c, err := newMultiController(options, device.Path)
+
+			if err != nil {
+				unlock()
+				return err
+			}
+
+			c.unlock = unlock
+
+			if calibration != nil {
+				c.calibration = &calibrations{controllers: calibration.controllers}
+			}
+
+			controllers[device.Path] = c
+			wg.Add(1)
+
+			go func() {
+				defer wg.Done()
+
+				if err := c.run(); err != nil {
+					log.Printf("warning: controller %s stopped: %v", c.path, err)
+				}
+			}()
+		}
+
+		select {
+		case <-ticker.C:
+		case <-stop:
+			return nil
+		}
+	}
+}
+
+func newMultiController(options stadiacontroller.StadiaControllerOptions, path string) (*multiController, error) {
+	options.DevicePath = path
+	options.Transport = stadiacontroller.TransportUnknown
+
+	controller, err := stadiacontroller.NewStadiaControllerWithOptions(options)
+
+	if err != nil {
+		return nil, err
+	}
+
+	// Do not block the ViGEm notification thread while the controller handles
+	// the write.
+	emulator, err := stadiacontroller.NewEmulator(func(vibration stadiacontroller.Vibration) {
+		controller.VibrateAsync(vibration.LargeMotor, vibration.SmallMotor)
+	})
+
+	if err != nil {
+		controller.Close()
+		return nil, fmt.Errorf("unable to start ViGEm client: %w", err)
+	}
+
+	return &multiController{path: path, controller: controller, emulator: emulator}, nil
+}
+
+// run forwards the reports of the controller to an emulated controller, which
+// is plugged in while the controller is connected, until the controller is
+// closed.
+func (c *multiController) run() error {
+	defer c.emulator.Close()
+
+	var pad stadiacontroller.EmulatedController
+
+	unplug := func() {
+		if pad != nil {
+			pad.Disconnect()
+			pad.Close()
+			pad = nil
+		}
+	}
+
+	defer unplug()
+
+	for event := range c.controller.Events() {
+		switch event := event.(type) {
+		case stadiacontroller.ConnectedEvent:
+			p, err := createEmulatedController(c.emulator)
+
+			if err != nil {
+				return fmt.Errorf("unable to create emulated controller: %w", err)
+			}
+			if err := p.Connect(); err != nil {
+				p.Close()
+				return fmt.Errorf("unable to connect to emulated controller: %w", err)
+			}
+
+			pad = p
+			log.Printf("controller %s connected over %v", c.path, event.Transport)
+
+			if c.calibration != nil {
+				c.calibration.setController(event.Serial)
+			}
+
+		case stadiacontroller.DisconnectedEvent:
+			unplug()
+			log.Printf("controller %s disconnected", c.path)
+
+		case stadiacontroller.ErrorEvent:
+			if !errors.Is(event.Err, stadiacontroller.RetryError) {
+				return event.Err
+			}
+
+		case stadiacontroller.ReportEvent:
+			if pad == nil {
+				continue
+			}
+			if c.calibration != nil {
+				c.calibration.apply(&event.Report)
+			}
+			if err := pad.Send(&event.Report); err != nil {
+				return err
+			}
+		}
+	}
+
+	return nil
+}
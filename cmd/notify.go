@@ -0,0 +1,112 @@
+package main
+
+import (
+	"fmt"
+	"log"
+	"sync"
+	"sync/atomic"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Connections and disconnections notified within notifyConnectionWindow,
+// after which those which were not are summarized in one notification.
+const (
+	notifyConnectionWindow = 10 * time.Second
+	notifyConnectionLimit  = 2
+)
+
+// notifier shows notifications with -notify when the controller is connected
+// or disconnected, when the ViGEm bus fails, and when the battery is low. The
+// notifications are shown in the background, so they never block the input
+// loop.
+type notifier struct {
+	tray   *trayIcon
+	failed int32
+
+	mu         sync.Mutex
+	shown      int
+	suppressed int
+	connected  bool
+}
+
+func newNotifier(tray *trayIcon) *notifier {
+	return &notifier{tray: tray}
+}
+
+// connection notifies that the controller was connected, with the player
+// number of the emulated controller if known, or disconnected. A controller
+// whose connection flaps only shows a connection and a disconnection every
+// notifyConnectionWindow, followed by a summary of the others.
+func (n *notifier) connection(connected bool, transport stadiacontroller.Transport, player int) {
+	n.mu.Lock()
+	defer n.mu.Unlock()
+
+	n.connected = connected
+
+	if n.shown == 0 {
+		time.AfterFunc(notifyConnectionWindow, n.summarize)
+	}
+	if n.shown == notifyConnectionLimit {
+		n.suppressed++
+		return
+	}
+
+	n.shown++
+
+	switch {
+	case !connected:
+		n.show("Stadia controller disconnected", "Reconnect it to keep playing", niifWarning)
+	case player != 0:
+		n.show(fmt.Sprintf("Stadia controller connected (player %d)", player), "Connected over "+transport.String(), niifInfo)
+	default:
+		n.show("Stadia controller connected", "Connected over "+transport.String(), niifInfo)
+	}
+}
+
+// summarize notifies the connections and disconnections which were not
+// notified in the last window, if any, and starts a new window.
+func (n *notifier) summarize() {
+	n.mu.Lock()
+	suppressed, connected := n.suppressed, n.connected
+	n.shown, n.suppressed = 0, 0
+	n.mu.Unlock()
+
+	if suppressed == 0 {
+		return
+	}
+
+	state := "disconnected"
+
+	if connected {
+		state = "connected"
+	}
+
+	n.show("Stadia controller connection unstable", fmt.Sprintf("%d more connections and disconnections in %v, now %s", suppressed, notifyConnectionWindow, state), niifWarning)
+}
+
+// busError notifies that the ViGEm bus became unavailable.
+func (n *notifier) busError(err error) {
+	n.show("ViGEm error", fmt.Sprintf("ViGEm error: %v", err), niifError)
+}
+
+// profile notifies that the given profile became active.
+func (n *notifier) profile(name string) {
+	n.show("Stadia controller profile", fmt.Sprintf("Profile: %s", name), niifInfo)
+}
+
+// lowBattery notifies that the battery of the controller is low.
+func (n *notifier) lowBattery(status stadiacontroller.BatteryStatus) {
+	n.show("Stadia controller battery low", fmt.Sprintf("Battery: %v", status), niifWarning)
+}
+
+// show shows a notification in the background. If notifications cannot be
+// shown, e.g. because they are disabled, a warning is logged once.
+func (n *notifier) show(title, text string, flags uint32) {
+	go func() {
+		if err := n.tray.notify(title, text, flags); err != nil && atomic.CompareAndSwapInt32(&n.failed, 0, 1) {
+			log.Printf("warning: cannot show notifications: %v", err)
+		}
+	}()
+}
@@ -0,0 +1,408 @@
+package main
+
+import (
+	"bufio"
+	"crypto/sha256"
+	"encoding/base64"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"log"
+	"net"
+	"strconv"
+	"strings"
+	"sync"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Opcodes of obs-websocket v5 messages.
+const (
+	obsOpHello           = 0
+	obsOpIdentify        = 1
+	obsOpIdentified      = 2
+	obsOpRequest         = 6
+	obsOpRequestResponse = 7
+)
+
+// Bounds of the delay between two attempts to connect to OBS.
+const (
+	obsMinBackoff = 1 * time.Second
+	obsMaxBackoff = 30 * time.Second
+)
+
+// obsRequest is a request sent to OBS, such as SaveReplayBuffer.
+type obsRequest struct {
+	Type string
+	Data json.RawMessage
+}
+
+// parseOBSRequest parses a request given as its type, optionally followed by
+// a space and its data as a JSON object, e.g.
+//
+//     SetCurrentProgramScene {"sceneName": "Gaming"}
+func parseOBSRequest(s string) (*obsRequest, error) {
+	s = strings.TrimSpace(s)
+
+	if s == "" {
+		return nil, nil
+	}
+
+	request := &obsRequest{Type: s}
+
+	if i := strings.IndexByte(s, ' '); i != -1 {
+		request.Type = s[:i]
+		request.Data = json.RawMessage(strings.TrimSpace(s[i+1:]))
+
+		var data map[string]interface{}
+
+		if err := json.Unmarshal(request.Data, &data); err != nil {
+			return nil, fmt.Errorf("invalid data for OBS request %s: %w", request.Type, err)
+		}
+	}
+
+	return request, nil
+}
+
+type obsMessage struct {
+	Op int             `json:"op"`
+	D  json.RawMessage `json:"d"`
+}
+
+// obsClient sends requests to OBS through obs-websocket, reconnecting when
+// OBS is restarted.
+type obsClient struct {
+	url       string
+	password  string
+	onFailure func(error)
+
+	requests chan obsRequest
+	done     chan struct{}
+
+	mu   sync.Mutex
+	conn net.Conn
+}
+
+// newOBSClient returns a client which connects to obs-websocket at the given
+// URL in the background. onFailure is called, from another goroutine, when a
+// request cannot be sent or fails.
+func newOBSClient(url, password string, onFailure func(error)) *obsClient {
+	c := &obsClient{
+		url:       url,
+		password:  password,
+		onFailure: onFailure,
+		requests:  make(chan obsRequest, 16),
+		done:      make(chan struct{}),
+	}
+
+	go c.run()
+
+	return c
+}
+
+// Close disconnects from OBS.
+func (c *obsClient) Close() {
+	close(c.done)
+
+	c.mu.Lock()
+	if c.conn != nil {
+		c.conn.Close()
+	}
+	c.mu.Unlock()
+}
+
+// send queues the given request without blocking.
+func (c *obsClient) send(request obsRequest) {
+	select {
+	case c.requests <- request:
+	default:
+		go c.onFailure(fmt.Errorf("cannot send OBS request %s: too many pending requests", request.Type))
+	}
+}
+
+func (c *obsClient) run() {
+	backoff := obsMinBackoff
+
+	for {
+		start := time.Now()
+		err := c.session()
+
+		// Start over with a short delay if OBS was restarted after a while,
+		// rather than after failed attempts.
+		if time.Since(start) > obsMaxBackoff {
+			backoff = obsMinBackoff
+		}
+
+		select {
+		case <-c.done:
+			return
+		default:
+		}
+
+		if err != nil {
+			log.Printf("OBS connection failed, retrying in %v: %v", backoff, err)
+		}
+
+		// Requests made while disconnected would be surprising once OBS is
+		// back, so they fail instead.
+		retry := time.NewTimer(backoff)
+
+	wait:
+		for {
+			select {
+			case request := <-c.requests:
+				c.onFailure(fmt.Errorf("cannot send OBS request %s: not connected", request.Type))
+			case <-retry.C:
+				break wait
+			case <-c.done:
+				retry.Stop()
+				return
+			}
+		}
+
+		if backoff *= 2; backoff > obsMaxBackoff {
+			backoff = obsMaxBackoff
+		}
+	}
+}
+
+// session connects and identifies to OBS, and sends requests until the
+// connection is lost.
+func (c *obsClient) session() error {
+	conn, r, err := dialWS(c.url, "obswebsocket.json")
+
+	if err != nil {
+		return err
+	}
+
+	c.mu.Lock()
+	c.conn = conn
+	c.mu.Unlock()
+
+	defer func() {
+		c.mu.Lock()
+		c.conn = nil
+		c.mu.Unlock()
+
+		conn.Close()
+	}()
+
+	var writeMu sync.Mutex
+
+	write := func(op int, data interface{}) error {
+		encoded, err := json.Marshal(data)
+
+		if err != nil {
+			return err
+		}
+
+		message, err := json.Marshal(obsMessage{Op: op, D: encoded})
+
+		if err != nil {
+			return err
+		}
+
+		writeMu.Lock()
+		defer writeMu.Unlock()
+
+		return writeWSFrame(conn, wsOpText, message, true)
+	}
+
+	if err := c.identify(r, write); err != nil {
+		return err
+	}
+
+	log.Printf("connected to OBS at %s", c.url)
+
+	closed := make(chan error, 1)
+
+	go func() {
+		closed <- c.readResponses(r, func(payload []byte) error {
+			writeMu.Lock()
+			defer writeMu.Unlock()
+
+			return writeWSFrame(conn, wsOpPong, payload, true)
+		})
+	}()
+
+	for id := 1; ; id++ {
+		select {
+		case request := <-c.requests:
+			data := map[string]interface{}{
+				"requestType": request.Type,
+				"requestId":   strconv.Itoa(id) + ":" + request.Type,
+			}
+
+			if request.Data != nil {
+				data["requestData"] = request.Data
+			}
+
+			if err := write(obsOpRequest, data); err != nil {
+				c.onFailure(fmt.Errorf("cannot send OBS request %s: %w", request.Type, err))
+				return err
+			}
+
+		case err := <-closed:
+			return err
+
+		case <-c.done:
+			return nil
+		}
+	}
+}
+
+// identify answers the hello message of OBS, authenticating if needed.
+func (c *obsClient) identify(r *bufio.Reader, write func(int, interface{}) error) error {
+	var hello struct {
+		Authentication *struct {
+			Challenge string `json:"challenge"`
+			Salt      string `json:"salt"`
+		} `json:"authentication"`
+	}
+
+	if err := readOBSMessage(r, obsOpHello, &hello); err != nil {
+		return err
+	}
+
+	identify := map[string]interface{}{
+		"rpcVersion":         1,
+		"eventSubscriptions": 0,
+	}
+
+	if auth := hello.Authentication; auth != nil {
+		secret := sha256.Sum256([]byte(c.password + auth.Salt))
+		response := sha256.Sum256([]byte(base64.StdEncoding.EncodeToString(secret[:]) + auth.Challenge))
+
+		identify["authentication"] = base64.StdEncoding.EncodeToString(response[:])
+	}
+
+	if err := write(obsOpIdentify, identify); err != nil {
+		return err
+	}
+
+	if err := readOBSMessage(r, obsOpIdentified, nil); err != nil {
+		return fmt.Errorf("not identified (wrong password?): %w", err)
+	}
+
+	return nil
+}
+
+// readResponses reads messages from OBS until the connection is closed,
+// reporting failed requests.
+func (c *obsClient) readResponses(r *bufio.Reader, pong func([]byte) error) error {
+	for {
+		opcode, payload, err := readWSFrame(r, false)
+
+		if err != nil {
+			return err
+		}
+
+		switch opcode {
+		case wsOpClose:
+			return errors.New("closed by OBS")
+
+		case wsOpPing:
+			if err := pong(payload); err != nil {
+				return err
+			}
+
+		case wsOpText:
+			var message obsMessage
+
+			if err := json.Unmarshal(payload, &message); err != nil || message.Op != obsOpRequestResponse {
+				continue
+			}
+
+			var response struct {
+				RequestType   string `json:"requestType"`
+				RequestStatus struct {
+					Result  bool   `json:"result"`
+					Code    int    `json:"code"`
+					Comment string `json:"comment"`
+				} `json:"requestStatus"`
+			}
+
+			if err := json.Unmarshal(message.D, &response); err != nil {
+				continue
+			}
+
+			if status := response.RequestStatus; !status.Result {
+				c.onFailure(fmt.Errorf("OBS request %s failed with code %d: %s", response.RequestType, status.Code, status.Comment))
+			}
+		}
+	}
+}
+
+// readOBSMessage reads the next message, which must have the given opcode, and
+// decodes its data into v unless it is nil.
+func readOBSMessage(r *bufio.Reader, op int, v interface{}) error {
+	opcode, payload, err := readWSFrame(r, false)
+
+	if err != nil {
+		return err
+	}
+	if opcode == wsOpClose {
+		return errors.New("closed by OBS")
+	}
+
+	var message obsMessage
+
+	if err := json.Unmarshal(payload, &message); err != nil {
+		return err
+	}
+	if message.Op != op {
+		return fmt.Errorf("expected message %d from OBS, got %d", op, message.Op)
+	}
+	if v == nil {
+		return nil
+	}
+
+	return json.Unmarshal(message.D, v)
+}
+
+// newOBSGestures connects to OBS as configured by the command line, and
+// returns a detector sending the configured OBS requests for the gestures of
+// the Capture button.
+func newOBSGestures(controller *stadiacontroller.StadiaController) (*obsClient, *gestureDetector, error) {
+	var requests [len(gestureNames)]*obsRequest
+
+	for g, spec := range map[gesture]string{
+		gestureTap:         *obsCaptureTap,
+		gestureDoublePress: *obsCaptureDoublePress,
+		gestureLongPress:   *obsCaptureLongPress,
+	} {
+		request, err := parseOBSRequest(spec)
+
+		if err != nil {
+			return nil, nil, err
+		}
+
+		requests[g] = request
+	}
+
+	client := newOBSClient(*obsURL, *obsPassword, func(err error) {
+		log.Printf("warning: %v", err)
+
+		if *obsErrorRumble {
+			errorRumble(controller)
+		}
+	})
+
+	detector := newGestureDetector(requests[gestureDoublePress] != nil, func(g gesture) {
+		if request := requests[g]; request != nil {
+			client.send(*request)
+		}
+	})
+
+	return client, detector, nil
+}
+
+// errorRumble vibrates the controller briefly to signal an error.
+func errorRumble(controller *stadiacontroller.StadiaController) {
+	controller.VibrateAsync(255, 255)
+
+	time.AfterFunc(150*time.Millisecond, func() {
+		controller.VibrateAsync(0, 0)
+	})
+}
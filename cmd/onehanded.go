@@ -0,0 +1,196 @@
+package main
+
+import (
+	"fmt"
+	"strings"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Value above which a trigger held when switching layers stays released.
+const oneHandedTriggerThreshold = 16
+
+// Buttons of the left half of the controller mirrored to the right half by
+// default. The table of the right half is its inverse.
+var leftHandedMirror = map[stadiacontroller.StadiaButtons]stadiacontroller.StadiaButtons{
+	stadiacontroller.StadiaButtonDpadUp:    stadiacontroller.StadiaButtonY,
+	stadiacontroller.StadiaButtonDpadDown:  stadiacontroller.StadiaButtonA,
+	stadiacontroller.StadiaButtonDpadLeft:  stadiacontroller.StadiaButtonX,
+	stadiacontroller.StadiaButtonDpadRight: stadiacontroller.StadiaButtonB,
+	stadiacontroller.StadiaButtonL1:        stadiacontroller.StadiaButtonR1,
+	stadiacontroller.StadiaButtonL3:        stadiacontroller.StadiaButtonR3,
+	stadiacontroller.StadiaButtonOptions:   stadiacontroller.StadiaButtonMenu,
+}
+
+// oneHandedLayout implements the one-handed mode: while the modifier is held,
+// the half of the controller in use acts as the other half, mirroring its
+// stick, trigger and buttons.
+type oneHandedLayout struct {
+	left     bool
+	modifier stadiacontroller.StadiaButtons
+	mirror   map[stadiacontroller.StadiaButtons]stadiacontroller.StadiaButtons
+
+	mirrored     bool
+	blocked      stadiacontroller.StadiaButtons
+	blockedLeft  bool
+	blockedRight bool
+}
+
+// newOneHandedLayout returns the one-handed mode for the given half of the
+// controller ("left" or "right"), switched by the given modifier (the stick
+// button of that half if empty). overrides is a comma-separated list of
+// button=button entries added to or replacing entries of the mirroring table,
+// e.g. "dpad_up=x,dpad_left=y".
+func newOneHandedLayout(side, modifier, overrides string) (*oneHandedLayout, error) {
+	l := &oneHandedLayout{mirror: map[stadiacontroller.StadiaButtons]stadiacontroller.StadiaButtons{}}
+
+	switch strings.ToLower(side) {
+	case "left":
+		l.left = true
+		l.modifier = stadiacontroller.StadiaButtonL3
+
+		for from, to := range leftHandedMirror {
+			l.mirror[from] = to
+		}
+	case "right":
+		l.modifier = stadiacontroller.StadiaButtonR3
+
+		for from, to := range leftHandedMirror {
+			l.mirror[to] = from
+		}
+	default:
+		return nil, fmt.Errorf("invalid one-handed side %q, expected left or right", side)
+	}
+
+	if modifier != "" {
+		buttons, err := stadiacontroller.ParseStadiaButtons(modifier)
+
+		if err != nil {
+			return nil, err
+		}
+
+		l.modifier = buttons
+	}
+
+	if l.modifier == 0 {
+		return nil, fmt.Errorf("one-handed mode needs a modifier")
+	}
+
+	for _, entry := range strings.Split(overrides, ",") {
+		if entry = strings.TrimSpace(entry); entry == "" {
+			continue
+		}
+
+		i := strings.IndexByte(entry, '=')
+
+		if i == -1 {
+			return nil, fmt.Errorf("invalid mirrored button %q, expected button=button", entry)
+		}
+
+		from, err := stadiacontroller.ParseStadiaButtons(entry[:i])
+
+		if err != nil {
+			return nil, err
+		}
+
+		to, err := stadiacontroller.ParseStadiaButtons(entry[i+1:])
+
+		if err != nil {
+			return nil, err
+		}
+
+		l.mirror[from] = to
+	}
+
+	// The modifier only switches layers.
+	for from := range l.mirror {
+		if from&l.modifier != 0 {
+			delete(l.mirror, from)
+		}
+	}
+
+	return l, nil
+}
+
+// apply switches layers according to the modifier in the given report, and
+// mirrors the report if the modifier is held. Inputs held while switching are
+// released until they are released on the controller, so that nothing stays
+// pressed in the layer that was left.
+func (l *oneHandedLayout) apply(report *stadiacontroller.Xbox360ControllerReport) {
+	original := report.Buttons
+	buttons := original &^ l.modifier
+	leftTrigger, rightTrigger := report.GetLeftTrigger(), report.GetRightTrigger()
+
+	if mirrored := original.Has(l.modifier); mirrored != l.mirrored {
+		l.mirrored = mirrored
+		l.blocked = buttons
+		l.blockedLeft = leftTrigger > oneHandedTriggerThreshold
+		l.blockedRight = rightTrigger > oneHandedTriggerThreshold
+	}
+
+	l.blocked &= buttons
+	l.blockedLeft = l.blockedLeft && leftTrigger > oneHandedTriggerThreshold
+	l.blockedRight = l.blockedRight && rightTrigger > oneHandedTriggerThreshold
+
+	buttons &^= l.blocked
+
+	if l.blockedLeft {
+		leftTrigger = 0
+	}
+	if l.blockedRight {
+		rightTrigger = 0
+	}
+
+	if l.mirrored {
+		var sources, targets stadiacontroller.StadiaButtons
+
+		for from, to := range l.mirror {
+			if buttons.Has(from) {
+				sources |= from
+				targets |= to
+			}
+		}
+
+		buttons = buttons&^sources | targets
+
+		if l.left {
+			x, y := report.GetLeftThumb()
+			report.SetRightThumb(x, y)
+			report.SetLeftThumb(0, 0)
+			leftTrigger, rightTrigger = 0, leftTrigger
+		} else {
+			x, y := report.GetRightThumb()
+			report.SetLeftThumb(x, y)
+			report.SetRightThumb(0, 0)
+			leftTrigger, rightTrigger = rightTrigger, 0
+		}
+	}
+
+	report.Buttons = buttons
+	report.Capture = buttons.Has(stadiacontroller.StadiaButtonCapture)
+	report.Assistant = buttons.Has(stadiacontroller.StadiaButtonAssistant)
+	report.SetButtons(report.GetButtons()&^original.Xbox360Buttons() | buttons.Xbox360Buttons())
+	report.SetLeftTrigger(leftTrigger)
+	report.SetRightTrigger(rightTrigger)
+}
+
+// reset returns to the normal layer.
+func (l *oneHandedLayout) reset() {
+	l.mirrored = false
+	l.blocked, l.blockedLeft, l.blockedRight = 0, false, false
+}
+
+// newOneHandedStep returns the step of -one-handed, or nil if it is not given.
+func newOneHandedStep() (*reportStep, error) {
+	if *oneHanded == "" {
+		return nil, nil
+	}
+
+	layout, err := newOneHandedLayout(*oneHanded, *oneHandedModifier, *oneHandedMap)
+
+	if err != nil {
+		return nil, err
+	}
+
+	return transformStep(layout.apply, layout.reset), nil
+}
@@ -0,0 +1,310 @@
+package main
+
+import (
+	"errors"
+	"fmt"
+	"io"
+	"log"
+	"os"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// outputs show the state of the controller outside of the emulated
+// controller: the servers, the overlay, the JSON events, the terminal view,
+// the tray icon and notifications. Each of them is nil unless enabled.
+type outputs struct {
+	ws            *wsServer
+	dsu           *dsuServer
+	shm           *sharedStateWriter
+	overlay       *overlayWindow
+	pipe          *controlPipe
+	json          *jsonOutput
+	view          *terminalView
+	tray          *trayIcon
+	notifications *notifier
+
+	// Set by -output json, in which case stdout is left to JSON events.
+	stdoutJSON bool
+}
+
+// newOutputs starts the outputs enabled by the command line. Commands received
+// by the control pipe, the web UI and the tray icon are sent to commands until
+// stopCommands is closed, and the web UI reports the given measures.
+func newOutputs(commands chan<- controlCommand, stopCommands <-chan struct{}, m measures) (*outputs, error) {
+	o := &outputs{}
+
+	if err := o.start(commands, stopCommands, m); err != nil {
+		o.Close()
+
+		return nil, err
+	}
+
+	return o, nil
+}
+
+func (o *outputs) start(commands chan<- controlCommand, stopCommands <-chan struct{}, m measures) error {
+	var err error
+
+	if o.stdoutJSON, err = parseOutputFormat(*outputFormat); err != nil {
+		return err
+	}
+
+	if *wsAddr != "" {
+		control := controlHandler{commands, stopCommands, *wsAllowRemote}
+
+		if o.ws, err = newWSServer(*wsAddr, *wsSampleRate, *wsAllowRemote, control, &metrics{m.latency, m.rate}); err != nil {
+			return fmt.Errorf("unable to start WebSocket server: %w", err)
+		}
+	}
+
+	if *sharedStateEnabled {
+		shm, err := newSharedStateWriter(sharedStateName)
+
+		if err != nil {
+			// Overlays are optional, so do not prevent the controller from
+			// working (e.g. if another instance already publishes its state).
+			log.Printf("warning: %v", err)
+		} else {
+			o.shm = shm
+		}
+	}
+
+	if *overlayEnabled {
+		if o.shm == nil {
+			return fmt.Errorf("the overlay needs -shared-state")
+		}
+		if o.overlay, err = newOverlayWindow(o.shm, *overlayPosition, *overlayFPS); err != nil {
+			return err
+		}
+	}
+
+	if *controlPipeName != "" {
+		if o.pipe, err = newControlPipe(*controlPipeName, commands); err != nil {
+			return fmt.Errorf("unable to create control pipe: %w", err)
+		}
+	}
+
+	// Events are written to stdout with -output json, and to the clients of
+	// the control pipe which subscribe to them.
+	var writers []io.Writer
+
+	if o.stdoutJSON {
+		writers = append(writers, os.Stdout)
+	}
+	if o.pipe != nil {
+		writers = append(writers, o.pipe.subscribers)
+	}
+	if len(writers) > 0 {
+		o.json = newJSONOutput(writers...)
+	}
+
+	if *tuiEnabled {
+		if o.stdoutJSON {
+			return errors.New("-tui cannot be used with -output json, which writes to the console as well")
+		}
+		if *trayEnabled || *background {
+			return errors.New("-tui needs the console, which -tray and -background close")
+		}
+		if o.view, err = newTerminalView(); err != nil {
+			return err
+		}
+	}
+
+	// Notifications are shown next to the icon of -tray, so -notify adds it
+	// as well.
+	if *trayEnabled || *notifyEnabled {
+		if o.tray, err = newTrayIcon(commands, stopCommands); err != nil {
+			return err
+		}
+	}
+	if *trayEnabled {
+		detachConsole()
+	}
+	if *notifyEnabled {
+		o.notifications = newNotifier(o.tray)
+	}
+
+	if *dsuPort != 0 {
+		if o.dsu, err = newDSUServer(*dsuPort); err != nil {
+			return fmt.Errorf("unable to start DSU server: %w", err)
+		}
+	}
+
+	return nil
+}
+
+// Close stops the outputs, in the reverse order of their start.
+func (o *outputs) Close() {
+	if o.dsu != nil {
+		o.dsu.Close()
+	}
+	if o.tray != nil {
+		o.tray.Close()
+	}
+	if o.view != nil {
+		o.view.Close()
+	}
+	if o.pipe != nil {
+		o.pipe.Close()
+	}
+	if o.overlay != nil {
+		o.overlay.Close()
+	}
+	if o.shm != nil {
+		o.shm.Close()
+	}
+	if o.ws != nil {
+		o.ws.Close()
+	}
+}
+
+func (o *outputs) connected(transport stadiacontroller.Transport, player int) {
+	if o.view != nil {
+		o.view.setConnected(true, transport)
+	}
+	if o.dsu != nil {
+		o.dsu.setConnected(true)
+	}
+	if o.ws != nil {
+		o.ws.setConnected(true, transport)
+	}
+	if o.tray != nil {
+		o.tray.setConnected(true, transport)
+	}
+	if o.notifications != nil {
+		o.notifications.connection(true, transport, player)
+	}
+	if o.json != nil {
+		o.json.connected(transport)
+	}
+	if o.shm != nil {
+		o.shm.setConnected(true)
+	}
+}
+
+// handedOff shows that the controller now sends its reports over the given
+// transport.
+func (o *outputs) handedOff(transport stadiacontroller.Transport) {
+	if o.ws != nil {
+		o.ws.setConnected(true, transport)
+	}
+	if o.tray != nil {
+		o.tray.setConnected(true, transport)
+	}
+	if o.view != nil {
+		o.view.setConnected(true, transport)
+	}
+}
+
+// disconnected shows that the controller, last connected over the given
+// transport, was disconnected.
+func (o *outputs) disconnected(last stadiacontroller.Transport, player int) {
+	if o.view != nil {
+		o.view.setConnected(false, last)
+	}
+	if o.dsu != nil {
+		o.dsu.setConnected(false)
+	}
+	if o.ws != nil {
+		o.ws.setConnected(false, stadiacontroller.TransportUnknown)
+	}
+	if o.tray != nil {
+		o.tray.setConnected(false, stadiacontroller.TransportUnknown)
+	}
+	if o.notifications != nil {
+		o.notifications.connection(false, stadiacontroller.TransportUnknown, player)
+	}
+	if o.json != nil {
+		o.json.disconnected()
+	}
+	if o.shm != nil {
+		o.shm.setConnected(false)
+	}
+}
+
+// received shows a report read from the controller, before it is changed.
+func (o *outputs) received(report *stadiacontroller.Xbox360ControllerReport) {
+	if o.view != nil {
+		o.view.update(report)
+	}
+}
+
+// report publishes a report handled by the input loop, whether it was
+// forwarded or not.
+func (o *outputs) report(report *stadiacontroller.Xbox360ControllerReport, paused bool) {
+	if o.shm != nil {
+		o.shm.update(report, paused)
+	}
+	if o.dsu != nil {
+		o.dsu.send(report)
+	}
+	if o.ws != nil {
+		o.ws.report(report)
+	}
+	if o.json != nil {
+		o.json.report(report)
+	}
+}
+
+// vibrationRequested shows a vibration requested by a game, even if it is not
+// played.
+func (o *outputs) vibrationRequested(vibration stadiacontroller.Vibration) {
+	if o.json != nil {
+		o.json.vibration(vibration)
+	}
+	if o.view != nil {
+		o.view.vibration(vibration)
+	}
+}
+
+// rumble shows a vibration played by the controller.
+func (o *outputs) rumble(vibration stadiacontroller.Vibration) {
+	if o.ws != nil {
+		o.ws.rumble(vibration)
+	}
+	if o.shm != nil {
+		o.shm.rumble(vibration)
+	}
+}
+
+func (o *outputs) setBattery(status stadiacontroller.BatteryStatus, readAt time.Time) {
+	if o.ws != nil {
+		o.ws.setBattery(status, readAt)
+	}
+}
+
+func (o *outputs) lowBattery(status stadiacontroller.BatteryStatus) {
+	if o.notifications != nil {
+		o.notifications.lowBattery(status)
+	}
+}
+
+// setProfile shows the active profile, with a notification if it was
+// switched.
+func (o *outputs) setProfile(name string, switched bool) {
+	if o.tray != nil {
+		o.tray.setProfile(name)
+	}
+	if o.notifications != nil && switched {
+		o.notifications.profile(name)
+	}
+}
+
+func (o *outputs) setPaused(paused bool) {
+	if o.tray != nil {
+		o.tray.setPaused(paused)
+	}
+}
+
+// setBusError shows that ViGEmBus failed with the given error, or recovered if
+// nil.
+func (o *outputs) setBusError(err error) {
+	if o.tray != nil {
+		o.tray.setBusError(err)
+	}
+	if o.notifications != nil && err != nil {
+		o.notifications.busError(err)
+	}
+}
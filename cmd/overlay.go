@@ -0,0 +1,412 @@
+package main
+
+import (
+	"fmt"
+	"runtime"
+	"sync/atomic"
+	"time"
+	"unsafe"
+
+	"github.com/71/stadiacontroller"
+	"golang.org/x/sys/windows"
+)
+
+var (
+	gdi32 = windows.NewLazySystemDLL("gdi32.dll")
+
+	procGetModuleHandleW = kernel32.NewProc("GetModuleHandleW")
+
+	procRegisterClassExW           = user32.NewProc("RegisterClassExW")
+	procCreateWindowExW            = user32.NewProc("CreateWindowExW")
+	procDefWindowProcW             = user32.NewProc("DefWindowProcW")
+	procShowWindow                 = user32.NewProc("ShowWindow")
+	procSetLayeredWindowAttributes = user32.NewProc("SetLayeredWindowAttributes")
+	procSetTimer                   = user32.NewProc("SetTimer")
+	procKillTimer                  = user32.NewProc("KillTimer")
+	procInvalidateRect             = user32.NewProc("InvalidateRect")
+	procBeginPaint                 = user32.NewProc("BeginPaint")
+	procEndPaint                   = user32.NewProc("EndPaint")
+	procFillRect                   = user32.NewProc("FillRect")
+	procGetMessageW                = user32.NewProc("GetMessageW")
+	procDispatchMessageW           = user32.NewProc("DispatchMessageW")
+	procPostMessageW               = user32.NewProc("PostMessageW")
+	procPostQuitMessage            = user32.NewProc("PostQuitMessage")
+
+	procCreateSolidBrush       = gdi32.NewProc("CreateSolidBrush")
+	procCreateCompatibleDC     = gdi32.NewProc("CreateCompatibleDC")
+	procCreateCompatibleBitmap = gdi32.NewProc("CreateCompatibleBitmap")
+	procSelectObject           = gdi32.NewProc("SelectObject")
+	procDeleteObject           = gdi32.NewProc("DeleteObject")
+	procDeleteDC               = gdi32.NewProc("DeleteDC")
+	procGetStockObject         = gdi32.NewProc("GetStockObject")
+	procEllipse                = gdi32.NewProc("Ellipse")
+	procBitBlt                 = gdi32.NewProc("BitBlt")
+)
+
+// Constants used to create and draw the overlay window.
+const (
+	wsPopup           = 0x80000000
+	wsExTopmost       = 0x00000008
+	wsExTransparent   = 0x00000020
+	wsExToolWindow    = 0x00000080
+	wsExLayered       = 0x00080000
+	wsExNoActivate    = 0x08000000
+	swHide            = 0
+	swShowNoActivate  = 4
+	lwaAlpha          = 0x2
+	wmDestroy         = 0x0002
+	wmPaint           = 0x000F
+	wmClose           = 0x0010
+	wmEraseBkgnd      = 0x0014
+	wmMouseActivate   = 0x0021
+	wmTimer           = 0x0113
+	wmOverlayShow     = 0x8000 + 1 // WM_APP + 1, with the visibility in wParam.
+	maNoActivate      = 3
+	nullPen           = 8
+	srcCopy           = 0x00CC0020
+	overlayTimerID    = 1
+	overlayClassName  = "StadiaControllerOverlay"
+	overlayWidth      = 240
+	overlayHeight     = 115
+	overlayAlpha      = 220
+	maxOverlayFPS     = 60
+	overlayStickRange = 22
+)
+
+// Colors of the overlay, as COLORREF values (0x00BBGGRR).
+const (
+	overlayBackground = 0x00202020
+	overlayReleased   = 0x00505050
+	overlayPressed    = 0x001D4CFF
+)
+
+type wndClassEx struct {
+	size       uint32
+	style      uint32
+	wndProc    uintptr
+	clsExtra   int32
+	wndExtra   int32
+	instance   uintptr
+	icon       uintptr
+	cursor     uintptr
+	background uintptr
+	menuName   *uint16
+	className  *uint16
+	iconSm     uintptr
+}
+
+type winRect struct {
+	left, top, right, bottom int32
+}
+
+type paintStruct struct {
+	hdc       uintptr
+	erase     int32
+	paint     winRect
+	restore   int32
+	incUpdate int32
+	reserved  [32]byte
+}
+
+type winMsg struct {
+	hwnd    uintptr
+	message uint32
+	wParam  uintptr
+	lParam  uintptr
+	time    uint32
+	x, y    int32
+	private uint32
+}
+
+// Position of the buttons drawn by the overlay.
+var overlayButtons = [...]struct {
+	button stadiacontroller.StadiaButtons
+	rect   winRect
+}{
+	{stadiacontroller.StadiaButtonL1, winRect{10, 17, 70, 24}},
+	{stadiacontroller.StadiaButtonR1, winRect{170, 17, 230, 24}},
+	{stadiacontroller.StadiaButtonDpadUp, winRect{88, 55, 100, 67}},
+	{stadiacontroller.StadiaButtonDpadDown, winRect{88, 79, 100, 91}},
+	{stadiacontroller.StadiaButtonDpadLeft, winRect{76, 67, 88, 79}},
+	{stadiacontroller.StadiaButtonDpadRight, winRect{100, 67, 112, 79}},
+	{stadiacontroller.StadiaButtonY, winRect{140, 55, 152, 67}},
+	{stadiacontroller.StadiaButtonA, winRect{140, 79, 152, 91}},
+	{stadiacontroller.StadiaButtonX, winRect{128, 67, 140, 79}},
+	{stadiacontroller.StadiaButtonB, winRect{152, 67, 164, 79}},
+	{stadiacontroller.StadiaButtonOptions, winRect{90, 32, 104, 40}},
+	{stadiacontroller.StadiaButtonStadia, winRect{113, 30, 127, 42}},
+	{stadiacontroller.StadiaButtonMenu, winRect{136, 32, 150, 40}},
+	{stadiacontroller.StadiaButtonAssistant, winRect{96, 100, 114, 106}},
+	{stadiacontroller.StadiaButtonCapture, winRect{126, 100, 144, 106}},
+}
+
+// overlayWindow is a small, borderless, always-on-top window showing the
+// inputs of the controller. It lets clicks through and is never activated, so
+// it does not take the focus from games.
+//
+// It redraws the state published to the shared-memory section at most at a
+// given frame rate, on a thread of its own.
+type overlayWindow struct {
+	shm      *sharedStateWriter
+	x, y     int32
+	interval time.Duration
+
+	hwnd    uintptr
+	visible int32
+	done    chan struct{}
+
+	// Accessed by the window thread only.
+	report    stadiacontroller.Xbox360ControllerReport
+	connected bool
+}
+
+// newOverlayWindow opens the overlay at the given position ("x,y" in pixels),
+// redrawing the state of shm up to fps times per second.
+func newOverlayWindow(shm *sharedStateWriter, position string, fps int) (*overlayWindow, error) {
+	if fps <= 0 || fps > maxOverlayFPS {
+		return nil, fmt.Errorf("overlay frame rate must be between 1 and %d", maxOverlayFPS)
+	}
+
+	o := &overlayWindow{
+		shm:      shm,
+		interval: time.Second / time.Duration(fps),
+		done:     make(chan struct{}),
+		report:   stadiacontroller.NewXbox360ControllerReport(),
+	}
+
+	if _, err := fmt.Sscanf(position, "%d,%d", &o.x, &o.y); err != nil {
+		return nil, fmt.Errorf("invalid overlay position %q, expected x,y", position)
+	}
+
+	ready := make(chan error, 1)
+
+	go o.run(ready)
+
+	if err := <-ready; err != nil {
+		return nil, err
+	}
+
+	return o, nil
+}
+
+// run creates the window and dispatches its messages until it is destroyed.
+// Windows belong to the thread which created them, so it runs on a locked
+// thread.
+func (o *overlayWindow) run(ready chan<- error) {
+	runtime.LockOSThread()
+	defer close(o.done)
+
+	instance, _, _ := procGetModuleHandleW.Call(0)
+	className, _ := windows.UTF16PtrFromString(overlayClassName)
+
+	class := wndClassEx{
+		size:      uint32(unsafe.Sizeof(wndClassEx{})),
+		wndProc:   windows.NewCallback(o.windowProc),
+		instance:  instance,
+		className: className,
+	}
+
+	if r, _, err := procRegisterClassExW.Call(uintptr(unsafe.Pointer(&class))); r == 0 {
+		ready <- fmt.Errorf("cannot register overlay window class: %w", err)
+		return
+	}
+
+	hwnd, _, err := procCreateWindowExW.Call(
+		wsExLayered|wsExTransparent|wsExTopmost|wsExToolWindow|wsExNoActivate,
+		uintptr(unsafe.Pointer(className)),
+		uintptr(unsafe.Pointer(className)),
+		wsPopup,
+		uintptr(o.x), uintptr(o.y), overlayWidth, overlayHeight,
+		0, 0, instance, 0)
+
+	if hwnd == 0 {
+		ready <- fmt.Errorf("cannot create overlay window: %w", err)
+		return
+	}
+
+	o.hwnd = hwnd
+	atomic.StoreInt32(&o.visible, 1)
+
+	procSetLayeredWindowAttributes.Call(hwnd, 0, overlayAlpha, lwaAlpha)
+	procShowWindow.Call(hwnd, swShowNoActivate)
+	procSetTimer.Call(hwnd, overlayTimerID, uintptr(o.interval/time.Millisecond), 0)
+
+	ready <- nil
+
+	var msg winMsg
+
+	for {
+		r, _, _ := procGetMessageW.Call(uintptr(unsafe.Pointer(&msg)), 0, 0, 0)
+
+		// 0 is WM_QUIT, and -1 an error.
+		if int32(r) <= 0 {
+			return
+		}
+
+		procDispatchMessageW.Call(uintptr(unsafe.Pointer(&msg)))
+	}
+}
+
+func (o *overlayWindow) windowProc(hwnd, msg, wParam, lParam uintptr) uintptr {
+	switch msg {
+	case wmTimer:
+		report, connected := o.shm.snapshot()
+
+		if report != o.report || connected != o.connected {
+			o.report, o.connected = report, connected
+			procInvalidateRect.Call(hwnd, 0, 0)
+		}
+
+		return 0
+
+	case wmPaint:
+		o.paint(hwnd)
+		return 0
+
+	case wmEraseBkgnd:
+		// The whole window is drawn by wmPaint.
+		return 1
+
+	case wmMouseActivate:
+		return maNoActivate
+
+	case wmOverlayShow:
+		if wParam != 0 {
+			procShowWindow.Call(hwnd, swShowNoActivate)
+		} else {
+			procShowWindow.Call(hwnd, swHide)
+		}
+
+		return 0
+
+	case wmDestroy:
+		procKillTimer.Call(hwnd, overlayTimerID)
+		procPostQuitMessage.Call(0)
+		return 0
+	}
+
+	r, _, _ := procDefWindowProcW.Call(hwnd, msg, wParam, lParam)
+
+	return r
+}
+
+// paint draws the window to a bitmap, then copies it to the window at once to
+// avoid flickering.
+func (o *overlayWindow) paint(hwnd uintptr) {
+	var ps paintStruct
+
+	hdc, _, _ := procBeginPaint.Call(hwnd, uintptr(unsafe.Pointer(&ps)))
+	defer procEndPaint.Call(hwnd, uintptr(unsafe.Pointer(&ps)))
+
+	dc, _, _ := procCreateCompatibleDC.Call(hdc)
+	bitmap, _, _ := procCreateCompatibleBitmap.Call(hdc, overlayWidth, overlayHeight)
+	oldBitmap, _, _ := procSelectObject.Call(dc, bitmap)
+
+	o.draw(dc)
+
+	procBitBlt.Call(hdc, 0, 0, overlayWidth, overlayHeight, dc, 0, 0, srcCopy)
+	procSelectObject.Call(dc, oldBitmap)
+	procDeleteObject.Call(bitmap)
+	procDeleteDC.Call(dc)
+}
+
+func (o *overlayWindow) draw(dc uintptr) {
+	report := &o.report
+
+	fillRect(dc, winRect{0, 0, overlayWidth, overlayHeight}, overlayBackground)
+
+	if !o.connected {
+		// Only draw the outline of the controller.
+		report = &stadiacontroller.Xbox360ControllerReport{}
+	}
+
+	for _, button := range overlayButtons {
+		fillRect(dc, button.rect, buttonColor(report.Buttons.Has(button.button)))
+	}
+
+	drawTrigger(dc, winRect{10, 5, 70, 12}, report.GetLeftTrigger())
+	drawTrigger(dc, winRect{170, 5, 230, 12}, report.GetRightTrigger())
+
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	drawStick(dc, 40, 70, lx, ly, report.Buttons.Has(stadiacontroller.StadiaButtonL3))
+	drawStick(dc, 200, 70, rx, ry, report.Buttons.Has(stadiacontroller.StadiaButtonR3))
+}
+
+func buttonColor(pressed bool) uintptr {
+	if pressed {
+		return overlayPressed
+	}
+
+	return overlayReleased
+}
+
+// drawTrigger draws a bar filled according to the given trigger value.
+func drawTrigger(dc uintptr, r winRect, value byte) {
+	fillRect(dc, r, overlayReleased)
+
+	r.right = r.left + (r.right-r.left)*int32(value)/255
+	fillRect(dc, r, overlayPressed)
+}
+
+// drawStick draws a stick centered on the given point, with a dot at its
+// position.
+func drawStick(dc uintptr, cx, cy int32, x, y int16, pressed bool) {
+	radius := int32(overlayStickRange + 6)
+
+	ellipse(dc, winRect{cx - radius, cy - radius, cx + radius, cy + radius}, buttonColor(pressed))
+	ellipse(dc, winRect{cx - radius + 2, cy - radius + 2, cx + radius - 2, cy + radius - 2}, overlayBackground)
+
+	// Y grows upwards on the controller and downwards on the screen.
+	dx := int32(x) * overlayStickRange / 32768
+	dy := -int32(y) * overlayStickRange / 32768
+
+	ellipse(dc, winRect{cx + dx - 5, cy + dy - 5, cx + dx + 5, cy + dy + 5}, overlayPressed)
+}
+
+func fillRect(dc uintptr, r winRect, color uintptr) {
+	brush, _, _ := procCreateSolidBrush.Call(color)
+
+	procFillRect.Call(dc, uintptr(unsafe.Pointer(&r)), brush)
+	procDeleteObject.Call(brush)
+}
+
+func ellipse(dc uintptr, r winRect, color uintptr) {
+	brush, _, _ := procCreateSolidBrush.Call(color)
+	pen, _, _ := procGetStockObject.Call(nullPen)
+
+	oldBrush, _, _ := procSelectObject.Call(dc, brush)
+	oldPen, _, _ := procSelectObject.Call(dc, pen)
+
+	procEllipse.Call(dc, uintptr(r.left), uintptr(r.top), uintptr(r.right), uintptr(r.bottom))
+
+	procSelectObject.Call(dc, oldPen)
+	procSelectObject.Call(dc, oldBrush)
+	procDeleteObject.Call(brush)
+}
+
+// isVisible returns whether the overlay is shown.
+func (o *overlayWindow) isVisible() bool {
+	return atomic.LoadInt32(&o.visible) != 0
+}
+
+// setVisible shows or hides the overlay.
+func (o *overlayWindow) setVisible(visible bool) {
+	var show uintptr
+
+	if visible {
+		show = 1
+		atomic.StoreInt32(&o.visible, 1)
+	} else {
+		atomic.StoreInt32(&o.visible, 0)
+	}
+
+	procPostMessageW.Call(o.hwnd, wmOverlayShow, show, 0)
+}
+
+// Close closes the overlay, and waits for its thread to exit.
+func (o *overlayWindow) Close() {
+	procPostMessageW.Call(o.hwnd, wmClose, 0, 0)
+	<-o.done
+}
@@ -0,0 +1,73 @@
+package main
+
+import (
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Duration of the Guide press sent when the Stadia button is released before
+// -pause-hold.
+const pauseHoldTap = 80 * time.Millisecond
+
+// pauseHold toggles pausing when the Stadia button is held for -pause-hold.
+// The button is hidden from the rest of the input loop while held, so that
+// games do not receive a Guide press when pausing or resuming. If it is
+// released early, Guide is pressed for pauseHoldTap instead.
+type pauseHold struct {
+	duration time.Duration
+
+	pressed   bool
+	pressedAt time.Time
+	triggered bool
+	tapUntil  time.Time
+}
+
+// newPauseHold returns the toggle, or nil if duration is 0.
+func newPauseHold(duration time.Duration) *pauseHold {
+	if duration <= 0 {
+		return nil
+	}
+
+	return &pauseHold{duration: duration}
+}
+
+// update returns whether pausing must be toggled, and the time at which the
+// report must be handled again to do so if the button is still held, or to
+// release the tap of Guide. It hides the Stadia button from the given report
+// while it is held, and presses it during the tap.
+func (h *pauseHold) update(report *stadiacontroller.Xbox360ControllerReport, now time.Time) (bool, time.Time) {
+	pressed := report.Buttons.Has(stadiacontroller.StadiaButtonStadia)
+	var toggle bool
+	var wake time.Time
+
+	switch {
+	case !pressed && h.pressed && !h.triggered:
+		h.pressed, h.tapUntil = false, now.Add(pauseHoldTap)
+	case !pressed:
+		h.pressed, h.triggered = false, false
+	case !h.pressed:
+		h.pressed, h.pressedAt, h.tapUntil = true, now, time.Time{}
+		wake = now.Add(h.duration)
+	case !h.triggered && now.Sub(h.pressedAt) >= h.duration:
+		h.triggered, toggle = true, true
+	case !h.triggered:
+		wake = h.pressedAt.Add(h.duration)
+	}
+
+	if pressed {
+		report.Buttons &^= stadiacontroller.StadiaButtonStadia
+		report.SetButtons(report.GetButtons() &^ stadiacontroller.StadiaButtonStadia.Xbox360Buttons())
+	} else if now.Before(h.tapUntil) {
+		report.Buttons |= stadiacontroller.StadiaButtonStadia
+		report.SetButtons(report.GetButtons() | stadiacontroller.StadiaButtonStadia.Xbox360Buttons())
+		wake = h.tapUntil
+	}
+
+	return toggle, wake
+}
+
+// reset forgets the button held, e.g. after the controller disconnected.
+func (h *pauseHold) reset() {
+	h.pressed, h.triggered, h.tapUntil = false, false, time.Time{}
+}
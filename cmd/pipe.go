@@ -0,0 +1,679 @@
+package main
+
+import (
+	"bufio"
+	"encoding/json"
+	"fmt"
+	"log"
+	"os"
+	"sync"
+	"sync/atomic"
+	"syscall"
+	"time"
+	"unsafe"
+
+	"github.com/71/stadiacontroller"
+	"golang.org/x/sys/windows"
+)
+
+var (
+	advapi32 = windows.NewLazySystemDLL("advapi32.dll")
+
+	procCreateNamedPipeW = kernel32.NewProc("CreateNamedPipeW")
+	procConnectNamedPipe = kernel32.NewProc("ConnectNamedPipe")
+
+	procConvertStringSecurityDescriptorToSecurityDescriptorW = advapi32.NewProc("ConvertStringSecurityDescriptorToSecurityDescriptorW")
+)
+
+// Constants used to create named pipes.
+const (
+	pipeAccessDuplex          = 0x00000003
+	fileFlagFirstPipeInstance = 0x00080000
+	pipeTypeByte              = 0x00000000
+	pipeReadModeByte          = 0x00000000
+	pipeWait                  = 0x00000000
+	pipeRejectRemoteClients   = 0x00000008
+	pipeUnlimitedInstances    = 255
+	pipeBufferSize            = 4096
+	sddlRevision1             = 1
+
+	errorPipeConnected syscall.Errno = 535
+)
+
+// Default name of the control pipe.
+const defaultControlPipe = `\\.\pipe\stadiacontroller`
+
+// Maximum size of a request sent to the control pipe.
+const maxControlRequestSize = 64 * 1024
+
+// Maximum number of events queued for a client subscribed to the control
+// pipe. Events are dropped for clients which are too far behind.
+const maxQueuedControlEvents = 256
+
+// Codes of the errors returned by the control pipe.
+const (
+	controlErrorInvalidRequest  = "invalid_request"
+	controlErrorUnknownCommand  = "unknown_command"
+	controlErrorInvalidArgument = "invalid_argument"
+	controlErrorShuttingDown    = "shutting_down"
+)
+
+// controlRequest is a command sent to the control pipe, encoded as a single
+// line of JSON.
+type controlRequest struct {
+	Command string `json:"command"`
+
+	// Arguments of the "rumble" command.
+	LargeMotor *byte `json:"large_motor,omitempty"`
+	SmallMotor *byte `json:"small_motor,omitempty"`
+	DurationMs *int  `json:"duration_ms,omitempty"`
+
+	// Argument of the "rumble_pattern" command, e.g. "200@100,0@100,200@100".
+	Pattern string `json:"pattern,omitempty"`
+
+	// Argument of the "slow", "overlay", "keyboard", "mouse", "delay" and
+	// "vibration" commands, which toggle slow mode, the overlay, the keyboard
+	// hook, mouse aim, the added delay and vibrations if omitted.
+	Enabled *bool `json:"enabled,omitempty"`
+}
+
+// controlResponse is the response to a controlRequest, encoded as a single
+// line of JSON.
+type controlResponse struct {
+	OK     bool                                      `json:"ok"`
+	Error  *controlError                             `json:"error,omitempty"`
+	Status *controlStatus                            `json:"status,omitempty"`
+	Report *stadiacontroller.Xbox360ControllerReport `json:"report,omitempty"`
+}
+
+type controlError struct {
+	Code    string `json:"code"`
+	Message string `json:"message"`
+}
+
+type controlStatus struct {
+	Connected bool         `json:"connected"`
+	Transport string       `json:"transport,omitempty"`
+	Paused    bool         `json:"paused"`
+	Reports   uint64       `json:"reports"`
+	Battery   *batteryJSON `json:"battery"`
+
+	SlowMode        bool    `json:"slow_mode"`
+	SlowModeSeconds float64 `json:"slow_mode_seconds"`
+}
+
+func controlFailure(code, format string, args ...interface{}) controlResponse {
+	return controlResponse{Error: &controlError{Code: code, Message: fmt.Sprintf(format, args...)}}
+}
+
+// controlState is the state of the input loop which control commands query
+// and modify.
+type controlState struct {
+	controller *stadiacontroller.StadiaController
+	overlay    *overlayWindow
+	keyboard   *keyboardInput
+	mouse      *mouseAim
+	delayed    *delayQueue
+	patterns   *rumblePlayer
+
+	connected  bool
+	transport  stadiacontroller.Transport
+	devicePath string
+	player     int
+	paused     bool
+	slow       bool
+	shutdown   bool
+	battery    batteryMonitor
+
+	// report is the last report sent to games.
+	report stadiacontroller.Xbox360ControllerReport
+}
+
+// hookEnv returns the environment variables describing the state, given to
+// the commands run by the input loop.
+func (s *controlState) hookEnv() []string {
+	env := []string{"STADIA_TRANSPORT=" + s.transport.String(), "STADIA_DEVICE_PATH=" + s.devicePath}
+
+	if s.player != 0 {
+		env = append(env, fmt.Sprintf("STADIA_PLAYER=%d", s.player))
+	}
+
+	return env
+}
+
+// execute executes the given request, and returns its response.
+func (s *controlState) execute(request controlRequest) controlResponse {
+	switch request.Command {
+	case "status":
+		status := &controlStatus{
+			Connected: s.connected,
+			Paused:    s.paused,
+			Reports:   atomic.LoadUint64(&stats.reportsReceived),
+			Battery:   newBatteryJSON(s.battery.status, s.battery.readAt),
+
+			SlowMode:        s.slow,
+			SlowModeSeconds: slowModeDuration().Seconds(),
+		}
+
+		if s.connected {
+			status.Transport = s.transport.String()
+		}
+
+		return controlResponse{OK: true, Status: status}
+
+	case "report":
+		report := s.report
+
+		return controlResponse{OK: true, Report: &report}
+
+	case "pause":
+		s.paused = true
+
+	case "resume":
+		s.paused = false
+
+	case "rumble":
+		largeMotor, smallMotor, durationMs := byte(255), byte(255), 500
+
+		if request.LargeMotor != nil {
+			largeMotor = *request.LargeMotor
+		}
+		if request.SmallMotor != nil {
+			smallMotor = *request.SmallMotor
+		}
+		if request.DurationMs != nil {
+			durationMs = *request.DurationMs
+		}
+		if durationMs <= 0 || durationMs > 10000 {
+			return controlFailure(controlErrorInvalidArgument, "duration must be between 1 and 10000ms")
+		}
+
+		s.controller.VibrateAsync(largeMotor, smallMotor)
+
+		time.AfterFunc(time.Duration(durationMs)*time.Millisecond, func() {
+			s.controller.VibrateAsync(0, 0)
+		})
+
+	case "rumble_pattern":
+		pattern, err := parseRumblePattern(request.Pattern)
+
+		if err != nil {
+			return controlFailure(controlErrorInvalidArgument, "%v", err)
+		}
+		if pattern == nil {
+			return controlFailure(controlErrorInvalidArgument, "missing pattern")
+		}
+
+		s.patterns.play(pattern)
+
+	case "slow":
+		if request.Enabled != nil {
+			s.setSlowMode(*request.Enabled)
+		} else {
+			s.setSlowMode(!s.slow)
+		}
+
+	case "overlay":
+		if s.overlay == nil {
+			return controlFailure(controlErrorInvalidArgument, "the overlay is not enabled (-overlay)")
+		}
+
+		if request.Enabled != nil {
+			s.overlay.setVisible(*request.Enabled)
+		} else {
+			s.overlay.setVisible(!s.overlay.isVisible())
+		}
+
+	case "keyboard":
+		if s.keyboard == nil {
+			return controlFailure(controlErrorInvalidArgument, "no keys are bound (-keyboard)")
+		}
+
+		enabled := !s.keyboard.isInstalled()
+
+		if request.Enabled != nil {
+			enabled = *request.Enabled
+		}
+
+		if !enabled {
+			s.keyboard.uninstall()
+		} else if err := s.keyboard.install(); err != nil {
+			return controlFailure(controlErrorInvalidArgument, "%v", err)
+		}
+
+	case "mouse":
+		if s.mouse == nil {
+			return controlFailure(controlErrorInvalidArgument, "the mouse is not listened to (-mouse-aim)")
+		}
+
+		if request.Enabled != nil {
+			s.setMouseAim(*request.Enabled)
+		} else {
+			s.setMouseAim(!s.mouse.isActive())
+		}
+
+	case "delay":
+		if s.delayed == nil {
+			return controlFailure(controlErrorInvalidArgument, "no delay is added (-added-delay)")
+		}
+
+		enabled := !s.delayed.enabled
+
+		if request.Enabled != nil {
+			enabled = *request.Enabled
+		}
+		if enabled != s.delayed.enabled {
+			s.delayed.setEnabled(enabled)
+		}
+
+	case "vibration":
+		enabled := vibrationsMuted()
+
+		if request.Enabled != nil {
+			enabled = *request.Enabled
+		}
+
+		setVibrationsMuted(!enabled)
+
+		if !enabled {
+			s.controller.VibrateAsync(0, 0)
+		}
+
+	case "reconnect":
+		log.Printf("reopening the controller")
+
+		// Release waits for the disconnection event to be received.
+		go s.controller.Release()
+
+	case "shutdown":
+		s.shutdown = true
+
+	case "subscribe":
+		return controlFailure(controlErrorInvalidArgument, "only clients of the control pipe can subscribe to events")
+
+	default:
+		return controlFailure(controlErrorUnknownCommand, "unknown command %q", request.Command)
+	}
+
+	return controlResponse{OK: true}
+}
+
+// A controlCommand is a request received by the control pipe, which must be
+// answered by sending a response to reply.
+type controlCommand struct {
+	request controlRequest
+	reply   chan<- controlResponse
+}
+
+// controlPipe is a named pipe accepting commands from other processes of the
+// current user. Commands are sent to the input loop, which executes them and
+// replies. Clients which send "subscribe" also receive the events written to
+// subscribers, as with -output json.
+type controlPipe struct {
+	name        string
+	sa          *windows.SecurityAttributes
+	commands    chan<- controlCommand
+	subscribers *controlSubscribers
+
+	mu     sync.Mutex
+	closed bool
+	done   chan struct{}
+}
+
+// newControlPipe creates the named pipe with the given name, and starts
+// accepting clients whose commands are sent to the given channel.
+func newControlPipe(name string, commands chan<- controlCommand) (*controlPipe, error) {
+	sa, err := currentUserSecurityAttributes()
+
+	if err != nil {
+		return nil, err
+	}
+
+	p := &controlPipe{
+		name:        name,
+		sa:          sa,
+		commands:    commands,
+		subscribers: &controlSubscribers{clients: map[chan []byte]struct{}{}},
+		done:        make(chan struct{}),
+	}
+
+	// Create the first instance here, so that errors (e.g. another instance
+	// of the program already running) are reported immediately.
+	handle, err := p.createInstance(true)
+
+	if err != nil {
+		return nil, err
+	}
+
+	go p.serve(handle)
+
+	log.Printf("control pipe listening on %s", name)
+
+	return p, nil
+}
+
+// currentUserSecurityAttributes returns security attributes granting access
+// to the current user and the system only.
+func currentUserSecurityAttributes() (*windows.SecurityAttributes, error) {
+	token, err := windows.OpenCurrentProcessToken()
+
+	if err != nil {
+		return nil, err
+	}
+
+	defer token.Close()
+
+	user, err := token.GetTokenUser()
+
+	if err != nil {
+		return nil, err
+	}
+
+	sid, err := user.User.Sid.String()
+
+	if err != nil {
+		return nil, err
+	}
+
+	sddl, err := windows.UTF16PtrFromString("D:P(A;;GA;;;" + sid + ")(A;;GA;;;SY)")
+
+	if err != nil {
+		return nil, err
+	}
+
+	var descriptor uintptr
+
+	r, _, err := procConvertStringSecurityDescriptorToSecurityDescriptorW.Call(
+		uintptr(unsafe.Pointer(sddl)), sddlRevision1, uintptr(unsafe.Pointer(&descriptor)), 0)
+
+	if r == 0 {
+		return nil, err
+	}
+
+	// The descriptor is used for the whole lifetime of the process, and is
+	// therefore never freed.
+	return &windows.SecurityAttributes{
+		Length:             uint32(unsafe.Sizeof(windows.SecurityAttributes{})),
+		SecurityDescriptor: descriptor,
+	}, nil
+}
+
+func (p *controlPipe) createInstance(first bool) (windows.Handle, error) {
+	name, err := windows.UTF16PtrFromString(p.name)
+
+	if err != nil {
+		return windows.InvalidHandle, err
+	}
+
+	openMode := uintptr(pipeAccessDuplex)
+
+	if first {
+		openMode |= fileFlagFirstPipeInstance
+	}
+
+	r, _, err := procCreateNamedPipeW.Call(
+		uintptr(unsafe.Pointer(name)),
+		openMode,
+		pipeTypeByte|pipeReadModeByte|pipeWait|pipeRejectRemoteClients,
+		pipeUnlimitedInstances,
+		pipeBufferSize,
+		pipeBufferSize,
+		0,
+		uintptr(unsafe.Pointer(p.sa)))
+
+	if windows.Handle(r) == windows.InvalidHandle {
+		return windows.InvalidHandle, fmt.Errorf("cannot create pipe %s: %w", p.name, err)
+	}
+
+	return windows.Handle(r), nil
+}
+
+// serve waits for clients on the given pipe instance, and on new instances
+// after each client connects, until the pipe is closed.
+func (p *controlPipe) serve(handle windows.Handle) {
+	for {
+		r, _, err := procConnectNamedPipe.Call(uintptr(handle), 0)
+
+		if r == 0 && err != errorPipeConnected {
+			log.Printf("cannot accept control pipe client: %v", err)
+			windows.CloseHandle(handle)
+			return
+		}
+
+		if p.isClosed() {
+			windows.CloseHandle(handle)
+			return
+		}
+
+		go p.handleClient(os.NewFile(uintptr(handle), p.name))
+
+		handle, err = p.createInstance(false)
+
+		if err != nil {
+			log.Print(err)
+			return
+		}
+	}
+}
+
+// handleClient answers the requests of the given client until it disconnects.
+// Once the client subscribed, events are written between the responses.
+func (p *controlPipe) handleClient(client *os.File) {
+	defer client.Close()
+
+	scanner := bufio.NewScanner(client)
+	scanner.Buffer(make([]byte, 4096), maxControlRequestSize)
+	encoder := json.NewEncoder(client)
+
+	var writeMu sync.Mutex
+	var events chan []byte
+
+	defer func() {
+		if events != nil {
+			p.subscribers.unsubscribe(events)
+		}
+	}()
+
+	for scanner.Scan() {
+		var request controlRequest
+		var response controlResponse
+
+		if err := json.Unmarshal(scanner.Bytes(), &request); err != nil {
+			response = controlFailure(controlErrorInvalidRequest, "cannot decode request: %v", err)
+		} else if request.Command == "subscribe" {
+			if events == nil {
+				events = p.subscribers.subscribe()
+
+				go forwardControlEvents(client, &writeMu, events)
+			}
+
+			response = controlResponse{OK: true}
+		} else {
+			response = sendCommand(p.commands, p.done, request)
+		}
+
+		writeMu.Lock()
+		err := encoder.Encode(response)
+		writeMu.Unlock()
+
+		if err != nil {
+			return
+		}
+	}
+}
+
+// forwardControlEvents writes the given events to a client until they are
+// closed or the client disconnects.
+func forwardControlEvents(client *os.File, writeMu *sync.Mutex, events <-chan []byte) {
+	for line := range events {
+		writeMu.Lock()
+		_, err := client.Write(line)
+		writeMu.Unlock()
+
+		if err != nil {
+			return
+		}
+	}
+}
+
+// controlSubscribers sends the lines written to it to the clients of the
+// control pipe which subscribed to events. Writing never blocks.
+type controlSubscribers struct {
+	mu      sync.Mutex
+	clients map[chan []byte]struct{}
+}
+
+func (s *controlSubscribers) Write(p []byte) (int, error) {
+	line := append([]byte(nil), p...)
+
+	s.mu.Lock()
+	defer s.mu.Unlock()
+
+	for client := range s.clients {
+		select {
+		case client <- line:
+		default:
+		}
+	}
+
+	return len(p), nil
+}
+
+// listening returns whether a client subscribed to events, so that events are
+// not encoded for nobody.
+func (s *controlSubscribers) listening() bool {
+	s.mu.Lock()
+	defer s.mu.Unlock()
+
+	return len(s.clients) > 0
+}
+
+func (s *controlSubscribers) subscribe() chan []byte {
+	events := make(chan []byte, maxQueuedControlEvents)
+
+	s.mu.Lock()
+	s.clients[events] = struct{}{}
+	s.mu.Unlock()
+
+	return events
+}
+
+func (s *controlSubscribers) unsubscribe(events chan []byte) {
+	s.mu.Lock()
+	delete(s.clients, events)
+	s.mu.Unlock()
+
+	close(events)
+}
+
+// sendCommand sends the given request to the input loop through commands, and
+// waits for its response. It fails if done is closed first.
+func sendCommand(commands chan<- controlCommand, done <-chan struct{}, request controlRequest) controlResponse {
+	reply := make(chan controlResponse, 1)
+
+	select {
+	case commands <- controlCommand{request, reply}:
+	case <-done:
+		return controlFailure(controlErrorShuttingDown, "shutting down")
+	}
+
+	select {
+	case response := <-reply:
+		return response
+	case <-done:
+		return controlFailure(controlErrorShuttingDown, "shutting down")
+	}
+}
+
+func (p *controlPipe) isClosed() bool {
+	p.mu.Lock()
+	defer p.mu.Unlock()
+
+	return p.closed
+}
+
+// Close stops accepting clients. Connected clients are answered with an error.
+func (p *controlPipe) Close() {
+	p.mu.Lock()
+	if p.closed {
+		p.mu.Unlock()
+		return
+	}
+	p.closed = true
+	close(p.done)
+	p.mu.Unlock()
+
+	// Unblock the pending ConnectNamedPipe call by connecting to it.
+	if client, err := os.OpenFile(p.name, os.O_RDWR, 0); err == nil {
+		client.Close()
+	}
+}
+
+// runCtl sends the command given on the command line to a running instance
+// through its control pipe, and prints its response.
+func runCtl(pipe string, args []string) error {
+	if len(args) == 0 {
+		return fmt.Errorf("usage: ctl <status|report|subscribe|pause|resume|rumble [large small duration_ms]|rumble_pattern pattern|slow [on|off]|overlay [on|off]|keyboard [on|off]|mouse [on|off]|delay [on|off]|vibration [on|off]|reconnect|shutdown>")
+	}
+
+	request, err := parseCommandArgs(args)
+
+	if err != nil {
+		return err
+	}
+
+	conn, err := os.OpenFile(pipe, os.O_RDWR, 0)
+
+	if err != nil {
+		return fmt.Errorf("cannot connect to running instance: %w", err)
+	}
+
+	defer conn.Close()
+
+	if err := json.NewEncoder(conn).Encode(request); err != nil {
+		return err
+	}
+
+	reader := bufio.NewReader(conn)
+	line, err := reader.ReadBytes('\n')
+
+	if err != nil {
+		return fmt.Errorf("cannot read response: %w", err)
+	}
+
+	fmt.Print(string(line))
+
+	var response controlResponse
+
+	if err := json.Unmarshal(line, &response); err != nil {
+		return err
+	}
+	if !response.OK {
+		os.Exit(1)
+	}
+
+	// Print events until the instance exits.
+	if request.Command == "subscribe" {
+		for {
+			event, err := reader.ReadBytes('\n')
+
+			if err != nil {
+				return nil
+			}
+
+			fmt.Print(string(event))
+		}
+	}
+
+	return nil
+}
+
+// runStop asks the instance listening on the control pipe to exit, e.g. an
+// instance started with -background.
+func runStop(args []string) error {
+	if len(args) != 0 {
+		return fmt.Errorf("usage: stop")
+	}
+
+	return runCtl(*controlPipeName, []string{"shutdown"})
+}
@@ -0,0 +1,91 @@
+package main
+
+import (
+	"flag"
+	"fmt"
+)
+
+// A preset is a named set of flag values. Flags given on the command line
+// override the values of the preset.
+type preset struct {
+	name        string
+	description string
+	flags       [][2]string
+}
+
+var presets = [...]preset{
+	{"default", "forwards the controller unchanged", nil},
+	{"retro", "swaps A and B as on Nintendo layouts", [][2]string{
+		{"remap", "a=b,b=a"},
+	}},
+	{"accessibility", "keeps the triggers pulled until pulled again and ignores presses shorter than 60ms", [][2]string{
+		{"sticky", "l2,r2"},
+		{"min-press", "all=60ms"},
+	}},
+	{"southpaw", "swaps the left and right halves of the controller", [][2]string{
+		{"left-handed", "true"},
+	}},
+}
+
+func findPreset(name string) (*preset, error) {
+	for i := range presets {
+		if presets[i].name == name {
+			return &presets[i], nil
+		}
+	}
+
+	return nil, fmt.Errorf("unknown preset %q, see 'preset list'", name)
+}
+
+// applyPreset sets the flags of the given preset which were not given on the
+// command line.
+func applyPreset(name string) error {
+	p, err := findPreset(name)
+
+	if err != nil {
+		return err
+	}
+
+	for _, f := range p.flags {
+		if isFlagSet(f[0]) {
+			continue
+		}
+		if err := flag.Set(f[0], f[1]); err != nil {
+			return fmt.Errorf("invalid preset %s: %w", p.name, err)
+		}
+	}
+
+	return nil
+}
+
+// runPreset lists the presets, or prints the flags a preset expands to.
+func runPreset(args []string) error {
+	switch {
+	case len(args) == 1 && args[0] == "list":
+		for _, p := range presets {
+			fmt.Printf("%-14s %s\n", p.name, p.description)
+		}
+
+		return nil
+
+	case len(args) == 2 && args[0] == "show":
+		p, err := findPreset(args[1])
+
+		if err != nil {
+			return err
+		}
+
+		if len(p.flags) == 0 {
+			fmt.Println("(no flags)")
+		}
+
+		for _, f := range p.flags {
+			fmt.Printf("-%s=%s\n", f[0], f[1])
+		}
+
+		return nil
+
+	default:
+		return fmt.Errorf("usage: preset <list|show name>")
+	}
+}
@@ -0,0 +1,171 @@
+package main
+
+import (
+	"fmt"
+	"strings"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Maximum minimum press duration, which bounds the delay added to presses.
+const maxMinPress = 500 * time.Millisecond
+
+// pressFilter ignores presses shorter than a minimum duration, e.g. caused by
+// a tremor, and optionally presses following the previous one too closely.
+// Presses are only forwarded once they lasted the minimum duration (or until
+// the end of the minimum gap), and are therefore delayed by at most the sum of
+// both.
+type pressFilter struct {
+	minPress map[stadiacontroller.StadiaButtons]time.Duration
+	minGap   time.Duration
+
+	states map[stadiacontroller.StadiaButtons]*pressState
+}
+
+type pressState struct {
+	pressed      bool
+	pressedAt    time.Time
+	forwarded    bool
+	lastReleased time.Time
+}
+
+// newPressFilter returns a filter configured by a comma-separated list of
+// button=duration entries, where "all" applies to all buttons (e.g.
+// "all=60ms,a=100ms"), and by the minimum time between the release of a
+// button and its next press.
+func newPressFilter(spec string, minGap time.Duration) (*pressFilter, error) {
+	if minGap < 0 || minGap > maxMinPress {
+		return nil, fmt.Errorf("minimum gap between presses must be between 0 and %v", maxMinPress)
+	}
+
+	f := &pressFilter{
+		minPress: map[stadiacontroller.StadiaButtons]time.Duration{},
+		minGap:   minGap,
+		states:   map[stadiacontroller.StadiaButtons]*pressState{},
+	}
+
+	var all time.Duration
+
+	for _, entry := range strings.Split(spec, ",") {
+		if entry = strings.TrimSpace(entry); entry == "" {
+			continue
+		}
+
+		i := strings.IndexByte(entry, '=')
+
+		if i == -1 {
+			return nil, fmt.Errorf("invalid minimum press %q, expected button=duration", entry)
+		}
+
+		d, err := time.ParseDuration(entry[i+1:])
+
+		if err != nil {
+			return nil, err
+		}
+		if d < 0 || d > maxMinPress {
+			return nil, fmt.Errorf("minimum press duration must be between 0 and %v", maxMinPress)
+		}
+
+		if name := entry[:i]; strings.EqualFold(name, "all") {
+			all = d
+		} else {
+			button, err := stadiacontroller.ParseStadiaButtons(name)
+
+			if err != nil {
+				return nil, err
+			}
+
+			f.minPress[button] = d
+		}
+	}
+
+	for button := stadiacontroller.StadiaButtons(1); button <= stadiacontroller.StadiaButtonVolumeDown; button <<= 1 {
+		if _, ok := f.minPress[button]; !ok && (all > 0 || minGap > 0) {
+			f.minPress[button] = all
+		}
+		if _, ok := f.minPress[button]; ok {
+			f.states[button] = &pressState{}
+		}
+	}
+
+	return f, nil
+}
+
+// apply removes from the given report, received at the given time, the
+// presses which are not long enough yet or follow the previous one too
+// closely. If a press may become long enough without another report, it
+// returns the time at which apply must be called again with the same report.
+func (f *pressFilter) apply(report *stadiacontroller.Xbox360ControllerReport, now time.Time) time.Time {
+	var wake time.Time
+	var suppressed stadiacontroller.StadiaButtons
+
+	for button, state := range f.states {
+		pressed := report.Buttons.Has(button)
+
+		if pressed != state.pressed {
+			state.pressed = pressed
+			state.pressedAt = now
+
+			if !pressed && state.forwarded {
+				state.forwarded = false
+				state.lastReleased = now
+			}
+		}
+
+		if !pressed || state.forwarded {
+			continue
+		}
+
+		// Presses too close to the previous one are only forwarded once they
+		// are held past the minimum gap.
+		ready := state.pressedAt.Add(f.minPress[button])
+
+		if gapEnd := state.lastReleased.Add(f.minGap); gapEnd.After(ready) {
+			ready = gapEnd
+		}
+
+		if now.Before(ready) {
+			suppressed |= button
+
+			if wake.IsZero() || ready.Before(wake) {
+				wake = ready
+			}
+			continue
+		}
+
+		state.forwarded = true
+	}
+
+	if suppressed != 0 {
+		report.Buttons &^= suppressed
+		report.Capture = report.Buttons.Has(stadiacontroller.StadiaButtonCapture)
+		report.Assistant = report.Buttons.Has(stadiacontroller.StadiaButtonAssistant)
+		report.SetButtons(report.GetButtons() &^ suppressed.Xbox360Buttons())
+	}
+
+	return wake
+}
+
+// reset forgets the state of all buttons.
+func (f *pressFilter) reset() {
+	for button := range f.states {
+		f.states[button] = &pressState{}
+	}
+}
+
+// newPressFilterStep returns the step filtering presses as configured by
+// -min-press and -min-press-gap, or nil if presses are not filtered.
+func newPressFilterStep() (*reportStep, error) {
+	if *minPress == "" && *minPressGap == 0 {
+		return nil, nil
+	}
+
+	f, err := newPressFilter(*minPress, *minPressGap)
+
+	if err != nil {
+		return nil, err
+	}
+
+	return timedStep(f.apply, f.reset), nil
+}
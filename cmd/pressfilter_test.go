@@ -0,0 +1,125 @@
+package main
+
+import (
+	"testing"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// pressStep is a report received some milliseconds after the first one,
+// with the buttons held, and the buttons and wake-up time expected after
+// filtering it. A wake-up time of 0 means that none is expected.
+type pressStep struct {
+	ms        int
+	held      stadiacontroller.StadiaButtons
+	forwarded stadiacontroller.StadiaButtons
+	wakeMs    int
+}
+
+func checkPressSteps(t *testing.T, f *pressFilter, steps []pressStep) {
+	t.Helper()
+
+	start := time.Now()
+
+	for _, step := range steps {
+		var report stadiacontroller.Xbox360ControllerReport
+		report.Buttons = step.held
+		report.SetButtons(step.held.Xbox360Buttons())
+
+		wake := f.apply(&report, start.Add(time.Duration(step.ms)*time.Millisecond))
+
+		if report.Buttons != step.forwarded || report.GetButtons() != step.forwarded.Xbox360Buttons() {
+			t.Errorf("at %dms, %v held: forwarded %v (%#06x), expected %v", step.ms, step.held, report.Buttons, report.GetButtons(), step.forwarded)
+		}
+
+		if step.wakeMs == 0 && !wake.IsZero() {
+			t.Errorf("at %dms, %v held: wake up after %v, expected none", step.ms, step.held, wake.Sub(start))
+		} else if step.wakeMs != 0 && !wake.Equal(start.Add(time.Duration(step.wakeMs)*time.Millisecond)) {
+			t.Errorf("at %dms, %v held: wake up after %v, expected %dms", step.ms, step.held, wake.Sub(start), step.wakeMs)
+		}
+	}
+}
+
+func TestPressFilterMinPress(t *testing.T) {
+	const a, b, x = stadiacontroller.StadiaButtonA, stadiacontroller.StadiaButtonB, stadiacontroller.StadiaButtonX
+
+	f, err := newPressFilter("a=60ms, b=100ms", 0)
+
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	checkPressSteps(t, f, []pressStep{
+		{0, 0, 0, 0},
+
+		// A tap shorter than the minimum is never forwarded.
+		{0, a, 0, 60},
+		{30, a, 0, 60},
+		{40, 0, 0, 0},
+
+		// Longer presses are, once they lasted long enough.
+		{100, a | b, 0, 160},
+		{160, a | b, a, 200},
+		{200, a | b, a | b, 0},
+		{210, b, b, 0},
+		{220, 0, 0, 0},
+
+		// Buttons without a minimum are forwarded right away.
+		{300, x | a, x, 360},
+		{360, x | a, x | a, 0},
+	})
+}
+
+func TestPressFilterMinGap(t *testing.T) {
+	const b, x = stadiacontroller.StadiaButtonB, stadiacontroller.StadiaButtonX
+
+	f, err := newPressFilter("", 150*time.Millisecond)
+
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	checkPressSteps(t, f, []pressStep{
+		{0, x, x, 0},
+		{50, 0, 0, 0},
+
+		// Repeats within the gap are dropped, and do not extend it.
+		{100, x, 0, 200},
+		{120, 0, 0, 0},
+		{130, x, 0, 200},
+
+		// Unless held until its end.
+		{200, x, x, 0},
+		{210, x | b, x | b, 0},
+		{400, 0, 0, 0},
+		{550, x, x, 0},
+		{560, 0, 0, 0},
+	})
+
+	// Resetting the filter forgets the last release.
+	f.reset()
+
+	checkPressSteps(t, f, []pressStep{{600, x, x, 0}})
+}
+
+func TestNewPressFilterInvalid(t *testing.T) {
+	invalid := []struct {
+		spec   string
+		minGap time.Duration
+	}{
+		{"a", 0},
+		{"a=fast", 0},
+		{"a=-1ms", 0},
+		{"all=501ms", 0},
+		{"start=60ms", 0},
+		{"", -time.Millisecond},
+		{"a=60ms", time.Second},
+	}
+
+	for _, test := range invalid {
+		if _, err := newPressFilter(test.spec, test.minGap); err == nil {
+			t.Errorf("newPressFilter(%q, %v) did not fail", test.spec, test.minGap)
+		}
+	}
+}
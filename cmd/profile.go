@@ -0,0 +1,537 @@
+package main
+
+import (
+	"flag"
+	"fmt"
+	"os"
+	"sort"
+	"strconv"
+	"strings"
+	"text/tabwriter"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Name of the profile given by the command line and the top-level keys of the
+// configuration file.
+const defaultProfileName = "default"
+
+// Flags which profiles of the configuration file may set. They configure the
+// parts of the input loop which are built again for each profile.
+var profileFlags = map[string]bool{
+	"left-deadzone":      true,
+	"right-deadzone":     true,
+	"left-curve":         true,
+	"right-curve":        true,
+	"trigger-mode":       true,
+	"trigger-threshold":  true,
+	"remap":              true,
+	"swap-ab-xy":         true,
+	"swap-sticks":        true,
+	"swap-triggers":      true,
+	"left-handed":        true,
+	"capture-button":     true,
+	"assistant-button":   true,
+	"consume":            true,
+	"on-pressed":         true,
+	"on-released":        true,
+	"assistant-is-shift": true,
+}
+
+// Flags set back to their default value after loading a profile, which
+// isFlagSet must not report as given.
+var resetFlags = map[string]bool{}
+
+// A profile holds the parts of the input loop configured by a profile of the
+// configuration file. The active profile is switched by -profile-combo.
+type profile struct {
+	name     string
+	sticks   *stickResponses
+	triggers *triggerResponse
+	remap    *remapping
+	hooks    *buttonHooks
+	mapping  buttonMap
+	shift    *shiftLayer
+
+	// Executables activating the profile when in the foreground (see
+	// foregroundWatcher).
+	match []string
+}
+
+// loadProfiles returns the default profile followed by the profiles of the
+// given configuration file, if any. All profiles are loaded on startup, so
+// that errors are reported right away and switching does not fail.
+func loadProfiles(config *configFile) ([]*profile, error) {
+	base, err := newProfile(defaultProfileName, config, nil)
+
+	if err != nil {
+		return nil, err
+	}
+
+	profiles := []*profile{base}
+
+	if config == nil {
+		return profiles, nil
+	}
+
+	for _, p := range config.profiles {
+		if p.name == defaultProfileName {
+			return nil, fmt.Errorf("%s:%d: the %s profile is given by the top-level keys", config.path, p.line, defaultProfileName)
+		}
+
+		loaded, err := loadConfigProfile(config, p)
+
+		if err != nil {
+			return nil, err
+		}
+
+		profiles = append(profiles, loaded)
+	}
+
+	return profiles, nil
+}
+
+// loadConfigProfile sets the flags of the given profile, builds the profile,
+// and restores the flags. Repeated flags such as -on-pressed replace the
+// values given outside of the profile.
+func loadConfigProfile(config *configFile, p *configProfile) (*profile, error) {
+	values := map[string]string{}
+	commands := map[string]buttonCommandsFlag{}
+	given := map[string]bool{}
+
+	defer func() {
+		for name, value := range values {
+			flag.Set(name, value)
+
+			if !given[name] {
+				resetFlags[name] = true
+			}
+		}
+		for name, value := range commands {
+			*flag.Lookup(name).Value.(*buttonCommandsFlag) = value
+		}
+	}()
+
+	for _, entry := range p.flags {
+		if !profileFlags[entry.key] {
+			return nil, fmt.Errorf("%s:%d: flag %q cannot be set by a profile", config.path, entry.line, entry.key)
+		}
+
+		f := flag.Lookup(entry.key)
+
+		if c, ok := f.Value.(*buttonCommandsFlag); ok {
+			if _, saved := commands[entry.key]; !saved {
+				commands[entry.key] = *c
+				*c = nil
+			}
+		} else if _, saved := values[entry.key]; !saved {
+			values[entry.key] = f.Value.String()
+			given[entry.key] = isFlagSet(entry.key)
+		}
+
+		delete(resetFlags, entry.key)
+
+		if err := flag.Set(entry.key, entry.value); err != nil {
+			return nil, fmt.Errorf("%s:%d: %v", config.path, entry.line, err)
+		}
+	}
+
+	return newProfile(p.name, config, p)
+}
+
+// newProfile builds a profile from the current flags, and the given profile
+// of the configuration file if any.
+func newProfile(name string, config *configFile, p *configProfile) (*profile, error) {
+	var err error
+	loaded := &profile{name: name}
+
+	if p != nil {
+		loaded.match = p.match
+	}
+
+	if loaded.sticks, err = newStickResponses(); err != nil {
+		return nil, fmt.Errorf("profile %s: %w", name, err)
+	}
+	if loaded.triggers, err = newTriggerResponse(); err != nil {
+		return nil, fmt.Errorf("profile %s: %w", name, err)
+	}
+	if loaded.remap, err = newRemapping(); err != nil {
+		return nil, fmt.Errorf("profile %s: %w", name, err)
+	}
+	if loaded.hooks, err = newButtonHooks(); err != nil {
+		return nil, fmt.Errorf("profile %s: %w", name, err)
+	}
+	if loaded.mapping, err = newButtonMap(config, p); err != nil {
+		return nil, fmt.Errorf("profile %s: %w", name, err)
+	}
+	if loaded.shift, err = newShiftLayer(config, p); err != nil {
+		return nil, fmt.Errorf("profile %s: %w", name, err)
+	}
+
+	return loaded, nil
+}
+
+// findProfile returns the index of the profile with the given name.
+func findProfile(profiles []*profile, name string) (int, error) {
+	for i, p := range profiles {
+		if p.name == name {
+			return i, nil
+		}
+	}
+
+	return 0, fmt.Errorf("unknown profile %q", name)
+}
+
+// The steps of the parts of the profile, which are nil for the parts it does
+// not configure.
+
+func (p *profile) sticksStep() *reportStep {
+	if p.sticks == nil {
+		return nil
+	}
+
+	return transformStep(p.sticks.apply, nil)
+}
+
+func (p *profile) triggersStep() *reportStep {
+	if p.triggers == nil {
+		return nil
+	}
+
+	return transformStep(p.triggers.apply, nil)
+}
+
+func (p *profile) remapStep() *reportStep {
+	if p.remap == nil {
+		return nil
+	}
+
+	return transformStep(p.remap.apply, nil)
+}
+
+func (p *profile) mappingStep() *reportStep {
+	if p.mapping == nil {
+		return nil
+	}
+
+	return transformStep(p.mapping.apply, nil)
+}
+
+// shiftStep returns the step of the shift layer, which sets commands to the
+// commands to run once the report is forwarded.
+func (p *profile) shiftStep(commands *[]buttonCommand) *reportStep {
+	shift := p.shift
+
+	if shift == nil {
+		return nil
+	}
+
+	apply := func(report *stadiacontroller.Xbox360ControllerReport, now time.Time) (time.Time, error) {
+		*commands = shift.apply(report)
+		return time.Time{}, nil
+	}
+
+	return &reportStep{apply: apply, reset: shift.reset}
+}
+
+// findConfigProfile returns the profile of the configuration file with the
+// given name, or nil if there is none.
+func findConfigProfile(name string) *configProfile {
+	for _, p := range config.profiles {
+		if p.name == name {
+			return p
+		}
+	}
+
+	return nil
+}
+
+// runProfile lists, shows, copies or deletes the profiles of the configuration
+// file given to -config. The file is edited in place, leaving the sections of
+// other profiles, the top-level keys and comments untouched.
+func runProfile(args []string) error {
+	if config == nil {
+		return fmt.Errorf("profile requires -config")
+	}
+
+	switch {
+	case len(args) == 1 && args[0] == "list":
+		fmt.Println(defaultProfileName)
+
+		for _, p := range config.profiles {
+			if len(p.match) > 0 {
+				fmt.Printf("%-14s matches %s\n", p.name, strings.Join(p.match, ", "))
+			} else {
+				fmt.Println(p.name)
+			}
+		}
+
+		return nil
+
+	case len(args) == 2 && args[0] == "show":
+		return showProfile(args[1])
+
+	case len(args) == 3 && args[0] == "copy":
+		return copyProfile(args[1], args[2])
+
+	case len(args) == 2 && args[0] == "delete":
+		return deleteProfile(args[1])
+
+	default:
+		return fmt.Errorf("usage: profile <list|show name|copy from to|delete name>")
+	}
+}
+
+// showProfile prints the settings of the given profile once inherited from the
+// top-level keys, the command line and the defaults, along with where each one
+// comes from.
+func showProfile(name string) error {
+	var p *configProfile
+
+	if name != defaultProfileName {
+		if p = findConfigProfile(name); p == nil {
+			return fmt.Errorf("unknown profile %q, see 'profile list'", name)
+		}
+	}
+
+	w := tabwriter.NewWriter(os.Stdout, 0, 0, 2, ' ', 0)
+	fromProfile := "# profile " + name
+
+	if p != nil && len(p.match) > 0 {
+		quoted := make([]string, len(p.match))
+
+		for i, executable := range p.match {
+			quoted[i] = strconv.Quote(executable)
+		}
+
+		fmt.Fprintf(w, "match = [%s]\t%s\n", strings.Join(quoted, ", "), fromProfile)
+	}
+
+	keys := make([]string, 0, len(profileFlags))
+
+	for key := range profileFlags {
+		keys = append(keys, key)
+	}
+
+	sort.Strings(keys)
+
+	for _, key := range keys {
+		var values []string
+		source := fromProfile
+
+		if p != nil {
+			for _, entry := range p.flags {
+				if entry.key == key {
+					values = append(values, entry.value)
+				}
+			}
+		}
+
+		if len(values) == 0 {
+			values, source = baseFlagValues(key), "# "+baseFlagSource(key)
+		}
+
+		for _, value := range values {
+			fmt.Fprintf(w, "%s = %s\t%s\n", key, formatConfigValue(value), source)
+		}
+	}
+
+	// Entries of the profile replace the top-level ones of the same buttons.
+	var buttons, shift []configEntry
+
+	if p != nil {
+		buttons, shift = p.buttons, p.shift
+	}
+
+	for _, section := range []struct {
+		name            string
+		base, overrides []configEntry
+	}{
+		{"buttons", config.buttons, buttons},
+		{"shift", config.shift, shift},
+	} {
+		var entries []configEntry
+		var sources []string
+		index := map[string]int{}
+
+		for i, list := range [][]configEntry{section.base, section.overrides} {
+			source := [...]string{"# configuration file", fromProfile}[i]
+
+			for _, entry := range list {
+				if j, ok := index[strings.ToLower(entry.key)]; ok {
+					entries[j], sources[j] = entry, source
+				} else {
+					index[strings.ToLower(entry.key)] = len(entries)
+					entries, sources = append(entries, entry), append(sources, source)
+				}
+			}
+		}
+
+		if len(entries) == 0 {
+			continue
+		}
+
+		fmt.Fprintf(w, "\n[%s]\n", section.name)
+
+		for i, entry := range entries {
+			fmt.Fprintf(w, "%s = %s\t%s\n", entry.key, formatConfigValue(entry.value), sources[i])
+		}
+	}
+
+	return w.Flush()
+}
+
+// baseFlagValues returns the values of the given flag outside of profiles,
+// with one value per command of repeated flags such as -on-pressed.
+func baseFlagValues(name string) []string {
+	value := flag.Lookup(name).Value
+
+	commands, ok := value.(*buttonCommandsFlag)
+
+	if !ok {
+		return []string{value.String()}
+	}
+
+	var values []string
+
+	for _, c := range *commands {
+		values = append(values, c.button.String()+"="+c.command)
+	}
+
+	return values
+}
+
+// baseFlagSource returns where the value of the given flag outside of profiles
+// comes from.
+func baseFlagSource(name string) string {
+	if explicitFlags[name] {
+		return "command line"
+	}
+
+	for _, entry := range config.flags {
+		if entry.key == name {
+			return "configuration file"
+		}
+	}
+
+	if isFlagSet(name) {
+		return "preset"
+	}
+
+	return "default"
+}
+
+// formatConfigValue formats a value as written in the configuration file.
+func formatConfigValue(value string) string {
+	if value == "true" || value == "false" {
+		return value
+	}
+	if _, err := strconv.ParseFloat(value, 64); err == nil {
+		return value
+	}
+
+	return strconv.Quote(value)
+}
+
+// copyProfile adds a profile with the settings of another one to the end of
+// the configuration file. Copying the default profile adds an empty profile,
+// which inherits all the top-level keys.
+func copyProfile(from, to string) error {
+	if from != defaultProfileName && findConfigProfile(from) == nil {
+		return fmt.Errorf("unknown profile %q, see 'profile list'", from)
+	}
+	if to == defaultProfileName || findConfigProfile(to) != nil {
+		return fmt.Errorf("profile %q already exists", to)
+	}
+	if !validProfileName(to) {
+		return fmt.Errorf("invalid profile name %q", to)
+	}
+
+	lines, sections, err := readConfigLines(config.path)
+
+	if err != nil {
+		return err
+	}
+
+	// The keys of a profile given by several sections are gathered in one
+	// section, followed by its buttons and shift sections.
+	bodies := map[string][]string{}
+
+	for _, section := range sections {
+		name, suffix, ok := profileSection(section.name)
+
+		if ok && name == from {
+			bodies[suffix] = append(bodies[suffix], trimConfigLines(lines[section.header+1:section.end])...)
+		}
+	}
+
+	copied := append([]string{"", fmt.Sprintf("[profile.%s]", to)}, bodies[""]...)
+
+	for _, suffix := range []string{".buttons", ".shift"} {
+		if len(bodies[suffix]) > 0 {
+			copied = append(copied, "", fmt.Sprintf("[profile.%s%s]", to, suffix))
+			copied = append(copied, bodies[suffix]...)
+		}
+	}
+
+	lines = append(append(trimConfigLines(lines), copied...), "")
+
+	if err := saveConfig(config.path, lines); err != nil {
+		return err
+	}
+
+	fmt.Printf("profile %s copied to %s in %s (previous file saved to %s.bak)\n", from, to, config.path, config.path)
+
+	return nil
+}
+
+// deleteProfile removes all the sections of the given profile from the
+// configuration file.
+func deleteProfile(name string) error {
+	if name == defaultProfileName {
+		return fmt.Errorf("the %s profile is given by the top-level keys and cannot be deleted", defaultProfileName)
+	}
+	if findConfigProfile(name) == nil {
+		return fmt.Errorf("unknown profile %q, see 'profile list'", name)
+	}
+
+	for _, entry := range config.flags {
+		if entry.key == "profile" && entry.value == name {
+			return fmt.Errorf("%s:%d: profile %s is selected on startup, select another one first", config.path, entry.line, name)
+		}
+	}
+
+	lines, sections, err := readConfigLines(config.path)
+
+	if err != nil {
+		return err
+	}
+
+	var kept []string
+
+	for _, section := range sections {
+		if profile, _, ok := profileSection(section.name); ok && profile == name {
+			continue
+		}
+
+		kept = append(kept, lines[section.start:section.end]...)
+	}
+
+	if err := saveConfig(config.path, append(trimConfigLines(kept), "")); err != nil {
+		return err
+	}
+
+	fmt.Printf("profile %s deleted from %s (previous file saved to %s.bak)\n", name, config.path, config.path)
+
+	return nil
+}
+
+// trimConfigLines returns the given lines without their trailing blank lines.
+func trimConfigLines(lines []string) []string {
+	for len(lines) > 0 && strings.TrimSpace(lines[len(lines)-1]) == "" {
+		lines = lines[:len(lines)-1]
+	}
+
+	return lines
+}
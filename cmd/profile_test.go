@@ -0,0 +1,128 @@
+package main
+
+import (
+	"io/ioutil"
+	"os"
+	"path/filepath"
+	"testing"
+)
+
+func TestCopyAndDeleteProfile(t *testing.T) {
+	defer func(saved *configFile) { config = saved }(config)
+
+	dir, err := ioutil.TempDir("", "stadiacontroller")
+
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	defer os.RemoveAll(dir)
+
+	path := filepath.Join(dir, "config.toml")
+	original := `low-battery = 20
+
+# Racing games.
+[profile.racing]
+match = ["acc.exe"]
+left-deadzone = "15%" # Worn stick.
+
+[buttons]
+a = "B"
+
+[profile.racing.buttons]
+r1 = "A"
+
+# Other settings of racing games.
+[profile.racing]
+trigger-mode = "analog"
+`
+
+	// Each step edits the file saved by the previous one.
+	steps := []struct {
+		edit     func() error
+		expected string
+	}{
+		{func() error { return copyProfile("racing", "rally") }, original + `
+[profile.rally]
+match = ["acc.exe"]
+left-deadzone = "15%" # Worn stick.
+trigger-mode = "analog"
+
+[profile.rally.buttons]
+r1 = "A"
+`},
+		{func() error { return deleteProfile("racing") }, `low-battery = 20
+
+[buttons]
+a = "B"
+
+[profile.rally]
+match = ["acc.exe"]
+left-deadzone = "15%" # Worn stick.
+trigger-mode = "analog"
+
+[profile.rally.buttons]
+r1 = "A"
+`},
+		{func() error { return copyProfile("default", "empty") }, `low-battery = 20
+
+[buttons]
+a = "B"
+
+[profile.rally]
+match = ["acc.exe"]
+left-deadzone = "15%" # Worn stick.
+trigger-mode = "analog"
+
+[profile.rally.buttons]
+r1 = "A"
+
+[profile.empty]
+`},
+	}
+
+	previous := original
+
+	if err := ioutil.WriteFile(path, []byte(original), 0644); err != nil {
+		t.Fatal(err)
+	}
+
+	for i, step := range steps {
+		if config, err = loadConfig(path); err != nil {
+			t.Fatal(err)
+		}
+		if err := step.edit(); err != nil {
+			t.Fatalf("step %d: %v", i, err)
+		}
+
+		if data, _ := ioutil.ReadFile(path); string(data) != step.expected {
+			t.Errorf("step %d: file is\n%s\nexpected\n%s", i, data, step.expected)
+		}
+		if data, _ := ioutil.ReadFile(path + ".bak"); string(data) != previous {
+			t.Errorf("step %d: backup is\n%s\nexpected\n%s", i, data, previous)
+		}
+
+		previous = step.expected
+	}
+
+	if config, err = loadConfig(path); err != nil {
+		t.Fatal(err)
+	}
+
+	for _, invalid := range []func() error{
+		func() error { return copyProfile("racing", "copy") },
+		func() error { return copyProfile("rally", "empty") },
+		func() error { return copyProfile("rally", "default") },
+		func() error { return copyProfile("rally", "a.b") },
+		func() error { return deleteProfile("default") },
+		func() error { return deleteProfile("racing") },
+	} {
+		if err := invalid(); err == nil {
+			t.Errorf("invalid edit succeeded")
+		}
+	}
+
+	if data, _ := ioutil.ReadFile(path); string(data) != previous {
+		t.Errorf("invalid edits changed the file to\n%s", data)
+	}
+}
@@ -0,0 +1,92 @@
+package main
+
+import (
+	"log"
+	"sync"
+	"time"
+)
+
+// rateMonitor tracks the rate at which reports are received, warning when it
+// stays below a floor, and counts unusually long gaps between reports as
+// suspected drops.
+type rateMonitor struct {
+	mu sync.Mutex
+
+	floor float64
+	gap   time.Duration
+
+	windowStart time.Time
+	windowCount int
+	rate        float64
+	degraded    bool
+
+	last  time.Time
+	drops uint64
+	gaps  histogram
+}
+
+func newRateMonitor(floor float64, gap time.Duration) *rateMonitor {
+	return &rateMonitor{floor: floor, gap: gap}
+}
+
+// record adds a report received at the given time.
+func (m *rateMonitor) record(received time.Time) {
+	m.mu.Lock()
+	defer m.mu.Unlock()
+
+	if m.gap > 0 && !m.last.IsZero() {
+		if gap := received.Sub(m.last); gap > m.gap {
+			m.drops++
+			m.gaps.add(gap)
+		}
+	}
+
+	m.last = received
+
+	if m.windowStart.IsZero() {
+		m.windowStart = received
+	}
+
+	m.windowCount++
+
+	elapsed := received.Sub(m.windowStart)
+
+	if elapsed < time.Second {
+		return
+	}
+
+	m.rate = float64(m.windowCount) / elapsed.Seconds()
+	m.windowStart = received
+	m.windowCount = 0
+
+	if m.floor <= 0 {
+		return
+	}
+
+	if m.rate < m.floor && !m.degraded {
+		m.degraded = true
+		log.Printf("warning: report rate dropped to %.0f Hz (below %.0f Hz)", m.rate, m.floor)
+	} else if m.rate >= m.floor && m.degraded {
+		m.degraded = false
+		log.Printf("report rate recovered to %.0f Hz", m.rate)
+	}
+}
+
+// isDegraded returns whether the report rate is currently below the floor.
+func (m *rateMonitor) isDegraded() bool {
+	m.mu.Lock()
+	defer m.mu.Unlock()
+
+	return m.degraded
+}
+
+func (m *rateMonitor) print() {
+	m.mu.Lock()
+	defer m.mu.Unlock()
+
+	log.Printf("report rate: %.0f Hz, suspected drops: %d", m.rate, m.drops)
+
+	if m.gap > 0 {
+		log.Printf("gaps longer than %v: %v", m.gap, &m.gaps)
+	}
+}
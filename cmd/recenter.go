@@ -0,0 +1,200 @@
+package main
+
+import (
+	"log"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Maximum distance from the center at which the sticks are considered to rest
+// when recentering; beyond it, they are assumed to be touched.
+const maxRecenterOffset = 8000
+
+// Duration for which the positions of the sticks are averaged by -auto-center.
+const autoCenterDuration = 500 * time.Millisecond
+
+// stickRecenter recenters the sticks at runtime: when its combination is held
+// for a while, the current positions of the sticks become their centers. With
+// -auto-center, the average positions of the sticks during the first
+// autoCenterDuration after each connection become their centers as well.
+//
+// Centers are kept for each controller, by serial number, until exit.
+type stickRecenter struct {
+	controller *stadiacontroller.StadiaController
+	combo      stadiacontroller.StadiaButtons
+	hold       time.Duration
+	auto       bool
+
+	pressedAt time.Time
+	fired     bool
+	serial    string
+	offsets   map[string][4]int16
+
+	// Positions of the sticks summed while centering them automatically.
+	sampling    bool
+	samplingEnd time.Time
+	sums        [4]int32
+	samples     int32
+}
+
+// newStickRecenter returns a stickRecenter triggered by holding combo for the
+// given duration and, if auto is true, by each connection, or nil if combo is
+// empty and auto is false.
+func newStickRecenter(controller *stadiacontroller.StadiaController, combo stadiacontroller.StadiaButtons, hold time.Duration, auto bool) *stickRecenter {
+	if combo == 0 && !auto {
+		return nil
+	}
+
+	return &stickRecenter{
+		controller: controller,
+		combo:      combo,
+		hold:       hold,
+		auto:       auto,
+		offsets:    map[string][4]int16{},
+	}
+}
+
+// setController selects the centers of the controller with the given serial
+// number, which was just connected. With -auto-center, its centers are
+// measured again, since they change over time.
+func (r *stickRecenter) setController(serial string) {
+	r.serial = serial
+	r.pressedAt = time.Time{}
+
+	if r.auto {
+		r.sampling, r.samplingEnd = true, time.Time{}
+		r.sums, r.samples = [4]int32{}, 0
+	}
+}
+
+// update recenters the sticks if the combination was held long enough given
+// the report received at the given time, which must not be recentered yet.
+// It returns when the combination will have been held long enough, or the
+// zero time.
+func (r *stickRecenter) update(report *stadiacontroller.Xbox360ControllerReport, now time.Time) time.Time {
+	if r.sampling {
+		return r.sample(report, now)
+	}
+	if r.combo == 0 || !report.Buttons.Has(r.combo) {
+		r.pressedAt = time.Time{}
+		r.fired = false
+
+		return time.Time{}
+	}
+	if r.fired {
+		return time.Time{}
+	}
+	if r.pressedAt.IsZero() {
+		r.pressedAt = now
+	}
+	if due := r.pressedAt.Add(r.hold); now.Before(due) {
+		return due
+	}
+
+	r.fired = true
+
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+	measured := [4]int16{lx, ly, rx, ry}
+
+	for _, value := range measured {
+		if abs16(value) > maxRecenterOffset {
+			log.Printf("warning: not recentering the sticks, which are deflected (left %d, %d, right %d, %d)", lx, ly, rx, ry)
+			rumblePulses(r.controller, 3, 60*time.Millisecond)
+
+			return time.Time{}
+		}
+	}
+
+	r.offsets[r.serial] = measured
+
+	log.Printf("recentered the sticks (left %d, %d, right %d, %d)", lx, ly, rx, ry)
+	rumblePulses(r.controller, 1, 40*time.Millisecond)
+
+	return time.Time{}
+}
+
+// sample adds the positions of the sticks in the given report to the average
+// measured by -auto-center, and returns when the measure ends. Deflected
+// sticks cancel the measure, since they are most likely being moved.
+func (r *stickRecenter) sample(report *stadiacontroller.Xbox360ControllerReport, now time.Time) time.Time {
+	if r.samplingEnd.IsZero() {
+		r.samplingEnd = now.Add(autoCenterDuration)
+	}
+
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	for i, value := range [4]int16{lx, ly, rx, ry} {
+		if abs16(value) > maxRecenterOffset {
+			r.sampling = false
+			log.Printf("warning: not centering the sticks automatically, which are deflected (left %d, %d, right %d, %d)", lx, ly, rx, ry)
+
+			return time.Time{}
+		}
+
+		r.sums[i] += int32(value)
+	}
+
+	r.samples++
+
+	if now.Before(r.samplingEnd) {
+		return r.samplingEnd
+	}
+
+	r.sampling = false
+
+	var centers [4]int16
+
+	for i, sum := range r.sums {
+		centers[i] = int16(sum / r.samples)
+	}
+
+	r.offsets[r.serial] = centers
+
+	log.Printf("centered the sticks automatically (left %d, %d, right %d, %d)", centers[0], centers[1], centers[2], centers[3])
+
+	return time.Time{}
+}
+
+// apply recenters the sticks of the given report.
+func (r *stickRecenter) apply(report *stadiacontroller.Xbox360ControllerReport) {
+	offsets, ok := r.offsets[r.serial]
+
+	if !ok {
+		return
+	}
+
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	report.SetLeftThumb(recenterAxis(lx, offsets[0]), recenterAxis(ly, offsets[1]))
+	report.SetRightThumb(recenterAxis(rx, offsets[2]), recenterAxis(ry, offsets[3]))
+}
+
+// recenterAxis moves the given center of an axis to 0, scaling both sides so
+// that they still reach the ends of the axis.
+func recenterAxis(value, center int16) int16 {
+	v, c := int32(value), int32(center)
+
+	if v >= c {
+		return int16((v - c) * 32767 / (32767 - c))
+	}
+
+	return int16((v - c) * 32768 / (32768 + c))
+}
+
+// step returns the step recentering the sticks, or nil if r is nil.
+func (r *stickRecenter) step() *reportStep {
+	if r == nil {
+		return nil
+	}
+
+	return timedStep(func(report *stadiacontroller.Xbox360ControllerReport, now time.Time) time.Time {
+		wake := r.update(report, now)
+		r.apply(report)
+
+		return wake
+	}, nil)
+}
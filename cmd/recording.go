@@ -0,0 +1,453 @@
+package main
+
+import (
+	"bufio"
+	"encoding/binary"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"io"
+	"io/ioutil"
+	"log"
+	"os"
+	"sync"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Recordings written by -record start with recordingMagic and
+// recordingVersion, followed by records starting with their kind:
+//
+//   - recordDevice: the length of the following data as an uint16, then the
+//     information of the device opened, in JSON;
+//   - recordReport: the time elapsed since the previous record in
+//     microseconds as an uint32, the length of the report as an uint16, then
+//     the report as read from the device.
+//
+// Integers are little-endian.
+const (
+	recordingMagic   = "STADIARC"
+	recordingVersion = 1
+
+	recordDevice = 'D'
+	recordReport = 'R'
+)
+
+// Interval at which recordings are flushed, so that a crash only loses the
+// last reports.
+const recordingFlushInterval = 1 * time.Second
+
+// Recorder of -record and recording replayed by -replay, if any.
+var (
+	recorder *reportRecorder
+	replay   *replayBackend
+)
+
+// openRecording creates the recording of -record or loads the one of -replay,
+// if any.
+func openRecording() error {
+	if *recordPath != "" && *replayPath != "" {
+		return errors.New("-record cannot be used with -replay")
+	}
+
+	var err error
+
+	if *recordPath != "" {
+		if recorder, err = newReportRecorder(*recordPath); err != nil {
+			return err
+		}
+	}
+	if *replayPath != "" {
+		if replay, err = loadRecording(*replayPath, *replaySpeed, *replayLoop); err != nil {
+			return err
+		}
+	}
+
+	return nil
+}
+
+// closeWhenReplayed closes the given controller once the recording of -replay
+// was replayed, if any.
+func closeWhenReplayed(controller *stadiacontroller.StadiaController) {
+	if replay == nil {
+		return
+	}
+
+	go func() {
+		<-replay.ended
+		log.Printf("replay finished")
+		controller.Close()
+	}()
+}
+
+// reportRecorder writes the devices opened and the reports read from them to
+// a recording.
+type reportRecorder struct {
+	mu   sync.Mutex
+	file *os.File
+	w    *bufio.Writer
+	last time.Time
+	err  error
+	done chan struct{}
+}
+
+// newReportRecorder creates a recording at the given path, replacing any
+// existing file.
+func newReportRecorder(path string) (*reportRecorder, error) {
+	file, err := os.Create(path)
+
+	if err != nil {
+		return nil, err
+	}
+
+	r := &reportRecorder{file: file, w: bufio.NewWriter(file), last: time.Now(), done: make(chan struct{})}
+
+	r.w.WriteString(recordingMagic)
+	binary.Write(r.w, binary.LittleEndian, uint16(recordingVersion))
+
+	if err := r.w.Flush(); err != nil {
+		file.Close()
+		return nil, err
+	}
+
+	go func() {
+		ticker := time.NewTicker(recordingFlushInterval)
+		defer ticker.Stop()
+
+		for {
+			select {
+			case <-ticker.C:
+				r.mu.Lock()
+				r.fail(r.w.Flush())
+				r.mu.Unlock()
+			case <-r.done:
+				return
+			}
+		}
+	}()
+
+	return r, nil
+}
+
+// fail logs the first error of the recording. Records are dropped afterwards.
+func (r *reportRecorder) fail(err error) {
+	if err != nil && r.err == nil {
+		r.err = err
+		log.Printf("warning: cannot write recording, recording stopped: %v", err)
+	}
+}
+
+// device records that the given device was opened.
+func (r *reportRecorder) device(info *stadiacontroller.DeviceInfo) {
+	data, err := json.Marshal(info)
+
+	r.mu.Lock()
+	defer r.mu.Unlock()
+
+	if r.err != nil {
+		return
+	}
+
+	r.fail(err)
+	r.w.WriteByte(recordDevice)
+	binary.Write(r.w, binary.LittleEndian, uint16(len(data)))
+	_, err = r.w.Write(data)
+	r.fail(err)
+}
+
+// report records the given report, read now.
+func (r *reportRecorder) report(data []byte) {
+	now := time.Now()
+
+	r.mu.Lock()
+	defer r.mu.Unlock()
+
+	if r.err != nil {
+		return
+	}
+
+	var header [7]byte
+
+	header[0] = recordReport
+	binary.LittleEndian.PutUint32(header[1:], uint32(now.Sub(r.last)/time.Microsecond))
+	binary.LittleEndian.PutUint16(header[5:], uint16(len(data)))
+	r.last = now
+
+	r.w.Write(header[:])
+	_, err := r.w.Write(data)
+	r.fail(err)
+}
+
+// Close flushes and closes the recording.
+func (r *reportRecorder) Close() error {
+	close(r.done)
+
+	r.mu.Lock()
+	defer r.mu.Unlock()
+
+	if err := r.w.Flush(); err != nil {
+		r.file.Close()
+		return err
+	}
+
+	return r.file.Close()
+}
+
+// recordingBackend records the devices opened by another backend, and the
+// reports read from them.
+type recordingBackend struct {
+	stadiacontroller.Backend
+	recorder *reportRecorder
+}
+
+func (b recordingBackend) Open(info *stadiacontroller.DeviceInfo) (stadiacontroller.Device, error) {
+	device, err := b.Backend.Open(info)
+
+	if err != nil {
+		return nil, err
+	}
+
+	b.recorder.device(info)
+
+	d := &recordingDevice{Device: device, readCh: make(chan []byte), closed: make(chan struct{})}
+
+	go d.forward(b.recorder)
+
+	return d, nil
+}
+
+// recordingDevice records the reports read from a device before passing them
+// on.
+type recordingDevice struct {
+	stadiacontroller.Device
+
+	readCh    chan []byte
+	closed    chan struct{}
+	closeOnce sync.Once
+}
+
+// forward records the reports of the device and sends them to readCh until
+// the device is closed. Reports are copied to buffers of their own, since the
+// buffers of the device are reused once the next report is received.
+func (d *recordingDevice) forward(recorder *reportRecorder) {
+	defer close(d.readCh)
+
+	var bufs [2][]byte
+
+	for i := 0; ; i ^= 1 {
+		data, ok := <-d.Device.ReadCh()
+
+		if !ok {
+			return
+		}
+
+		bufs[i] = append(bufs[i][:0], data...)
+		recorder.report(bufs[i])
+
+		select {
+		case d.readCh <- bufs[i]:
+		case <-d.closed:
+			return
+		}
+	}
+}
+
+func (d *recordingDevice) ReadCh() <-chan []byte {
+	return d.readCh
+}
+
+func (d *recordingDevice) Close() {
+	d.closeOnce.Do(func() { close(d.closed) })
+	d.Device.Close()
+}
+
+// A replayedReport is a report of a recording, read after delay.
+type replayedReport struct {
+	delay time.Duration
+	data  []byte
+}
+
+// replayBackend replays a recording as if the first device it opened was
+// connected, at the given speed, and once or in a loop. Devices opened later
+// in the recording are replayed as the first one.
+type replayBackend struct {
+	info    *stadiacontroller.DeviceInfo
+	reports []replayedReport
+	speed   float64
+	loop    bool
+
+	// Closed once the recording was replayed, unless looping.
+	ended   chan struct{}
+	endOnce sync.Once
+
+	mu       sync.Mutex
+	finished bool
+}
+
+// loadRecording reads the recording at the given path.
+func loadRecording(path string, speed float64, loop bool) (*replayBackend, error) {
+	if speed <= 0 {
+		return nil, errors.New("replay speed must be positive")
+	}
+
+	data, err := ioutil.ReadFile(path)
+
+	if err != nil {
+		return nil, err
+	}
+
+	if len(data) < len(recordingMagic)+2 || string(data[:len(recordingMagic)]) != recordingMagic {
+		return nil, fmt.Errorf("%s is not a recording", path)
+	}
+	if version := binary.LittleEndian.Uint16(data[len(recordingMagic):]); version != recordingVersion {
+		return nil, fmt.Errorf("%s is a recording of unsupported version %d", path, version)
+	}
+
+	b := &replayBackend{speed: speed, loop: loop, ended: make(chan struct{})}
+	data = data[len(recordingMagic)+2:]
+
+	var delay time.Duration
+
+	for len(data) > 0 {
+		switch data[0] {
+		case recordDevice:
+			if len(data) < 3 || len(data) < 3+int(binary.LittleEndian.Uint16(data[1:])) {
+				return nil, io.ErrUnexpectedEOF
+			}
+
+			n := int(binary.LittleEndian.Uint16(data[1:]))
+
+			if b.info == nil {
+				b.info = &stadiacontroller.DeviceInfo{}
+
+				if err := json.Unmarshal(data[3:3+n], b.info); err != nil {
+					return nil, fmt.Errorf("invalid device in recording: %w", err)
+				}
+			}
+
+			data = data[3+n:]
+
+		case recordReport:
+			if len(data) < 7 || len(data) < 7+int(binary.LittleEndian.Uint16(data[5:])) {
+				// The recording was interrupted while writing this report.
+				data = nil
+				break
+			}
+
+			n := int(binary.LittleEndian.Uint16(data[5:]))
+			delay += time.Duration(binary.LittleEndian.Uint32(data[1:])) * time.Microsecond
+
+			b.reports = append(b.reports, replayedReport{delay, data[7 : 7+n]})
+			delay = 0
+			data = data[7+n:]
+
+		default:
+			return nil, fmt.Errorf("invalid record of kind %d in recording", data[0])
+		}
+	}
+
+	if b.info == nil || len(b.reports) == 0 {
+		return nil, fmt.Errorf("%s contains no report", path)
+	}
+
+	// Do not wait before the first report.
+	b.reports[0].delay = 0
+
+	return b, nil
+}
+
+func (b *replayBackend) Devices() ([]*stadiacontroller.DeviceInfo, error) {
+	b.mu.Lock()
+	defer b.mu.Unlock()
+
+	if b.finished {
+		return nil, nil
+	}
+
+	return []*stadiacontroller.DeviceInfo{b.info}, nil
+}
+
+func (b *replayBackend) ByPath(path string) (*stadiacontroller.DeviceInfo, error) {
+	devices, _ := b.Devices()
+
+	if len(devices) == 0 || devices[0].Path != path {
+		return nil, errors.New("device not found")
+	}
+
+	return devices[0], nil
+}
+
+func (b *replayBackend) Open(info *stadiacontroller.DeviceInfo) (stadiacontroller.Device, error) {
+	d := &replayDevice{readCh: make(chan []byte), closed: make(chan struct{})}
+
+	go d.replay(b)
+
+	return d, nil
+}
+
+// replayDevice sends the reports of a recording with their original timing,
+// scaled by the replay speed. Writes are ignored.
+type replayDevice struct {
+	readCh    chan []byte
+	readErr   error
+	closed    chan struct{}
+	closeOnce sync.Once
+}
+
+func (d *replayDevice) replay(b *replayBackend) {
+	defer close(d.readCh)
+
+	for {
+		start := time.Now()
+		var elapsed time.Duration
+
+		for _, report := range b.reports {
+			// Wait relative to the start, so that delays do not accumulate.
+			elapsed += report.delay
+
+			select {
+			case <-time.After(time.Until(start.Add(time.Duration(float64(elapsed) / b.speed)))):
+			case <-d.closed:
+				return
+			}
+
+			select {
+			case d.readCh <- report.data:
+			case <-d.closed:
+				return
+			}
+		}
+
+		if !b.loop {
+			break
+		}
+	}
+
+	b.mu.Lock()
+	b.finished = true
+	b.mu.Unlock()
+
+	d.readErr = io.EOF
+	b.endOnce.Do(func() { close(b.ended) })
+}
+
+func (d *replayDevice) ReadCh() <-chan []byte {
+	return d.readCh
+}
+
+func (d *replayDevice) ReadError() error {
+	return d.readErr
+}
+
+func (d *replayDevice) Write([]byte) error {
+	return nil
+}
+
+func (d *replayDevice) Flush() error {
+	return nil
+}
+
+func (d *replayDevice) Close() {
+	d.closeOnce.Do(func() { close(d.closed) })
+}
@@ -0,0 +1,197 @@
+package main
+
+import (
+	"flag"
+	"fmt"
+	"strings"
+
+	"github.com/71/stadiacontroller"
+)
+
+// remapping changes the inputs of the controller before they are forwarded:
+// buttons are replaced by other buttons, and sticks and triggers may be
+// swapped.
+type remapping struct {
+	buttons      map[stadiacontroller.StadiaButtons]stadiacontroller.StadiaButtons
+	swapSticks   bool
+	swapTriggers bool
+}
+
+// leftHandedRemapping returns the southpaw preset: the sticks (along with
+// their buttons), the D-pad and face buttons, and the bumpers and triggers
+// are swapped.
+func leftHandedRemapping() *remapping {
+	r := &remapping{
+		buttons:      map[stadiacontroller.StadiaButtons]stadiacontroller.StadiaButtons{},
+		swapSticks:   true,
+		swapTriggers: true,
+	}
+
+	for from, to := range map[stadiacontroller.StadiaButtons]stadiacontroller.StadiaButtons{
+		stadiacontroller.StadiaButtonDpadUp:    stadiacontroller.StadiaButtonY,
+		stadiacontroller.StadiaButtonDpadDown:  stadiacontroller.StadiaButtonA,
+		stadiacontroller.StadiaButtonDpadLeft:  stadiacontroller.StadiaButtonX,
+		stadiacontroller.StadiaButtonDpadRight: stadiacontroller.StadiaButtonB,
+		stadiacontroller.StadiaButtonL1:        stadiacontroller.StadiaButtonR1,
+		stadiacontroller.StadiaButtonL3:        stadiacontroller.StadiaButtonR3,
+	} {
+		r.buttons[from] = to
+		r.buttons[to] = from
+	}
+
+	return r
+}
+
+// swapFaceButtons swaps A with B and X with Y, as on Nintendo layouts, after
+// the buttons which are already remapped: the button at the bottom of the
+// face buttons then always presses B.
+func (r *remapping) swapFaceButtons() {
+	swapped := map[stadiacontroller.StadiaButtons]stadiacontroller.StadiaButtons{
+		stadiacontroller.StadiaButtonA: stadiacontroller.StadiaButtonB,
+		stadiacontroller.StadiaButtonB: stadiacontroller.StadiaButtonA,
+		stadiacontroller.StadiaButtonX: stadiacontroller.StadiaButtonY,
+		stadiacontroller.StadiaButtonY: stadiacontroller.StadiaButtonX,
+	}
+	buttons := map[stadiacontroller.StadiaButtons]stadiacontroller.StadiaButtons{}
+
+	for from, to := range r.buttons {
+		buttons[from] = to
+	}
+	for from := range swapped {
+		if _, ok := buttons[from]; !ok {
+			buttons[from] = from
+		}
+	}
+
+	for from, to := range buttons {
+		if s, ok := swapped[to]; ok {
+			to = s
+		}
+
+		if from == to {
+			delete(buttons, from)
+		} else {
+			buttons[from] = to
+		}
+	}
+
+	r.buttons = buttons
+}
+
+// set adds or replaces the buttons given by a comma-separated list of
+// button=button entries, e.g. "a=b,b=a".
+func (r *remapping) set(entries string) error {
+	for _, entry := range strings.Split(entries, ",") {
+		if entry = strings.TrimSpace(entry); entry == "" {
+			continue
+		}
+
+		i := strings.IndexByte(entry, '=')
+
+		if i == -1 {
+			return fmt.Errorf("invalid remapped button %q, expected button=button", entry)
+		}
+
+		from, err := stadiacontroller.ParseStadiaButtons(entry[:i])
+
+		if err != nil {
+			return err
+		}
+
+		to, err := stadiacontroller.ParseStadiaButtons(entry[i+1:])
+
+		if err != nil {
+			return err
+		}
+
+		if from == to {
+			delete(r.buttons, from)
+		} else {
+			r.buttons[from] = to
+		}
+	}
+
+	return nil
+}
+
+// apply remaps the inputs of the given report.
+func (r *remapping) apply(report *stadiacontroller.Xbox360ControllerReport) {
+	original := report.Buttons
+	var sources, targets stadiacontroller.StadiaButtons
+
+	for from, to := range r.buttons {
+		if original.Has(from) {
+			sources |= from
+			targets |= to
+		}
+	}
+
+	buttons := original&^sources | targets
+
+	report.Buttons = buttons
+	report.Capture = buttons.Has(stadiacontroller.StadiaButtonCapture)
+	report.Assistant = buttons.Has(stadiacontroller.StadiaButtonAssistant)
+	report.SetButtons(report.GetButtons()&^original.Xbox360Buttons() | buttons.Xbox360Buttons())
+
+	if r.swapSticks {
+		lx, ly := report.GetLeftThumb()
+		rx, ry := report.GetRightThumb()
+
+		report.SetLeftThumb(rx, ry)
+		report.SetRightThumb(lx, ly)
+	}
+	if r.swapTriggers {
+		left, right := report.GetLeftTrigger(), report.GetRightTrigger()
+
+		report.SetLeftTrigger(right)
+		report.SetRightTrigger(left)
+	}
+}
+
+// newRemapping returns the remapping configured by the command line, or nil if
+// inputs are not remapped. Explicit flags override the -left-handed preset,
+// and -remap overrides -swap-ab-xy.
+func newRemapping() (*remapping, error) {
+	r := &remapping{buttons: map[stadiacontroller.StadiaButtons]stadiacontroller.StadiaButtons{}}
+
+	if *leftHanded {
+		r = leftHandedRemapping()
+	}
+	if *swapABXY {
+		r.swapFaceButtons()
+	}
+
+	if err := r.set(*remapButtons); err != nil {
+		return nil, err
+	}
+
+	if isFlagSet("swap-sticks") {
+		r.swapSticks = *swapSticks
+	}
+	if isFlagSet("swap-triggers") {
+		r.swapTriggers = *swapTriggers
+	}
+
+	if len(r.buttons) == 0 && !r.swapSticks && !r.swapTriggers {
+		return nil, nil
+	}
+
+	return r, nil
+}
+
+// isFlagSet returns whether the given flag was given on the command line.
+func isFlagSet(name string) bool {
+	if resetFlags[name] {
+		return false
+	}
+
+	set := false
+
+	flag.Visit(func(f *flag.Flag) {
+		if f.Name == name {
+			set = true
+		}
+	})
+
+	return set
+}
@@ -0,0 +1,84 @@
+package main
+
+import (
+	"fmt"
+	"strings"
+	"testing"
+
+	"github.com/71/stadiacontroller"
+)
+
+// mappingTable returns the effective mapping of a remapping, as the
+// from=to entries of its buttons in the order of their bits followed by the
+// swapped sticks and triggers.
+func mappingTable(r *remapping) string {
+	var entries []string
+
+	for from := stadiacontroller.StadiaButtons(1); from <= stadiacontroller.StadiaButtonVolumeDown; from <<= 1 {
+		if to, ok := r.buttons[from]; ok {
+			entries = append(entries, fmt.Sprintf("%v=%v", from, to))
+		}
+	}
+
+	return fmt.Sprintf("%s sticks:%v triggers:%v", strings.Join(entries, ","), r.swapSticks, r.swapTriggers)
+}
+
+func TestLeftHandedRemapping(t *testing.T) {
+	r := leftHandedRemapping()
+
+	expected := "a=dpad_down,b=dpad_right,x=dpad_left,y=dpad_up,l1=r1,r1=l1,l3=r3,r3=l3," +
+		"dpad_up=y,dpad_down=a,dpad_left=x,dpad_right=b sticks:true triggers:true"
+
+	if table := mappingTable(r); table != expected {
+		t.Errorf("left-handed mapping is\n%s\nexpected\n%s", table, expected)
+	}
+
+	// The preset is applied first, and explicit settings replace its entries.
+	r.swapFaceButtons()
+
+	expected = "a=dpad_down,b=dpad_right,x=dpad_left,y=dpad_up,l1=r1,r1=l1,l3=r3,r3=l3," +
+		"dpad_up=x,dpad_down=b,dpad_left=y,dpad_right=a sticks:true triggers:true"
+
+	if table := mappingTable(r); table != expected {
+		t.Errorf("left-handed mapping with swapped face buttons is\n%s\nexpected\n%s", table, expected)
+	}
+
+	if err := r.set("dpad_up=dpad_up, l1=capture"); err != nil {
+		t.Fatal(err)
+	}
+	r.swapTriggers = false
+
+	expected = "a=dpad_down,b=dpad_right,x=dpad_left,y=dpad_up,l1=capture,r1=l1,l3=r3,r3=l3," +
+		"dpad_down=b,dpad_left=y,dpad_right=a sticks:true triggers:false"
+
+	if table := mappingTable(r); table != expected {
+		t.Errorf("left-handed mapping with overrides is\n%s\nexpected\n%s", table, expected)
+	}
+}
+
+func TestLeftHandedRemappingApply(t *testing.T) {
+	var report stadiacontroller.Xbox360ControllerReport
+	held := stadiacontroller.StadiaButtonDpadUp | stadiacontroller.StadiaButtonL1 | stadiacontroller.StadiaButtonMenu
+	report.Buttons = held
+	report.SetButtons(held.Xbox360Buttons())
+	report.SetLeftThumb(100, -200)
+	report.SetRightThumb(-300, 400)
+	report.SetLeftTrigger(255)
+
+	leftHandedRemapping().apply(&report)
+
+	remapped := stadiacontroller.StadiaButtonY | stadiacontroller.StadiaButtonR1 | stadiacontroller.StadiaButtonMenu
+
+	if report.Buttons != remapped || report.GetButtons() != remapped.Xbox360Buttons() {
+		t.Errorf("%v was remapped to %v (%#06x), expected %v", held, report.Buttons, report.GetButtons(), remapped)
+	}
+	if lx, ly := report.GetLeftThumb(); lx != -300 || ly != 400 {
+		t.Errorf("left stick is (%d, %d), expected the right one", lx, ly)
+	}
+	if rx, ry := report.GetRightThumb(); rx != 100 || ry != -200 {
+		t.Errorf("right stick is (%d, %d), expected the left one", rx, ry)
+	}
+	if report.GetLeftTrigger() != 0 || report.GetRightTrigger() != 255 {
+		t.Errorf("triggers are %d and %d, expected 0 and 255", report.GetLeftTrigger(), report.GetRightTrigger())
+	}
+}
@@ -0,0 +1,74 @@
+package main
+
+import (
+	"encoding/json"
+	"fmt"
+	"io/ioutil"
+	"os"
+	"strings"
+
+	"github.com/71/stadiacontroller"
+)
+
+// loadReportMap reads a report map from the JSON file at the given path.
+// Fields missing from the file keep the values of the default map.
+func loadReportMap(path string) (stadiacontroller.ReportMap, error) {
+	m := stadiacontroller.DefaultReportMap()
+	data, err := ioutil.ReadFile(path)
+
+	if err != nil {
+		return m, err
+	}
+	if err := json.Unmarshal(data, &m); err != nil {
+		return m, fmt.Errorf("cannot parse report map %s: %w", path, err)
+	}
+	if err := m.Validate(); err != nil {
+		return m, fmt.Errorf("invalid report map %s: %w", path, err)
+	}
+
+	return m, nil
+}
+
+// loadReportMaps reads the report maps at the given comma-separated paths.
+func loadReportMaps(paths string) ([]stadiacontroller.ReportMap, error) {
+	var maps []stadiacontroller.ReportMap
+
+	for _, path := range strings.Split(paths, ",") {
+		if path = strings.TrimSpace(path); path == "" {
+			continue
+		}
+
+		m, err := loadReportMap(path)
+
+		if err != nil {
+			return nil, err
+		}
+
+		maps = append(maps, m)
+	}
+
+	return maps, nil
+}
+
+// runReportMap prints the default report map, or the report map in the given
+// file once validated, as a starting point for new hardware.
+func runReportMap(args []string) error {
+	m := stadiacontroller.DefaultReportMap()
+
+	switch len(args) {
+	case 0:
+	case 1:
+		var err error
+
+		if m, err = loadReportMap(args[0]); err != nil {
+			return err
+		}
+	default:
+		return fmt.Errorf("usage: report-map [file]")
+	}
+
+	encoder := json.NewEncoder(os.Stdout)
+	encoder.SetIndent("", "  ")
+
+	return encoder.Encode(m)
+}
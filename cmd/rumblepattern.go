@@ -0,0 +1,179 @@
+package main
+
+import (
+	"fmt"
+	"strconv"
+	"strings"
+	"sync"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Longest segment of a vibration pattern.
+const maxRumbleSegment = 10 * time.Second
+
+// rumbleSegment is a step of a vibration pattern, during which the motors
+// vibrate at the given intensities.
+type rumbleSegment struct {
+	large, small byte
+	duration     time.Duration
+}
+
+// parseRumblePattern parses a vibration pattern given as comma-separated
+// intensity@milliseconds segments, e.g. "200@100,0@100,200@100". The
+// intensity (0-255) drives both motors, or is given as large/small to drive
+// them separately, e.g. "255/0@200". An empty pattern is nil.
+func parseRumblePattern(s string) ([]rumbleSegment, error) {
+	var pattern []rumbleSegment
+
+	if s == "" {
+		return nil, nil
+	}
+
+	for _, part := range strings.Split(s, ",") {
+		part = strings.TrimSpace(part)
+		i := strings.IndexByte(part, '@')
+
+		if i == -1 {
+			return nil, fmt.Errorf("invalid vibration segment %q: expected intensity@ms", part)
+		}
+
+		intensity, ms := part[:i], part[i+1:]
+		large, small := intensity, intensity
+
+		if j := strings.IndexByte(intensity, '/'); j != -1 {
+			large, small = intensity[:j], intensity[j+1:]
+		}
+
+		l, err := strconv.ParseUint(large, 10, 8)
+
+		if err != nil {
+			return nil, fmt.Errorf("invalid intensity %q in vibration segment %q", large, part)
+		}
+
+		r, err := strconv.ParseUint(small, 10, 8)
+
+		if err != nil {
+			return nil, fmt.Errorf("invalid intensity %q in vibration segment %q", small, part)
+		}
+
+		duration, err := strconv.Atoi(ms)
+
+		if err != nil || duration <= 0 || time.Duration(duration)*time.Millisecond > maxRumbleSegment {
+			return nil, fmt.Errorf("invalid duration %q in vibration segment %q: expected 1 to %d milliseconds", ms, part, maxRumbleSegment.Milliseconds())
+		}
+
+		pattern = append(pattern, rumbleSegment{byte(l), byte(r), time.Duration(duration) * time.Millisecond})
+	}
+
+	return pattern, nil
+}
+
+// rumblePlayer plays vibration patterns on the controller. The vibrations
+// requested by the game are held back while a pattern plays, and the last one
+// is restored once it ends.
+type rumblePlayer struct {
+	controller *stadiacontroller.StadiaController
+
+	mu sync.Mutex
+
+	// Closed to stop the pattern playing, or nil if none is.
+	stop chan struct{}
+
+	// Last vibration requested by the game.
+	large, small byte
+}
+
+func newRumblePlayer(controller *stadiacontroller.StadiaController) *rumblePlayer {
+	return &rumblePlayer{controller: controller}
+}
+
+// newFeedbackRumbles returns the player of the patterns given by the command
+// line, and the pattern played when the controller connects. The patterns of
+// -on-command-success-rumble and -on-command-failure-rumble are played once
+// commands finish.
+func newFeedbackRumbles(controller *stadiacontroller.StadiaController) (*rumblePlayer, []rumbleSegment, error) {
+	patterns := newRumblePlayer(controller)
+	var connectPattern, successPattern, failurePattern []rumbleSegment
+
+	for _, f := range []struct {
+		name    string
+		value   string
+		pattern *[]rumbleSegment
+	}{
+		{"on-connect-rumble", *onConnectRumble, &connectPattern},
+		{"on-command-success-rumble", *onCommandSuccessRumble, &successPattern},
+		{"on-command-failure-rumble", *onCommandFailureRumble, &failurePattern},
+	} {
+		var err error
+
+		if *f.pattern, err = parseRumblePattern(f.value); err != nil {
+			return nil, nil, fmt.Errorf("invalid -%s: %w", f.name, err)
+		}
+	}
+
+	if successPattern != nil || failurePattern != nil {
+		commandFinished = func(err error) {
+			if err == nil {
+				patterns.play(successPattern)
+			} else {
+				patterns.play(failurePattern)
+			}
+		}
+	}
+
+	return patterns, connectPattern, nil
+}
+
+// vibrate makes the controller vibrate as requested by the game, unless a
+// pattern plays.
+func (p *rumblePlayer) vibrate(large, small byte) {
+	p.mu.Lock()
+	defer p.mu.Unlock()
+
+	p.large, p.small = large, small
+
+	if p.stop == nil {
+		p.controller.VibrateAsync(large, small)
+	}
+}
+
+// play plays the given pattern in the background, replacing the pattern
+// playing if any.
+func (p *rumblePlayer) play(pattern []rumbleSegment) {
+	if len(pattern) == 0 {
+		return
+	}
+
+	stop := make(chan struct{})
+
+	p.mu.Lock()
+
+	if p.stop != nil {
+		close(p.stop)
+	}
+
+	p.stop = stop
+	p.mu.Unlock()
+
+	go func() {
+		for _, segment := range pattern {
+			p.controller.VibrateAsync(segment.large, segment.small)
+
+			select {
+			case <-time.After(segment.duration):
+			case <-stop:
+				return
+			}
+		}
+
+		p.mu.Lock()
+		defer p.mu.Unlock()
+
+		if p.stop == stop {
+			p.stop = nil
+			p.controller.VibrateAsync(p.large, p.small)
+		}
+	}()
+}
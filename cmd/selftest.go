@@ -0,0 +1,200 @@
+package main
+
+import (
+	"errors"
+	"fmt"
+	"os"
+	"text/tabwriter"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Resting values of the sticks and triggers above which they are considered
+// to drift.
+const (
+	stickDriftThreshold   = 3000
+	triggerDriftThreshold = 10
+)
+
+// Durations of the steps of the self-test, and number of reports per second
+// below which the report stream is considered unsteady.
+const (
+	selfTestConnectTimeout = 10 * time.Second
+	selfTestReportDuration = 1 * time.Second
+	selfTestRumbleDuration = 200 * time.Millisecond
+	selfTestMinReportRate  = 50
+)
+
+// Outcomes of a self-test check.
+const (
+	selfTestPass = "PASS"
+	selfTestWarn = "WARN"
+	selfTestFail = "FAIL"
+)
+
+type selfTestResult struct {
+	check   string
+	outcome string
+	detail  string
+}
+
+// runSelfTest checks that the controller sends reports steadily, that its
+// sticks and triggers rest near their centers, and that both motors accept
+// vibrations, then prints a summary. It returns an error if a check failed;
+// warnings do not prevent using the controller.
+func runSelfTest() error {
+	controller, err := newController()
+
+	if err != nil {
+		return err
+	}
+
+	defer controller.Close()
+
+	results := selfTest(controller)
+
+	w := tabwriter.NewWriter(os.Stdout, 0, 0, 2, ' ', 0)
+
+	for _, result := range results {
+		fmt.Fprintf(w, "%s\t%s\t%s\n", result.outcome, result.check, result.detail)
+	}
+
+	w.Flush()
+
+	for _, result := range results {
+		if result.outcome == selfTestFail {
+			return errors.New("self-test failed")
+		}
+	}
+
+	return nil
+}
+
+func selfTest(controller *stadiacontroller.StadiaController) []selfTestResult {
+	events := controller.Events()
+	timeout := time.After(selfTestConnectTimeout)
+
+	// Wait for the controller.
+	for connected := false; !connected; {
+		select {
+		case event, ok := <-events:
+			if !ok {
+				return []selfTestResult{{"connection", selfTestFail, "the controller could not be opened"}}
+			}
+
+			switch event := event.(type) {
+			case stadiacontroller.ConnectedEvent:
+				connected = true
+			case stadiacontroller.ErrorEvent:
+				if errors.Is(event.Err, stadiacontroller.ErrAccessDenied) {
+					return []selfTestResult{{"connection", selfTestFail, "access denied: close Steam or other programs using the controller"}}
+				}
+			}
+
+		case <-timeout:
+			return []selfTestResult{{"connection", selfTestFail, "no controller found: plug it in over USB or pair it over Bluetooth"}}
+		}
+	}
+
+	results := []selfTestResult{{"connection", selfTestPass, ""}}
+
+	// Collect reports.
+	var last stadiacontroller.Xbox360ControllerReport
+	reports := 0
+	end := time.After(selfTestReportDuration)
+
+collect:
+	for {
+		select {
+		case event, ok := <-events:
+			if !ok {
+				break collect
+			}
+
+			switch event := event.(type) {
+			case stadiacontroller.ReportEvent:
+				last = event.Report
+				reports++
+			case stadiacontroller.DisconnectedEvent:
+				return append(results, selfTestResult{"reports", selfTestFail, "the controller was disconnected: check the cable or the Bluetooth connection"})
+			}
+
+		case <-end:
+			break collect
+		}
+	}
+
+	rate := float64(reports) / selfTestReportDuration.Seconds()
+
+	switch {
+	case reports == 0:
+		return append(results, selfTestResult{"reports", selfTestFail, "no report received: reconnect the controller, and check that its firmware is supported with controller-info"})
+	case rate < selfTestMinReportRate:
+		results = append(results, selfTestResult{"reports", selfTestWarn, fmt.Sprintf("%.0f reports/s, inputs may lag: move the controller closer or use USB", rate)})
+	default:
+		results = append(results, selfTestResult{"reports", selfTestPass, fmt.Sprintf("%.0f reports/s", rate)})
+	}
+
+	// Check resting values.
+	lx, ly := last.GetLeftThumb()
+	rx, ry := last.GetRightThumb()
+
+	results = append(results,
+		checkStickRest("left stick", lx, ly),
+		checkStickRest("right stick", rx, ry),
+		checkTriggerRest("left trigger", last.GetLeftTrigger()),
+		checkTriggerRest("right trigger", last.GetRightTrigger()))
+
+	// Check both motors.
+	for _, motor := range []struct {
+		name         string
+		large, small byte
+	}{
+		{"large motor", 255, 0},
+		{"small motor", 0, 255},
+	} {
+		err := controller.Vibrate(motor.large, motor.small)
+
+		if err == nil {
+			time.Sleep(selfTestRumbleDuration)
+			err = controller.Vibrate(0, 0)
+		}
+
+		if err != nil {
+			results = append(results, selfTestResult{motor.name, selfTestFail, fmt.Sprintf("vibration rejected (%v): reconnect the controller", err)})
+		} else {
+			results = append(results, selfTestResult{motor.name, selfTestPass, ""})
+		}
+	}
+
+	return results
+}
+
+func checkStickRest(name string, x, y int16) selfTestResult {
+	detail := fmt.Sprintf("resting at %d, %d", x, y)
+
+	if abs16(x) > stickDriftThreshold || abs16(y) > stickDriftThreshold {
+		return selfTestResult{name, selfTestWarn, detail + ": drifting, or moved during the test"}
+	}
+
+	return selfTestResult{name, selfTestPass, detail}
+}
+
+func checkTriggerRest(name string, value byte) selfTestResult {
+	detail := fmt.Sprintf("resting at %d", value)
+
+	if value > triggerDriftThreshold {
+		return selfTestResult{name, selfTestWarn, detail + ": drifting, or pulled during the test"}
+	}
+
+	return selfTestResult{name, selfTestPass, detail}
+}
+
+func abs16(v int16) int32 {
+	if v < 0 {
+		return -int32(v)
+	}
+
+	return int32(v)
+}
@@ -0,0 +1,411 @@
+package main
+
+import (
+	"archive/zip"
+	"bufio"
+	"bytes"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"io/ioutil"
+	"os"
+	"runtime/debug"
+	"strings"
+	"time"
+)
+
+// Settings bundles, written by export-settings and read by import-settings,
+// are zip archives of a manifest, of the configuration file given to -config
+// and of the calibration file, the last two only if they exist.
+const (
+	settingsBundleVersion = 1
+
+	settingsManifestName    = "manifest.json"
+	settingsConfigName      = "config.toml"
+	settingsCalibrationName = "calibration.json"
+)
+
+// settingsManifest describes a settings bundle. Bundles of a later version
+// are refused, since their files may mean something else.
+type settingsManifest struct {
+	Version int       `json:"version"`
+	Tool    string    `json:"tool"`
+	Created time.Time `json:"created"`
+}
+
+// A settingsFile is a file of a settings bundle.
+type settingsFile struct {
+	name string
+	data []byte
+}
+
+// toolVersion returns the version of the module the executable was built from,
+// which is "(devel)" unless built by go install or go get.
+func toolVersion() string {
+	if info, ok := debug.ReadBuildInfo(); ok {
+		return info.Main.Version
+	}
+
+	return "unknown"
+}
+
+func runExportSettings(args []string) error {
+	if len(args) != 1 {
+		return errors.New("usage: export-settings <bundle.zip>")
+	}
+
+	return exportSettings(args[0])
+}
+
+func runImportSettings(args []string) error {
+	if len(args) != 1 {
+		return errors.New("usage: import-settings <bundle.zip>")
+	}
+
+	return importSettings(args[0], bufio.NewReader(os.Stdin))
+}
+
+// exportSettings writes the configuration and calibration files to a bundle
+// at the given path.
+func exportSettings(path string) error {
+	var files []settingsFile
+
+	if config != nil {
+		data, err := ioutil.ReadFile(config.path)
+
+		if err != nil {
+			return err
+		}
+
+		files = append(files, settingsFile{settingsConfigName, data})
+	}
+
+	calibration, err := calibrationPath()
+
+	if err != nil {
+		return err
+	}
+
+	data, err := ioutil.ReadFile(calibration)
+
+	switch {
+	case err == nil:
+		files = append(files, settingsFile{settingsCalibrationName, data})
+	case !os.IsNotExist(err):
+		return err
+	case len(files) == 0:
+		return fmt.Errorf("nothing to export without -config, and %s does not exist", calibration)
+	}
+
+	manifest, err := json.MarshalIndent(settingsManifest{settingsBundleVersion, toolVersion(), time.Now().UTC()}, "", "  ")
+
+	if err != nil {
+		return err
+	}
+
+	files = append([]settingsFile{{settingsManifestName, append(manifest, '\n')}}, files...)
+
+	if err := writeSettingsBundle(path, files); err != nil {
+		return err
+	}
+
+	for _, file := range files[1:] {
+		fmt.Printf("exported %s\n", file.name)
+	}
+
+	return nil
+}
+
+// writeSettingsBundle writes the given files to a zip archive at the given
+// path, which is removed if it cannot be written completely.
+func writeSettingsBundle(path string, files []settingsFile) error {
+	f, err := os.Create(path)
+
+	if err != nil {
+		return err
+	}
+
+	err = writeZipFiles(zip.NewWriter(f), files)
+
+	if closeErr := f.Close(); err == nil {
+		err = closeErr
+	}
+	if err != nil {
+		os.Remove(path)
+	}
+
+	return err
+}
+
+func writeZipFiles(w *zip.Writer, files []settingsFile) error {
+	for _, file := range files {
+		entry, err := w.Create(file.name)
+
+		if err != nil {
+			return err
+		}
+		if _, err := entry.Write(file.data); err != nil {
+			return err
+		}
+	}
+
+	return w.Close()
+}
+
+// readSettingsBundle reads the manifest and the files of the bundle at the
+// given path, refusing bundles of later versions and unknown files.
+func readSettingsBundle(path string) (settingsManifest, map[string][]byte, error) {
+	var manifest settingsManifest
+
+	r, err := zip.OpenReader(path)
+
+	if err != nil {
+		return manifest, nil, err
+	}
+
+	defer r.Close()
+
+	files := map[string][]byte{}
+
+	for _, f := range r.File {
+		rc, err := f.Open()
+
+		if err != nil {
+			return manifest, nil, err
+		}
+
+		data, err := ioutil.ReadAll(rc)
+		rc.Close()
+
+		if err != nil {
+			return manifest, nil, err
+		}
+
+		files[f.Name] = data
+	}
+
+	data, ok := files[settingsManifestName]
+
+	if !ok {
+		return manifest, nil, fmt.Errorf("%s is not a settings bundle, it has no %s", path, settingsManifestName)
+	}
+	if err := json.Unmarshal(data, &manifest); err != nil || manifest.Version < 1 {
+		return manifest, nil, fmt.Errorf("%s has an invalid %s", path, settingsManifestName)
+	}
+	if manifest.Version > settingsBundleVersion {
+		return manifest, nil, fmt.Errorf("%s was exported by a later version of stadiacontroller (%s, bundle version %d), update it to import the bundle (this version reads bundles up to version %d)", path, manifest.Tool, manifest.Version, settingsBundleVersion)
+	}
+
+	for name := range files {
+		if name != settingsManifestName && name != settingsConfigName && name != settingsCalibrationName {
+			return manifest, nil, fmt.Errorf("%s has an unknown file %s", path, name)
+		}
+	}
+
+	return manifest, files, nil
+}
+
+// An importedSettingsFile is a file of a bundle, along with its destination
+// and the way it is merged with the file already there.
+type importedSettingsFile struct {
+	path    string
+	data    []byte
+	merge   func(existing, imported []byte) ([]byte, error)
+	existed bool
+}
+
+// importSettings writes the files of the bundle at the given path to the path
+// given to -config and to the calibration file, once all of them were checked
+// like on startup. When a file already exists, answers tells whether it is
+// overwritten, kept or merged with the imported one.
+func importSettings(path string, answers *bufio.Reader) error {
+	manifest, files, err := readSettingsBundle(path)
+
+	if err != nil {
+		return err
+	}
+
+	// The destination of the calibration file is found before the imported
+	// configuration is checked, which may set -calibration.
+	calibration, err := calibrationPath()
+
+	if err != nil {
+		return err
+	}
+
+	var imported []importedSettingsFile
+
+	if data, ok := files[settingsConfigName]; ok {
+		if *configPath == "" {
+			return fmt.Errorf("%s has a configuration file, give its destination with -config", path)
+		}
+		if err := checkConfig(path+":"+settingsConfigName, data); err != nil {
+			return err
+		}
+
+		imported = append(imported, importedSettingsFile{path: *configPath, data: data, merge: mergeConfig})
+	}
+	if data, ok := files[settingsCalibrationName]; ok {
+		if _, err := parseCalibrations(path+":"+settingsCalibrationName, data); err != nil {
+			return err
+		}
+
+		imported = append(imported, importedSettingsFile{path: calibration, data: data, merge: mergeCalibrations})
+	}
+	if len(imported) == 0 {
+		return fmt.Errorf("%s has no settings", path)
+	}
+
+	fmt.Printf("importing settings exported by stadiacontroller %s on %s\n", manifest.Tool, manifest.Created.Local().Format("2006-01-02 15:04"))
+
+	// Every conflict is resolved before any file is written.
+	var written []importedSettingsFile
+
+	for _, file := range imported {
+		existing, err := ioutil.ReadFile(file.path)
+
+		switch {
+		case os.IsNotExist(err):
+
+		case err != nil:
+			return err
+
+		case bytes.Equal(existing, file.data):
+			fmt.Printf("%s is unchanged\n", file.path)
+			continue
+
+		default:
+			file.existed = true
+			answer, err := askSettingsConflict(answers, file.path)
+
+			if err != nil {
+				return err
+			}
+			if answer == "keep" {
+				fmt.Printf("%s is kept\n", file.path)
+				continue
+			}
+			if answer == "merge" {
+				if file.data, err = file.merge(existing, file.data); err != nil {
+					return err
+				}
+			}
+		}
+
+		written = append(written, file)
+	}
+
+	for _, file := range written {
+		if err := replaceFile(file.path, file.data); err != nil {
+			return err
+		}
+
+		if file.existed {
+			fmt.Printf("%s imported (previous file saved to %s.bak)\n", file.path, file.path)
+		} else {
+			fmt.Printf("%s imported\n", file.path)
+		}
+	}
+
+	return nil
+}
+
+// askSettingsConflict asks whether the existing file at the given path is
+// overwritten by the imported one, kept, or merged with it, and returns
+// "overwrite", "keep" or "merge".
+func askSettingsConflict(answers *bufio.Reader, path string) (string, error) {
+	for {
+		fmt.Printf("%s already exists, [o]verwrite, [k]eep or [m]erge it? ", path)
+
+		line, err := answers.ReadString('\n')
+
+		switch strings.ToLower(strings.TrimSpace(line)) {
+		case "o", "overwrite":
+			return "overwrite", nil
+		case "k", "keep":
+			return "keep", nil
+		case "m", "merge":
+			return "merge", nil
+		}
+
+		if err != nil {
+			return "", fmt.Errorf("no answer for %s: %w", path, err)
+		}
+	}
+}
+
+// checkConfig checks the configuration file at the given path like on
+// startup, setting its flags and loading its profiles. The flags are not
+// restored, since import-settings exits afterwards.
+func checkConfig(path string, data []byte) error {
+	checked, err := parseConfig(data)
+
+	if err != nil {
+		return fmt.Errorf("%s:%w", path, err)
+	}
+
+	checked.path = path
+
+	if err := checked.setFlags(nil); err != nil {
+		return err
+	}
+
+	_, err = loadProfiles(checked)
+
+	return err
+}
+
+// mergeConfig adds the profiles of the imported configuration which the
+// existing one does not have to its end. The top-level keys and the buttons
+// of the existing configuration are kept.
+func mergeConfig(existing, imported []byte) ([]byte, error) {
+	existingLines, existingSections := splitConfigLines(existing)
+	importedLines, importedSections := splitConfigLines(imported)
+	profiles := map[string]bool{}
+
+	for _, section := range existingSections {
+		if name, _, ok := profileSection(section.name); ok {
+			profiles[name] = true
+		}
+	}
+
+	lines := trimConfigLines(existingLines)
+
+	for _, section := range importedSections {
+		if name, _, ok := profileSection(section.name); ok && !profiles[name] {
+			lines = append(append(lines, ""), trimConfigLines(importedLines[section.start:section.end])...)
+		}
+	}
+
+	merged := []byte(strings.Join(append(lines, ""), "\n"))
+
+	if err := checkConfig("merged configuration", merged); err != nil {
+		return nil, err
+	}
+
+	return merged, nil
+}
+
+// mergeCalibrations adds the calibrations of the imported file of the
+// controllers which were not calibrated yet to the existing ones.
+func mergeCalibrations(existing, imported []byte) ([]byte, error) {
+	controllers, err := parseCalibrations("existing calibration", existing)
+
+	if err != nil {
+		return nil, err
+	}
+
+	added, err := parseCalibrations("imported calibration", imported)
+
+	if err != nil {
+		return nil, err
+	}
+
+	for serial, calibration := range added {
+		if _, ok := controllers[serial]; !ok {
+			controllers[serial] = calibration
+		}
+	}
+
+	return encodeCalibrations(controllers)
+}
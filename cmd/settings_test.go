@@ -0,0 +1,191 @@
+package main
+
+import (
+	"bufio"
+	"io/ioutil"
+	"os"
+	"path/filepath"
+	"strings"
+	"testing"
+)
+
+func TestExportAndImportSettings(t *testing.T) {
+	defer func(saved *configFile) { config = saved }(config)
+	defer func(saved string) { *configPath = saved }(*configPath)
+	defer func(saved string) { *calibrationFile = saved }(*calibrationFile)
+	defer func(saved uint) { *lowBatteryLevel = saved }(*lowBatteryLevel)
+
+	dir, err := ioutil.TempDir("", "stadiacontroller")
+
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	defer os.RemoveAll(dir)
+
+	write := func(name, data string) {
+		if err := ioutil.WriteFile(filepath.Join(dir, name), []byte(data), 0644); err != nil {
+			t.Fatal(err)
+		}
+	}
+	expectFile := func(path, expected string) {
+		t.Helper()
+
+		if data, _ := ioutil.ReadFile(path); string(data) != expected {
+			t.Errorf("%s is\n%s\nexpected\n%s", path, data, expected)
+		}
+	}
+	use := func(configName, calibrationName string) {
+		*configPath = filepath.Join(dir, configName)
+		*calibrationFile = filepath.Join(dir, calibrationName)
+
+		if config, err = loadConfig(*configPath); os.IsNotExist(err) {
+			config = nil
+		} else if err != nil {
+			t.Fatal(err)
+		}
+	}
+	importAnswering := func(bundle, answers string) error {
+		return importSettings(bundle, bufio.NewReader(strings.NewReader(answers)))
+	}
+
+	exportedConfig := `low-battery = 20
+
+[profile.racing]
+left-deadzone = "15%"
+
+[profile.racing.buttons]
+r1 = "A"
+`
+	exportedCalibration := `{"A1": {"left_x": {"min": -30000, "center": 100, "max": 31000}}}`
+
+	write("exported.toml", exportedConfig)
+	write("exported.json", exportedCalibration)
+	use("exported.toml", "exported.json")
+
+	bundle := filepath.Join(dir, "bundle.zip")
+
+	if err := exportSettings(bundle); err != nil {
+		t.Fatal(err)
+	}
+
+	// Files which do not exist are created without asking.
+	use("new.toml", "new.json")
+
+	if err := importAnswering(bundle, ""); err != nil {
+		t.Fatal(err)
+	}
+
+	expectFile(*configPath, exportedConfig)
+	expectFile(*calibrationFile, exportedCalibration)
+
+	// Merging adds the profiles and calibrations which are missing.
+	existingConfig := `low-battery = 10
+
+[profile.racing]
+left-deadzone = "5%"
+
+[profile.flight]
+swap-sticks = true
+`
+	existingCalibration := `{"A1": {"left_x": {"min": -20000, "center": 0, "max": 20000}}, "B2": {}}`
+
+	write("existing.toml", existingConfig)
+	write("existing.json", existingCalibration)
+	use("existing.toml", "existing.json")
+
+	if err := importAnswering(bundle, "maybe\nmerge\nm\n"); err != nil {
+		t.Fatal(err)
+	}
+
+	expectFile(*configPath, existingConfig)
+	expectFile(*configPath+".bak", existingConfig)
+
+	calibrations, err := readCalibrationFile(*calibrationFile)
+
+	if err != nil {
+		t.Fatal(err)
+	}
+	if len(calibrations) != 2 || calibrations["A1"].LeftX.Max != 20000 {
+		t.Errorf("%d merged calibrations, expected those of existing.json", len(calibrations))
+	}
+
+	write("existing.toml", `low-battery = 10
+
+[profile.flight]
+swap-sticks = true
+`)
+	use("existing.toml", "existing.json")
+
+	if err := importAnswering(bundle, "m\nk\n"); err != nil {
+		t.Fatal(err)
+	}
+
+	mergedConfig := `low-battery = 10
+
+[profile.flight]
+swap-sticks = true
+
+[profile.racing]
+left-deadzone = "15%"
+
+[profile.racing.buttons]
+r1 = "A"
+`
+
+	expectFile(*configPath, mergedConfig)
+
+	// Overwriting replaces the files, once every conflict has an answer.
+	if err := importAnswering(bundle, "o\n"); err == nil {
+		t.Errorf("import without an answer for %s succeeded", *calibrationFile)
+	}
+
+	expectFile(*configPath, mergedConfig)
+
+	if err := importAnswering(bundle, "overwrite\noverwrite\n"); err != nil {
+		t.Fatal(err)
+	}
+
+	expectFile(*configPath, exportedConfig)
+	expectFile(*calibrationFile, exportedCalibration)
+
+	// Bundles of later versions and invalid files are refused before anything
+	// is written.
+	use("new.toml", "new.json")
+	write("new.toml", "")
+
+	for name, files := range map[string][]settingsFile{
+		"later.zip": {
+			{settingsManifestName, []byte(`{"version": 2, "tool": "v9.0.0"}`)},
+			{settingsConfigName, []byte(exportedConfig)},
+		},
+		"unknown-flag.zip": {
+			{settingsManifestName, []byte(`{"version": 1}`)},
+			{settingsConfigName, []byte("no-such-flag = 1\n")},
+		},
+		"invalid-calibration.zip": {
+			{settingsManifestName, []byte(`{"version": 1}`)},
+			{settingsConfigName, []byte(exportedConfig)},
+			{settingsCalibrationName, []byte("{")},
+		},
+		"unknown-file.zip": {
+			{settingsManifestName, []byte(`{"version": 1}`)},
+			{"macros.json", []byte("{}")},
+		},
+		"no-manifest.zip": {
+			{settingsConfigName, []byte(exportedConfig)},
+		},
+	} {
+		invalid := filepath.Join(dir, name)
+
+		if err := writeSettingsBundle(invalid, files); err != nil {
+			t.Fatal(err)
+		}
+		if err := importAnswering(invalid, "o\no\n"); err == nil {
+			t.Errorf("import of %s succeeded", name)
+		}
+	}
+
+	expectFile(*configPath, "")
+	expectFile(*calibrationFile, exportedCalibration)
+}
@@ -0,0 +1,206 @@
+package main
+
+import (
+	"fmt"
+	"sync"
+	"sync/atomic"
+	"unsafe"
+
+	"github.com/71/stadiacontroller"
+	"golang.org/x/sys/windows"
+)
+
+// Name of the shared-memory section holding the state of the controller.
+const sharedStateName = `Local\stadiacontroller_state`
+
+// Version of the layout of sharedState, incremented on incompatible changes.
+const sharedStateVersion = 1
+
+// Flags of sharedState.
+const (
+	sharedStateConnected = 1 << 0
+	sharedStatePaused    = 1 << 1
+)
+
+// sharedState is the layout of the shared-memory section, which other
+// processes can poll to read the state of the controller. All fields are
+// little-endian and at fixed offsets:
+//
+//     offset  size  field
+//          0     4  version (sharedStateVersion)
+//          4     4  size of the struct (48)
+//          8     8  sequence number
+//         16     4  flags (1: controller connected, 2: forwarding paused)
+//         20     4  profile index (always 0 for now)
+//         24     4  Stadia buttons, as in stadiacontroller.StadiaButtons
+//         28     2  Xbox 360 buttons, as in XUSB_REPORT.wButtons
+//         30     1  left trigger
+//         31     1  right trigger
+//         32     2  left thumb X
+//         34     2  left thumb Y
+//         36     2  right thumb X
+//         38     2  right thumb Y
+//         40     1  large motor
+//         41     1  small motor
+//
+// The sequence number is odd while the state is being written. Readers must
+// read it, copy the struct, and read it again: the copy is consistent if both
+// reads returned the same even number.
+type sharedState struct {
+	Version      uint32
+	Size         uint32
+	Seq          uint64
+	Flags        uint32
+	Profile      uint32
+	Buttons      uint32
+	XButtons     uint16
+	LeftTrigger  uint8
+	RightTrigger uint8
+	LeftThumbX   int16
+	LeftThumbY   int16
+	RightThumbX  int16
+	RightThumbY  int16
+	LargeMotor   uint8
+	SmallMotor   uint8
+	_            [6]byte
+}
+
+// sharedStateWriter publishes the state of the controller to the
+// shared-memory section.
+type sharedStateWriter struct {
+	mapping windows.Handle
+	view    uintptr
+	state   *sharedState
+
+	mu        sync.Mutex
+	report    stadiacontroller.Xbox360ControllerReport
+	connected bool
+	paused    bool
+	vibration stadiacontroller.Vibration
+}
+
+// newSharedStateWriter creates the shared-memory section with the given name.
+func newSharedStateWriter(name string) (*sharedStateWriter, error) {
+	namePtr, err := windows.UTF16PtrFromString(name)
+
+	if err != nil {
+		return nil, err
+	}
+
+	size := uint32(unsafe.Sizeof(sharedState{}))
+	mapping, err := windows.CreateFileMapping(windows.InvalidHandle, nil, windows.PAGE_READWRITE, 0, size, namePtr)
+
+	if err != nil {
+		return nil, fmt.Errorf("cannot create shared memory %s: %w", name, err)
+	}
+
+	view, err := windows.MapViewOfFile(mapping, windows.FILE_MAP_WRITE, 0, 0, uintptr(size))
+
+	if err != nil {
+		windows.CloseHandle(mapping)
+
+		return nil, fmt.Errorf("cannot map shared memory %s: %w", name, err)
+	}
+
+	w := &sharedStateWriter{
+		mapping: mapping,
+		view:    view,
+		state:   (*sharedState)(unsafe.Pointer(view)),
+		report:  stadiacontroller.NewXbox360ControllerReport(),
+	}
+
+	w.state.Version = sharedStateVersion
+	w.state.Size = size
+	w.publish()
+
+	return w, nil
+}
+
+// Close unmaps the shared-memory section, which is destroyed once no reader
+// has it open either.
+func (w *sharedStateWriter) Close() {
+	w.mu.Lock()
+	defer w.mu.Unlock()
+
+	windows.UnmapViewOfFile(w.view)
+	windows.CloseHandle(w.mapping)
+	w.state = nil
+}
+
+// update publishes the given report, and whether it was forwarded.
+func (w *sharedStateWriter) update(report *stadiacontroller.Xbox360ControllerReport, paused bool) {
+	w.mu.Lock()
+	defer w.mu.Unlock()
+
+	w.report = *report
+	w.paused = paused
+	w.publish()
+}
+
+// setConnected publishes whether the controller is connected. The inputs are
+// released when it is disconnected.
+func (w *sharedStateWriter) setConnected(connected bool) {
+	w.mu.Lock()
+	defer w.mu.Unlock()
+
+	w.connected = connected
+
+	if !connected {
+		w.report = stadiacontroller.NewXbox360ControllerReport()
+	}
+
+	w.publish()
+}
+
+// rumble publishes the vibration of the controller.
+func (w *sharedStateWriter) rumble(vibration stadiacontroller.Vibration) {
+	w.mu.Lock()
+	defer w.mu.Unlock()
+
+	w.vibration = vibration
+	w.publish()
+}
+
+// snapshot returns the last published report, and whether the controller is
+// connected.
+func (w *sharedStateWriter) snapshot() (stadiacontroller.Xbox360ControllerReport, bool) {
+	w.mu.Lock()
+	defer w.mu.Unlock()
+
+	return w.report, w.connected
+}
+
+// publish writes the current state to the shared-memory section. w.mu must be
+// held.
+func (w *sharedStateWriter) publish() {
+	s := w.state
+
+	if s == nil {
+		return
+	}
+
+	// Make the sequence number odd while writing, and even again afterwards.
+	// Atomic operations order the writes in between for readers.
+	atomic.AddUint64(&s.Seq, 1)
+
+	var flags uint32
+
+	if w.connected {
+		flags |= sharedStateConnected
+	}
+	if w.paused {
+		flags |= sharedStatePaused
+	}
+
+	s.Flags = flags
+	s.Buttons = uint32(w.report.Buttons)
+	s.XButtons = w.report.GetButtons()
+	s.LeftTrigger = w.report.GetLeftTrigger()
+	s.RightTrigger = w.report.GetRightTrigger()
+	s.LeftThumbX, s.LeftThumbY = w.report.GetLeftThumb()
+	s.RightThumbX, s.RightThumbY = w.report.GetRightThumb()
+	s.LargeMotor = w.vibration.LargeMotor
+	s.SmallMotor = w.vibration.SmallMotor
+
+	atomic.AddUint64(&s.Seq, 1)
+}
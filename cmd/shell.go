@@ -0,0 +1,81 @@
+package main
+
+import (
+	"fmt"
+	"strings"
+	"unicode"
+)
+
+// shellCommand returns the program and arguments running the given command:
+// the -shell program with the arguments of -shell-args, where "{}" is the
+// command, or the command itself split into arguments with -shell none.
+func shellCommand(cmd string) (string, []string, error) {
+	if strings.EqualFold(*shell, "none") {
+		args, err := splitArgs(cmd)
+
+		if err != nil {
+			return "", nil, err
+		}
+		if len(args) == 0 {
+			return "", nil, fmt.Errorf("empty command")
+		}
+
+		return args[0], args[1:], nil
+	}
+
+	args := strings.Fields(*shellArgs)
+	replaced := false
+
+	for i, arg := range args {
+		if strings.Contains(arg, "{}") {
+			args[i] = strings.Replace(arg, "{}", cmd, -1)
+			replaced = true
+		}
+	}
+
+	if !replaced {
+		args = append(args, cmd)
+	}
+
+	return *shell, args, nil
+}
+
+// splitArgs splits a command into arguments separated by spaces. Double quotes
+// group arguments containing spaces, and a backslash escapes a double quote.
+func splitArgs(cmd string) ([]string, error) {
+	var args []string
+	var arg strings.Builder
+	inArg, quoted := false, false
+
+	for i := 0; i < len(cmd); i++ {
+		c := cmd[i]
+
+		switch {
+		case c == '\\' && i+1 < len(cmd) && cmd[i+1] == '"':
+			arg.WriteByte('"')
+			inArg = true
+			i++
+		case c == '"':
+			quoted = !quoted
+			inArg = true
+		case !quoted && unicode.IsSpace(rune(c)):
+			if inArg {
+				args = append(args, arg.String())
+				arg.Reset()
+				inArg = false
+			}
+		default:
+			arg.WriteByte(c)
+			inArg = true
+		}
+	}
+
+	if quoted {
+		return nil, fmt.Errorf("unterminated quote in command %q", cmd)
+	}
+	if inArg {
+		args = append(args, arg.String())
+	}
+
+	return args, nil
+}
@@ -0,0 +1,144 @@
+package main
+
+import (
+	"fmt"
+	"strings"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Prefix of the values of shift sections which run a command rather than
+// press a button of the emulated controller.
+const shiftCommandPrefix = "run:"
+
+// shiftLayer makes Assistant act as a shift key: buttons pressed while it is
+// held press the alternate targets given by the [shift] section of the
+// configuration file instead of their usual ones, or run a command. A button
+// keeps the layer it was pressed in until it is released, so that releasing
+// Assistant first does not press its usual target.
+type shiftLayer struct {
+	targets  map[stadiacontroller.StadiaButtons]uint16
+	commands []buttonCommand
+	buttons  stadiacontroller.StadiaButtons
+
+	// Whether Assistant is hidden from the rest of the input loop, including
+	// -assistant-pressed.
+	assistantIsShift bool
+
+	shifted  stadiacontroller.StadiaButtons
+	previous stadiacontroller.StadiaButtons
+}
+
+// newShiftLayer returns the layer given by the [shift] section of the
+// configuration file and by the shift section of the given profile if any,
+// or nil if it is empty and -assistant-is-shift is not given.
+func newShiftLayer(config *configFile, profile *configProfile) (*shiftLayer, error) {
+	l := &shiftLayer{
+		targets:          map[stadiacontroller.StadiaButtons]uint16{},
+		assistantIsShift: *assistantIsShift,
+	}
+
+	if config != nil {
+		if err := l.setEntries(config.path, config.shift); err != nil {
+			return nil, err
+		}
+	}
+	if profile != nil {
+		if err := l.setEntries(config.path, profile.shift); err != nil {
+			return nil, err
+		}
+	}
+
+	if l.buttons == 0 && !l.assistantIsShift {
+		return nil, nil
+	}
+
+	return l, nil
+}
+
+// setEntries adds the given entries of a shift section of the configuration
+// file at the given path, replacing those of the same buttons.
+func (l *shiftLayer) setEntries(path string, entries []configEntry) error {
+	for _, entry := range entries {
+		from, err := stadiacontroller.ParseStadiaButtons(entry.key)
+
+		if err != nil || from == 0 || from&(from-1) != 0 || from == stadiacontroller.StadiaButtonAssistant {
+			return fmt.Errorf("%s:%d: invalid shifted button %q", path, entry.line, entry.key)
+		}
+
+		delete(l.targets, from)
+
+		for i, c := range l.commands {
+			if c.button == from {
+				l.commands = append(l.commands[:i], l.commands[i+1:]...)
+				break
+			}
+		}
+
+		l.buttons |= from
+
+		if strings.HasPrefix(entry.value, shiftCommandPrefix) {
+			l.commands = append(l.commands, buttonCommand{from, strings.TrimPrefix(entry.value, shiftCommandPrefix)})
+			continue
+		}
+
+		to, err := parseXbox360Button(entry.value)
+
+		if err != nil {
+			return fmt.Errorf("%s:%d: %v", path, entry.line, err)
+		}
+
+		l.targets[from] = to
+	}
+
+	return nil
+}
+
+// apply replaces the shifted buttons of the given report by their alternate
+// targets, and returns the commands of the shifted buttons pressed since the
+// last update.
+func (l *shiftLayer) apply(report *stadiacontroller.Xbox360ControllerReport) []buttonCommand {
+	buttons := report.Buttons
+	pressed := buttons &^ l.previous
+
+	l.previous = buttons
+	l.shifted &= buttons
+
+	if buttons.Has(stadiacontroller.StadiaButtonAssistant) {
+		l.shifted |= pressed & l.buttons
+	}
+
+	var targets uint16
+	var commands []buttonCommand
+
+	for from, to := range l.targets {
+		if l.shifted&from != 0 {
+			targets |= to
+		}
+	}
+	for _, c := range l.commands {
+		if pressed&l.shifted&c.button != 0 {
+			commands = append(commands, c)
+		}
+	}
+
+	// Shifted buttons are consumed, so that later steps do not press their
+	// usual targets or run their usual commands.
+	consumed := l.shifted
+
+	if l.assistantIsShift {
+		consumed |= buttons & stadiacontroller.StadiaButtonAssistant
+	}
+
+	report.Buttons &^= consumed
+	report.Capture = report.Buttons.Has(stadiacontroller.StadiaButtonCapture)
+	report.Assistant = report.Buttons.Has(stadiacontroller.StadiaButtonAssistant)
+	report.SetButtons(report.GetButtons()&^consumed.Xbox360Buttons() | targets)
+
+	return commands
+}
+
+// reset forgets the buttons held, e.g. after the controller disconnected.
+func (l *shiftLayer) reset() {
+	l.shifted, l.previous = 0, 0
+}
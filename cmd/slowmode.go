@@ -0,0 +1,66 @@
+package main
+
+import (
+	"log"
+	"sync/atomic"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// slowMode scales the sticks and triggers while it is active, giving more
+// time to react. It is toggled by a combination of buttons or by the "slow"
+// command, and stays active across reconnections.
+type slowMode struct {
+	factor float64
+	combo  buttonCombo
+}
+
+// apply scales the sticks and triggers of the given report.
+func (m *slowMode) apply(report *stadiacontroller.Xbox360ControllerReport) {
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	report.SetLeftThumb(m.scaleAxis(lx), m.scaleAxis(ly))
+	report.SetRightThumb(m.scaleAxis(rx), m.scaleAxis(ry))
+	report.SetLeftTrigger(byte(float64(report.GetLeftTrigger()) * m.factor))
+	report.SetRightTrigger(byte(float64(report.GetRightTrigger()) * m.factor))
+}
+
+func (m *slowMode) scaleAxis(value int16) int16 {
+	return int16(float64(value) * m.factor)
+}
+
+// setSlowMode enables or disables slow mode, confirming the change with three
+// short vibrations when enabled and a long one when disabled.
+func (s *controlState) setSlowMode(enabled bool) {
+	if enabled == s.slow {
+		return
+	}
+
+	s.slow = enabled
+
+	now := time.Now().UnixNano()
+
+	if enabled {
+		log.Printf("slow mode enabled")
+		atomic.StoreInt64(&stats.slowModeSince, now)
+		rumblePulses(s.controller, 3, 40*time.Millisecond)
+	} else {
+		log.Printf("slow mode disabled")
+		since := atomic.SwapInt64(&stats.slowModeSince, 0)
+		atomic.AddInt64(&stats.slowModeNanos, now-since)
+		rumblePulses(s.controller, 1, 300*time.Millisecond)
+	}
+}
+
+// slowModeDuration returns the total time spent in slow mode.
+func slowModeDuration() time.Duration {
+	total := atomic.LoadInt64(&stats.slowModeNanos)
+
+	if since := atomic.LoadInt64(&stats.slowModeSince); since != 0 {
+		total += time.Now().UnixNano() - since
+	}
+
+	return time.Duration(total)
+}
@@ -0,0 +1,121 @@
+package main
+
+import (
+	"errors"
+	"flag"
+	"fmt"
+	"os"
+	"path/filepath"
+	"strings"
+	"syscall"
+
+	"golang.org/x/sys/windows"
+	"golang.org/x/sys/windows/registry"
+)
+
+// Registry key of the programs run when the user logs in, and name of the
+// value added by install-startup.
+const (
+	startupRunKey    = `Software\Microsoft\Windows\CurrentVersion\Run`
+	startupValueName = "StadiaController"
+)
+
+// Flags given paths, which are made absolute since the program is started in
+// another directory at login.
+var startupPathFlags = []string{"config", "log-file", "calibration"}
+
+// runInstallStartup registers the executable to run when the current user
+// logs in, with the flags given before the subcommand, replacing the command
+// registered before if any.
+func runInstallStartup(args []string) error {
+	if len(args) != 0 {
+		return errors.New("usage: [flags] install-startup")
+	}
+
+	executable, err := os.Executable()
+
+	if err != nil {
+		return err
+	}
+
+	command := []string{syscall.EscapeArg(executable)}
+
+	for _, arg := range os.Args[1 : len(os.Args)-flag.NArg()] {
+		command = append(command, syscall.EscapeArg(arg))
+	}
+
+	// Flags given later override those given before.
+	for _, name := range startupPathFlags {
+		path := flag.Lookup(name).Value.String()
+
+		if path == "" || filepath.IsAbs(path) {
+			continue
+		}
+		if path, err = filepath.Abs(path); err != nil {
+			return err
+		}
+
+		command = append(command, syscall.EscapeArg("-"+name+"="+path))
+	}
+
+	key, _, err := registry.CreateKey(registry.CURRENT_USER, startupRunKey, registry.QUERY_VALUE|registry.SET_VALUE)
+
+	if err != nil {
+		return startupError(err)
+	}
+
+	defer key.Close()
+
+	previous, _, err := key.GetStringValue(startupValueName)
+	existed := err == nil
+
+	if err := key.SetStringValue(startupValueName, strings.Join(command, " ")); err != nil {
+		return startupError(err)
+	}
+
+	action := "created"
+
+	if existed {
+		action = "updated"
+		fmt.Printf("previous command: %s\n", previous)
+	}
+
+	fmt.Printf("%s HKEY_CURRENT_USER\\%s\\%s, run at login:\n%s\n", action, startupRunKey, startupValueName, strings.Join(command, " "))
+
+	return nil
+}
+
+// runUninstallStartup removes the command registered by install-startup.
+func runUninstallStartup(args []string) error {
+	if len(args) != 0 {
+		return errors.New("usage: uninstall-startup")
+	}
+
+	key, err := registry.OpenKey(registry.CURRENT_USER, startupRunKey, registry.SET_VALUE)
+
+	if err != nil {
+		return startupError(err)
+	}
+
+	defer key.Close()
+
+	if err := key.DeleteValue(startupValueName); err == windows.ERROR_FILE_NOT_FOUND {
+		fmt.Println("nothing to remove: the program does not run at login")
+		return nil
+	} else if err != nil {
+		return startupError(err)
+	}
+
+	fmt.Printf("removed HKEY_CURRENT_USER\\%s\\%s\n", startupRunKey, startupValueName)
+
+	return nil
+}
+
+// startupError explains errors caused by missing permissions.
+func startupError(err error) error {
+	if err == windows.ERROR_ACCESS_DENIED {
+		return fmt.Errorf("cannot change the programs run at login: access denied, the registry key may be locked by a policy or a security program")
+	}
+
+	return fmt.Errorf("cannot change the programs run at login: %w", err)
+}
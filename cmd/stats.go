@@ -0,0 +1,168 @@
+package main
+
+import (
+	"log"
+	"sort"
+	"sync"
+	"sync/atomic"
+	"time"
+)
+
+// Maximum number of latencies kept per window of -stats to compute their 99th
+// percentile; the latest ones are kept.
+const maxStatsSamples = 8192
+
+// forwardingStats summarizes the reports forwarded in each window of -stats:
+// their rate, the time from their read to the return of the emulated
+// controller update, the reports which were not forwarded or could not be
+// parsed, and the vibrations. Recording a report does not allocate.
+type forwardingStats struct {
+	mu sync.Mutex
+
+	start   time.Time
+	count   int
+	min     time.Duration
+	sum     time.Duration
+	samples [maxStatsSamples]time.Duration
+
+	// Counters at the start of the window.
+	received, forwarded, parseFailures, vibrations uint64
+}
+
+// newForwardingStats returns stats printing a summary every given interval.
+func newForwardingStats(every time.Duration) *forwardingStats {
+	s := &forwardingStats{}
+	s.reset(time.Now())
+
+	go func() {
+		for range time.Tick(every) {
+			s.print()
+		}
+	}()
+
+	return s
+}
+
+// record adds a report read at the given time, and sent to the emulated
+// controller at the other given time.
+func (s *forwardingStats) record(received, sent time.Time) {
+	latency := sent.Sub(received)
+
+	s.mu.Lock()
+	defer s.mu.Unlock()
+
+	if s.count == 0 || latency < s.min {
+		s.min = latency
+	}
+
+	s.samples[s.count%maxStatsSamples] = latency
+	s.sum += latency
+	s.count++
+}
+
+// reset starts a new window at the given time.
+func (s *forwardingStats) reset(now time.Time) {
+	s.start = now
+	s.count, s.min, s.sum = 0, 0, 0
+	s.received = atomic.LoadUint64(&stats.reportsReceived)
+	s.forwarded = atomic.LoadUint64(&stats.reportsForwarded)
+	s.parseFailures = atomic.LoadUint64(&stats.parseFailures)
+	s.vibrations = atomic.LoadUint64(&stats.vibrations)
+}
+
+// print logs the summary of the current window, and starts a new one.
+func (s *forwardingStats) print() {
+	now := time.Now()
+
+	s.mu.Lock()
+	elapsed := now.Sub(s.start).Seconds()
+	count, min, sum := s.count, s.min, s.sum
+	n := count
+
+	if n > maxStatsSamples {
+		n = maxStatsSamples
+	}
+
+	samples := append([]time.Duration(nil), s.samples[:n]...)
+	received := atomic.LoadUint64(&stats.reportsReceived) - s.received
+	forwarded := atomic.LoadUint64(&stats.reportsForwarded) - s.forwarded
+	parseFailures := atomic.LoadUint64(&stats.parseFailures) - s.parseFailures
+	vibrations := atomic.LoadUint64(&stats.vibrations) - s.vibrations
+	s.reset(now)
+	s.mu.Unlock()
+
+	// Reports forwarded in the window may have been received in the previous
+	// one, e.g. with -added-delay.
+	var notForwarded uint64
+
+	if received > forwarded {
+		notForwarded = received - forwarded
+	}
+
+	if count == 0 {
+		log.Printf("stats: %.0f Hz, no report forwarded, %d parse failures, %.1f vibrations/s",
+			float64(received)/elapsed, parseFailures, float64(vibrations)/elapsed)
+		return
+	}
+
+	sort.Slice(samples, func(i, j int) bool { return samples[i] < samples[j] })
+
+	p99 := samples[len(samples)*99/100]
+
+	log.Printf("stats: %.0f Hz, latency min=%v avg=%v p99=%v, %d not forwarded, %d parse failures, %.1f vibrations/s",
+		float64(received)/elapsed, min, sum/time.Duration(count), p99, notForwarded, parseFailures, float64(vibrations)/elapsed)
+}
+
+// measures are the statistics of the forwarded reports, each nil unless
+// enabled.
+type measures struct {
+	latency    *latencyStats
+	forwarding *forwardingStats
+	rate       *rateMonitor
+}
+
+// newMeasures returns the statistics enabled by the command line. The metrics
+// endpoint of -ws reports the latency and the rate, which are then collected
+// even if they are not printed.
+func newMeasures() measures {
+	var m measures
+
+	if *measureLatency {
+		m.latency = newLatencyStats(5*time.Second, *lowLatency)
+	} else if *wsAddr != "" {
+		m.latency = newLatencyStats(0, *lowLatency)
+	}
+
+	if *statsInterval > 0 {
+		m.forwarding = newForwardingStats(*statsInterval)
+	}
+
+	if *minReportRate > 0 || *reportGap > 0 {
+		m.rate = newRateMonitor(*minReportRate, *reportGap)
+	} else if *wsAddr != "" {
+		m.rate = newRateMonitor(0, 0)
+	}
+
+	return m
+}
+
+// record measures a report read at received and forwarded at sent.
+func (m measures) record(received, sent time.Time) {
+	if m.forwarding != nil {
+		m.forwarding.record(received, sent)
+	}
+	if m.latency != nil {
+		m.latency.record(received, sent)
+	}
+}
+
+// print prints the summaries of the latency and rate, if asked for on the
+// command line.
+func (m measures) print() {
+	if *measureLatency {
+		m.latency.print()
+	}
+	if *minReportRate > 0 || *reportGap > 0 {
+		m.rate.print()
+	}
+}
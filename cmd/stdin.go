@@ -0,0 +1,148 @@
+package main
+
+import (
+	"bufio"
+	"encoding/json"
+	"fmt"
+	"io"
+	"os"
+	"strconv"
+	"strings"
+)
+
+// readStdinCommands sends the commands read from stdin, one per line, to the
+// input loop through commands, and prints the result of each one to out.
+// It returns when stdin is closed or done is closed.
+//
+// Commands are the ones accepted by the control pipe, with arguments given
+// after the command name (e.g. "rumble 255 0 500"). "quit" is an alias of
+// "shutdown".
+func readStdinCommands(commands chan<- controlCommand, done <-chan struct{}, out io.Writer) {
+	scanner := bufio.NewScanner(os.Stdin)
+
+	for scanner.Scan() {
+		fields := strings.Fields(scanner.Text())
+
+		if len(fields) == 0 {
+			continue
+		}
+
+		request, err := parseCommandArgs(fields)
+
+		var response controlResponse
+
+		if err != nil {
+			response = controlFailure(controlErrorInvalidArgument, "%v", err)
+		} else {
+			response = sendCommand(commands, done, request)
+		}
+
+		fmt.Fprintln(out, formatStdinResponse(response))
+
+		if response.Error != nil && response.Error.Code == controlErrorShuttingDown {
+			return
+		}
+	}
+}
+
+// parseCommandArgs parses a command given as its name followed by its
+// arguments. Unknown commands are left to the input loop to reject.
+func parseCommandArgs(fields []string) (controlRequest, error) {
+	request := controlRequest{Command: fields[0]}
+	args := fields[1:]
+
+	switch request.Command {
+	case "quit":
+		request.Command = "shutdown"
+
+	case "status", "report", "subscribe", "pause", "resume", "reconnect", "shutdown":
+
+	case "rumble":
+		if len(args) == 0 {
+			break
+		}
+		if len(args) != 3 {
+			return request, fmt.Errorf("usage: rumble [large small duration_ms]")
+		}
+
+		large, err := strconv.ParseUint(args[0], 10, 8)
+		if err != nil {
+			return request, fmt.Errorf("invalid large motor intensity %q", args[0])
+		}
+		small, err := strconv.ParseUint(args[1], 10, 8)
+		if err != nil {
+			return request, fmt.Errorf("invalid small motor intensity %q", args[1])
+		}
+		duration, err := strconv.Atoi(args[2])
+		if err != nil {
+			return request, fmt.Errorf("invalid duration %q", args[2])
+		}
+
+		largeMotor, smallMotor := byte(large), byte(small)
+		request.LargeMotor, request.SmallMotor, request.DurationMs = &largeMotor, &smallMotor, &duration
+
+		return request, nil
+
+	case "rumble_pattern":
+		if len(args) == 0 {
+			return request, fmt.Errorf("usage: rumble_pattern intensity@ms[,intensity@ms...]")
+		}
+
+		request.Pattern = strings.Join(args, " ")
+
+		return request, nil
+
+	case "slow", "overlay", "keyboard", "mouse", "delay", "vibration":
+		if len(args) == 0 {
+			break
+		}
+		if len(args) != 1 || (args[0] != "on" && args[0] != "off") {
+			return request, fmt.Errorf("usage: %s [on|off]", request.Command)
+		}
+
+		enabled := args[0] == "on"
+		request.Enabled = &enabled
+
+		return request, nil
+
+	default:
+		return request, nil
+	}
+
+	if len(args) > 0 {
+		return request, fmt.Errorf("%s takes no arguments", fields[0])
+	}
+
+	return request, nil
+}
+
+func formatStdinResponse(response controlResponse) string {
+	switch {
+	case response.Error != nil:
+		return fmt.Sprintf("error %s: %s", response.Error.Code, response.Error.Message)
+	case response.Report != nil:
+		report, _ := json.Marshal(response.Report)
+
+		return "ok " + string(report)
+	case response.Status != nil:
+		battery := "unknown"
+
+		if b := response.Status.Battery; b != nil && b.Charging {
+			battery = fmt.Sprintf("%d%%,charging", b.Level)
+		} else if b != nil {
+			battery = fmt.Sprintf("%d%%", b.Level)
+		}
+
+		transport := response.Status.Transport
+
+		if transport == "" {
+			transport = "none"
+		}
+
+		return fmt.Sprintf("ok connected=%v transport=%s paused=%v reports=%d battery=%s slow=%v",
+			response.Status.Connected, transport, response.Status.Paused, response.Status.Reports, battery,
+			response.Status.SlowMode)
+	default:
+		return "ok"
+	}
+}
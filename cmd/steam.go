@@ -0,0 +1,82 @@
+package main
+
+import (
+	"errors"
+	"strings"
+	"unsafe"
+
+	"github.com/71/stadiacontroller"
+	"golang.org/x/sys/windows"
+)
+
+// steamWarning explains how to stop Steam from using the controller.
+const steamWarning = `
+*** The controller cannot be opened, most likely because Steam uses it. ***
+Games would otherwise receive every input twice: once from Steam Input, and
+once from the emulated Xbox 360 controller. To let this program use it:
+  1. Open Steam > Settings > Controller > General Controller Settings.
+  2. Uncheck "Generic Gamepad Configuration Support" (and any Stadia option).
+  3. Restart Steam, or disconnect and reconnect the controller.
+`
+
+// Executables of other controller mappers which may hold the controller.
+var knownMappers = []string{"DS4Windows.exe", "reWASDEngine.exe", "x360ce.exe", "JoyToKey.exe"}
+
+// runningMappers returns the known controller mappers among the given
+// running processes.
+func runningMappers(processes []string) []string {
+	var mappers []string
+
+	for _, process := range processes {
+		for _, mapper := range knownMappers {
+			if strings.EqualFold(process, mapper) {
+				mappers = append(mappers, process)
+			}
+		}
+	}
+
+	return mappers
+}
+
+// isSteamConflict returns whether the given error, returned when opening the
+// controller, is likely caused by Steam Input using the controller given the
+// names of the running processes.
+func isSteamConflict(err error, processes []string) bool {
+	if !errors.Is(err, stadiacontroller.ErrAccessDenied) {
+		return false
+	}
+
+	for _, process := range processes {
+		if strings.EqualFold(process, "steam.exe") {
+			return true
+		}
+	}
+
+	return false
+}
+
+// runningProcesses returns the executable names of all running processes.
+func runningProcesses() ([]string, error) {
+	snapshot, err := windows.CreateToolhelp32Snapshot(windows.TH32CS_SNAPPROCESS, 0)
+
+	if err != nil {
+		return nil, err
+	}
+
+	defer windows.CloseHandle(snapshot)
+
+	var entry windows.ProcessEntry32
+	entry.Size = uint32(unsafe.Sizeof(entry))
+
+	var names []string
+
+	for err = windows.Process32First(snapshot, &entry); err == nil; err = windows.Process32Next(snapshot, &entry) {
+		names = append(names, windows.UTF16ToString(entry.ExeFile[:]))
+	}
+
+	if err != windows.ERROR_NO_MORE_FILES {
+		return names, err
+	}
+
+	return names, nil
+}
@@ -0,0 +1,218 @@
+package main
+
+import (
+	"fmt"
+	"math"
+	"strconv"
+	"strings"
+
+	"github.com/71/stadiacontroller"
+)
+
+// stickResponse shapes the deflection of a stick.
+type stickResponse struct {
+	// deadzone is the deflection, from 0 to 1, under which the stick is
+	// considered centered. Deflections above it are rescaled to start at 0.
+	deadzone float64
+
+	// exponent is the power to which the rescaled deflection is raised:
+	// exponents above 1 make small deflections more precise.
+	exponent float64
+}
+
+// Named response curves.
+var stickCurves = map[string]float64{
+	"linear":  1,
+	"squared": 2,
+	"cubed":   3,
+}
+
+// parseDeadzone parses a deadzone given as a percentage of full deflection
+// (e.g. "8%") or as a raw value from 0 to 32767 (e.g. "2600").
+func parseDeadzone(s string) (float64, error) {
+	s = strings.TrimSpace(s)
+
+	if s == "" {
+		return 0, nil
+	}
+
+	if strings.HasSuffix(s, "%") {
+		percent, err := strconv.ParseFloat(strings.TrimSuffix(s, "%"), 64)
+
+		if err != nil || percent < 0 || percent >= 100 {
+			return 0, fmt.Errorf("invalid deadzone %q, expected a percentage below 100%%", s)
+		}
+
+		return percent / 100, nil
+	}
+
+	raw, err := strconv.Atoi(s)
+
+	if err != nil || raw < 0 || raw >= math.MaxInt16 {
+		return 0, fmt.Errorf("invalid deadzone %q, expected a percentage or a value below 32767", s)
+	}
+
+	return float64(raw) / math.MaxInt16, nil
+}
+
+// parseCurve parses a response curve given as "linear", "squared", "cubed" or
+// a custom exponent (e.g. "pow:1.5").
+func parseCurve(s string) (float64, error) {
+	s = strings.ToLower(strings.TrimSpace(s))
+
+	if s == "" {
+		return 1, nil
+	}
+	if exponent, ok := stickCurves[s]; ok {
+		return exponent, nil
+	}
+	if strings.HasPrefix(s, "pow:") {
+		if exponent, err := strconv.ParseFloat(s[4:], 64); err == nil && exponent > 0 && !math.IsInf(exponent, 0) {
+			return exponent, nil
+		}
+	}
+
+	return 0, fmt.Errorf("invalid curve %q, expected linear, squared, cubed or pow:exponent with a positive exponent", s)
+}
+
+// isIdentity returns whether the response leaves the stick unchanged.
+func (r stickResponse) isIdentity() bool {
+	return r.deadzone == 0 && r.exponent == 1
+}
+
+// apply returns the shaped deflection of a stick. The deadzone and the curve
+// are radial, so that the direction of the stick is kept, and the curve is
+// applied to the deflection rescaled past the deadzone.
+func (r stickResponse) apply(x, y int16) (int16, int16) {
+	if r.isIdentity() {
+		return x, y
+	}
+
+	fx, fy := float64(x)/math.MaxInt16, float64(y)/math.MaxInt16
+	magnitude := math.Hypot(fx, fy)
+
+	if magnitude <= r.deadzone {
+		return 0, 0
+	}
+
+	// Magnitudes above 1 (corners, and -32768) stay above 1, so that the
+	// extremes are still reached once clamped.
+	scaled := (magnitude - r.deadzone) / (1 - r.deadzone)
+
+	if r.exponent != 1 {
+		scaled = math.Pow(scaled, r.exponent)
+	}
+
+	factor := scaled / magnitude
+
+	return clampAxis(fx * factor * math.MaxInt16), clampAxis(fy * factor * math.MaxInt16)
+}
+
+func clampAxis(value float64) int16 {
+	if value >= math.MaxInt16 {
+		return math.MaxInt16
+	}
+	if value <= math.MinInt16 {
+		return math.MinInt16
+	}
+
+	return int16(math.Round(value))
+}
+
+// stickResponses shapes both sticks of the controller.
+type stickResponses struct {
+	left, right stickResponse
+}
+
+// newStickResponses returns the responses configured by the command line, or
+// nil if both sticks are left unchanged.
+func newStickResponses() (*stickResponses, error) {
+	var r stickResponses
+	var err error
+
+	if r.left.deadzone, err = parseDeadzone(*leftDeadzone); err != nil {
+		return nil, err
+	}
+	if r.right.deadzone, err = parseDeadzone(*rightDeadzone); err != nil {
+		return nil, err
+	}
+	if r.left.exponent, err = parseCurve(*leftCurve); err != nil {
+		return nil, err
+	}
+	if r.right.exponent, err = parseCurve(*rightCurve); err != nil {
+		return nil, err
+	}
+	if r.left.isIdentity() && r.right.isIdentity() {
+		return nil, nil
+	}
+
+	return &r, nil
+}
+
+// apply shapes the sticks of the given report.
+func (r *stickResponses) apply(report *stadiacontroller.Xbox360ControllerReport) {
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	report.SetLeftThumb(r.left.apply(lx, ly))
+	report.SetRightThumb(r.right.apply(rx, ry))
+}
+
+// dpadButtons are the directions of the D-pad.
+const dpadButtons = stadiacontroller.StadiaButtonDpadUp | stadiacontroller.StadiaButtonDpadDown | stadiacontroller.StadiaButtonDpadLeft | stadiacontroller.StadiaButtonDpadRight
+
+// applyDpadAsStick moves the left stick of the given report with the D-pad
+// instead of pressing the D-pad buttons, for games which only read the stick.
+// Diagonals have the same magnitude as other directions, and the left stick
+// wins when it is pushed further than the D-pad.
+func applyDpadAsStick(report *stadiacontroller.Xbox360ControllerReport) {
+	dpad := report.Buttons & dpadButtons
+
+	if dpad == 0 {
+		return
+	}
+
+	var dx, dy float64
+
+	if dpad.Has(stadiacontroller.StadiaButtonDpadUp) {
+		dy++
+	}
+	if dpad.Has(stadiacontroller.StadiaButtonDpadDown) {
+		dy--
+	}
+	if dpad.Has(stadiacontroller.StadiaButtonDpadRight) {
+		dx++
+	}
+	if dpad.Has(stadiacontroller.StadiaButtonDpadLeft) {
+		dx--
+	}
+
+	report.Buttons &^= dpadButtons
+	report.SetButtons(report.GetButtons() &^ dpad.Xbox360Buttons())
+
+	// Opposite directions cancel out.
+	if dx == 0 && dy == 0 {
+		return
+	}
+	if dx != 0 && dy != 0 {
+		dx, dy = dx*math.Sqrt2/2, dy*math.Sqrt2/2
+	}
+
+	lx, ly := report.GetLeftThumb()
+
+	if math.Hypot(float64(lx), float64(ly)) > math.MaxInt16 {
+		return
+	}
+
+	report.SetLeftThumb(clampAxis(dx*math.MaxInt16), clampAxis(dy*math.MaxInt16))
+}
+
+// newDpadAsStickStep returns the step of -dpad-as-stick, or nil if it is not
+// given.
+func newDpadAsStickStep() *reportStep {
+	if !*dpadAsStick {
+		return nil
+	}
+
+	return transformStep(applyDpadAsStick, nil)
+}
@@ -0,0 +1,156 @@
+package main
+
+import (
+	"fmt"
+	"log"
+	"strings"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Value above which a trigger is considered pulled by sticky mode.
+const stickyTriggerThreshold = 128
+
+// Duration of the vibration pulses confirming that an input was latched or
+// released.
+const stickyPulse = 60 * time.Millisecond
+
+// stickyInputs implements sticky mode: a press of a sticky button or trigger
+// latches it, and its next press releases it. Triggers latch fully pulled.
+// Pressing the release combination releases all latched inputs.
+type stickyInputs struct {
+	controller *stadiacontroller.StadiaController
+
+	buttons      stadiacontroller.StadiaButtons
+	leftTrigger  bool
+	rightTrigger bool
+	release      stadiacontroller.StadiaButtons
+
+	latched        stadiacontroller.StadiaButtons
+	latchedLeft    bool
+	latchedRight   bool
+	previous       stadiacontroller.StadiaButtons
+	previousLeft   bool
+	previousRight  bool
+	releasePressed bool
+}
+
+// newStickyInputs returns the sticky mode configured by the comma-separated
+// list of sticky inputs (Stadia button names, "l2" and "r2"), and the
+// combination of buttons releasing them.
+func newStickyInputs(controller *stadiacontroller.StadiaController, inputs, release string) (*stickyInputs, error) {
+	s := &stickyInputs{controller: controller}
+
+	for _, name := range strings.Split(inputs, ",") {
+		switch name = strings.ToLower(strings.TrimSpace(name)); name {
+		case "":
+		case "l2":
+			s.leftTrigger = true
+		case "r2":
+			s.rightTrigger = true
+		default:
+			button, err := stadiacontroller.ParseStadiaButtons(name)
+
+			if err != nil {
+				return nil, err
+			}
+
+			s.buttons |= button
+		}
+	}
+
+	releaseButtons, err := stadiacontroller.ParseStadiaButtons(release)
+
+	if err != nil {
+		return nil, err
+	}
+	if releaseButtons == 0 {
+		return nil, fmt.Errorf("sticky mode needs a combination releasing all inputs")
+	}
+
+	s.release = releaseButtons
+
+	return s, nil
+}
+
+// apply updates the latched inputs from the given report, and replaces the
+// state of the sticky inputs in the report by their latched state.
+func (s *stickyInputs) apply(report *stadiacontroller.Xbox360ControllerReport) {
+	pressed := report.Buttons & s.buttons
+	left := report.GetLeftTrigger() > stickyTriggerThreshold
+	right := report.GetRightTrigger() > stickyTriggerThreshold
+
+	releasePressed := report.Buttons.Has(s.release)
+
+	if releasePressed && !s.releasePressed {
+		// Do not toggle the buttons of the combination as well.
+		if s.reset() {
+			log.Printf("released all latched inputs")
+			rumblePulses(s.controller, 2, stickyPulse)
+		}
+	} else if !releasePressed {
+		toggled := pressed &^ s.previous
+		toggledLeft := s.leftTrigger && left && !s.previousLeft
+		toggledRight := s.rightTrigger && right && !s.previousRight
+
+		s.latched ^= toggled
+		s.latchedLeft = s.latchedLeft != toggledLeft
+		s.latchedRight = s.latchedRight != toggledRight
+
+		// Latching and releasing at once only confirms the latch.
+		if toggled&s.latched != 0 || (toggledLeft && s.latchedLeft) || (toggledRight && s.latchedRight) {
+			rumblePulses(s.controller, 1, stickyPulse)
+		} else if toggled != 0 || toggledLeft || toggledRight {
+			rumblePulses(s.controller, 2, stickyPulse)
+		}
+	}
+
+	s.previous, s.previousLeft, s.previousRight = pressed, left, right
+	s.releasePressed = releasePressed
+
+	// Replace the sticky inputs by their latched state.
+	report.Buttons = report.Buttons&^s.buttons | s.latched
+	report.Capture = report.Buttons.Has(stadiacontroller.StadiaButtonCapture)
+	report.Assistant = report.Buttons.Has(stadiacontroller.StadiaButtonAssistant)
+	report.SetButtons(report.GetButtons()&^s.buttons.Xbox360Buttons() | s.latched.Xbox360Buttons())
+
+	if s.leftTrigger {
+		report.SetLeftTrigger(latchedTrigger(s.latchedLeft))
+	}
+	if s.rightTrigger {
+		report.SetRightTrigger(latchedTrigger(s.latchedRight))
+	}
+}
+
+func latchedTrigger(latched bool) byte {
+	if latched {
+		return 255
+	}
+
+	return 0
+}
+
+// reset releases all latched inputs, and returns whether any was latched.
+func (s *stickyInputs) reset() bool {
+	wasLatched := s.latched != 0 || s.latchedLeft || s.latchedRight
+
+	s.latched, s.latchedLeft, s.latchedRight = 0, false, false
+
+	return wasLatched
+}
+
+// newStickyStep returns the step of -sticky, or nil if it is not given.
+func newStickyStep(controller *stadiacontroller.StadiaController) (*reportStep, error) {
+	if *stickyInputsList == "" {
+		return nil, nil
+	}
+
+	sticky, err := newStickyInputs(controller, *stickyInputsList, *stickyRelease)
+
+	if err != nil {
+		return nil, err
+	}
+
+	return transformStep(sticky.apply, func() { sticky.reset() }), nil
+}
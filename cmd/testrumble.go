@@ -0,0 +1,174 @@
+package main
+
+import (
+	"errors"
+	"fmt"
+	"syscall"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Duration of each ramp of the rumble test, and interval between two writes.
+const (
+	testRumbleRampDuration = 2 * time.Second
+	testRumbleStep         = 50 * time.Millisecond
+)
+
+// runTestRumble acquires the controller and ramps up and down its large motor,
+// then its small motor, then both, printing every write error. With "loop",
+// the sequence repeats until Ctrl-C. No controller is emulated, so it works
+// without ViGEmBus.
+func runTestRumble(args []string) error {
+	loop := false
+
+	switch {
+	case len(args) == 0:
+	case len(args) == 1 && args[0] == "loop":
+		loop = true
+	default:
+		return fmt.Errorf("usage: test-rumble [loop]")
+	}
+
+	controller, err := newController()
+
+	if err != nil {
+		return err
+	}
+
+	defer controller.Close()
+
+	if err := waitForController(controller); err != nil {
+		return err
+	}
+
+	stop := make(chan struct{})
+	onInterrupt(func() { close(stop) })
+
+	failed := false
+
+sequence:
+	for {
+		for _, ramp := range []struct {
+			name         string
+			large, small bool
+		}{
+			{"large motor", true, false},
+			{"small motor", false, true},
+			{"both motors", true, true},
+		} {
+			fmt.Printf("%s: ramping from 0 to 255 and back to 0\n", ramp.name)
+
+			if !testRumbleRamp(controller, ramp.large, ramp.small, stop) {
+				failed = true
+			}
+
+			select {
+			case <-stop:
+				break sequence
+			default:
+			}
+		}
+
+		if !loop {
+			break
+		}
+	}
+
+	if failed {
+		return errors.New("vibration test failed")
+	}
+
+	fmt.Println("vibration test done")
+
+	return nil
+}
+
+// waitForController waits until the controller is acquired.
+func waitForController(controller *stadiacontroller.StadiaController) error {
+	timeout := time.After(selfTestConnectTimeout)
+
+	for {
+		select {
+		case event, ok := <-controller.Events():
+			if !ok {
+				return errors.New("the controller could not be opened")
+			}
+
+			switch event := event.(type) {
+			case stadiacontroller.ConnectedEvent:
+				fmt.Printf("controller connected over %v\n", event.Transport)
+				return nil
+			case stadiacontroller.ErrorEvent:
+				fmt.Printf("cannot open the controller: %v\n", event.Err)
+			}
+
+		case <-timeout:
+			return errors.New("no controller found: plug it in over USB or pair it over Bluetooth")
+		}
+	}
+}
+
+// testRumbleRamp vibrates the given motors with an intensity going from 0 to
+// 255 and back to 0, and returns whether all writes succeeded. Consecutive
+// identical errors are only printed once.
+func testRumbleRamp(controller *stadiacontroller.StadiaController, large, small bool, stop <-chan struct{}) bool {
+	steps := int(testRumbleRampDuration / testRumbleStep)
+	ticker := time.NewTicker(testRumbleStep)
+	defer ticker.Stop()
+
+	var lastErr string
+	ok := true
+
+	for i := 0; i <= steps; i++ {
+		// Triangle from 0 at the first step to 255 halfway to 0 at the last.
+		intensity := byte(255 - abs(2*i-steps)*255/steps)
+
+		var largeMotor, smallMotor byte
+
+		if large {
+			largeMotor = intensity
+		}
+		if small {
+			smallMotor = intensity
+		}
+
+		if err := controller.Vibrate(largeMotor, smallMotor); err != nil {
+			ok = false
+
+			if err.Error() != lastErr {
+				lastErr = err.Error()
+				fmt.Printf("  write of %d, %d failed: %s\n", largeMotor, smallMotor, describeWriteError(err))
+			}
+		}
+
+		select {
+		case <-ticker.C:
+		case <-stop:
+			controller.Vibrate(0, 0)
+			return ok
+		}
+	}
+
+	return ok
+}
+
+// describeWriteError returns the error along with the Windows error code
+// behind it, if any.
+func describeWriteError(err error) string {
+	var errno syscall.Errno
+
+	if errors.As(err, &errno) {
+		return fmt.Sprintf("%v (error %d)", err, uintptr(errno))
+	}
+
+	return err.Error()
+}
+
+func abs(x int) int {
+	if x < 0 {
+		return -x
+	}
+
+	return x
+}
@@ -0,0 +1,116 @@
+package main
+
+import (
+	"log"
+	"sync"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// throttledController limits the rate at which the sticks of the emulated
+// controller are updated with -max-update-rate. Reports only moving the
+// sticks are delayed until the interval since the last update elapsed, and
+// replaced by the next such report in the meantime; other reports are sent
+// right away, so that presses never wait. The last delayed report is always
+// sent once due.
+type throttledController struct {
+	stadiacontroller.EmulatedController
+	interval time.Duration
+
+	mu      sync.Mutex
+	last    stadiacontroller.Xbox360ControllerReport
+	sentAt  time.Time
+	pending *stadiacontroller.Xbox360ControllerReport
+	flush   *time.Timer
+}
+
+// newThrottledController limits the updates of the given controller to rate
+// per second.
+func newThrottledController(pad stadiacontroller.EmulatedController, rate float64) *throttledController {
+	return &throttledController{EmulatedController: pad, interval: time.Duration(float64(time.Second) / rate)}
+}
+
+func (t *throttledController) Send(report *stadiacontroller.Xbox360ControllerReport) error {
+	t.mu.Lock()
+	defer t.mu.Unlock()
+
+	now := time.Now()
+	elapsed := now.Sub(t.sentAt)
+
+	if elapsed >= t.interval || !sameButtonsAndTriggers(&t.last, report) {
+		return t.send(report, now)
+	}
+
+	pending := *report
+	t.pending = &pending
+
+	if t.flush == nil {
+		t.flush = time.AfterFunc(t.interval-elapsed, t.flushPending)
+	}
+
+	return nil
+}
+
+// send sends the given report now, replacing the pending one.
+func (t *throttledController) send(report *stadiacontroller.Xbox360ControllerReport, now time.Time) error {
+	t.pending = nil
+
+	if t.flush != nil {
+		t.flush.Stop()
+		t.flush = nil
+	}
+
+	t.last, t.sentAt = *report, now
+
+	return t.EmulatedController.Send(report)
+}
+
+// flushPending sends the pending report, if it was not replaced since.
+func (t *throttledController) flushPending() {
+	t.mu.Lock()
+	defer t.mu.Unlock()
+
+	t.flush = nil
+
+	if t.pending == nil {
+		return
+	}
+
+	if err := t.send(t.pending, time.Now()); err != nil {
+		log.Printf("warning: cannot send delayed report: %v", err)
+	}
+}
+
+// SetBattery shows the given battery status on the throttled controller, if it
+// can show it.
+func (t *throttledController) SetBattery(status stadiacontroller.BatteryStatus) error {
+	t.mu.Lock()
+	defer t.mu.Unlock()
+
+	if reporter, ok := t.EmulatedController.(stadiacontroller.BatteryReporter); ok {
+		return reporter.SetBattery(status)
+	}
+
+	return nil
+}
+
+func (t *throttledController) Close() error {
+	t.mu.Lock()
+	t.pending = nil
+
+	if t.flush != nil {
+		t.flush.Stop()
+		t.flush = nil
+	}
+
+	t.mu.Unlock()
+
+	return t.EmulatedController.Close()
+}
+
+// sameButtonsAndTriggers returns whether the given reports press the same
+// buttons and triggers of the emulated controller.
+func sameButtonsAndTriggers(a, b *stadiacontroller.Xbox360ControllerReport) bool {
+	return a.GetButtons() == b.GetButtons() && a.GetLeftTrigger() == b.GetLeftTrigger() && a.GetRightTrigger() == b.GetRightTrigger()
+}
@@ -0,0 +1,140 @@
+package main
+
+import (
+	"fmt"
+	"log"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// toggles are the combinations of buttons switching features while playing,
+// and the pause hold.
+type toggles struct {
+	loop *inputLoop
+
+	exit    buttonCombo
+	profile buttonCombo
+	overlay buttonCombo
+	mouse   buttonCombo
+	slow    slowMode
+
+	// pause is nil unless -pause-hold is given.
+	pause *pauseHold
+}
+
+// newToggles returns the toggles given by the command line, which change the
+// state of the given loop.
+func newToggles(l *inputLoop) (*toggles, error) {
+	if *slowModeFactor <= 0 || *slowModeFactor > 1 {
+		return nil, fmt.Errorf("slow mode factor must be between 0 (excluded) and 1")
+	}
+
+	t := &toggles{
+		loop:  l,
+		slow:  slowMode{factor: *slowModeFactor},
+		pause: newPauseHold(*pauseHoldDuration),
+	}
+
+	for _, toggle := range []struct {
+		buttons string
+		combo   *buttonCombo
+	}{
+		{*exitCombo, &t.exit},
+		{*profileCombo, &t.profile},
+		{*slowModeCombo, &t.slow.combo},
+		{*overlayCombo, &t.overlay},
+		{*mouseAimCombo, &t.mouse},
+	} {
+		buttons, err := stadiacontroller.ParseStadiaButtons(toggle.buttons)
+
+		if err != nil {
+			return nil, err
+		}
+
+		toggle.combo.buttons = buttons
+	}
+
+	return t, nil
+}
+
+// systemStep returns the step of the exit and profile combinations, which
+// come before all other steps.
+func (t *toggles) systemStep() *reportStep {
+	return &reportStep{apply: t.applySystem}
+}
+
+func (t *toggles) applySystem(report *stadiacontroller.Xbox360ControllerReport, now time.Time) (time.Time, error) {
+	l := t.loop
+
+	if t.exit.update(report.Buttons) {
+		log.Printf("exit combination pressed, shutting down")
+		l.controller.Close()
+	}
+	if t.profile.update(report.Buttons) && len(l.profiles) > 1 {
+		l.switchProfile((l.active+1)%len(l.profiles), report.Buttons)
+	}
+
+	return time.Time{}, nil
+}
+
+// featuresStep returns the step of the slow mode, overlay and mouse aim
+// combinations and of the pause hold.
+func (t *toggles) featuresStep() *reportStep {
+	return &reportStep{
+		apply: t.applyFeatures,
+		reset: func() {
+			if t.pause != nil {
+				t.pause.reset()
+			}
+		},
+	}
+}
+
+func (t *toggles) applyFeatures(report *stadiacontroller.Xbox360ControllerReport, now time.Time) (time.Time, error) {
+	state := &t.loop.state
+
+	if t.slow.combo.update(report.Buttons) {
+		state.setSlowMode(!state.slow)
+	}
+	if t.overlay.update(report.Buttons) && state.overlay != nil {
+		state.overlay.setVisible(!state.overlay.isVisible())
+	}
+	if t.mouse.update(report.Buttons) && state.mouse != nil {
+		state.setMouseAim(!state.mouse.isActive())
+	}
+
+	// The pause hold hides the Stadia button while it is held, after the
+	// combinations which include it.
+	if t.pause == nil {
+		return time.Time{}, nil
+	}
+
+	toggle, wake := t.pause.update(report, now)
+
+	if !toggle {
+		return wake, nil
+	}
+
+	state.paused = !state.paused
+
+	if err := t.loop.pauseChanged(); err != nil {
+		return wake, err
+	}
+	if *pauseRumble && state.paused {
+		rumblePulses(state.controller, 1, 100*time.Millisecond)
+	} else if *pauseRumble {
+		rumblePulses(state.controller, 2, 100*time.Millisecond)
+	}
+
+	return wake, nil
+}
+
+// slowStep returns the step scaling the sticks and triggers in slow mode.
+func (t *toggles) slowStep() *reportStep {
+	return transformStep(func(report *stadiacontroller.Xbox360ControllerReport) {
+		if t.loop.state.slow {
+			t.slow.apply(report)
+		}
+	}, nil)
+}
@@ -0,0 +1,456 @@
+package main
+
+import (
+	"errors"
+	"fmt"
+	"log"
+	"runtime"
+	"sync"
+	"unsafe"
+
+	"github.com/71/stadiacontroller"
+	"golang.org/x/sys/windows"
+)
+
+var (
+	shell32 = windows.NewLazySystemDLL("shell32.dll")
+
+	procShellNotifyIconW = shell32.NewProc("Shell_NotifyIconW")
+
+	procCreatePopupMenu     = user32.NewProc("CreatePopupMenu")
+	procAppendMenuW         = user32.NewProc("AppendMenuW")
+	procTrackPopupMenu      = user32.NewProc("TrackPopupMenu")
+	procDestroyMenu         = user32.NewProc("DestroyMenu")
+	procSetForegroundWindow = user32.NewProc("SetForegroundWindow")
+	procGetCursorPos        = user32.NewProc("GetCursorPos")
+	procDestroyWindow       = user32.NewProc("DestroyWindow")
+	procCreateIconIndirect  = user32.NewProc("CreateIconIndirect")
+	procDestroyIcon         = user32.NewProc("DestroyIcon")
+	procGetDC               = user32.NewProc("GetDC")
+	procReleaseDC           = user32.NewProc("ReleaseDC")
+
+	procCreateBitmap = gdi32.NewProc("CreateBitmap")
+
+	procFreeConsole = kernel32.NewProc("FreeConsole")
+)
+
+// Constants used to create the notification area icon and its menu.
+const (
+	nimAdd          = 0
+	nimModify       = 1
+	nimDelete       = 2
+	nifMessage      = 0x1
+	nifIcon         = 0x2
+	nifTip          = 0x4
+	nifInfo         = 0x10
+	niifInfo        = 0x1
+	niifWarning     = 0x2
+	niifError       = 0x3
+	wmNull          = 0x0000
+	wmLButtonUp     = 0x0202
+	wmRButtonUp     = 0x0205
+	wmTrayNotify    = 0x8000 + 2 // WM_APP + 2, sent by the shell.
+	wmTrayUpdate    = 0x8000 + 3 // WM_APP + 3, sent when the state changes.
+	mfString        = 0x0000
+	mfChecked       = 0x0008
+	mfSeparator     = 0x0800
+	tpmRightButton  = 0x0002
+	tpmNoNotify     = 0x0080
+	tpmReturnCmd    = 0x0100
+	trayIconID      = 1
+	trayClassName   = "StadiaControllerTray"
+	trayIconSize    = 16
+	trayMenuReopen  = 1
+	trayMenuVibrate = 2
+	trayMenuExit    = 3
+)
+
+// Colors of the icon, as COLORREF values (0x00BBGGRR).
+const (
+	trayWaitingColor   = 0x00808080
+	trayConnectedColor = 0x0050C040
+	trayErrorColor     = 0x003030E0
+	trayPausedColor    = 0x0000A0F0
+)
+
+// States shown by the icon.
+const (
+	trayWaiting = iota
+	trayConnected
+	trayError
+	trayPaused
+)
+
+type notifyIconData struct {
+	size            uint32
+	hwnd            uintptr
+	id              uint32
+	flags           uint32
+	callbackMessage uint32
+	icon            uintptr
+	tip             [128]uint16
+	state           uint32
+	stateMask       uint32
+	info            [256]uint16
+	version         uint32
+	infoTitle       [64]uint16
+	infoFlags       uint32
+	guidItem        windows.GUID
+	balloonIcon     uintptr
+}
+
+type iconInfo struct {
+	icon     int32
+	xHotspot uint32
+	yHotspot uint32
+	mask     uintptr
+	color    uintptr
+}
+
+type winPoint struct {
+	x, y int32
+}
+
+// trayIcon is an icon in the notification area showing whether a controller
+// is connected, with a menu sending commands to the input loop like the
+// control pipe. Like the overlay, its window runs on a thread of its own.
+type trayIcon struct {
+	commands chan<- controlCommand
+	done     <-chan struct{}
+
+	hwnd    uintptr
+	stopped chan struct{}
+
+	mu        sync.Mutex
+	connected bool
+	transport stadiacontroller.Transport
+	busError  bool
+	paused    bool
+	profile   string
+
+	// Accessed by the window thread only.
+	icons [4]uintptr
+}
+
+// newTrayIcon adds the icon to the notification area. Commands chosen in its
+// menu are sent to commands until done is closed.
+func newTrayIcon(commands chan<- controlCommand, done <-chan struct{}) (*trayIcon, error) {
+	t := &trayIcon{commands: commands, done: done, stopped: make(chan struct{})}
+	ready := make(chan error, 1)
+
+	go t.run(ready)
+
+	if err := <-ready; err != nil {
+		return nil, err
+	}
+
+	return t, nil
+}
+
+// run creates the window receiving the messages of the icon and dispatches
+// them until it is destroyed.
+func (t *trayIcon) run(ready chan<- error) {
+	runtime.LockOSThread()
+	defer close(t.stopped)
+
+	instance, _, _ := procGetModuleHandleW.Call(0)
+	className, _ := windows.UTF16PtrFromString(trayClassName)
+
+	class := wndClassEx{
+		size:      uint32(unsafe.Sizeof(wndClassEx{})),
+		wndProc:   windows.NewCallback(t.windowProc),
+		instance:  instance,
+		className: className,
+	}
+
+	if r, _, err := procRegisterClassExW.Call(uintptr(unsafe.Pointer(&class))); r == 0 {
+		ready <- fmt.Errorf("cannot register tray window class: %w", err)
+		return
+	}
+
+	// The window is never shown, but a message-only window cannot own the
+	// menu of the icon.
+	hwnd, _, err := procCreateWindowExW.Call(
+		wsExToolWindow,
+		uintptr(unsafe.Pointer(className)),
+		uintptr(unsafe.Pointer(className)),
+		wsPopup,
+		0, 0, 0, 0,
+		0, 0, instance, 0)
+
+	if hwnd == 0 {
+		ready <- fmt.Errorf("cannot create tray window: %w", err)
+		return
+	}
+
+	t.hwnd = hwnd
+
+	for i, color := range [...]uintptr{trayWaitingColor, trayConnectedColor, trayErrorColor, trayPausedColor} {
+		t.icons[i] = createDotIcon(color)
+	}
+
+	data := t.notifyIconData()
+	data.flags |= nifMessage
+	data.callbackMessage = wmTrayNotify
+
+	if r, _, _ := procShellNotifyIconW.Call(nimAdd, uintptr(unsafe.Pointer(&data))); r == 0 {
+		procDestroyWindow.Call(hwnd)
+		t.destroyIcons()
+		ready <- fmt.Errorf("cannot add the icon to the notification area")
+		return
+	}
+
+	ready <- nil
+
+	var msg winMsg
+
+	for {
+		r, _, _ := procGetMessageW.Call(uintptr(unsafe.Pointer(&msg)), 0, 0, 0)
+
+		// 0 is WM_QUIT, and -1 an error.
+		if int32(r) <= 0 {
+			return
+		}
+
+		procDispatchMessageW.Call(uintptr(unsafe.Pointer(&msg)))
+	}
+}
+
+func (t *trayIcon) windowProc(hwnd, msg, wParam, lParam uintptr) uintptr {
+	switch msg {
+	case wmTrayNotify:
+		if lParam == wmRButtonUp || lParam == wmLButtonUp {
+			t.showMenu(hwnd)
+		}
+
+		return 0
+
+	case wmTrayUpdate:
+		data := t.notifyIconData()
+		procShellNotifyIconW.Call(nimModify, uintptr(unsafe.Pointer(&data)))
+
+		return 0
+
+	case wmDestroy:
+		data := notifyIconData{size: uint32(unsafe.Sizeof(notifyIconData{})), hwnd: hwnd, id: trayIconID}
+		procShellNotifyIconW.Call(nimDelete, uintptr(unsafe.Pointer(&data)))
+		t.destroyIcons()
+		procPostQuitMessage.Call(0)
+
+		return 0
+	}
+
+	r, _, _ := procDefWindowProcW.Call(hwnd, msg, wParam, lParam)
+
+	return r
+}
+
+// notifyIconData returns the icon and tooltip of the current state.
+func (t *trayIcon) notifyIconData() notifyIconData {
+	t.mu.Lock()
+	state, tip := trayWaiting, "Stadia controller: waiting for a controller"
+
+	switch {
+	case t.busError:
+		state, tip = trayError, "Stadia controller: ViGEm bus unavailable"
+	case t.connected && t.paused:
+		state, tip = trayPaused, "Stadia controller: connected over "+t.transport.String()+", paused"
+	case t.connected:
+		state, tip = trayConnected, "Stadia controller: connected over "+t.transport.String()
+	}
+	if t.profile != "" {
+		tip += ", profile " + t.profile
+	}
+	t.mu.Unlock()
+
+	data := notifyIconData{
+		size:  uint32(unsafe.Sizeof(notifyIconData{})),
+		hwnd:  t.hwnd,
+		id:    trayIconID,
+		flags: nifIcon | nifTip,
+		icon:  t.icons[state],
+	}
+
+	tip16, _ := windows.UTF16FromString(tip)
+	copy(data.tip[:len(data.tip)-1], tip16)
+
+	return data
+}
+
+// showMenu shows the menu of the icon at the cursor, and sends the chosen
+// command to the input loop.
+func (t *trayIcon) showMenu(hwnd uintptr) {
+	menu, _, _ := procCreatePopupMenu.Call()
+
+	if menu == 0 {
+		return
+	}
+
+	defer procDestroyMenu.Call(menu)
+
+	vibrationFlags := uintptr(mfString)
+
+	if vibrationsMuted() {
+		vibrationFlags |= mfChecked
+	}
+
+	appendMenu(menu, mfString, trayMenuReopen, "Reconnect")
+	appendMenu(menu, vibrationFlags, trayMenuVibrate, "Disable vibration")
+	appendMenu(menu, mfSeparator, 0, "")
+	appendMenu(menu, mfString, trayMenuExit, "Exit")
+
+	var cursor winPoint
+
+	procGetCursorPos.Call(uintptr(unsafe.Pointer(&cursor)))
+
+	// The menu is only closed when clicking elsewhere if its window is in the
+	// foreground, and the null message lets it close on the first click.
+	procSetForegroundWindow.Call(hwnd)
+	item, _, _ := procTrackPopupMenu.Call(menu, tpmRightButton|tpmNoNotify|tpmReturnCmd, uintptr(cursor.x), uintptr(cursor.y), 0, hwnd, 0)
+	procPostMessageW.Call(hwnd, wmNull, 0, 0)
+
+	var request controlRequest
+
+	switch item {
+	case trayMenuReopen:
+		request.Command = "reconnect"
+	case trayMenuVibrate:
+		enabled := vibrationsMuted()
+		request.Command, request.Enabled = "vibration", &enabled
+	case trayMenuExit:
+		request.Command = "shutdown"
+	default:
+		return
+	}
+
+	// Do not block the window thread while the input loop is busy.
+	go func() {
+		if response := sendCommand(t.commands, t.done, request); response.Error != nil {
+			log.Printf("warning: cannot run %s from the tray: %s", request.Command, response.Error.Message)
+		}
+	}()
+}
+
+func appendMenu(menu, flags, id uintptr, text string) {
+	text16, _ := windows.UTF16PtrFromString(text)
+
+	procAppendMenuW.Call(menu, flags, id, uintptr(unsafe.Pointer(text16)))
+}
+
+// createDotIcon returns an icon showing a dot of the given color, transparent
+// around it.
+func createDotIcon(color uintptr) uintptr {
+	screen, _, _ := procGetDC.Call(0)
+	defer procReleaseDC.Call(0, screen)
+
+	dc, _, _ := procCreateCompatibleDC.Call(screen)
+	defer procDeleteDC.Call(dc)
+
+	// Pixels of the mask are white where the screen shows through, and those
+	// of the color bitmap black there.
+	mask, _, _ := procCreateBitmap.Call(trayIconSize, trayIconSize, 1, 1, 0)
+	colorBitmap, _, _ := procCreateCompatibleBitmap.Call(screen, trayIconSize, trayIconSize)
+	defer procDeleteObject.Call(mask)
+	defer procDeleteObject.Call(colorBitmap)
+
+	dot := winRect{2, 2, trayIconSize - 2, trayIconSize - 2}
+	oldBitmap, _, _ := procSelectObject.Call(dc, mask)
+
+	fillRect(dc, winRect{0, 0, trayIconSize, trayIconSize}, 0x00FFFFFF)
+	ellipse(dc, dot, 0)
+
+	procSelectObject.Call(dc, colorBitmap)
+
+	fillRect(dc, winRect{0, 0, trayIconSize, trayIconSize}, 0)
+	ellipse(dc, dot, color)
+
+	procSelectObject.Call(dc, oldBitmap)
+
+	info := iconInfo{icon: 1, mask: mask, color: colorBitmap}
+	icon, _, _ := procCreateIconIndirect.Call(uintptr(unsafe.Pointer(&info)))
+
+	return icon
+}
+
+func (t *trayIcon) destroyIcons() {
+	for _, icon := range t.icons {
+		if icon != 0 {
+			procDestroyIcon.Call(icon)
+		}
+	}
+}
+
+// setConnected updates the icon when the controller is connected over the
+// given transport, or disconnected.
+func (t *trayIcon) setConnected(connected bool, transport stadiacontroller.Transport) {
+	t.mu.Lock()
+	t.connected, t.transport = connected, transport
+	t.mu.Unlock()
+
+	procPostMessageW.Call(t.hwnd, wmTrayUpdate, 0, 0)
+}
+
+// setProfile shows the given profile in the tooltip of the icon.
+func (t *trayIcon) setProfile(name string) {
+	t.mu.Lock()
+	t.profile = name
+	t.mu.Unlock()
+
+	procPostMessageW.Call(t.hwnd, wmTrayUpdate, 0, 0)
+}
+
+// setPaused updates the icon when forwarding is paused or resumed.
+func (t *trayIcon) setPaused(paused bool) {
+	t.mu.Lock()
+	t.paused = paused
+	t.mu.Unlock()
+
+	procPostMessageW.Call(t.hwnd, wmTrayUpdate, 0, 0)
+}
+
+// setBusError updates the icon when the ViGEm bus becomes unavailable with the
+// given error, or available again with nil.
+func (t *trayIcon) setBusError(err error) {
+	t.mu.Lock()
+	t.busError = err != nil
+	t.mu.Unlock()
+
+	procPostMessageW.Call(t.hwnd, wmTrayUpdate, 0, 0)
+}
+
+// notify shows a notification next to the icon, as a toast on Windows 10 and
+// later. It waits for the shell to handle it, so it must not be called by the
+// input loop.
+func (t *trayIcon) notify(title, text string, flags uint32) error {
+	data := notifyIconData{
+		size:      uint32(unsafe.Sizeof(notifyIconData{})),
+		hwnd:      t.hwnd,
+		id:        trayIconID,
+		flags:     nifInfo,
+		infoFlags: flags,
+	}
+
+	title16, _ := windows.UTF16FromString(title)
+	text16, _ := windows.UTF16FromString(text)
+	copy(data.infoTitle[:len(data.infoTitle)-1], title16)
+	copy(data.info[:len(data.info)-1], text16)
+
+	if r, _, _ := procShellNotifyIconW.Call(nimModify, uintptr(unsafe.Pointer(&data))); r == 0 {
+		return errors.New("the notification was rejected by the shell")
+	}
+
+	return nil
+}
+
+// Close removes the icon, and waits for its thread to exit.
+func (t *trayIcon) Close() {
+	procPostMessageW.Call(t.hwnd, wmClose, 0, 0)
+	<-t.stopped
+}
+
+// detachConsole closes the console window of the program if it was the only
+// program using it, e.g. when started from the Explorer. Logs are lost unless
+// -log-file is given.
+func detachConsole() {
+	procFreeConsole.Call()
+}
@@ -0,0 +1,79 @@
+package main
+
+import (
+	"fmt"
+	"math"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Modes of the triggers, and their default thresholds.
+const (
+	triggerModeAnalog  = "analog"
+	triggerModeDigital = "digital"
+	triggerModeHybrid  = "hybrid"
+
+	defaultDigitalTriggerThreshold = 128
+	defaultHybridTriggerThreshold  = 10
+)
+
+// triggerResponse shapes the values of the triggers. In digital mode, they are
+// released below threshold and fully pulled at or above it. In hybrid mode,
+// they are released below threshold and rescaled above it, so that triggers
+// resting slightly pulled are released without losing their full range.
+type triggerResponse struct {
+	digital   bool
+	threshold byte
+}
+
+// newTriggerResponse returns the response configured by the command line, or
+// nil in analog mode.
+func newTriggerResponse() (*triggerResponse, error) {
+	if *triggerThreshold < 0 || *triggerThreshold > math.MaxUint8 {
+		return nil, fmt.Errorf("trigger threshold must be between 0 and 255")
+	}
+
+	threshold := *triggerThreshold
+
+	switch *triggerMode {
+	case triggerModeAnalog:
+		return nil, nil
+
+	case triggerModeDigital:
+		if threshold == 0 {
+			threshold = defaultDigitalTriggerThreshold
+		}
+
+		return &triggerResponse{digital: true, threshold: byte(threshold)}, nil
+
+	case triggerModeHybrid:
+		if threshold == 0 {
+			threshold = defaultHybridTriggerThreshold
+		}
+		if threshold == math.MaxUint8 {
+			return nil, fmt.Errorf("the threshold of hybrid triggers must be below 255")
+		}
+
+		return &triggerResponse{threshold: byte(threshold)}, nil
+
+	default:
+		return nil, fmt.Errorf("unknown trigger mode %q, expected analog, digital or hybrid", *triggerMode)
+	}
+}
+
+// apply shapes the triggers of the given report.
+func (t *triggerResponse) apply(report *stadiacontroller.Xbox360ControllerReport) {
+	report.SetLeftTrigger(t.shape(report.GetLeftTrigger()))
+	report.SetRightTrigger(t.shape(report.GetRightTrigger()))
+}
+
+func (t *triggerResponse) shape(value byte) byte {
+	switch {
+	case value < t.threshold:
+		return 0
+	case t.digital:
+		return math.MaxUint8
+	default:
+		return byte((int(value-t.threshold)*math.MaxUint8 + int(math.MaxUint8-t.threshold)/2) / int(math.MaxUint8-t.threshold))
+	}
+}
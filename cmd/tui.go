@@ -0,0 +1,330 @@
+package main
+
+import (
+	"bytes"
+	"fmt"
+	"io"
+	"log"
+	"os"
+	"strings"
+	"sync"
+	"time"
+
+	"github.com/71/stadiacontroller"
+	"golang.org/x/sys/windows"
+)
+
+// Interval at which the terminal view is redrawn, whatever the rate of
+// reports.
+const terminalViewRefresh = time.Second / 30
+
+// Number of log messages shown below the terminal view, and width of the
+// lines of the view.
+const (
+	terminalViewLogLines = 6
+	terminalViewWidth    = 79
+)
+
+// Size of the boxes showing the position of the sticks, in characters,
+// excluding their borders.
+const (
+	stickBoxWidth  = 21
+	stickBoxHeight = 9
+)
+
+// Escape sequences used by the terminal view.
+const (
+	escapeAlternateScreen = "\x1b[?1049h\x1b[?25l\x1b[2J"
+	escapeMainScreen      = "\x1b[?25h\x1b[?1049l"
+	escapeClearLine       = "\x1b[K"
+	escapeReverse         = "\x1b[7m"
+	escapeReset           = "\x1b[0m"
+)
+
+// terminalView shows the state of the controller in the console with -tui:
+// its buttons, sticks and triggers as read from the controller, its
+// connection, the rate of reports and the vibrations requested by games, with
+// the last log messages below. The view is redrawn at most every
+// terminalViewRefresh, only rewriting the lines which changed.
+//
+// The log is shown by the view rather than printed, and the console is
+// restored by Close.
+type terminalView struct {
+	console windows.Handle
+	mode    uint32
+	logger  io.Writer
+	done    chan struct{}
+	stopped chan struct{}
+
+	mu           sync.Mutex
+	connected    bool
+	transport    stadiacontroller.Transport
+	report       stadiacontroller.Xbox360ControllerReport
+	reports      int
+	rate         int
+	large, small byte
+	logs         []string
+	partial      []byte
+
+	// Lines currently shown.
+	lines []string
+}
+
+// newTerminalView switches the console to a blank screen showing the view.
+func newTerminalView() (*terminalView, error) {
+	console := windows.Handle(os.Stdout.Fd())
+	var mode uint32
+
+	if err := windows.GetConsoleMode(console, &mode); err != nil {
+		return nil, fmt.Errorf("-tui needs a console: %w", err)
+	}
+	if err := windows.SetConsoleMode(console, mode|windows.ENABLE_VIRTUAL_TERMINAL_PROCESSING); err != nil {
+		return nil, fmt.Errorf("the console does not support -tui: %w", err)
+	}
+
+	v := &terminalView{
+		console: console,
+		mode:    mode,
+		logger:  log.Writer(),
+		done:    make(chan struct{}),
+		stopped: make(chan struct{}),
+		report:  stadiacontroller.NewXbox360ControllerReport(),
+	}
+
+	os.Stdout.WriteString(escapeAlternateScreen)
+
+	// Keep the level and the time of the messages, but show them in the view,
+	// and still write them to -log-file.
+	if w, ok := v.logger.(*levelWriter); ok && *logFile != "" {
+		log.SetOutput(&levelWriter{io.MultiWriter(w.w, v), w.level})
+	} else if ok {
+		log.SetOutput(&levelWriter{v, w.level})
+	} else {
+		log.SetOutput(v)
+	}
+
+	go v.run()
+
+	return v, nil
+}
+
+func (v *terminalView) run() {
+	defer close(v.stopped)
+
+	ticker := time.NewTicker(terminalViewRefresh)
+	defer ticker.Stop()
+
+	rateTicker := time.NewTicker(time.Second)
+	defer rateTicker.Stop()
+
+	for {
+		select {
+		case <-ticker.C:
+			v.redraw()
+		case <-rateTicker.C:
+			v.mu.Lock()
+			v.rate, v.reports = v.reports, 0
+			v.mu.Unlock()
+		case <-v.done:
+			return
+		}
+	}
+}
+
+// setConnected shows whether the controller is connected, and over which
+// transport.
+func (v *terminalView) setConnected(connected bool, transport stadiacontroller.Transport) {
+	v.mu.Lock()
+	defer v.mu.Unlock()
+
+	v.connected, v.transport = connected, transport
+
+	if !connected {
+		v.report = stadiacontroller.NewXbox360ControllerReport()
+	}
+}
+
+// update shows the given report, read from the controller.
+func (v *terminalView) update(report *stadiacontroller.Xbox360ControllerReport) {
+	v.mu.Lock()
+	defer v.mu.Unlock()
+
+	v.report = *report
+	v.reports++
+}
+
+// vibration shows the given vibration, requested by a game.
+func (v *terminalView) vibration(vibration stadiacontroller.Vibration) {
+	v.mu.Lock()
+	defer v.mu.Unlock()
+
+	v.large, v.small = vibration.LargeMotor, vibration.SmallMotor
+}
+
+// Write keeps the last log messages written.
+func (v *terminalView) Write(p []byte) (int, error) {
+	v.mu.Lock()
+	defer v.mu.Unlock()
+
+	v.partial = append(v.partial, p...)
+
+	for {
+		i := bytes.IndexByte(v.partial, '\n')
+
+		if i == -1 {
+			break
+		}
+
+		v.logs = append(v.logs, string(v.partial[:i]))
+		v.partial = v.partial[i+1:]
+	}
+
+	if len(v.logs) > terminalViewLogLines {
+		v.logs = v.logs[len(v.logs)-terminalViewLogLines:]
+	}
+
+	return len(p), nil
+}
+
+// redraw rewrites the lines of the view which changed since the last redraw.
+func (v *terminalView) redraw() {
+	v.mu.Lock()
+	lines := v.render()
+	previous := v.lines
+	v.lines = lines
+	v.mu.Unlock()
+
+	var b strings.Builder
+
+	for i, line := range lines {
+		if i < len(previous) && previous[i] == line {
+			continue
+		}
+
+		fmt.Fprintf(&b, "\x1b[%d;1H%s%s", i+1, line, escapeClearLine)
+	}
+	for i := len(lines); i < len(previous); i++ {
+		fmt.Fprintf(&b, "\x1b[%d;1H%s", i+1, escapeClearLine)
+	}
+
+	if b.Len() > 0 {
+		os.Stdout.WriteString(b.String())
+	}
+}
+
+// render returns the lines of the view. It is called with mu locked.
+func (v *terminalView) render() []string {
+	r := &v.report
+	status := "disconnected"
+
+	if v.connected {
+		status = "connected over " + v.transport.String()
+	}
+
+	lx, ly := r.GetLeftThumb()
+	rx, ry := r.GetRightThumb()
+
+	lines := []string{
+		fmt.Sprintf("Stadia controller %-40s %5d reports/s", status, v.rate),
+		"",
+		fmt.Sprintf("L2 %s %3d      R2 %s %3d", triggerBar(r.GetLeftTrigger()), r.GetLeftTrigger(), triggerBar(r.GetRightTrigger()), r.GetRightTrigger()),
+		buttonKeys(r.Buttons, stadiacontroller.StadiaButtonL1) + strings.Repeat(" ", 31) + buttonKeys(r.Buttons, stadiacontroller.StadiaButtonR1),
+		"",
+		fmt.Sprintf("left stick  (%6d, %6d)         right stick (%6d, %6d)", lx, ly, rx, ry),
+	}
+
+	left, right := stickBox(lx, ly), stickBox(rx, ry)
+
+	for i := range left {
+		lines = append(lines, fmt.Sprintf("%-35s%s", left[i], right[i]))
+	}
+
+	lines = append(lines,
+		"",
+		"D-pad "+buttonKeys(r.Buttons, stadiacontroller.StadiaButtonDpadUp, stadiacontroller.StadiaButtonDpadDown, stadiacontroller.StadiaButtonDpadLeft, stadiacontroller.StadiaButtonDpadRight),
+		buttonKeys(r.Buttons, stadiacontroller.StadiaButtonA, stadiacontroller.StadiaButtonB, stadiacontroller.StadiaButtonX, stadiacontroller.StadiaButtonY, stadiacontroller.StadiaButtonL3, stadiacontroller.StadiaButtonR3),
+		buttonKeys(r.Buttons, stadiacontroller.StadiaButtonOptions, stadiacontroller.StadiaButtonMenu, stadiacontroller.StadiaButtonStadia, stadiacontroller.StadiaButtonAssistant, stadiacontroller.StadiaButtonCapture),
+		"",
+		fmt.Sprintf("rumble      large %3d  small %3d", v.large, v.small),
+		"",
+	)
+
+	for _, message := range v.logs {
+		if len(message) > terminalViewWidth {
+			message = message[:terminalViewWidth]
+		}
+
+		lines = append(lines, message)
+	}
+
+	return lines
+}
+
+// triggerBar returns a bar filled in proportion to the given trigger value.
+func triggerBar(value byte) string {
+	const width = 20
+	filled := (int(value)*width + 127) / 255
+
+	return "[" + strings.Repeat("#", filled) + strings.Repeat(".", width-filled) + "]"
+}
+
+// buttonKeys returns the names of the given buttons, highlighting those
+// pressed in buttons.
+func buttonKeys(buttons stadiacontroller.StadiaButtons, keys ...stadiacontroller.StadiaButtons) string {
+	var b strings.Builder
+
+	for _, key := range keys {
+		if buttons.Has(key) {
+			b.WriteString(escapeReverse + " " + key.String() + " " + escapeReset)
+		} else {
+			b.WriteString(" " + key.String() + " ")
+		}
+	}
+
+	return b.String()
+}
+
+// stickBox returns the lines of a box showing the given stick position, with
+// its center marked.
+func stickBox(x, y int16) []string {
+	border := "+" + strings.Repeat("-", stickBoxWidth) + "+"
+	lines := []string{border}
+
+	// Stick Y axes point up, rows point down.
+	column := (int(x) + 32768) * (stickBoxWidth - 1) / 65535
+	row := (32767 - int(y)) * (stickBoxHeight - 1) / 65535
+
+	for i := 0; i < stickBoxHeight; i++ {
+		line := []byte(strings.Repeat(" ", stickBoxWidth))
+
+		if i == stickBoxHeight/2 {
+			line[stickBoxWidth/2] = '+'
+		}
+		if i == row {
+			line[column] = 'o'
+		}
+
+		lines = append(lines, "|"+string(line)+"|")
+	}
+
+	return append(lines, border)
+}
+
+// Close restores the console and the log, printing the last log messages
+// unless they were written to -log-file.
+func (v *terminalView) Close() {
+	close(v.done)
+	<-v.stopped
+
+	log.SetOutput(v.logger)
+	os.Stdout.WriteString(escapeMainScreen)
+	windows.SetConsoleMode(v.console, v.mode)
+
+	if *logFile == "" {
+		v.mu.Lock()
+		for _, message := range v.logs {
+			fmt.Fprintln(os.Stderr, message)
+		}
+		v.mu.Unlock()
+	}
+}
@@ -0,0 +1,102 @@
+package main
+
+import (
+	"fmt"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Maximum number of presses per second of turbo buttons.
+const maxTurboRate = 30
+
+// turboButtons repeatedly presses and releases buttons while they are held.
+// Each button starts pressed, and is released immediately when it is
+// released.
+type turboButtons struct {
+	buttons  stadiacontroller.StadiaButtons
+	half     time.Duration
+	heldFrom map[stadiacontroller.StadiaButtons]time.Time
+}
+
+// newTurboButtons returns the turbo buttons given by a comma-separated list of
+// Stadia buttons, pressed rate times per second, or nil if the list is empty.
+func newTurboButtons(buttons string, rate float64) (*turboButtons, error) {
+	turbo, err := stadiacontroller.ParseStadiaButtons(buttons)
+
+	if err != nil {
+		return nil, err
+	}
+	if turbo == 0 {
+		return nil, nil
+	}
+	if rate <= 0 || rate > maxTurboRate {
+		return nil, fmt.Errorf("turbo rate must be positive and at most %d", maxTurboRate)
+	}
+
+	return &turboButtons{
+		buttons:  turbo,
+		half:     time.Duration(float64(time.Second) / rate / 2),
+		heldFrom: map[stadiacontroller.StadiaButtons]time.Time{},
+	}, nil
+}
+
+// apply releases the held turbo buttons during the second half of each period
+// in the given report, and returns when it must be applied again, or the zero
+// time if no turbo button is held.
+func (t *turboButtons) apply(report *stadiacontroller.Xbox360ControllerReport, now time.Time) time.Time {
+	var released stadiacontroller.StadiaButtons
+	var wake time.Time
+
+	for button := stadiacontroller.StadiaButtons(1); button <= stadiacontroller.StadiaButtonVolumeDown; button <<= 1 {
+		if t.buttons&button == 0 {
+			continue
+		}
+		if !report.Buttons.Has(button) {
+			delete(t.heldFrom, button)
+			continue
+		}
+
+		from, ok := t.heldFrom[button]
+
+		if !ok {
+			from = now
+			t.heldFrom[button] = now
+		}
+
+		halves := now.Sub(from) / t.half
+
+		if halves%2 == 1 {
+			released |= button
+		}
+
+		wake = earliest(wake, from.Add((halves+1)*t.half))
+	}
+
+	if released != 0 {
+		buttons := report.Buttons &^ released
+
+		report.Buttons = buttons
+		report.Capture = buttons.Has(stadiacontroller.StadiaButtonCapture)
+		report.Assistant = buttons.Has(stadiacontroller.StadiaButtonAssistant)
+		report.SetButtons(report.GetButtons()&^released.Xbox360Buttons() | buttons.Xbox360Buttons())
+	}
+
+	return wake
+}
+
+// reset forgets the held buttons.
+func (t *turboButtons) reset() {
+	t.heldFrom = map[stadiacontroller.StadiaButtons]time.Time{}
+}
+
+// newTurboStep returns the step of -turbo, or nil if no button is turbo.
+func newTurboStep() (*reportStep, error) {
+	turbo, err := newTurboButtons(*turboButtonsList, *turboRate)
+
+	if err != nil || turbo == nil {
+		return nil, err
+	}
+
+	return timedStep(turbo.apply, turbo.reset), nil
+}
@@ -0,0 +1,249 @@
+package main
+
+import (
+	"errors"
+	"fmt"
+	"os"
+	"text/tabwriter"
+	"time"
+	"unsafe"
+
+	"github.com/71/stadiacontroller"
+	"golang.org/x/sys/windows"
+)
+
+var (
+	xinput = windows.NewLazySystemDLL("xinput1_4.dll")
+
+	procXInputGetState = xinput.NewProc("XInputGetState")
+)
+
+// xinputState is the XINPUT_STATE structure.
+type xinputState struct {
+	PacketNumber uint32
+	Buttons      uint16
+	LeftTrigger  uint8
+	RightTrigger uint8
+	ThumbLX      int16
+	ThumbLY      int16
+	ThumbRX      int16
+	ThumbRY      int16
+}
+
+// Maximum time to wait for Windows to make the emulated controller visible,
+// and for each report to be visible through XInput.
+const (
+	verifyPlugTimeout   = 5 * time.Second
+	verifyReportTimeout = 500 * time.Millisecond
+)
+
+func xinputGetState(index uint32) (xinputState, error) {
+	var state xinputState
+
+	if err := xinput.Load(); err != nil {
+		return state, err
+	}
+
+	r, _, _ := procXInputGetState.Call(uintptr(index), uintptr(unsafe.Pointer(&state)))
+
+	if r != 0 {
+		return state, windows.Errno(r)
+	}
+
+	return state, nil
+}
+
+// A verifyStep is a report sent to the emulated controller, which must then be
+// read back through XInput.
+type verifyStep struct {
+	name   string
+	report stadiacontroller.Xbox360ControllerReport
+}
+
+// verifySteps returns reports pressing each button, moving each stick to its
+// extremes and pulling each trigger progressively.
+func verifySteps() []verifyStep {
+	var steps []verifyStep
+
+	add := func(name string, update func(r *stadiacontroller.Xbox360ControllerReport)) {
+		report := stadiacontroller.NewXbox360ControllerReport()
+		update(&report)
+		steps = append(steps, verifyStep{name, report})
+	}
+
+	// The Guide button is not reported by XInputGetState, and is therefore not
+	// verified.
+	buttons := []struct {
+		name  string
+		shift int
+	}{
+		{"dpad up", stadiacontroller.Xbox360ControllerButtonUp},
+		{"dpad down", stadiacontroller.Xbox360ControllerButtonDown},
+		{"dpad left", stadiacontroller.Xbox360ControllerButtonLeft},
+		{"dpad right", stadiacontroller.Xbox360ControllerButtonRight},
+		{"start", stadiacontroller.Xbox360ControllerButtonStart},
+		{"back", stadiacontroller.Xbox360ControllerButtonBack},
+		{"left thumb", stadiacontroller.Xbox360ControllerButtonLeftThumb},
+		{"right thumb", stadiacontroller.Xbox360ControllerButtonRightThumb},
+		{"left shoulder", stadiacontroller.Xbox360ControllerButtonLeftShoulder},
+		{"right shoulder", stadiacontroller.Xbox360ControllerButtonRightShoulder},
+		{"a", stadiacontroller.Xbox360ControllerButtonA},
+		{"b", stadiacontroller.Xbox360ControllerButtonB},
+		{"x", stadiacontroller.Xbox360ControllerButtonX},
+		{"y", stadiacontroller.Xbox360ControllerButtonY},
+	}
+
+	for _, button := range buttons {
+		shift := button.shift
+		add("button "+button.name, func(r *stadiacontroller.Xbox360ControllerReport) { r.SetButton(shift) })
+	}
+
+	extremes := []struct {
+		name string
+		x, y int16
+	}{
+		{"left", -32768, 0},
+		{"right", 32767, 0},
+		{"down", 0, -32768},
+		{"up", 0, 32767},
+	}
+
+	for _, e := range extremes {
+		x, y := e.x, e.y
+		add("left stick "+e.name, func(r *stadiacontroller.Xbox360ControllerReport) { r.SetLeftThumb(x, y) })
+		add("right stick "+e.name, func(r *stadiacontroller.Xbox360ControllerReport) { r.SetRightThumb(x, y) })
+	}
+
+	for _, value := range []byte{64, 128, 192, 255} {
+		value := value
+		add(fmt.Sprintf("left trigger %d", value), func(r *stadiacontroller.Xbox360ControllerReport) { r.SetLeftTrigger(value) })
+		add(fmt.Sprintf("right trigger %d", value), func(r *stadiacontroller.Xbox360ControllerReport) { r.SetRightTrigger(value) })
+	}
+
+	add("released", func(r *stadiacontroller.Xbox360ControllerReport) {})
+
+	return steps
+}
+
+// xinputMismatch returns a description of the differences between the given
+// report and the given XInput state, or an empty string if they match.
+func xinputMismatch(report *stadiacontroller.Xbox360ControllerReport, state xinputState) string {
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	expected := xinputState{
+		Buttons:      report.GetButtons(),
+		LeftTrigger:  report.GetLeftTrigger(),
+		RightTrigger: report.GetRightTrigger(),
+		ThumbLX:      lx,
+		ThumbLY:      ly,
+		ThumbRX:      rx,
+		ThumbRY:      ry,
+	}
+
+	state.PacketNumber = 0
+
+	if state == expected {
+		return ""
+	}
+
+	return fmt.Sprintf("expected %+v, got %+v", expected, state)
+}
+
+// runVerify creates an emulated controller, sends it known reports and checks
+// that XInput sees them, printing the result of each check. The physical
+// controller is not needed.
+func runVerify() error {
+	emulator, err := stadiacontroller.NewEmulator(func(stadiacontroller.Vibration) {})
+
+	if err != nil {
+		return fmt.Errorf("unable to start ViGEm client: %w", err)
+	}
+
+	defer emulator.Close()
+
+	x360, err := emulator.CreateXbox360Controller()
+
+	if err != nil {
+		return fmt.Errorf("unable to create emulated Xbox 360 controller: %w", err)
+	}
+
+	defer x360.Close()
+
+	if err = x360.Connect(); err != nil {
+		return fmt.Errorf("unable to connect to emulated Xbox 360 controller: %w", err)
+	}
+
+	defer x360.Disconnect()
+
+	table := tabwriter.NewWriter(os.Stdout, 0, 8, 2, ' ', 0)
+	fmt.Fprintln(table, "CHECK\tRESULT\tDETAILS")
+
+	defer table.Flush()
+
+	// Wait for Windows to assign an XInput slot to the controller.
+	var index uint32
+	var state xinputState
+	deadline := time.Now().Add(verifyPlugTimeout)
+
+	for {
+		if index, err = x360.UserIndex(); err == nil {
+			if state, err = xinputGetState(index); err == nil {
+				break
+			}
+		}
+		if time.Now().After(deadline) {
+			fmt.Fprintf(table, "visible to XInput\tFAIL\t%v\n", err)
+
+			return errors.New("the emulated controller is not visible to games")
+		}
+
+		time.Sleep(100 * time.Millisecond)
+	}
+
+	fmt.Fprintf(table, "visible to XInput\tPASS\tuser index %d\n", index)
+
+	failures := 0
+
+	for _, step := range verifySteps() {
+		if err := x360.Send(&step.report); err != nil {
+			fmt.Fprintf(table, "%s\tFAIL\tcannot send report: %v\n", step.name, err)
+			failures++
+			continue
+		}
+
+		// XInput may not have seen the report yet, so retry until it matches
+		// or the timeout expires.
+		deadline := time.Now().Add(verifyReportTimeout)
+		mismatch := ""
+
+		for {
+			state, err = xinputGetState(index)
+
+			if err != nil {
+				mismatch = fmt.Sprintf("cannot read state: %v", err)
+			} else {
+				mismatch = xinputMismatch(&step.report, state)
+			}
+
+			if mismatch == "" || time.Now().After(deadline) {
+				break
+			}
+
+			time.Sleep(5 * time.Millisecond)
+		}
+
+		if mismatch != "" {
+			fmt.Fprintf(table, "%s\tFAIL\t%s\n", step.name, mismatch)
+			failures++
+		} else {
+			fmt.Fprintf(table, "%s\tPASS\t\n", step.name)
+		}
+	}
+
+	if failures > 0 {
+		return fmt.Errorf("%d checks failed", failures)
+	}
+
+	return nil
+}
@@ -0,0 +1,202 @@
+package main
+
+import (
+	"fmt"
+	"log"
+	"math"
+	"sync"
+	"sync/atomic"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Mappings of the motors requested by the game to the motors of the
+// controller.
+const (
+	rumbleMapDefault = "default"
+	rumbleMapSwap    = "swap"
+	rumbleMapMerge   = "merge"
+)
+
+// Highest strength of the vibrations, as a percentage.
+const maxVibrationStrength = 400
+
+// Whether the vibrations of the game are currently ignored, as set by the
+// vibration command.
+var vibrationsMutedFlag int32
+
+// vibrationsMuted returns whether the vibrations of the game are currently
+// ignored.
+func vibrationsMuted() bool {
+	return atomic.LoadInt32(&vibrationsMutedFlag) != 0
+}
+
+// setVibrationsMuted starts or stops ignoring the vibrations of the game.
+func setVibrationsMuted(muted bool) {
+	var value int32
+
+	if muted {
+		value = 1
+	}
+
+	atomic.StoreInt32(&vibrationsMutedFlag, value)
+}
+
+// vibrationResponse maps the motors requested by the game to the motors of the
+// controller, then scales the intensity of each motor of the controller by a
+// percentage.
+type vibrationResponse struct {
+	mapping      string
+	large, small uint
+}
+
+// newVibrationResponse returns the response configured by the command line, or
+// nil if vibrations are forwarded unchanged. -no-vibration is a strength of 0.
+func newVibrationResponse() (*vibrationResponse, error) {
+	large := *vibrationStrengthLarge
+	small := large
+
+	if isFlagSet("vibration-strength-small") {
+		small = *vibrationStrengthSmall
+	}
+	if large > maxVibrationStrength || small > maxVibrationStrength {
+		return nil, fmt.Errorf("vibration strength must be between 0 and %d", maxVibrationStrength)
+	}
+
+	switch *rumbleMap {
+	case rumbleMapDefault, rumbleMapSwap, rumbleMapMerge:
+	default:
+		return nil, fmt.Errorf("unknown rumble map %q, expected default, swap or merge", *rumbleMap)
+	}
+
+	if *noVibration {
+		large, small = 0, 0
+	}
+	if *rumbleMap == rumbleMapDefault && large == 100 && small == 100 {
+		return nil, nil
+	}
+
+	return &vibrationResponse{*rumbleMap, large, small}, nil
+}
+
+// disabled returns whether the vibrations of the game are not sent to the
+// controller at all.
+func (r *vibrationResponse) disabled() bool {
+	return r.large == 0 && r.small == 0
+}
+
+// apply maps and scales the intensities of the given vibration.
+func (r *vibrationResponse) apply(vibration stadiacontroller.Vibration) stadiacontroller.Vibration {
+	vibration = mapRumble(r.mapping, vibration)
+
+	return stadiacontroller.Vibration{
+		LargeMotor: scaleIntensity(vibration.LargeMotor, r.large),
+		SmallMotor: scaleIntensity(vibration.SmallMotor, r.small),
+	}
+}
+
+// mapRumble returns the vibration of the motors of the controller for the
+// given vibration requested by the game. With swap, the large motor of the
+// controller plays the small motor of the game and conversely. With merge,
+// both play the strongest motor of the game, for games which only use the
+// small motor and barely make the controller vibrate.
+func mapRumble(mapping string, vibration stadiacontroller.Vibration) stadiacontroller.Vibration {
+	switch mapping {
+	case rumbleMapSwap:
+		return stadiacontroller.Vibration{LargeMotor: vibration.SmallMotor, SmallMotor: vibration.LargeMotor}
+
+	case rumbleMapMerge:
+		intensity := vibration.LargeMotor
+
+		if vibration.SmallMotor > intensity {
+			intensity = vibration.SmallMotor
+		}
+
+		return stadiacontroller.Vibration{LargeMotor: intensity, SmallMotor: intensity}
+
+	default:
+		return vibration
+	}
+}
+
+// scaleIntensity returns the given intensity scaled by percent, rounded to the
+// nearest intensity and clamped to 255. Intensities which would be rounded to
+// zero are rounded to 1 instead, so that weak pulses of the game still reach
+// the controller.
+func scaleIntensity(intensity byte, percent uint) byte {
+	if intensity == 0 || percent == 0 {
+		return 0
+	}
+
+	scaled := (uint(intensity)*percent + 50) / 100
+
+	switch {
+	case scaled == 0:
+		return 1
+	case scaled > math.MaxUint8:
+		return math.MaxUint8
+	default:
+		return byte(scaled)
+	}
+}
+
+// rumbleWatchdog stops the motors of the controller when the game did not
+// request any vibration for a while, e.g. because it crashed while the
+// controller was vibrating.
+type rumbleWatchdog struct {
+	controller *stadiacontroller.StadiaController
+	max        time.Duration
+
+	mu    sync.Mutex
+	timer *time.Timer
+}
+
+func newRumbleWatchdog(controller *stadiacontroller.StadiaController, max time.Duration) (*rumbleWatchdog, error) {
+	if max < 0 {
+		return nil, fmt.Errorf("maximum rumble duration cannot be negative")
+	}
+	if max == 0 {
+		return nil, nil
+	}
+
+	return &rumbleWatchdog{controller: controller, max: max}, nil
+}
+
+// update restarts the watchdog after the given vibration was requested. The
+// watchdog only runs while the motors vibrate.
+func (w *rumbleWatchdog) update(vibration stadiacontroller.Vibration) {
+	w.mu.Lock()
+	defer w.mu.Unlock()
+
+	if w.timer != nil {
+		w.timer.Stop()
+		w.timer = nil
+	}
+
+	if vibration.LargeMotor == 0 && vibration.SmallMotor == 0 {
+		return
+	}
+
+	var timer *time.Timer
+
+	timer = time.AfterFunc(w.max, func() {
+		w.mu.Lock()
+		expired := w.timer == timer
+		if expired {
+			w.timer = nil
+		}
+		w.mu.Unlock()
+
+		if !expired {
+			return
+		}
+
+		log.Printf("warning: no vibration requested for %v, stopping the motors", w.max)
+
+		w.controller.VibrateAsync(0, 0)
+		setRumbleIntensity(stadiacontroller.Vibration{})
+	})
+
+	w.timer = timer
+}
@@ -0,0 +1,92 @@
+package main
+
+import (
+	"math"
+	"testing"
+
+	"github.com/71/stadiacontroller"
+)
+
+func TestScaleIntensity(t *testing.T) {
+	for _, percent := range []uint{0, 1, 50, 99, 100, 101, 150, 255, 399, maxVibrationStrength} {
+		previous := byte(0)
+
+		for i := 0; i <= math.MaxUint8; i++ {
+			intensity := byte(i)
+			scaled := scaleIntensity(intensity, percent)
+
+			expected := math.Floor(float64(intensity)*float64(percent)/100 + 0.5)
+
+			switch {
+			case intensity == 0 || percent == 0:
+				expected = 0
+			case expected == 0:
+				expected = 1
+			case expected > math.MaxUint8:
+				expected = math.MaxUint8
+			}
+
+			if float64(scaled) != expected {
+				t.Errorf("scaleIntensity(%d, %d) = %d, expected %v", intensity, percent, scaled, expected)
+			}
+
+			// Intensities too high for the motors are clamped, not wrapped.
+			if scaled < previous {
+				t.Errorf("scaleIntensity(%d, %d) = %d, less than for %d", intensity, percent, scaled, intensity-1)
+			}
+
+			previous = scaled
+		}
+	}
+}
+
+func TestMapRumble(t *testing.T) {
+	requested := []stadiacontroller.Vibration{
+		{LargeMotor: 0, SmallMotor: 0},
+		{LargeMotor: 200, SmallMotor: 0},
+		{LargeMotor: 0, SmallMotor: 90},
+		{LargeMotor: 30, SmallMotor: 255},
+	}
+
+	for _, v := range requested {
+		if mapped := mapRumble(rumbleMapDefault, v); mapped != v {
+			t.Errorf("default mapping of %+v is %+v", v, mapped)
+		}
+
+		if mapped := mapRumble(rumbleMapSwap, v); mapped.LargeMotor != v.SmallMotor || mapped.SmallMotor != v.LargeMotor {
+			t.Errorf("swapped mapping of %+v is %+v", v, mapped)
+		}
+
+		strongest := v.LargeMotor
+
+		if v.SmallMotor > strongest {
+			strongest = v.SmallMotor
+		}
+
+		if mapped := mapRumble(rumbleMapMerge, v); mapped.LargeMotor != strongest || mapped.SmallMotor != strongest {
+			t.Errorf("merged mapping of %+v is %+v, expected both motors at %d", v, mapped, strongest)
+		}
+	}
+}
+
+func TestVibrationResponseApply(t *testing.T) {
+	// Gains apply to the motors of the controller, after mapping: a game which
+	// only uses its small motor drives the large one of the controller here.
+	r := &vibrationResponse{mapping: rumbleMapSwap, large: 300, small: 50}
+	v := r.apply(stadiacontroller.Vibration{LargeMotor: 0, SmallMotor: 100})
+
+	if v.LargeMotor != 255 || v.SmallMotor != 0 {
+		t.Errorf("swapped and scaled vibration is %+v, expected 255 and 0", v)
+	}
+
+	r = &vibrationResponse{mapping: rumbleMapMerge, large: 100, small: 50}
+	v = r.apply(stadiacontroller.Vibration{LargeMotor: 1, SmallMotor: 101})
+
+	if v.LargeMotor != 101 || v.SmallMotor != 51 {
+		t.Errorf("merged and scaled vibration is %+v, expected 101 and 51", v)
+	}
+
+	if !(&vibrationResponse{mapping: rumbleMapMerge}).disabled() {
+		t.Errorf("response without strength is not disabled")
+	}
+}
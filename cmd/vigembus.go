@@ -0,0 +1,381 @@
+package main
+
+import (
+	"errors"
+	"fmt"
+	"log"
+	"sync"
+	"syscall"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// Delays between two attempts to connect to the ViGEm bus again.
+const (
+	busRetryMinDelay = 500 * time.Millisecond
+	busRetryMaxDelay = 10 * time.Second
+)
+
+// busController is the emulated controller selected by -emulate, plugged into
+// the ViGEm bus. When the bus becomes unavailable, e.g. because its driver was
+// restarted, it connects to the bus again in the background and plugs in a new
+// emulated controller; reports sent in the meantime are dropped.
+//
+// Reports which would not change the state of the emulated controller are not
+// sent, since the controller sends the same report periodically.
+type busController struct {
+	onVibration func(stadiacontroller.Vibration)
+	retry       bool
+	done        chan struct{}
+
+	// Called with the error of the bus when it becomes unavailable, and with
+	// nil when it is available again, if set before the first call to Send.
+	onBusChange func(err error)
+
+	mu        sync.Mutex
+	emulator  *stadiacontroller.Emulator
+	pad       stadiacontroller.EmulatedController
+	closed    bool
+	unplugged bool
+	last      stadiacontroller.Xbox360ControllerReport
+	sent      bool
+
+	// battery is shown by each controller plugged in once set.
+	battery    stadiacontroller.BatteryStatus
+	batterySet bool
+}
+
+// newBusController connects to the ViGEm bus and plugs in the emulated
+// controller, which calls onVibration when a game makes it vibrate. Unless
+// retry is set, errors of the bus are returned by Send instead.
+//
+// If unplugged is set, the controller is only plugged in by Connect, and the
+// bus is only checked to be available.
+func newBusController(onVibration func(stadiacontroller.Vibration), retry, unplugged bool) (*busController, error) {
+	if unplugged {
+		emulator, err := stadiacontroller.NewEmulator(onVibration)
+
+		if err != nil {
+			return nil, fmt.Errorf("unable to start ViGEm client: %w", err)
+		}
+
+		emulator.Close()
+
+		return &busController{
+			onVibration: onVibration,
+			retry:       retry,
+			done:        make(chan struct{}),
+			unplugged:   true,
+		}, nil
+	}
+
+	emulator, pad, err := plugEmulatedController(onVibration)
+
+	if err != nil {
+		return nil, err
+	}
+
+	return &busController{
+		onVibration: onVibration,
+		retry:       retry,
+		done:        make(chan struct{}),
+		emulator:    emulator,
+		pad:         pad,
+	}, nil
+}
+
+// plugEmulatedController connects to the ViGEm bus and plugs in the controller
+// selected by -emulate.
+func plugEmulatedController(onVibration func(stadiacontroller.Vibration)) (*stadiacontroller.Emulator, stadiacontroller.EmulatedController, error) {
+	emulator, err := stadiacontroller.NewEmulator(onVibration)
+
+	if err != nil {
+		return nil, nil, fmt.Errorf("unable to start ViGEm client: %w", err)
+	}
+
+	pad, err := createEmulatedController(emulator)
+
+	if err != nil {
+		emulator.Close()
+		return nil, nil, fmt.Errorf("unable to create emulated controller: %w", err)
+	}
+
+	if err := pad.Connect(); err != nil {
+		pad.Close()
+		emulator.Close()
+		return nil, nil, fmt.Errorf("unable to connect to emulated controller: %w", err)
+	}
+
+	return emulator, pad, nil
+}
+
+// unplugEmulatedController unplugs the given controller and disconnects from
+// the bus, ignoring errors since the bus may be gone.
+func unplugEmulatedController(emulator *stadiacontroller.Emulator, pad stadiacontroller.EmulatedController) {
+	pad.Disconnect()
+	pad.Close()
+	emulator.Close()
+}
+
+// isBusError returns whether the given error of the emulated controller was
+// caused by the bus, rather than by the report: either an error of the bus, or
+// a failed call to its driver.
+func isBusError(err error) bool {
+	var errno syscall.Errno
+
+	return errors.Is(err, stadiacontroller.ErrBusUnavailable) || errors.As(err, &errno)
+}
+
+// Connect plugs the controller in again after Disconnect, and does nothing
+// otherwise, since the controller is plugged in as soon as it is created.
+func (b *busController) Connect() error {
+	b.mu.Lock()
+	unplugged := b.unplugged && !b.closed
+	b.unplugged = false
+	b.mu.Unlock()
+
+	if !unplugged {
+		return nil
+	}
+
+	emulator, pad, err := plugEmulatedController(b.onVibration)
+
+	if err != nil {
+		if !b.retry {
+			return err
+		}
+
+		log.Printf("warning: %v, connecting to the ViGEm bus again", err)
+		go b.reconnect(nil, nil)
+
+		return nil
+	}
+
+	if !b.plugged(emulator, pad) {
+		unplugEmulatedController(emulator, pad)
+	}
+
+	return nil
+}
+
+// Disconnect unplugs the controller and disconnects from the bus until Connect
+// is called. Reports are dropped in the meantime.
+func (b *busController) Disconnect() error {
+	b.mu.Lock()
+	emulator, pad := b.emulator, b.pad
+	b.emulator, b.pad = nil, nil
+	b.unplugged = true
+	b.mu.Unlock()
+
+	if pad != nil {
+		unplugEmulatedController(emulator, pad)
+	}
+
+	return nil
+}
+
+// plugged stores the given controller, plugged in by Connect or reconnect,
+// and returns whether it is used. It is not if the controller was closed or
+// unplugged since, or if another controller was plugged in meanwhile.
+func (b *busController) plugged(emulator *stadiacontroller.Emulator, pad stadiacontroller.EmulatedController) bool {
+	b.mu.Lock()
+	defer b.mu.Unlock()
+
+	if b.closed || b.unplugged || b.pad != nil {
+		return false
+	}
+
+	b.emulator, b.pad = emulator, pad
+
+	// The new controller starts released: send the next report even if it
+	// did not change.
+	b.sent = false
+
+	if reporter, ok := pad.(stadiacontroller.BatteryReporter); ok && b.batterySet {
+		if err := reporter.SetBattery(b.battery); err != nil {
+			log.Printf("warning: cannot show the battery on the emulated controller: %v", err)
+		}
+	}
+
+	return true
+}
+
+// Close unplugs the controller, stops connecting to the bus again, and
+// disconnects from it.
+func (b *busController) Close() error {
+	b.mu.Lock()
+	emulator, pad := b.emulator, b.pad
+	closed := b.closed
+	b.emulator, b.pad = nil, nil
+	b.closed = true
+	b.mu.Unlock()
+
+	if closed {
+		return nil
+	}
+
+	close(b.done)
+
+	if pad != nil {
+		unplugEmulatedController(emulator, pad)
+	}
+
+	return nil
+}
+
+// Send updates the state of the controller seen by games. It drops the report
+// while connecting to the bus again.
+func (b *busController) Send(report *stadiacontroller.Xbox360ControllerReport) error {
+	b.mu.Lock()
+	emulator, pad := b.emulator, b.pad
+	unchanged := b.sent && sameEmulatedState(&b.last, report)
+	b.last, b.sent = *report, pad != nil
+	b.mu.Unlock()
+
+	if pad == nil || unchanged {
+		return nil
+	}
+
+	err := pad.Send(report)
+
+	if err == nil || !b.retry || !isBusError(err) {
+		return err
+	}
+
+	log.Printf("warning: cannot send report to the ViGEm bus (%v), connecting to it again", err)
+
+	b.mu.Lock()
+	lost := b.pad == pad
+	if lost {
+		b.emulator, b.pad = nil, nil
+	}
+	b.sent = false
+	b.mu.Unlock()
+
+	if lost {
+		if b.onBusChange != nil {
+			b.onBusChange(err)
+		}
+
+		go b.reconnect(emulator, pad)
+	}
+
+	return nil
+}
+
+// SetBattery shows the given battery status on the emulated controller, if it
+// can show it, and on the controllers plugged in later.
+func (b *busController) SetBattery(status stadiacontroller.BatteryStatus) error {
+	b.mu.Lock()
+	pad := b.pad
+	b.battery, b.batterySet = status, true
+	b.mu.Unlock()
+
+	if reporter, ok := pad.(stadiacontroller.BatteryReporter); ok {
+		return reporter.SetBattery(status)
+	}
+
+	return nil
+}
+
+// reconnect unplugs the given controller if any, then connects to the bus
+// again until it succeeds or the controller is closed or unplugged.
+func (b *busController) reconnect(emulator *stadiacontroller.Emulator, pad stadiacontroller.EmulatedController) {
+	if pad != nil {
+		unplugEmulatedController(emulator, pad)
+	}
+
+	for delay := busRetryMinDelay; ; {
+		select {
+		case <-time.After(delay):
+		case <-b.done:
+			return
+		}
+
+		// Connect plugs the controller in again once unplugged.
+		b.mu.Lock()
+		unplugged := b.unplugged
+		b.mu.Unlock()
+
+		if unplugged {
+			return
+		}
+
+		emulator, pad, err := plugEmulatedController(b.onVibration)
+
+		if err != nil {
+			if delay *= 2; delay > busRetryMaxDelay {
+				delay = busRetryMaxDelay
+			}
+
+			log.Printf("warning: %v, retrying in %v", err, delay)
+			continue
+		}
+
+		if !b.plugged(emulator, pad) {
+			unplugEmulatedController(emulator, pad)
+			return
+		}
+
+		log.Printf("connected to the ViGEm bus again")
+
+		if b.onBusChange != nil {
+			b.onBusChange(nil)
+		}
+
+		return
+	}
+}
+
+// sameEmulatedState returns whether the given reports give the same state to
+// the emulated controller, ignoring the Stadia buttons they were built from.
+func sameEmulatedState(a, b *stadiacontroller.Xbox360ControllerReport) bool {
+	alx, aly := a.GetLeftThumb()
+	arx, ary := a.GetRightThumb()
+	blx, bly := b.GetLeftThumb()
+	brx, bry := b.GetRightThumb()
+
+	return a.GetButtons() == b.GetButtons() &&
+		a.GetLeftTrigger() == b.GetLeftTrigger() && a.GetRightTrigger() == b.GetRightTrigger() &&
+		alx == blx && aly == bly && arx == brx && ary == bry
+}
+
+// newEmulatedPad returns the emulated controller selected by the command line,
+// which calls onVibration when a game makes it vibrate: a ViGEm controller, or
+// a controller printing or discarding the reports with -dry-run. Errors of the
+// bus are shown by out.
+func newEmulatedPad(onVibration func(stadiacontroller.Vibration), out *outputs) (stadiacontroller.EmulatedController, error) {
+	if *maxUpdateRate < 0 {
+		return nil, errors.New("maximum update rate cannot be negative")
+	}
+
+	var pad stadiacontroller.EmulatedController
+
+	switch {
+	case *dryRun && out.stdoutJSON:
+		log.Printf("dry run: writing JSON events instead of emulating a controller")
+		pad = discardController{}
+	case *dryRun && out.view != nil:
+		log.Printf("dry run: showing reports instead of emulating a controller")
+		pad = discardController{}
+	case *dryRun:
+		log.Printf("dry run: printing reports instead of emulating a controller")
+		pad = &reportPrinter{}
+	default:
+		bus, err := newBusController(onVibration, !*noVigemRetry, !*persistentTarget)
+
+		if err != nil {
+			return nil, err
+		}
+
+		bus.onBusChange = out.setBusError
+		pad = bus
+	}
+
+	if *maxUpdateRate > 0 {
+		pad = newThrottledController(pad, *maxUpdateRate)
+	}
+
+	return pad, nil
+}
@@ -0,0 +1,128 @@
+package main
+
+import (
+	"log"
+	"sync"
+	"time"
+	"unsafe"
+
+	"github.com/71/stadiacontroller"
+	"golang.org/x/sys/windows"
+)
+
+var (
+	user32 = windows.NewLazySystemDLL("user32.dll")
+
+	procSendInput = user32.NewProc("SendInput")
+)
+
+// Constants used to send keys with SendInput.
+const (
+	inputKeyboard       = 1
+	keyEventExtendedKey = 0x0001
+	keyEventKeyUp       = 0x0002
+	vkVolumeDown        = 0xAE
+	vkVolumeUp          = 0xAF
+)
+
+// Delay before a held key repeats, and interval between repetitions, as with
+// the default keyboard settings of Windows.
+const (
+	keyRepeatDelay    = 500 * time.Millisecond
+	keyRepeatInterval = 100 * time.Millisecond
+)
+
+// keyInput is the INPUT structure with its KEYBDINPUT member, padded to the
+// size of its largest member, MOUSEINPUT.
+type keyInput struct {
+	typ       uint32
+	vk        uint16
+	scan      uint16
+	flags     uint32
+	time      uint32
+	extraInfo uintptr
+	_         [8]byte
+}
+
+// pressKey sends a press and a release of the given virtual key.
+func pressKey(vk uint16) {
+	inputs := [2]keyInput{
+		{typ: inputKeyboard, vk: vk, flags: keyEventExtendedKey},
+		{typ: inputKeyboard, vk: vk, flags: keyEventExtendedKey | keyEventKeyUp},
+	}
+
+	r, _, err := procSendInput.Call(uintptr(len(inputs)), uintptr(unsafe.Pointer(&inputs[0])), unsafe.Sizeof(inputs[0]))
+
+	if r != uintptr(len(inputs)) {
+		log.Printf("cannot send key: %v", err)
+	}
+}
+
+// volumeKeys presses the volume keys of the keyboard while the volume buttons
+// of a headset plugged into the controller are held.
+type volumeKeys struct {
+	mu      sync.Mutex
+	pressed stadiacontroller.StadiaButtons
+	repeat  *time.Timer
+	changes int
+}
+
+// update sets the buttons currently pressed.
+func (v *volumeKeys) update(buttons stadiacontroller.StadiaButtons) {
+	buttons &= stadiacontroller.StadiaButtonVolumeUp | stadiacontroller.StadiaButtonVolumeDown
+
+	v.mu.Lock()
+	defer v.mu.Unlock()
+
+	if buttons == v.pressed {
+		return
+	}
+
+	v.pressed = buttons
+	v.changes++
+
+	if v.repeat != nil {
+		v.repeat.Stop()
+		v.repeat = nil
+	}
+
+	vk := v.key()
+
+	if vk == 0 {
+		return
+	}
+
+	go pressKey(vk)
+
+	// Repetitions stop once the buttons change, even if the timer already
+	// fired.
+	changes := v.changes
+
+	var repeat func()
+	repeat = func() {
+		v.mu.Lock()
+		defer v.mu.Unlock()
+
+		if v.changes != changes {
+			return
+		}
+
+		pressKey(vk)
+		v.repeat = time.AfterFunc(keyRepeatInterval, repeat)
+	}
+
+	v.repeat = time.AfterFunc(keyRepeatDelay, repeat)
+}
+
+// key returns the key to press for the pressed buttons, or 0 if none or both
+// are pressed. v.mu must be held.
+func (v *volumeKeys) key() uint16 {
+	switch v.pressed {
+	case stadiacontroller.StadiaButtonVolumeUp:
+		return vkVolumeUp
+	case stadiacontroller.StadiaButtonVolumeDown:
+		return vkVolumeDown
+	default:
+		return 0
+	}
+}
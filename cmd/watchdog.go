@@ -0,0 +1,77 @@
+package main
+
+import (
+	"log"
+	"runtime"
+	"sync/atomic"
+	"time"
+)
+
+// A loopStep is a step of the input loop, as tracked by the watchdog.
+type loopStep int32
+
+const (
+	stepWaiting loopStep = iota
+	stepForwarding
+	stepRunningCommands
+)
+
+var loopStepNames = [...]string{
+	stepWaiting:         "waiting for a report",
+	stepForwarding:      "forwarding a report",
+	stepRunningCommands: "running button commands",
+}
+
+// watchdog detects the input loop getting stuck in one of its steps, e.g. in
+// a blocking call to ViGEm. Waiting for a report is never considered stuck,
+// since the controller does not send reports while it is idle.
+//
+// All methods can be called on a nil watchdog, in which case they do nothing.
+type watchdog struct {
+	step  int32
+	since int64
+}
+
+// newWatchdog starts a watchdog which logs a diagnostic when the input loop
+// stays in the same step for longer than the given timeout.
+func newWatchdog(timeout time.Duration) *watchdog {
+	w := &watchdog{since: time.Now().UnixNano()}
+
+	go w.monitor(timeout)
+
+	return w
+}
+
+// enter records that the input loop entered the given step.
+func (w *watchdog) enter(step loopStep) {
+	if w == nil {
+		return
+	}
+
+	atomic.StoreInt64(&w.since, time.Now().UnixNano())
+	atomic.StoreInt32(&w.step, int32(step))
+}
+
+func (w *watchdog) monitor(timeout time.Duration) {
+	reported := false
+
+	for range time.Tick(timeout / 2) {
+		step := loopStep(atomic.LoadInt32(&w.step))
+		stuckFor := time.Since(time.Unix(0, atomic.LoadInt64(&w.since)))
+
+		if step == stepWaiting || stuckFor < timeout {
+			reported = false
+			continue
+		}
+		if reported {
+			continue
+		}
+
+		reported = true
+
+		buf := make([]byte, 1<<20)
+		n := runtime.Stack(buf, true)
+
+		log.Printf("watchdog: input loop stuck %s for %v; goroutines:\n%s", loopStepNames[step], stuckFor.Round(time.Millisecond), buf[:n])
+	}
+}
@@ -0,0 +1,495 @@
+package main
+
+import (
+	"bufio"
+	"crypto/rand"
+	"crypto/sha1"
+	"encoding/base64"
+	"encoding/binary"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"io"
+	"log"
+	"net"
+	"net/http"
+	"net/url"
+	"strings"
+	"sync"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+// GUID appended to the key of a WebSocket handshake, as per RFC 6455.
+const wsGUID = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11"
+
+// Number of messages buffered for each client, after which messages to that
+// client are dropped until it catches up.
+const wsClientBacklog = 64
+
+// WebSocket opcodes used by the server.
+const (
+	wsOpText  = 0x1
+	wsOpClose = 0x8
+	wsOpPing  = 0x9
+	wsOpPong  = 0xA
+)
+
+// wsEvent is a message sent to WebSocket clients, encoded as JSON.
+type wsEvent struct {
+	Type string `json:"type"`
+
+	// Set for "connected" events.
+	Transport string `json:"transport,omitempty"`
+
+	// Set for "button" events.
+	Button  string `json:"button,omitempty"`
+	Pressed *bool  `json:"pressed,omitempty"`
+
+	// Set for "state" events.
+	Report *stadiacontroller.Xbox360ControllerReport `json:"report,omitempty"`
+
+	// Set for "rumble" events.
+	LargeMotor *byte `json:"large_motor,omitempty"`
+	SmallMotor *byte `json:"small_motor,omitempty"`
+
+	// Set for "battery" events, unless the battery status became unknown.
+	Battery *batteryJSON `json:"battery,omitempty"`
+}
+
+// wsServer pushes the events of the controller to WebSocket clients, e.g. to
+// display its inputs in a stream overlay.
+type wsServer struct {
+	server         *http.Server
+	sampleInterval time.Duration
+
+	mu         sync.Mutex
+	clients    map[*wsClient]struct{}
+	buttons    stadiacontroller.StadiaButtons
+	lastSample time.Time
+}
+
+type wsClient struct {
+	conn     net.Conn
+	writeMu  sync.Mutex
+	messages chan []byte
+	dropped  int
+}
+
+func (c *wsClient) write(opcode byte, payload []byte) error {
+	c.writeMu.Lock()
+	defer c.writeMu.Unlock()
+
+	return writeWSFrame(c.conn, opcode, payload, false)
+}
+
+// newWSServer starts a WebSocket server listening on the given address, which
+// pushes the state of the controller at most sampleRate times per second, and
+// serves the web UI, the given control endpoints and the given metrics. The
+// server only listens on loopback addresses unless allowRemote is true.
+func newWSServer(addr string, sampleRate float64, allowRemote bool, control, metrics http.Handler) (*wsServer, error) {
+	host, port, err := net.SplitHostPort(addr)
+
+	if err != nil {
+		return nil, err
+	}
+
+	if host == "" {
+		host = "localhost"
+	}
+
+	if !allowRemote && !isLoopback(host) {
+		return nil, fmt.Errorf("refusing to listen on non-loopback address %s without -ws-allow-remote", host)
+	}
+
+	listener, err := net.Listen("tcp", net.JoinHostPort(host, port))
+
+	if err != nil {
+		return nil, err
+	}
+
+	s := &wsServer{clients: map[*wsClient]struct{}{}}
+
+	if sampleRate > 0 {
+		s.sampleInterval = time.Duration(float64(time.Second) / sampleRate)
+	}
+
+	mux := http.NewServeMux()
+	mux.HandleFunc("/", serveWebUI)
+	mux.HandleFunc("/events", s.serveEvents)
+	mux.Handle("/control/", control)
+	mux.Handle("/metrics", metrics)
+
+	s.server = &http.Server{Handler: mux}
+
+	go s.server.Serve(listener)
+
+	log.Printf("WebSocket server listening on ws://%s/events, web UI at http://%s/", listener.Addr(), listener.Addr())
+
+	return s, nil
+}
+
+func isLoopback(host string) bool {
+	if host == "localhost" {
+		return true
+	}
+
+	ip := net.ParseIP(host)
+
+	return ip != nil && ip.IsLoopback()
+}
+
+// Close stops the server and disconnects all clients.
+func (s *wsServer) Close() error {
+	err := s.server.Close()
+
+	s.mu.Lock()
+	for client := range s.clients {
+		client.conn.Close()
+	}
+	s.mu.Unlock()
+
+	return err
+}
+
+// setConnected notifies clients that the controller was connected over the
+// given transport, or disconnected.
+func (s *wsServer) setConnected(connected bool, transport stadiacontroller.Transport) {
+	if connected {
+		s.broadcast(wsEvent{Type: "connected", Transport: transport.String()})
+	} else {
+		s.broadcast(wsEvent{Type: "disconnected"})
+	}
+}
+
+// rumble notifies clients of a change of the vibration of the controller.
+func (s *wsServer) rumble(vibration stadiacontroller.Vibration) {
+	s.broadcast(wsEvent{Type: "rumble", LargeMotor: &vibration.LargeMotor, SmallMotor: &vibration.SmallMotor})
+}
+
+// setBattery notifies clients of a change of the battery status, read at the
+// given time.
+func (s *wsServer) setBattery(status stadiacontroller.BatteryStatus, readAt time.Time) {
+	s.broadcast(wsEvent{Type: "battery", Battery: newBatteryJSON(status, readAt)})
+}
+
+// report notifies clients of the buttons pressed and released since the last
+// report, and of the state of the controller if the last one was sent long
+// enough ago.
+func (s *wsServer) report(report *stadiacontroller.Xbox360ControllerReport) {
+	s.mu.Lock()
+	previous := s.buttons
+	s.buttons = report.Buttons
+	now := time.Now()
+	sample := now.Sub(s.lastSample) >= s.sampleInterval
+	if sample {
+		s.lastSample = now
+	}
+	s.mu.Unlock()
+
+	if changed := previous ^ report.Buttons; changed != 0 {
+		for button := stadiacontroller.StadiaButtons(1); button != 0 && button <= changed; button <<= 1 {
+			if changed&button != 0 {
+				pressed := report.Buttons.Has(button)
+				s.broadcast(wsEvent{Type: "button", Button: button.String(), Pressed: &pressed})
+			}
+		}
+	}
+
+	if sample {
+		s.broadcast(wsEvent{Type: "state", Report: report})
+	}
+}
+
+// broadcast sends the given event to all clients, dropping it for clients
+// which are too far behind.
+func (s *wsServer) broadcast(event wsEvent) {
+	s.mu.Lock()
+	defer s.mu.Unlock()
+
+	if len(s.clients) == 0 {
+		return
+	}
+
+	message, err := json.Marshal(event)
+
+	if err != nil {
+		log.Printf("cannot encode WebSocket event: %v", err)
+		return
+	}
+
+	for client := range s.clients {
+		select {
+		case client.messages <- message:
+		default:
+			client.dropped++
+		}
+	}
+}
+
+func (s *wsServer) serveEvents(w http.ResponseWriter, r *http.Request) {
+	key := r.Header.Get("Sec-WebSocket-Key")
+
+	if key == "" || !strings.EqualFold(r.Header.Get("Upgrade"), "websocket") {
+		http.Error(w, "expected a WebSocket handshake", http.StatusBadRequest)
+		return
+	}
+
+	hijacker, ok := w.(http.Hijacker)
+
+	if !ok {
+		http.Error(w, "cannot upgrade connection", http.StatusInternalServerError)
+		return
+	}
+
+	conn, rw, err := hijacker.Hijack()
+
+	if err != nil {
+		return
+	}
+
+	hash := sha1.Sum([]byte(key + wsGUID))
+
+	fmt.Fprintf(rw, "HTTP/1.1 101 Switching Protocols\r\n"+
+		"Upgrade: websocket\r\n"+
+		"Connection: Upgrade\r\n"+
+		"Sec-WebSocket-Accept: %s\r\n\r\n", base64.StdEncoding.EncodeToString(hash[:]))
+
+	if err := rw.Flush(); err != nil {
+		conn.Close()
+		return
+	}
+
+	client := &wsClient{conn: conn, messages: make(chan []byte, wsClientBacklog)}
+
+	s.mu.Lock()
+	s.clients[client] = struct{}{}
+	s.mu.Unlock()
+
+	go s.writeLoop(client)
+	s.readLoop(client, rw.Reader)
+
+	s.mu.Lock()
+	delete(s.clients, client)
+	dropped := client.dropped
+	s.mu.Unlock()
+
+	close(client.messages)
+	conn.Close()
+
+	if dropped > 0 {
+		log.Printf("WebSocket client %s dropped %d messages", conn.RemoteAddr(), dropped)
+	}
+}
+
+// writeLoop sends the messages queued for the given client until the client
+// disconnects.
+func (s *wsServer) writeLoop(client *wsClient) {
+	for message := range client.messages {
+		if err := client.write(wsOpText, message); err != nil {
+			client.conn.Close()
+
+			for range client.messages {
+			}
+
+			return
+		}
+	}
+}
+
+// readLoop reads frames sent by the given client until it closes the
+// connection. Data frames are ignored.
+func (s *wsServer) readLoop(client *wsClient, r *bufio.Reader) {
+	for {
+		opcode, payload, err := readWSFrame(r, true)
+
+		if err != nil {
+			return
+		}
+
+		switch opcode {
+		case wsOpClose:
+			return
+		case wsOpPing:
+			// Pongs are written here rather than queued so that they are
+			// never dropped.
+			if err := client.write(wsOpPong, payload); err != nil {
+				return
+			}
+		}
+	}
+}
+
+// dialWS connects to the WebSocket server at the given ws:// URL, requesting
+// the given subprotocol unless it is empty.
+func dialWS(rawURL, protocol string) (net.Conn, *bufio.Reader, error) {
+	u, err := url.Parse(rawURL)
+
+	if err != nil {
+		return nil, nil, err
+	}
+	if u.Scheme != "ws" {
+		return nil, nil, fmt.Errorf("unsupported WebSocket URL %s, only ws:// is supported", rawURL)
+	}
+
+	host := u.Host
+
+	if u.Port() == "" {
+		host = net.JoinHostPort(u.Hostname(), "80")
+	}
+
+	conn, err := net.DialTimeout("tcp", host, 5*time.Second)
+
+	if err != nil {
+		return nil, nil, err
+	}
+
+	var nonce [16]byte
+
+	if _, err := rand.Read(nonce[:]); err != nil {
+		conn.Close()
+		return nil, nil, err
+	}
+
+	key := base64.StdEncoding.EncodeToString(nonce[:])
+	request := "GET " + u.RequestURI() + " HTTP/1.1\r\n" +
+		"Host: " + u.Host + "\r\n" +
+		"Upgrade: websocket\r\n" +
+		"Connection: Upgrade\r\n" +
+		"Sec-WebSocket-Key: " + key + "\r\n" +
+		"Sec-WebSocket-Version: 13\r\n"
+
+	if protocol != "" {
+		request += "Sec-WebSocket-Protocol: " + protocol + "\r\n"
+	}
+
+	if _, err := io.WriteString(conn, request+"\r\n"); err != nil {
+		conn.Close()
+		return nil, nil, err
+	}
+
+	r := bufio.NewReader(conn)
+	response, err := http.ReadResponse(r, nil)
+
+	if err != nil {
+		conn.Close()
+		return nil, nil, err
+	}
+
+	response.Body.Close()
+
+	hash := sha1.Sum([]byte(key + wsGUID))
+
+	if response.StatusCode != http.StatusSwitchingProtocols ||
+		response.Header.Get("Sec-WebSocket-Accept") != base64.StdEncoding.EncodeToString(hash[:]) {
+		conn.Close()
+		return nil, nil, fmt.Errorf("WebSocket handshake with %s failed: %s", rawURL, response.Status)
+	}
+
+	return conn, r, nil
+}
+
+// writeWSFrame writes a single frame with the given opcode. Frames sent by
+// clients must be masked, and frames sent by servers must not.
+func writeWSFrame(w io.Writer, opcode byte, payload []byte, masked bool) error {
+	header := make([]byte, 2, 14)
+	header[0] = 0x80 | opcode
+
+	switch {
+	case len(payload) < 126:
+		header[1] = byte(len(payload))
+	case len(payload) <= 0xFFFF:
+		header[1] = 126
+		header = header[:4]
+		binary.BigEndian.PutUint16(header[2:], uint16(len(payload)))
+	default:
+		header[1] = 127
+		header = header[:10]
+		binary.BigEndian.PutUint64(header[2:], uint64(len(payload)))
+	}
+
+	if masked {
+		var mask [4]byte
+
+		if _, err := rand.Read(mask[:]); err != nil {
+			return err
+		}
+
+		header[1] |= 0x80
+		header = append(header, mask[:]...)
+
+		maskedPayload := make([]byte, len(payload))
+
+		for i, b := range payload {
+			maskedPayload[i] = b ^ mask[i%4]
+		}
+
+		payload = maskedPayload
+	}
+
+	if _, err := w.Write(append(header, payload...)); err != nil {
+		return err
+	}
+
+	return nil
+}
+
+// readWSFrame reads a single frame. Frames sent by clients must be masked,
+// which is checked if fromClient is true.
+func readWSFrame(r *bufio.Reader, fromClient bool) (opcode byte, payload []byte, err error) {
+	var header [2]byte
+
+	if _, err := io.ReadFull(r, header[:]); err != nil {
+		return 0, nil, err
+	}
+
+	opcode = header[0] & 0x0F
+	length := uint64(header[1] & 0x7F)
+
+	switch length {
+	case 126:
+		var extended [2]byte
+		if _, err := io.ReadFull(r, extended[:]); err != nil {
+			return 0, nil, err
+		}
+		length = uint64(binary.BigEndian.Uint16(extended[:]))
+	case 127:
+		var extended [8]byte
+		if _, err := io.ReadFull(r, extended[:]); err != nil {
+			return 0, nil, err
+		}
+		length = binary.BigEndian.Uint64(extended[:])
+	}
+
+	masked := header[1]&0x80 != 0
+
+	if fromClient && !masked {
+		return 0, nil, errors.New("unmasked client frame")
+	}
+	if length > 1<<20 {
+		return 0, nil, errors.New("frame too large")
+	}
+
+	var mask [4]byte
+
+	if masked {
+		if _, err := io.ReadFull(r, mask[:]); err != nil {
+			return 0, nil, err
+		}
+	}
+
+	payload = make([]byte, length)
+
+	if _, err := io.ReadFull(r, payload); err != nil {
+		return 0, nil, err
+	}
+
+	if masked {
+		for i := range payload {
+			payload[i] ^= mask[i%4]
+		}
+	}
+
+	return opcode, payload, nil
+}
@@ -0,0 +1,248 @@
+package main
+
+import (
+	"bytes"
+	"encoding/json"
+	"io"
+	"io/ioutil"
+	"net"
+	"net/http"
+	"net/url"
+	"strings"
+)
+
+// controlHandler executes the commands posted to /control/<command>, whose
+// arguments are given as an optional JSON body as in the control pipe (e.g.
+// {"large_motor": 255}), and answers with the responses of the control pipe.
+// Commands are executed by the input loop like those of the control pipe.
+type controlHandler struct {
+	commands    chan<- controlCommand
+	done        <-chan struct{}
+	allowRemote bool
+}
+
+func (h controlHandler) ServeHTTP(w http.ResponseWriter, r *http.Request) {
+	if r.Method != http.MethodPost {
+		w.Header().Set("Allow", http.MethodPost)
+		http.Error(w, "method not allowed", http.StatusMethodNotAllowed)
+		return
+	}
+
+	// Web pages can make the browser send requests to local servers, so only
+	// accept requests from the web UI itself: browsers always set Origin on
+	// cross-origin POST requests, and a loopback Host rules out DNS rebinding.
+	if origin := r.Header.Get("Origin"); origin != "" {
+		if u, err := url.Parse(origin); err != nil || u.Host != r.Host {
+			http.Error(w, "cross-origin requests are not allowed", http.StatusForbidden)
+			return
+		}
+	}
+	if !h.allowRemote {
+		host, _, err := net.SplitHostPort(r.Host)
+
+		if err != nil {
+			host = r.Host
+		}
+		if !isLoopback(host) {
+			http.Error(w, "requests must be made to a loopback address", http.StatusForbidden)
+			return
+		}
+	}
+
+	var request controlRequest
+	var response controlResponse
+
+	body, err := ioutil.ReadAll(io.LimitReader(r.Body, maxControlRequestSize))
+
+	if err == nil && len(bytes.TrimSpace(body)) > 0 {
+		err = json.Unmarshal(body, &request)
+	}
+
+	if err != nil {
+		response = controlFailure(controlErrorInvalidRequest, "cannot decode request: %v", err)
+	} else {
+		request.Command = strings.TrimPrefix(r.URL.Path, "/control/")
+		response = sendCommand(h.commands, h.done, request)
+	}
+
+	w.Header().Set("Content-Type", "application/json")
+
+	if response.Error != nil {
+		switch response.Error.Code {
+		case controlErrorUnknownCommand:
+			w.WriteHeader(http.StatusNotFound)
+		case controlErrorShuttingDown:
+			w.WriteHeader(http.StatusServiceUnavailable)
+		default:
+			w.WriteHeader(http.StatusBadRequest)
+		}
+	}
+
+	json.NewEncoder(w).Encode(response)
+}
+
+func serveWebUI(w http.ResponseWriter, r *http.Request) {
+	if r.URL.Path != "/" {
+		http.NotFound(w, r)
+		return
+	}
+
+	w.Header().Set("Content-Type", "text/html; charset=utf-8")
+	io.WriteString(w, webUIPage)
+}
+
+// webUIPage shows whether the controller is connected, displays its inputs
+// using the events of the WebSocket server, and sends commands to the control
+// endpoints.
+const webUIPage = `<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <title>Stadia controller</title>
+  <style>
+    body { font-family: sans-serif; max-width: 40em; margin: 2em auto; padding: 0 1em; }
+    .status { font-size: 1.5em; font-weight: bold; }
+    .ok { color: #080; }
+    .warning { color: #b60; }
+    .error { color: #c00; }
+    .buttons span { display: inline-block; margin: 2px; padding: 4px 8px; border: 1px solid #999; border-radius: 4px; }
+    .buttons span.on { background: #f60; border-color: #f60; color: #fff; }
+    .stick { display: inline-block; position: relative; width: 80px; height: 80px; margin: 8px; border: 1px solid #999; border-radius: 50%; }
+    .stick div { position: absolute; width: 12px; height: 12px; margin: -6px; border-radius: 50%; background: #f60; }
+    meter { width: 80px; }
+    button { font-size: 1em; margin: 4px; padding: 8px 16px; }
+    #log { font-family: monospace; font-size: 0.8em; color: #666; }
+  </style>
+</head>
+<body>
+  <p class="status" id="status">Checking...</p>
+  <p id="details"></p>
+  <p id="battery"></p>
+
+  <h3>Inputs</h3>
+  <div class="buttons" id="buttons"></div>
+  <div>
+    <span class="stick"><div id="left-stick"></div></span>
+    <span class="stick"><div id="right-stick"></div></span>
+  </div>
+  <div>
+    L2 <meter id="left-trigger" max="255" value="0"></meter>
+    R2 <meter id="right-trigger" max="255" value="0"></meter>
+  </div>
+
+  <h3>Controls</h3>
+  <button onclick="control('pause')">Pause</button>
+  <button onclick="control('resume')">Resume</button>
+  <button onclick="control('rumble', { large_motor: 255, small_motor: 255, duration_ms: 500 })">Test rumble</button>
+  <p id="log"></p>
+
+  <script>
+    const buttonNames = ["a", "b", "x", "y", "l1", "r1", "l3", "r3", "options", "menu", "stadia",
+                         "assistant", "capture", "dpad_up", "dpad_down", "dpad_left", "dpad_right",
+                         "volume_up", "volume_down"];
+    const buttons = {};
+
+    for (const name of buttonNames) {
+      const element = document.createElement("span");
+      element.textContent = name.replace("_", " ");
+      document.getElementById("buttons").appendChild(element);
+      buttons[name] = element;
+    }
+
+    function setStatus(text, className, details) {
+      const status = document.getElementById("status");
+      status.textContent = text;
+      status.className = "status " + className;
+      document.getElementById("details").textContent = details || "";
+    }
+
+    async function control(command, args) {
+      try {
+        const response = await fetch("/control/" + command, {
+          method: "POST",
+          headers: { "Content-Type": "application/json" },
+          body: JSON.stringify(args || {}),
+        });
+        const result = await response.json();
+
+        if (command !== "status") {
+          document.getElementById("log").textContent =
+            command + ": " + (result.ok ? "done" : result.error.message);
+        }
+
+        return result;
+      } catch (e) {
+        setStatus("The program is not running.", "error");
+        return null;
+      }
+    }
+
+    async function refresh() {
+      const result = await control("status");
+
+      if (result === null || !result.ok) {
+        return;
+      }
+
+      const status = result.status;
+
+      const battery = status.battery === null ? "unknown"
+        : status.battery.level + "%" + (status.battery.charging ? ", charging" : "");
+
+      document.getElementById("battery").textContent = "Battery: " + battery;
+
+      if (!status.connected) {
+        setStatus("Running, but the controller is not connected.", "warning");
+      } else if (status.paused) {
+        setStatus("Running, but paused.", "warning", "Games do not receive the inputs of the controller.");
+      } else {
+        setStatus("Running, and the controller is connected.", "ok", "Connected over " + status.transport + ".");
+      }
+    }
+
+    function moveStick(id, x, y) {
+      const dot = document.getElementById(id);
+      dot.style.left = (50 + x / 32768 * 50) + "%";
+      dot.style.top = (50 - y / 32768 * 50) + "%";
+    }
+
+    function showReport(report) {
+      const pressed = report.stadia_buttons ? report.stadia_buttons.split("|") : [];
+
+      for (const name of buttonNames) {
+        buttons[name].className = pressed.includes(name) ? "on" : "";
+      }
+
+      moveStick("left-stick", report.left_thumb_x, report.left_thumb_y);
+      moveStick("right-stick", report.right_thumb_x, report.right_thumb_y);
+      document.getElementById("left-trigger").value = report.left_trigger;
+      document.getElementById("right-trigger").value = report.right_trigger;
+    }
+
+    function connect() {
+      const socket = new WebSocket("ws://" + location.host + "/events");
+
+      socket.onmessage = (message) => {
+        const event = JSON.parse(message.data);
+
+        if (event.type === "state") {
+          showReport(event.report);
+        } else if (event.type === "button") {
+          buttons[event.button].className = event.pressed ? "on" : "";
+        } else if (event.type === "connected" || event.type === "disconnected" || event.type === "battery") {
+          refresh();
+        }
+      };
+      socket.onclose = () => setTimeout(connect, 2000);
+    }
+
+    moveStick("left-stick", 0, 0);
+    moveStick("right-stick", 0, 0);
+    refresh();
+    setInterval(refresh, 2000);
+    connect();
+  </script>
+</body>
+</html>
+`
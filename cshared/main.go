@@ -0,0 +1,231 @@
+// Command cshared builds the stadiacontroller package as a C shared library:
+//
+//     go build -tags novigem -buildmode=c-shared -o stadia.dll ./cshared
+//
+// which also generates stadia.h, declaring the functions and types below.
+//
+// All functions can be called from any thread. A handle can be used by several
+// threads at once, except that stadia_close must not be called while another
+// function is using the same handle.
+package main
+
+/*
+#include <stdint.h>
+
+// Error codes returned by the functions of the library.
+#define STADIA_OK                       0
+#define STADIA_ERR_INVALID_HANDLE      -1
+#define STADIA_ERR_INVALID_CONFIG      -2
+#define STADIA_ERR_DEVICE_NOT_FOUND    -3
+#define STADIA_ERR_DEVICE_DISCONNECTED -4
+#define STADIA_ERR_ACCESS_DENIED       -5
+#define STADIA_ERR_MALFORMED_REPORT    -6
+#define STADIA_ERR_TIMEOUT             -7
+#define STADIA_ERR_CLOSED              -8
+#define STADIA_ERR_OTHER               -9
+
+// Options given to stadia_open. A zeroed config selects the first connected
+// Stadia controller.
+typedef struct
+{
+	uint16_t vendor_id;
+	uint16_t product_id;
+	const char *device_path;
+	const char *serial;
+	int32_t device_index;
+	uint32_t reconnect_interval_ms;
+	uint8_t flush_on_acquire;
+} stadia_config;
+
+// State of the controller returned by stadia_read_report.
+typedef struct
+{
+	uint16_t buttons;
+	uint8_t left_trigger;
+	uint8_t right_trigger;
+	int16_t left_thumb_x;
+	int16_t left_thumb_y;
+	int16_t right_thumb_x;
+	int16_t right_thumb_y;
+	uint32_t stadia_buttons;
+	uint8_t capture;
+	uint8_t assistant;
+} stadia_report;
+*/
+import "C"
+
+import (
+	"errors"
+	"sync"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+type handle struct {
+	controller *stadiacontroller.StadiaController
+	events     <-chan stadiacontroller.ControllerEvent
+}
+
+var (
+	handlesMu  sync.Mutex
+	handles    = map[C.int32_t]*handle{}
+	nextHandle = C.int32_t(1)
+)
+
+func getHandle(h C.int32_t) *handle {
+	handlesMu.Lock()
+	defer handlesMu.Unlock()
+
+	return handles[h]
+}
+
+// errorCode returns the error code describing the given error.
+func errorCode(err error) C.int32_t {
+	switch {
+	case err == nil:
+		return C.STADIA_OK
+	case errors.Is(err, stadiacontroller.ErrMalformedReport):
+		return C.STADIA_ERR_MALFORMED_REPORT
+	case errors.Is(err, stadiacontroller.ErrAccessDenied):
+		return C.STADIA_ERR_ACCESS_DENIED
+	case errors.Is(err, stadiacontroller.ErrDeviceDisconnected):
+		return C.STADIA_ERR_DEVICE_DISCONNECTED
+	case errors.Is(err, stadiacontroller.ErrDeviceNotFound):
+		return C.STADIA_ERR_DEVICE_NOT_FOUND
+	default:
+		return C.STADIA_ERR_OTHER
+	}
+}
+
+// stadia_open starts looking for the controller selected by config, which may
+// be NULL. It returns a positive handle, or a negative error code.
+//export stadia_open
+func stadia_open(config *C.stadia_config) C.int32_t {
+	options := stadiacontroller.StadiaControllerOptions{}
+
+	if config != nil {
+		options.VendorID = uint16(config.vendor_id)
+		options.DeviceIndex = int(config.device_index)
+		options.ReconnectInterval = time.Duration(config.reconnect_interval_ms) * time.Millisecond
+		options.FlushOnAcquire = config.flush_on_acquire != 0
+
+		if config.product_id != 0 {
+			options.ProductIDs = []uint16{uint16(config.product_id)}
+		}
+		if config.device_path != nil {
+			options.DevicePath = C.GoString(config.device_path)
+		}
+		if config.serial != nil {
+			options.Serial = C.GoString(config.serial)
+		}
+	}
+
+	controller, err := stadiacontroller.NewStadiaControllerWithOptions(options)
+
+	if err != nil {
+		return C.STADIA_ERR_INVALID_CONFIG
+	}
+
+	handlesMu.Lock()
+	defer handlesMu.Unlock()
+
+	h := nextHandle
+	nextHandle++
+	handles[h] = &handle{controller: controller, events: controller.Events()}
+
+	return h
+}
+
+// stadia_read_report waits up to timeout_ms milliseconds for the next report
+// of the controller, and stores it in out_report. A negative timeout waits
+// indefinitely. It returns STADIA_OK, or an error code.
+//export stadia_read_report
+func stadia_read_report(h C.int32_t, outReport *C.stadia_report, timeoutMs C.int32_t) C.int32_t {
+	handle := getHandle(h)
+
+	if handle == nil || outReport == nil {
+		return C.STADIA_ERR_INVALID_HANDLE
+	}
+
+	var timeout <-chan time.Time
+
+	if timeoutMs >= 0 {
+		timer := time.NewTimer(time.Duration(timeoutMs) * time.Millisecond)
+		defer timer.Stop()
+
+		timeout = timer.C
+	}
+
+	for {
+		select {
+		case event, ok := <-handle.events:
+			if !ok {
+				return C.STADIA_ERR_CLOSED
+			}
+
+			switch event := event.(type) {
+			case stadiacontroller.ReportEvent:
+				storeReport(&event.Report, outReport)
+				return C.STADIA_OK
+
+			case stadiacontroller.ErrorEvent:
+				return errorCode(event.Err)
+			}
+
+		case <-timeout:
+			return C.STADIA_ERR_TIMEOUT
+		}
+	}
+}
+
+func storeReport(report *stadiacontroller.Xbox360ControllerReport, out *C.stadia_report) {
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	out.buttons = C.uint16_t(report.GetButtons())
+	out.left_trigger = C.uint8_t(report.GetLeftTrigger())
+	out.right_trigger = C.uint8_t(report.GetRightTrigger())
+	out.left_thumb_x = C.int16_t(lx)
+	out.left_thumb_y = C.int16_t(ly)
+	out.right_thumb_x = C.int16_t(rx)
+	out.right_thumb_y = C.int16_t(ry)
+	out.stadia_buttons = C.uint32_t(report.Buttons)
+	out.capture = 0
+	out.assistant = 0
+
+	if report.Capture {
+		out.capture = 1
+	}
+	if report.Assistant {
+		out.assistant = 1
+	}
+}
+
+// stadia_vibrate sets the intensity of the large and small vibration motors
+// of the controller. It returns STADIA_OK, or an error code.
+//export stadia_vibrate
+func stadia_vibrate(h C.int32_t, largeMotor, smallMotor C.uint8_t) C.int32_t {
+	handle := getHandle(h)
+
+	if handle == nil {
+		return C.STADIA_ERR_INVALID_HANDLE
+	}
+
+	return errorCode(handle.controller.Vibrate(byte(largeMotor), byte(smallMotor)))
+}
+
+// stadia_close releases the controller and invalidates the handle.
+//export stadia_close
+func stadia_close(h C.int32_t) {
+	handlesMu.Lock()
+	handle := handles[h]
+	delete(handles, h)
+	handlesMu.Unlock()
+
+	if handle != nil {
+		handle.controller.Close()
+	}
+}
+
+func main() {}
@@ -0,0 +1,154 @@
+package stadiacontroller
+
+import (
+	"fmt"
+	"unsafe"
+
+	"golang.org/x/sys/windows"
+)
+
+var (
+	cfgmgr32 = windows.NewLazySystemDLL("cfgmgr32.dll")
+
+	procCMGetDeviceInterfacePropertyW = cfgmgr32.NewProc("CM_Get_Device_Interface_PropertyW")
+	procCMLocateDevNodeW              = cfgmgr32.NewProc("CM_Locate_DevNodeW")
+	procCMGetDevNodePropertyW         = cfgmgr32.NewProc("CM_Get_DevNode_PropertyW")
+	procCMGetParent                   = cfgmgr32.NewProc("CM_Get_Parent")
+)
+
+type devPropKey struct {
+	fmtid windows.GUID
+	pid   uint32
+}
+
+const (
+	crSuccess             = 0
+	crBufferSmall         = 0x1A
+	devPropTypeByte       = 0x03
+	devPropTypeStringList = 0x2012
+)
+
+var (
+	// DEVPKEY_Device_InstanceId.
+	devpkeyDeviceInstanceID = devPropKey{windows.GUID{0x78c34fc8, 0x104a, 0x4aca, [8]byte{0x9e, 0xa4, 0x52, 0x4d, 0x52, 0x99, 0x6e, 0x57}}, 256}
+
+	// DEVPKEY_Device_HardwareIds.
+	devpkeyDeviceHardwareIDs = devPropKey{windows.GUID{0xa45c254e, 0xdf1c, 0x4efd, [8]byte{0x80, 0x20, 0x67, 0xd1, 0x46, 0xa8, 0x50, 0xe0}}, 3}
+)
+
+// instanceID returns the NUL-terminated instance ID of the device of the
+// device interface at the given path.
+func instanceID(path string) (*[256]uint16, error) {
+	if err := cfgmgr32.Load(); err != nil {
+		return nil, err
+	}
+
+	pathPtr, err := windows.UTF16PtrFromString(path)
+
+	if err != nil {
+		return nil, err
+	}
+
+	var instanceID [256]uint16
+	var propType uint32
+	size := uint32(unsafe.Sizeof(instanceID))
+
+	r, _, _ := procCMGetDeviceInterfacePropertyW.Call(
+		uintptr(unsafe.Pointer(pathPtr)),
+		uintptr(unsafe.Pointer(&devpkeyDeviceInstanceID)),
+		uintptr(unsafe.Pointer(&propType)),
+		uintptr(unsafe.Pointer(&instanceID[0])),
+		uintptr(unsafe.Pointer(&size)),
+		0)
+
+	if r != crSuccess {
+		return nil, fmt.Errorf("cannot get instance ID of %s: configuration manager error %#x", path, r)
+	}
+
+	return &instanceID, nil
+}
+
+// devNode returns the device node of the device interface at the given path.
+func devNode(path string) (uint32, error) {
+	id, err := instanceID(path)
+
+	if err != nil {
+		return 0, err
+	}
+
+	var devInst uint32
+
+	if r, _, _ := procCMLocateDevNodeW.Call(uintptr(unsafe.Pointer(&devInst)), uintptr(unsafe.Pointer(&id[0])), 0); r != crSuccess {
+		return 0, fmt.Errorf("cannot find device node of %s: configuration manager error %#x", path, r)
+	}
+
+	return devInst, nil
+}
+
+// InstanceID returns the instance ID of the device, such as
+// "HID\VID_18D1&PID_9400\7&2A8E1D3C&0&0000", as listed in the details of the
+// device in the Device Manager.
+func (di *DeviceInfo) InstanceID() (string, error) {
+	id, err := instanceID(di.Path)
+
+	if err != nil {
+		return "", err
+	}
+
+	return windows.UTF16ToString(id[:]), nil
+}
+
+// HardwareIDs returns the hardware IDs of the device, as listed in the
+// details of the device in the Device Manager.
+func (di *DeviceInfo) HardwareIDs() ([]string, error) {
+	devInst, err := devNode(di.Path)
+
+	if err != nil {
+		return nil, err
+	}
+
+	var propType uint32
+	var size uint32
+
+	r, _, _ := procCMGetDevNodePropertyW.Call(
+		uintptr(devInst),
+		uintptr(unsafe.Pointer(&devpkeyDeviceHardwareIDs)),
+		uintptr(unsafe.Pointer(&propType)),
+		0,
+		uintptr(unsafe.Pointer(&size)),
+		0)
+
+	if r != crBufferSmall || size < 2 {
+		return nil, fmt.Errorf("cannot get hardware IDs: configuration manager error %#x", r)
+	}
+
+	buf := make([]uint16, size/2)
+
+	r, _, _ = procCMGetDevNodePropertyW.Call(
+		uintptr(devInst),
+		uintptr(unsafe.Pointer(&devpkeyDeviceHardwareIDs)),
+		uintptr(unsafe.Pointer(&propType)),
+		uintptr(unsafe.Pointer(&buf[0])),
+		uintptr(unsafe.Pointer(&size)),
+		0)
+
+	if r != crSuccess || propType != devPropTypeStringList {
+		return nil, fmt.Errorf("cannot get hardware IDs: configuration manager error %#x", r)
+	}
+
+	// The list is made of NUL-terminated strings, followed by another NUL.
+	var ids []string
+
+	for start := 0; start < len(buf) && buf[start] != 0; {
+		end := start
+
+		for end < len(buf) && buf[end] != 0 {
+			end++
+		}
+
+		ids = append(ids, windows.UTF16ToString(buf[start:end]))
+		start = end + 1
+	}
+
+	return ids, nil
+}
@@ -0,0 +1,337 @@
+// +build !novigem
+
+package stadiacontroller
+
+import (
+	"errors"
+	"unsafe"
+
+	"golang.org/x/sys/windows"
+)
+
+var (
+	procTargetDS4Alloc                  = client.NewProc("vigem_target_ds4_alloc")
+	procTargetDS4RegisterNotification   = client.NewProc("vigem_target_ds4_register_notification")
+	procTargetDS4UnregisterNotification = client.NewProc("vigem_target_ds4_unregister_notification")
+	procTargetDS4Update                 = client.NewProc("vigem_target_ds4_update")
+	procTargetDS4UpdateEx               = client.NewProc("vigem_target_ds4_update_ex")
+)
+
+// Bits of the buttons of a DualShock 4 report. The four lowest bits hold the
+// direction of the D-pad instead.
+const (
+	ds4ButtonSquare        = 1 << 4
+	ds4ButtonCross         = 1 << 5
+	ds4ButtonCircle        = 1 << 6
+	ds4ButtonTriangle      = 1 << 7
+	ds4ButtonShoulderLeft  = 1 << 8
+	ds4ButtonShoulderRight = 1 << 9
+	ds4ButtonTriggerLeft   = 1 << 10
+	ds4ButtonTriggerRight  = 1 << 11
+	ds4ButtonShare         = 1 << 12
+	ds4ButtonOptions       = 1 << 13
+	ds4ButtonThumbLeft     = 1 << 14
+	ds4ButtonThumbRight    = 1 << 15
+
+	ds4DpadNone  = 0x8
+	ds4SpecialPS = 1 << 0
+)
+
+// Battery of a DualShock 4 report, as read by Steam and DS4Windows: its level
+// from 0 to 8 on battery, or from 0 to 11 with the cable bit set while
+// charging. A full battery charging through the cable is shown otherwise.
+const (
+	ds4BatteryCable       = 1 << 4
+	ds4BatteryMaxOnCable  = 11
+	ds4BatteryMaxDraining = 8
+	ds4BatteryFull        = ds4BatteryCable | ds4BatteryMaxOnCable
+)
+
+// ds4Report is the DS4_REPORT structure of ViGEm.
+type ds4Report struct {
+	thumbLX, thumbLY byte
+	thumbRX, thumbRY byte
+	buttons          uint16
+	special          byte
+	triggerL         byte
+	triggerR         byte
+}
+
+// ds4ReportEx is the DS4_REPORT_EX structure of ViGEm, which is packed: a
+// DS4_REPORT followed by the timestamp, the battery, the motion sensors and
+// the touchpad.
+type ds4ReportEx [63]byte
+
+// Offset of the battery in a ds4ReportEx.
+const ds4ReportExBattery = 29
+
+// extended returns the report with the given battery byte, as given by
+// ds4BatteryLevel, and no motion nor touch.
+func (r *ds4Report) extended(battery byte) ds4ReportEx {
+	var ex ds4ReportEx
+
+	ex[0], ex[1], ex[2], ex[3] = r.thumbLX, r.thumbLY, r.thumbRX, r.thumbRY
+	ex[4], ex[5] = byte(r.buttons), byte(r.buttons>>8)
+	ex[6], ex[7], ex[8] = r.special, r.triggerL, r.triggerR
+	ex[ds4ReportExBattery] = battery
+
+	return ex
+}
+
+// ds4BatteryLevel returns the battery of a DualShock 4 report showing the
+// given status, rounding its level to the nearest step.
+func ds4BatteryLevel(status BatteryStatus) byte {
+	if !status.Known {
+		return ds4BatteryFull
+	}
+
+	level := int(status.Level)
+
+	if level > 100 {
+		level = 100
+	}
+	if status.Charging {
+		return ds4BatteryCable | byte((level*ds4BatteryMaxOnCable+50)/100)
+	}
+
+	return byte((level*ds4BatteryMaxDraining + 50) / 100)
+}
+
+// Xbox 360 equivalent of each DualShock 4 button.
+var ds4Buttons = [...]struct {
+	xbox360 int
+	ds4     uint16
+}{
+	{Xbox360ControllerButtonA, ds4ButtonCross},
+	{Xbox360ControllerButtonB, ds4ButtonCircle},
+	{Xbox360ControllerButtonX, ds4ButtonSquare},
+	{Xbox360ControllerButtonY, ds4ButtonTriangle},
+	{Xbox360ControllerButtonLeftShoulder, ds4ButtonShoulderLeft},
+	{Xbox360ControllerButtonRightShoulder, ds4ButtonShoulderRight},
+	{Xbox360ControllerButtonLeftThumb, ds4ButtonThumbLeft},
+	{Xbox360ControllerButtonRightThumb, ds4ButtonThumbRight},
+	{Xbox360ControllerButtonBack, ds4ButtonShare},
+	{Xbox360ControllerButtonStart, ds4ButtonOptions},
+}
+
+// Directions of the D-pad of a DualShock 4 report, indexed by the Up, Down,
+// Left and Right bits of an Xbox 360 report. Opposite directions cancel out.
+var ds4DpadDirections = func() [16]uint16 {
+	var directions [16]uint16
+
+	for bits := range directions {
+		up, down := bits&(1<<Xbox360ControllerButtonUp) != 0, bits&(1<<Xbox360ControllerButtonDown) != 0
+		left, right := bits&(1<<Xbox360ControllerButtonLeft) != 0, bits&(1<<Xbox360ControllerButtonRight) != 0
+
+		if up && down {
+			up, down = false, false
+		}
+		if left && right {
+			left, right = false, false
+		}
+
+		switch {
+		case up && right:
+			directions[bits] = 1
+		case down && right:
+			directions[bits] = 3
+		case down && left:
+			directions[bits] = 5
+		case up && left:
+			directions[bits] = 7
+		case up:
+			directions[bits] = 0
+		case right:
+			directions[bits] = 2
+		case down:
+			directions[bits] = 4
+		case left:
+			directions[bits] = 6
+		default:
+			directions[bits] = ds4DpadNone
+		}
+	}
+
+	return directions
+}()
+
+// newDS4Report converts the given report to a DualShock 4 report. Capture
+// presses Share along with Back, and Assistant has no equivalent.
+func newDS4Report(report *Xbox360ControllerReport) ds4Report {
+	buttons := report.GetButtons()
+	lx, ly := report.GetLeftThumb()
+	rx, ry := report.GetRightThumb()
+
+	r := ds4Report{
+		thumbLX:  ds4AxisValue(lx),
+		thumbLY:  ds4AxisValue(^ly),
+		thumbRX:  ds4AxisValue(rx),
+		thumbRY:  ds4AxisValue(^ry),
+		buttons:  ds4DpadDirections[buttons&0xF],
+		triggerL: report.GetLeftTrigger(),
+		triggerR: report.GetRightTrigger(),
+	}
+
+	for _, button := range ds4Buttons {
+		if buttons&(1<<button.xbox360) != 0 {
+			r.buttons |= button.ds4
+		}
+	}
+
+	if report.Capture {
+		r.buttons |= ds4ButtonShare
+	}
+	if r.triggerL > 0 {
+		r.buttons |= ds4ButtonTriggerLeft
+	}
+	if r.triggerR > 0 {
+		r.buttons |= ds4ButtonTriggerRight
+	}
+	if buttons&(1<<Xbox360ControllerButtonGuide) != 0 {
+		r.special |= ds4SpecialPS
+	}
+
+	return r
+}
+
+// ds4AxisValue converts a value of an axis from -32768 to 32767 to a value
+// from 0 to 255. The Y axes of DualShock 4 sticks grow downwards, and are
+// given inverted.
+func ds4AxisValue(value int16) byte {
+	return byte(int32(value)>>8 + 128)
+}
+
+// CreateDS4Controller creates an emulated DualShock 4 controller, which must
+// be connected using Connect before games can see it.
+func (e *Emulator) CreateDS4Controller() (*DS4Controller, error) {
+	handle, _, err := procTargetDS4Alloc.Call()
+
+	if !errors.Is(err, windows.ERROR_SUCCESS) {
+		return nil, err
+	}
+
+	// The color of the light bar is passed by reference, and ignored.
+	notificationHandler := func(client, target uintptr, largeMotor, smallMotor byte, lightbarColor uintptr) uintptr {
+		e.onVibration(Vibration{LargeMotor: largeMotor, SmallMotor: smallMotor})
+
+		return 0
+	}
+	callback := windows.NewCallback(notificationHandler)
+
+	return &DS4Controller{emulator: e, handle: handle, notificationHandler: callback}, nil
+}
+
+// DS4Controller is a DualShock 4 controller emulated with ViGEm. It is sent
+// the same reports as an Xbox360Controller, which it converts.
+type DS4Controller struct {
+	emulator            *Emulator
+	handle              uintptr
+	connected           bool
+	notificationHandler uintptr
+	report              ds4Report
+
+	// battery is the battery shown to games once set by SetBattery, before
+	// which ViGEm shows its own.
+	battery    byte
+	batterySet bool
+}
+
+// Close releases the controller.
+func (c *DS4Controller) Close() error {
+	_, _, err := procTargetFree.Call(c.handle)
+
+	return err
+}
+
+// Connect plugs the controller into the ViGEm bus, making it visible to games.
+func (c *DS4Controller) Connect() error {
+	libErr, _, err := procTargetAdd.Call(c.emulator.handle, c.handle)
+
+	if !errors.Is(err, windows.ERROR_SUCCESS) {
+		return err
+	}
+	if err := NewVigemError(libErr); err != nil {
+		return err
+	}
+
+	libErr, _, err = procTargetDS4RegisterNotification.Call(c.emulator.handle, c.handle, c.notificationHandler)
+
+	if !errors.Is(err, windows.ERROR_SUCCESS) {
+		return err
+	}
+	if err := NewVigemError(libErr); err != nil {
+		return err
+	}
+
+	c.connected = true
+
+	return nil
+}
+
+// Disconnect unplugs the controller from the ViGEm bus.
+func (c *DS4Controller) Disconnect() error {
+	libErr, _, err := procTargetDS4UnregisterNotification.Call(c.handle)
+
+	if !errors.Is(err, windows.ERROR_SUCCESS) {
+		return err
+	}
+	if err := NewVigemError(libErr); err != nil {
+		return err
+	}
+
+	libErr, _, err = procTargetRemove.Call(c.emulator.handle, c.handle)
+
+	if !errors.Is(err, windows.ERROR_SUCCESS) {
+		return err
+	}
+	if err := NewVigemError(libErr); err != nil {
+		return err
+	}
+
+	c.connected = false
+
+	return nil
+}
+
+// Send converts the given report, and updates the state of the controller
+// seen by games.
+func (c *DS4Controller) Send(report *Xbox360ControllerReport) error {
+	c.report = newDS4Report(report)
+
+	return c.update()
+}
+
+// SetBattery shows the given battery status to games, along with the last
+// report sent. Versions of ViGEm which cannot be given the battery keep
+// showing a full battery.
+func (c *DS4Controller) SetBattery(status BatteryStatus) error {
+	c.battery, c.batterySet = ds4BatteryLevel(status), true
+
+	if !c.connected {
+		return nil
+	}
+
+	return c.update()
+}
+
+// update sends the last report to ViGEm, with the battery if it was set.
+func (c *DS4Controller) update() error {
+	var libErr uintptr
+	var err error
+
+	if c.batterySet && procTargetDS4UpdateEx.Find() == nil {
+		ex := c.report.extended(c.battery)
+		libErr, _, err = procTargetDS4UpdateEx.Call(c.emulator.handle, c.handle, uintptr(unsafe.Pointer(&ex)))
+	} else {
+		libErr, _, err = procTargetDS4Update.Call(c.emulator.handle, c.handle, uintptr(unsafe.Pointer(&c.report)))
+	}
+
+	if !errors.Is(err, windows.ERROR_SUCCESS) {
+		return err
+	}
+	if err := NewVigemError(libErr); err != nil {
+		return err
+	}
+
+	return nil
+}
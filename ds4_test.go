@@ -0,0 +1,59 @@
+// +build !novigem
+
+package stadiacontroller
+
+import "testing"
+
+func TestDS4BatteryLevel(t *testing.T) {
+	for _, test := range []struct {
+		status   BatteryStatus
+		expected byte
+	}{
+		{BatteryStatus{}, ds4BatteryFull},
+		{BatteryStatus{Level: 40, Charging: true}, ds4BatteryFull},
+		{BatteryStatus{Known: true, Level: 100}, 8},
+		{BatteryStatus{Known: true, Level: 50}, 4},
+		{BatteryStatus{Known: true, Level: 6}, 0},
+		{BatteryStatus{Known: true, Level: 7}, 1},
+		{BatteryStatus{Known: true, Level: 0}, 0},
+		{BatteryStatus{Known: true, Level: 120}, 8},
+		{BatteryStatus{Known: true, Level: 100, Charging: true}, 0x1b},
+		{BatteryStatus{Known: true, Level: 50, Charging: true}, 0x16},
+		{BatteryStatus{Known: true, Level: 0, Charging: true}, 0x10},
+	} {
+		if level := ds4BatteryLevel(test.status); level != test.expected {
+			t.Errorf("ds4BatteryLevel(%+v) = %#02x, expected %#02x", test.status, level, test.expected)
+		}
+	}
+
+	// Levels never go down as the battery charges.
+	for _, charging := range []bool{false, true} {
+		previous := ds4BatteryLevel(BatteryStatus{Known: true, Charging: charging})
+
+		for level := uint8(1); level <= 100; level++ {
+			current := ds4BatteryLevel(BatteryStatus{Known: true, Level: level, Charging: charging})
+
+			if current < previous {
+				t.Errorf("ds4BatteryLevel of %d%% (charging: %v) = %#02x, below %#02x at %d%%", level, charging, current, previous, level-1)
+			}
+
+			previous = current
+		}
+	}
+}
+
+func TestDS4ReportExtended(t *testing.T) {
+	report := ds4Report{
+		thumbLX: 1, thumbLY: 2, thumbRX: 3, thumbRY: 4,
+		buttons: ds4ButtonCross | ds4ButtonShoulderRight | ds4DpadNone,
+		special: ds4SpecialPS, triggerL: 5, triggerR: 6,
+	}
+	ex := report.extended(0x16)
+
+	expected := ds4ReportEx{1, 2, 3, 4, 0x28, 0x02, 1, 5, 6}
+	expected[ds4ReportExBattery] = 0x16
+
+	if ex != expected {
+		t.Errorf("extended report is % x, expected % x", ex[:], expected[:])
+	}
+}
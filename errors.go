@@ -0,0 +1,90 @@
+package stadiacontroller
+
+import (
+	"errors"
+	"syscall"
+)
+
+// Errors that can be tested against with errors.Is to react to failures of
+// the controller, regardless of the operating system error behind them.
+var (
+	// ErrDeviceNotFound indicates that no matching device is connected.
+	ErrDeviceNotFound = errors.New("device not found")
+
+	// ErrDeviceDisconnected indicates that the device was disconnected while
+	// in use.
+	ErrDeviceDisconnected = errors.New("device disconnected")
+
+	// ErrAccessDenied indicates that the device cannot be opened, usually
+	// because another program holds it.
+	ErrAccessDenied = errors.New("access denied")
+
+	// ErrMalformedReport indicates that a report sent by the device could not
+	// be parsed.
+	ErrMalformedReport = errors.New("malformed report")
+)
+
+// Windows error codes classified by IOError.
+const (
+	errorFileNotFound       syscall.Errno = 2
+	errorPathNotFound       syscall.Errno = 3
+	errorAccessDenied       syscall.Errno = 5
+	errorSharingViolation   syscall.Errno = 32
+	errorDeviceNotConnected syscall.Errno = 1167
+)
+
+// IOError is an error returned by the operating system while opening, reading
+// from or writing to a device.
+type IOError struct {
+	// Op is the failed operation: "open", "read" or "write".
+	Op string
+
+	// Err is the underlying error, usually a syscall.Errno.
+	Err error
+}
+
+func (e *IOError) Error() string {
+	return "hid: " + e.Op + ": " + e.Err.Error()
+}
+
+func (e *IOError) Unwrap() error {
+	return e.Err
+}
+
+// Is classifies the operating system error into one of ErrDeviceNotFound,
+// ErrDeviceDisconnected and ErrAccessDenied.
+func (e *IOError) Is(target error) bool {
+	errno, ok := e.Err.(syscall.Errno)
+
+	if !ok {
+		return false
+	}
+
+	switch target {
+	case ErrDeviceNotFound:
+		return errno == errorFileNotFound || errno == errorPathNotFound
+	case ErrDeviceDisconnected:
+		return errno == errorDeviceNotConnected
+	case ErrAccessDenied:
+		return errno == errorAccessDenied || errno == errorSharingViolation
+	default:
+		return false
+	}
+}
+
+// retryError is a RetryError caused by another error.
+type retryError struct {
+	cause error
+}
+
+func (e *retryError) Error() string {
+	return RetryError.Error() + ": " + e.cause.Error()
+}
+
+func (e *retryError) Is(target error) bool {
+	return target == RetryError
+}
+
+func (e *retryError) Unwrap() error {
+	return e.cause
+}
@@ -0,0 +1,86 @@
+package stadiacontroller
+
+import (
+	"errors"
+	"fmt"
+	"syscall"
+	"testing"
+)
+
+func TestIOErrorIs(t *testing.T) {
+	targets := []error{ErrDeviceNotFound, ErrDeviceDisconnected, ErrAccessDenied, ErrMalformedReport, RetryError}
+
+	tests := []struct {
+		err      error
+		expected error
+	}{
+		{syscall.Errno(2), ErrDeviceNotFound},        // ERROR_FILE_NOT_FOUND
+		{syscall.Errno(3), ErrDeviceNotFound},        // ERROR_PATH_NOT_FOUND
+		{syscall.Errno(5), ErrAccessDenied},          // ERROR_ACCESS_DENIED
+		{syscall.Errno(32), ErrAccessDenied},         // ERROR_SHARING_VIOLATION
+		{syscall.Errno(1167), ErrDeviceDisconnected}, // ERROR_DEVICE_NOT_CONNECTED
+
+		// Other errors are not classified.
+		{syscall.ERROR_IO_PENDING, nil},
+		{syscall.Errno(995), nil}, // ERROR_OPERATION_ABORTED
+		{syscall.Errno(6), nil},   // ERROR_INVALID_HANDLE
+		{syscall.Errno(0), nil},
+		{errors.New("hid: device closed"), nil},
+	}
+
+	for _, test := range tests {
+		for _, op := range []string{"open", "read", "write"} {
+			err := &IOError{op, test.err}
+
+			for _, target := range targets {
+				if is := errors.Is(err, target); is != (target == test.expected) {
+					t.Errorf("errors.Is(%v, %v) = %v", err, target, is)
+				}
+			}
+
+			if !errors.Is(err, test.err) {
+				t.Errorf("%v does not wrap %v", err, test.err)
+			}
+
+			// Classification survives wrapping.
+			wrapped := fmt.Errorf("cannot %s device: %w", op, err)
+
+			if test.expected != nil && !errors.Is(wrapped, test.expected) {
+				t.Errorf("errors.Is(%v, %v) = false", wrapped, test.expected)
+			}
+		}
+	}
+}
+
+func TestRetryError(t *testing.T) {
+	causes := []error{
+		ErrDeviceNotFound,
+		&IOError{"open", syscall.Errno(32)},
+		&IOError{"read", syscall.Errno(1167)},
+		&otherReportError{0x01},
+	}
+
+	for _, cause := range causes {
+		err := &retryError{cause}
+
+		if !errors.Is(err, RetryError) {
+			t.Errorf("%v is not a RetryError", err)
+		}
+		if !errors.Is(err, cause) {
+			t.Errorf("%v does not wrap %v", err, cause)
+		}
+		if expected := "retry: " + cause.Error(); err.Error() != expected {
+			t.Errorf("retryError{%v}.Error() = %q, expected %q", cause, err.Error(), expected)
+		}
+	}
+
+	if errors.Is(&retryError{&IOError{"read", syscall.Errno(1167)}}, ErrAccessDenied) {
+		t.Errorf("retryError of a disconnection is ErrAccessDenied")
+	}
+	if !errors.Is(&retryError{&IOError{"read", syscall.Errno(1167)}}, ErrDeviceDisconnected) {
+		t.Errorf("retryError of a disconnection is not ErrDeviceDisconnected")
+	}
+	if errors.Is(&IOError{"read", syscall.Errno(1167)}, RetryError) {
+		t.Errorf("IOError is a RetryError")
+	}
+}
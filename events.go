@@ -0,0 +1,150 @@
+package stadiacontroller
+
+import (
+	"errors"
+	"time"
+)
+
+// A ControllerEvent is sent on the channel returned by
+// StadiaController.Events. It is one of ConnectedEvent, DisconnectedEvent,
+// HandOffEvent, ReportEvent and ErrorEvent.
+type ControllerEvent interface {
+	isControllerEvent()
+}
+
+// ConnectedEvent is sent when a controller is acquired.
+type ConnectedEvent struct {
+	Path   string
+	Serial string
+
+	// Firmware is the firmware revision of the controller, as given by the
+	// version number of its device.
+	Firmware uint16
+
+	// Transport is the way the controller is connected.
+	Transport Transport
+}
+
+// DisconnectedEvent is sent when the acquired controller is released, either
+// because it was disconnected or because Release was called.
+type DisconnectedEvent struct{}
+
+// HandOffEvent is sent when the standby controller takes over from the active
+// one (see StadiaControllerOptions.HandOffIdle). No DisconnectedEvent or
+// ConnectedEvent is sent for the swap.
+type HandOffEvent struct {
+	// From is the serial number of the controller which was active.
+	From string
+
+	// To is the controller which is now active.
+	To ConnectedEvent
+
+	// Disconnected is whether the previous controller was disconnected, rather
+	// than idle while a button of the standby controller was pressed.
+	Disconnected bool
+}
+
+// ReportEvent is sent for each report of the acquired controller.
+type ReportEvent struct {
+	Report Xbox360ControllerReport
+
+	// Received is the time at which the report was read from the device.
+	Received time.Time
+}
+
+// ErrorEvent is sent when an error occurs, e.g. when the controller cannot be
+// opened or a report cannot be parsed. Unless it wraps RetryError, no event
+// is sent after it and the channel is closed.
+type ErrorEvent struct {
+	Err error
+}
+
+func (ConnectedEvent) isControllerEvent()    {}
+func (DisconnectedEvent) isControllerEvent() {}
+func (HandOffEvent) isControllerEvent()      {}
+func (ReportEvent) isControllerEvent()       {}
+func (ErrorEvent) isControllerEvent()        {}
+
+// Events returns a channel which receives the reports of the controller along
+// with changes of its connection state. Reports are read in the background as
+// soon as Events is first called, so GetReport must not be called afterwards.
+//
+// The channel is closed after Close is called, or after a fatal ErrorEvent.
+func (c *StadiaController) Events() <-chan ControllerEvent {
+	c.eventsMu.Lock()
+	defer c.eventsMu.Unlock()
+
+	if c.events == nil {
+		c.events = make(chan ControllerEvent, 16)
+
+		go c.eventLoop()
+	}
+
+	return c.events
+}
+
+// emit sends the given event if Events was called, unless the controller is
+// closed.
+func (c *StadiaController) emit(event ControllerEvent) {
+	c.eventsMu.RLock()
+	defer c.eventsMu.RUnlock()
+
+	if c.events == nil || c.eventsClosed {
+		return
+	}
+
+	select {
+	case c.events <- event:
+	case <-c.done:
+	}
+}
+
+// closeEvents closes the channel returned by Events, if any.
+func (c *StadiaController) closeEvents() {
+	c.eventsMu.Lock()
+	defer c.eventsMu.Unlock()
+
+	if c.events != nil && !c.eventsClosed {
+		c.eventsClosed = true
+		close(c.events)
+	}
+}
+
+// eventLoop reads reports and sends them as events until the controller is
+// closed.
+func (c *StadiaController) eventLoop() {
+	defer c.closeEvents()
+
+	for {
+		report, err := c.GetReport()
+		received := time.Now()
+
+		select {
+		case <-c.done:
+			return
+		default:
+		}
+
+		if err == nil {
+			c.emit(ReportEvent{report, received})
+			continue
+		}
+
+		if !errors.Is(err, RetryError) {
+			c.emit(ErrorEvent{err})
+			return
+		}
+
+		if errors.Is(err, ErrMalformedReport) {
+			c.emit(ErrorEvent{err})
+			continue
+		}
+
+		// Wait for a controller to be acquired.
+		select {
+		case <-time.After(c.options.reconnectInterval()):
+		case <-c.done:
+			return
+		}
+	}
+}
@@ -0,0 +1,38 @@
+// Command printreports prints the state of a Stadia controller every time it
+// changes, using the stadiacontroller package without emulating a controller.
+package main
+
+import (
+	"errors"
+	"log"
+	"time"
+
+	"github.com/71/stadiacontroller"
+)
+
+func main() {
+	controller := stadiacontroller.NewStadiaController()
+
+	defer controller.Close()
+
+	for {
+		report, err := controller.GetReport()
+
+		if err != nil {
+			if errors.Is(err, stadiacontroller.RetryError) {
+				time.Sleep(1 * time.Second)
+				continue
+			}
+			log.Fatal(err)
+		}
+
+		lx, ly := report.GetLeftThumb()
+		rx, ry := report.GetRightThumb()
+
+		log.Printf(
+			"buttons=%016b left=(%6d, %6d) right=(%6d, %6d) triggers=(%3d, %3d) assistant=%v capture=%v",
+			report.GetButtons(), lx, ly, rx, ry, report.GetLeftTrigger(), report.GetRightTrigger(),
+			report.Assistant, report.Capture,
+		)
+	}
+}
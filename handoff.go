@@ -0,0 +1,172 @@
+package stadiacontroller
+
+import (
+	"log"
+	"time"
+)
+
+// standbyController is the controller acquired in addition to the active one
+// when hand-off is enabled. Its reports are only read to notice presses.
+type standbyController struct {
+	device Device
+	info   *DeviceInfo
+	parse  reportParser
+
+	// stop is closed to make watchStandby return, which then closes stopped.
+	stop    chan struct{}
+	stopped chan struct{}
+}
+
+// acquireStandby looks for a controller other than the active one at the given
+// path, and opens it as the standby controller.
+func (c *StadiaController) acquireStandby(activePath string) {
+	device, err := c.options.findStandbyDevice(activePath)
+
+	if err != nil || device == nil {
+		return
+	}
+
+	openDevice, err := c.options.backend().Open(device)
+
+	if err != nil {
+		log.Printf("cannot open standby device %s: %v", device.Path, err)
+		return
+	}
+
+	standby := &standbyController{
+		device:  openDevice,
+		info:    device,
+		parse:   c.parserFor(device),
+		stop:    make(chan struct{}),
+		stopped: make(chan struct{}),
+	}
+
+	c.mu.Lock()
+	acquired := c.device != nil && c.standby == nil && !c.closed
+	if acquired {
+		c.standby = standby
+	}
+	c.mu.Unlock()
+
+	if !acquired {
+		openDevice.Close()
+		return
+	}
+
+	log.Printf("opened standby device %s over %v: %s", device.Path, device.Transport(), describeDevice(device))
+
+	go c.watchStandby(standby)
+}
+
+// watchStandby reads the reports of the standby controller, and makes it the
+// active one when one of its buttons is pressed while the active controller is
+// idle. It returns once the standby controller took over or was released.
+func (c *StadiaController) watchStandby(standby *standbyController) {
+	defer close(standby.stopped)
+
+	for {
+		var buf []byte
+		var ok bool
+
+		select {
+		case buf, ok = <-standby.device.ReadCh():
+		case <-standby.stop:
+			return
+		}
+
+		if !ok {
+			break
+		}
+
+		var report Xbox360ControllerReport
+
+		if standby.parse(buf, &report) != nil || report.Buttons == 0 {
+			continue
+		}
+
+		c.mu.Lock()
+		active := c.device
+		idle := time.Since(c.lastInput) >= c.options.HandOffIdle
+		c.mu.Unlock()
+
+		if idle && c.handOff(active, false) {
+			return
+		}
+	}
+
+	c.mu.Lock()
+	lost := c.standby == standby
+	if lost {
+		c.standby = nil
+	}
+	c.mu.Unlock()
+
+	if lost {
+		log.Printf("standby device %s disconnected", standby.info.Path)
+		standby.device.Close()
+	}
+}
+
+// handOff makes the standby controller, if any, the active controller in place
+// of the given device, and returns whether it did. The given device is closed,
+// and its motors are stopped first unless it was disconnected. It is called by
+// GetReport when the device is disconnected, and by watchStandby otherwise.
+func (c *StadiaController) handOff(from *Device, disconnected bool) bool {
+	c.mu.Lock()
+	standby := c.standby
+	handOff := standby != nil && from != nil && c.device == from && !c.closed
+	previous := c.info
+
+	if handOff {
+		c.device = &standby.device
+		c.info = standby.info
+		c.parse = standby.parse
+		c.standby = nil
+		c.lastInput = time.Now()
+	}
+	c.mu.Unlock()
+
+	if !handOff {
+		return false
+	}
+
+	log.Printf("handed off from device %s to device %s", previous.Path, standby.info.Path)
+
+	// watchStandby would otherwise keep reading the reports of the new active
+	// controller along with GetReport. It returns by itself after making the
+	// hand-off.
+	if disconnected {
+		close(standby.stop)
+		<-standby.stopped
+	}
+
+	// Send the event before closing the previous device, which wakes up
+	// GetReport, so that it is received before any report of the new device.
+	c.emit(HandOffEvent{
+		From: previous.SerialNumber,
+		To: ConnectedEvent{
+			Path:      standby.info.Path,
+			Serial:    standby.info.SerialNumber,
+			Firmware:  standby.info.VersionNumber,
+			Transport: standby.info.Transport(),
+		},
+		Disconnected: disconnected,
+	})
+
+	if !disconnected {
+		(*from).Write([]byte{0x05, 0, 0, 0, 0})
+	}
+
+	(*from).Close()
+
+	return true
+}
+
+// replaced returns whether another device was acquired in place of the given
+// one, e.g. the standby controller.
+func (c *StadiaController) replaced(device *Device) bool {
+	c.mu.Lock()
+	defer c.mu.Unlock()
+
+	return c.device != nil && c.device != device
+}
@@ -0,0 +1,119 @@
+package stadiacontroller
+
+import (
+	"testing"
+	"time"
+)
+
+// withStandby returns a controller which acquired active, and then standby as
+// its standby controller.
+func withStandby(t *testing.T, handOffIdle time.Duration) (c *StadiaController, backend *fakeBackend, active, standby *fakeDevice) {
+	active = newFakeDevice(`\\?\hid#vid_18d1&pid_9400#1`, "active")
+	standby = newFakeDevice(`\\?\hid#vid_18d1&pid_9400#2`, "standby")
+
+	backend = &fakeBackend{}
+	backend.plug(nil, active)
+
+	c = newFakeController(t, backend, StadiaControllerOptions{HandOffIdle: handOffIdle})
+	waitUntil(t, "the active controller is acquired", acquired(c, active))
+
+	backend.plug(c, standby)
+	waitUntil(t, "the standby controller is acquired", func() bool {
+		c.mu.Lock()
+		defer c.mu.Unlock()
+
+		return c.standby != nil && c.standby.info.Path == standby.info.Path
+	})
+
+	return c, backend, active, standby
+}
+
+func TestHandOffOnDisconnect(t *testing.T) {
+	c, backend, active, standby := withStandby(t, time.Hour)
+	defer c.Close()
+
+	events := c.Events()
+	backend.unplug(active)
+
+	event := nextEvent(t, events)
+
+	if handOff, ok := event.(HandOffEvent); !ok || handOff.From != "active" || handOff.To.Serial != "standby" || !handOff.Disconnected {
+		t.Fatalf("received %#v, expected a HandOffEvent from the disconnected controller", event)
+	}
+
+	// Every report of the new active controller is forwarded, none being
+	// read by the goroutine which watched it as a standby.
+	for i := 0; i < 20; i++ {
+		raw := byte(0x80 + i)
+		go standby.send([]byte{0x03, 0x08, 0x00, 0x40, raw, 0x80, 0x80, 0x80, 0x00, 0x00})
+
+		event := nextEvent(t, events)
+		report, ok := event.(ReportEvent)
+
+		if !ok {
+			t.Fatalf("received %#v, expected report %d", event, i)
+		}
+		if x, _ := report.Report.GetLeftThumb(); x != axisValue(raw, false) {
+			t.Fatalf("received report with X %d, expected report %d", x, i)
+		}
+	}
+
+	if writes := active.written(); len(writes) != 0 {
+		t.Errorf("disconnected controller was written %x", writes)
+	}
+}
+
+func TestHandOffOnPress(t *testing.T) {
+	c, _, active, standby := withStandby(t, time.Minute)
+	defer c.Close()
+
+	pressed := []byte{0x03, 0x08, 0x00, 0x40, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00}
+	released := []byte{0x03, 0x08, 0x00, 0x00, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00}
+
+	// The handle of the standby controller only reads a report once the
+	// previous one was received, so after two more reports the previous ones
+	// were processed by watchStandby.
+	flush := func() {
+		standby.send(released)
+		standby.send(released)
+	}
+
+	// Presses of the standby controller are ignored while the active one is
+	// in use.
+	standby.send(pressed)
+	flush()
+
+	if !acquired(c, active)() {
+		t.Fatalf("standby controller took over from the active one in use")
+	}
+
+	c.mu.Lock()
+	c.lastInput = time.Now().Add(-time.Hour)
+	c.mu.Unlock()
+
+	// Releases do not take over either.
+	flush()
+
+	if !acquired(c, active)() {
+		t.Fatalf("standby controller took over without any press")
+	}
+
+	standby.send(pressed)
+	waitUntil(t, "the standby controller takes over", acquired(c, standby))
+
+	waitUntil(t, "the previous controller is closed", func() bool {
+		_, closed := active.openCount()
+		return closed == 1
+	})
+
+	// The motors of the previous controller are stopped before it is closed.
+	if writes := active.written(); len(writes) != 1 || string(writes[0]) != "\x05\x00\x00\x00\x00" {
+		t.Errorf("previous controller was written %x", writes)
+	}
+
+	go standby.send([]byte{0x03, 0x08, 0x00, 0x20, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00})
+
+	if report, err := c.GetReport(); err != nil || report.Buttons != StadiaButtonB {
+		t.Errorf("GetReport returned %q, %v", report.Buttons, err)
+	}
+}
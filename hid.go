@@ -40,8 +40,10 @@ import "C"
 import (
 	"errors"
 	"fmt"
+	"log"
 	"sync"
 	"syscall"
+	"time"
 	"unsafe"
 )
 
@@ -55,12 +57,14 @@ type DeviceInfo struct {
 	VersionNumber uint16
 	Manufacturer  string
 	Product       string
+	SerialNumber  string
 
 	UsagePage uint16
 	Usage     uint16
 
-	InputReportLength  uint16
-	OutputReportLength uint16
+	InputReportLength   uint16
+	OutputReportLength  uint16
+	FeatureReportLength uint16
 }
 
 // A Device provides access to a HID device.
@@ -75,11 +79,20 @@ type Device interface {
 	// ReadCh returns a channel that will be sent input reports from the device.
 	// If the device uses numbered reports, the first byte will be the report
 	// number.
+	//
+	// Received reports belong to the device: a report is only valid until the
+	// next receive from the channel, after which its buffer may be reused for
+	// subsequent reads, so it must be copied to be kept longer. Reports are
+	// never dropped: while the channel is full, the device is not read, and
+	// the system buffers its reports in the meantime.
 	ReadCh() <-chan []byte
 
 	// ReadError returns the read error, if any after the channel returned from
 	// ReadCh has been closed.
 	ReadError() error
+
+	// Flush discards the input reports buffered by the system for the device.
+	Flush() error
 }
 
 type winDevice struct {
@@ -88,8 +101,16 @@ type winDevice struct {
 
 	readSetup sync.Once
 	readCh    chan []byte
+	readBufs  [][]byte
 	readErr   error
 	readOl    *syscall.Overlapped
+
+	writeMu sync.Mutex
+	writeOl *syscall.Overlapped
+
+	closeOnce sync.Once
+	closed    chan struct{}
+	readDone  chan struct{}
 }
 
 // returns the casted handle of the device
@@ -103,14 +124,44 @@ func (d *winDevice) isValid() bool {
 }
 
 func (d *winDevice) Close() {
-	// cancel any pending reads and unblock read loop
-	d.readErr = errors.New("hid: device closed")
-	C.CancelIo(d.h())
-	C.SetEvent(C.HANDLE(unsafe.Pointer(d.readOl.HEvent)))
-	syscall.CloseHandle(d.readOl.HEvent)
-
-	syscall.CloseHandle(d.handle)
-	d.handle = syscall.InvalidHandle
+	d.closeOnce.Do(func() {
+		// cancel any pending reads and unblock read loop
+		d.readErr = errors.New("hid: device closed")
+		close(d.closed)
+
+		// Make sure the read loop can no longer start, and if it did, wait
+		// until its pending read was actually cancelled: otherwise the kernel
+		// could still signal the event or write into the read buffer after
+		// they are released. The cancellation is repeated in case the loop
+		// issued a new read right after the previous cancellation.
+		started := true
+		d.readSetup.Do(func() {
+			started = false
+			d.readCh = make(chan []byte)
+			close(d.readCh)
+		})
+
+		for started {
+			syscall.CancelIoEx(d.handle, d.readOl)
+
+			select {
+			case <-d.readDone:
+				started = false
+			case <-time.After(10 * time.Millisecond):
+			}
+		}
+
+		syscall.CloseHandle(d.readOl.HEvent)
+
+		// Same for writes, which are waited for by taking the write lock.
+		syscall.CancelIoEx(d.handle, d.writeOl)
+
+		d.writeMu.Lock()
+		syscall.CloseHandle(d.writeOl.HEvent)
+		syscall.CloseHandle(d.handle)
+		d.handle = syscall.InvalidHandle
+		d.writeMu.Unlock()
+	})
 }
 
 func (d *winDevice) Write(data []byte) error {
@@ -122,19 +173,28 @@ func (d *winDevice) Write(data []byte) error {
 		data = buf
 	}
 
-	ol := new(syscall.Overlapped)
+	// Writes get their own event: with a null event, GetOverlappedResult
+	// would also be woken up by the completion of the pending read.
+	d.writeMu.Lock()
+	defer d.writeMu.Unlock()
+
+	if !d.isValid() {
+		return errors.New("hid: device closed")
+	}
+
+	ol := d.writeOl
 	if err := syscall.WriteFile(d.handle, data, nil, ol); err != nil {
 		// IO Pending is ok we simply wait for it to finish a few lines below
 		// all other errors should be reported.
 		if err != syscall.ERROR_IO_PENDING {
-			return err
+			return &IOError{"write", err}
 		}
 	}
 
 	// now wait for the overlapped device access to finish.
 	var written C.DWORD
 	if C.GetOverlappedResult(d.h(), (*C.OVERLAPPED)((unsafe.Pointer)(ol)), &written, C.TRUE) == 0 {
-		return syscall.GetLastError()
+		return &IOError{"write", syscall.GetLastError()}
 	}
 
 	if int(written) != outSize {
@@ -185,6 +245,11 @@ func openDevice(info *DeviceInfo, enumerate bool) (*winDevice, error) {
 		readOl: &syscall.Overlapped{
 			HEvent: syscall.Handle(C.CreateEvent(nil, C.FALSE, C.FALSE, nil)),
 		},
+		writeOl: &syscall.Overlapped{
+			HEvent: syscall.Handle(C.CreateEvent(nil, C.FALSE, C.FALSE, nil)),
+		},
+		closed:   make(chan struct{}),
+		readDone: make(chan struct{}),
 	}, nil
 }
 
@@ -265,6 +330,10 @@ func ByPath(devicePath string) (*DeviceInfo, error) {
 	C.HidD_GetProductString(dev.h(), (C.PVOID)(&buff[0]), bufLen)
 	devInfo.Product = syscall.UTF16ToString(buff)
 
+	if C.HidD_GetSerialNumberString(dev.h(), (C.PVOID)(&buff[0]), bufLen) != 0 {
+		devInfo.SerialNumber = syscall.UTF16ToString(buff)
+	}
+
 	var preparsedData C.PHIDP_PREPARSED_DATA
 	if C.HidD_GetPreparsedData(dev.h(), &preparsedData) != 0 {
 		var caps C.HIDP_CAPS
@@ -274,6 +343,7 @@ func ByPath(devicePath string) (*DeviceInfo, error) {
 			devInfo.Usage = uint16(caps.Usage)
 			devInfo.InputReportLength = uint16(caps.InputReportByteLength - 1)
 			devInfo.OutputReportLength = uint16(caps.OutputReportByteLength - 1)
+			devInfo.FeatureReportLength = uint16(caps.FeatureReportByteLength - 1)
 		}
 
 		C.HidD_FreePreparsedData(preparsedData)
@@ -311,7 +381,7 @@ func Devices() ([]*DeviceInfo, error) {
 func (di *DeviceInfo) Open() (Device, error) {
 	d, err := openDevice(di, false)
 	if err != nil {
-		return nil, err
+		return nil, &IOError{"open", err}
 	}
 	if !d.isValid() {
 		d.Close()
@@ -319,7 +389,7 @@ func (di *DeviceInfo) Open() (Device, error) {
 		if err == nil {
 			err = errors.New("unable to open device")
 		}
-		return nil, err
+		return nil, &IOError{"open", err}
 	}
 	return d, nil
 }
@@ -327,6 +397,18 @@ func (di *DeviceInfo) Open() (Device, error) {
 func (d *winDevice) ReadCh() <-chan []byte {
 	d.readSetup.Do(func() {
 		d.readCh = make(chan []byte, 30)
+
+		// One buffer per channel slot, plus the one held by the consumer and
+		// the one being read into, so a buffer is never overwritten before
+		// the consumer moved on to the next report: readThread only reads
+		// into the buffer of a report again after sending the 31 reports
+		// which follow it, which the channel can only hold once the consumer
+		// received the next one.
+		d.readBufs = make([][]byte, cap(d.readCh)+2)
+		for i := range d.readBufs {
+			d.readBufs[i] = make([]byte, d.info.InputReportLength+1)
+		}
+
 		go d.readThread()
 	})
 	return d.readCh
@@ -336,17 +418,66 @@ func (d *winDevice) ReadError() error {
 	return d.readErr
 }
 
+func (d *winDevice) Flush() error {
+	if C.HidD_FlushQueue(d.h()) == 0 {
+		return syscall.GetLastError()
+	}
+	return nil
+}
+
+// maxZeroByteReads is the number of consecutive zero byte reads after which a
+// device is considered to be in a bad state.
+const maxZeroByteReads = 3
+
+// checkRead checks a read of n bytes into a buffer of the given length, which
+// completed with err, after zeroReads consecutive zero byte reads. It returns
+// the new number of consecutive zero byte reads, whether the read must be
+// issued again, and the error ending the reads from the device if any.
+func checkRead(err error, n, length, zeroReads int) (int, bool, error) {
+	if err != nil {
+		return zeroReads, false, &IOError{"read", err}
+	}
+
+	if n == 0 {
+		// Zero byte completions have been observed after resuming from
+		// sleep; retry a few times before reporting the device as broken,
+		// which makes the caller drop it and acquire it again.
+		zeroReads++
+
+		if zeroReads < maxZeroByteReads {
+			return zeroReads, true, nil
+		}
+		return zeroReads, false, errors.New("hid: zero byte read")
+	}
+
+	// Slicing the buffer past its end would panic.
+	if n > length {
+		return 0, false, fmt.Errorf("hid: read of %d bytes into a buffer of %d bytes", n, length)
+	}
+
+	return 0, false, nil
+}
+
 func (d *winDevice) readThread() {
+	defer close(d.readDone)
 	defer close(d.readCh)
 
-	for {
-		buf := make([]byte, d.info.InputReportLength+1)
+	zeroReads := 0
+
+	for i := 0; ; i = (i + 1) % len(d.readBufs) {
+		select {
+		case <-d.closed:
+			return
+		default:
+		}
+
+		buf := d.readBufs[i]
 		C.ResetEvent(C.HANDLE(unsafe.Pointer(d.readOl.HEvent)))
 
 		if err := syscall.ReadFile(d.handle, buf, nil, d.readOl); err != nil {
 			if err != syscall.ERROR_IO_PENDING {
 				if d.readErr == nil {
-					d.readErr = err
+					d.readErr = &IOError{"read", err}
 				}
 				return
 			}
@@ -362,18 +493,26 @@ func (d *winDevice) readThread() {
 		}
 
 		var n C.DWORD
+		var err error
+
 		if r := C.GetOverlappedResult(d.h(), (*C.OVERLAPPED)((unsafe.Pointer)(d.readOl)), &n, C.TRUE); r == 0 {
-			if d.readErr == nil {
-				d.readErr = fmt.Errorf("hid: unexpected read result state %d", r)
-			}
-			return
+			err = syscall.GetLastError()
+		} else if n == 0 {
+			log.Printf("hid: zero byte read from %s (%d/%d)", d.info.Path, zeroReads+1, maxZeroByteReads)
 		}
-		if n == 0 {
+
+		var retry bool
+		zeroReads, retry, err = checkRead(err, int(n), len(buf), zeroReads)
+
+		if err != nil {
 			if d.readErr == nil {
-				d.readErr = errors.New("hid: zero byte read")
+				d.readErr = err
 			}
 			return
 		}
+		if retry {
+			continue
+		}
 
 		if buf[0] == 0 {
 			// Report numbers are not being used, so remove zero to match other platforms
@@ -381,9 +520,12 @@ func (d *winDevice) readThread() {
 			n--
 		}
 
+		// Block until the report is consumed rather than dropping it when the
+		// channel is full; a missed report may be a missed button press.
 		select {
 		case d.readCh <- buf[:int(n)]:
-		default:
+		case <-d.closed:
+			return
 		}
 	}
 
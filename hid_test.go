@@ -0,0 +1,67 @@
+package stadiacontroller
+
+import (
+	"errors"
+	"syscall"
+	"testing"
+)
+
+func TestCheckRead(t *testing.T) {
+	tests := []struct {
+		name      string
+		err       error
+		n         int
+		zeroReads int
+
+		expectedZeroReads int
+		expectedRetry     bool
+		expectedErr       bool
+	}{
+		{"report", nil, 11, 0, 0, false, false},
+		{"full buffer", nil, 12, 0, 0, false, false},
+		{"report after zero byte reads", nil, 1, 2, 0, false, false},
+		{"first zero byte read", nil, 0, 0, 1, true, false},
+		{"second zero byte read", nil, 0, 1, 2, true, false},
+		{"last zero byte read", nil, 0, maxZeroByteReads - 1, maxZeroByteReads, false, true},
+		{"overlong read", nil, 13, 0, 0, false, true},
+		{"overlong read after zero byte reads", nil, 4096, 1, 0, false, true},
+		{"failed read", syscall.Errno(1167), 0, 0, 0, false, true},
+		{"failed read after zero byte reads", syscall.Errno(995), 11, 2, 2, false, true},
+	}
+
+	for _, test := range tests {
+		zeroReads, retry, err := checkRead(test.err, test.n, 12, test.zeroReads)
+
+		if zeroReads != test.expectedZeroReads || retry != test.expectedRetry || (err != nil) != test.expectedErr {
+			t.Errorf("%s: checkRead(%v, %d, 12, %d) = %d, %v, %v", test.name, test.err, test.n, test.zeroReads, zeroReads, retry, err)
+		}
+	}
+
+	// Errors of the system are kept for classification.
+	if _, _, err := checkRead(syscall.Errno(1167), 0, 12, 0); !errors.Is(err, ErrDeviceDisconnected) {
+		t.Errorf("checkRead of a disconnection returned %v", err)
+	}
+}
+
+func TestCheckReadZeroByteReads(t *testing.T) {
+	// A device only returning zero bytes is given up on after
+	// maxZeroByteReads reads, and never before.
+	zeroReads := 0
+
+	for i := 1; ; i++ {
+		var retry bool
+		var err error
+
+		zeroReads, retry, err = checkRead(nil, 0, 12, zeroReads)
+
+		if err != nil {
+			if i != maxZeroByteReads {
+				t.Errorf("gave up after %d zero byte reads, expected %d", i, maxZeroByteReads)
+			}
+			break
+		}
+		if !retry || i >= maxZeroByteReads {
+			t.Fatalf("zero byte read %d: retry is %v", i, retry)
+		}
+	}
+}
@@ -0,0 +1,69 @@
+package stadiacontroller
+
+import "encoding/json"
+
+// reportJSON is the JSON representation of an Xbox360ControllerReport. Its
+// field names are used by recordings and other tools, and must not change.
+type reportJSON struct {
+	Buttons      uint16 `json:"buttons"`
+	LeftTrigger  byte   `json:"left_trigger"`
+	RightTrigger byte   `json:"right_trigger"`
+	LeftThumbX   int16  `json:"left_thumb_x"`
+	LeftThumbY   int16  `json:"left_thumb_y"`
+	RightThumbX  int16  `json:"right_thumb_x"`
+	RightThumbY  int16  `json:"right_thumb_y"`
+
+	StadiaButtons StadiaButtons `json:"stadia_buttons"`
+	Capture       bool          `json:"capture"`
+	Assistant     bool          `json:"assistant"`
+}
+
+// MarshalJSON implements json.Marshaler. Buttons of the Xbox 360 controller
+// are encoded as a bit set, and Stadia buttons by name, e.g.
+//
+//     {"buttons":4096,"left_trigger":0,"right_trigger":0,"left_thumb_x":0,
+//      "left_thumb_y":0,"right_thumb_x":0,"right_thumb_y":0,
+//      "stadia_buttons":"a","capture":false,"assistant":false}
+//
+// Encoding a report allocates a few times, which is fine once per report but
+// should be kept out of latency-sensitive paths.
+func (r Xbox360ControllerReport) MarshalJSON() ([]byte, error) {
+	lx, ly := r.GetLeftThumb()
+	rx, ry := r.GetRightThumb()
+
+	return json.Marshal(reportJSON{
+		Buttons:      r.GetButtons(),
+		LeftTrigger:  r.GetLeftTrigger(),
+		RightTrigger: r.GetRightTrigger(),
+		LeftThumbX:   lx,
+		LeftThumbY:   ly,
+		RightThumbX:  rx,
+		RightThumbY:  ry,
+
+		StadiaButtons: r.Buttons,
+		Capture:       r.Capture,
+		Assistant:     r.Assistant,
+	})
+}
+
+// UnmarshalJSON implements json.Unmarshaler, decoding a report encoded by
+// MarshalJSON. Missing fields are left at their zero value.
+func (r *Xbox360ControllerReport) UnmarshalJSON(data []byte) error {
+	var decoded reportJSON
+
+	if err := json.Unmarshal(data, &decoded); err != nil {
+		return err
+	}
+
+	*r = NewXbox360ControllerReport()
+	r.SetButtons(decoded.Buttons)
+	r.SetLeftTrigger(decoded.LeftTrigger)
+	r.SetRightTrigger(decoded.RightTrigger)
+	r.SetLeftThumb(decoded.LeftThumbX, decoded.LeftThumbY)
+	r.SetRightThumb(decoded.RightThumbX, decoded.RightThumbY)
+	r.Buttons = decoded.StadiaButtons
+	r.Capture = decoded.Capture
+	r.Assistant = decoded.Assistant
+
+	return nil
+}
@@ -0,0 +1,119 @@
+package stadiacontroller
+
+import (
+	"encoding/json"
+	"testing"
+)
+
+func TestReportJSONRoundTrip(t *testing.T) {
+	var reports []Xbox360ControllerReport
+
+	for _, data := range [][]byte{
+		{0x03, 0x08, 0x00, 0x00, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00},
+		{0x03, 0x00, 0xf3, 0x7f, 0x00, 0xff, 0xff, 0x00, 0xff, 0xff, 0x03},
+		{0x03, 0x05, 0x12, 0x24, 0x01, 0x7f, 0x81, 0xfe, 0x40, 0xc0},
+	} {
+		var report Xbox360ControllerReport
+
+		if err := ParseReport(data, &report); err != nil {
+			t.Fatal(err)
+		}
+
+		reports = append(reports, report)
+	}
+
+	// Reports built by transforms need not match their Stadia buttons.
+	var remapped Xbox360ControllerReport
+	remapped.SetButtons(0xffff)
+	remapped.SetLeftThumb(-32768, 32767)
+	remapped.SetRightThumb(32767, -32768)
+	remapped.Buttons = StadiaButtonCapture
+	reports = append(reports, remapped)
+
+	for _, report := range reports {
+		encoded, err := json.Marshal(report)
+
+		if err != nil {
+			t.Fatal(err)
+		}
+
+		var decoded Xbox360ControllerReport
+
+		if err := json.Unmarshal(encoded, &decoded); err != nil {
+			t.Errorf("cannot decode %s: %v", encoded, err)
+		} else if decoded != report {
+			t.Errorf("%s was decoded to %+v, expected %+v", encoded, decoded, report)
+		}
+	}
+}
+
+func TestReportJSONFieldNames(t *testing.T) {
+	var report Xbox360ControllerReport
+	report.SetButton(Xbox360ControllerButtonA)
+	report.SetLeftTrigger(12)
+	report.SetRightThumb(-1, 300)
+	report.Buttons = StadiaButtonA | StadiaButtonL1
+
+	encoded, err := json.Marshal(report)
+
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	// Recordings depend on these names.
+	expected := `{"buttons":4096,"left_trigger":12,"right_trigger":0,"left_thumb_x":0,` +
+		`"left_thumb_y":0,"right_thumb_x":-1,"right_thumb_y":300,` +
+		`"stadia_buttons":"a|l1","capture":false,"assistant":false}`
+
+	if string(encoded) != expected {
+		t.Errorf("report was encoded to\n%s\nexpected\n%s", encoded, expected)
+	}
+
+	// Missing fields are zero, even if the report was not.
+	if err := json.Unmarshal([]byte(`{"right_trigger":255,"stadia_buttons":"b"}`), &report); err != nil {
+		t.Fatal(err)
+	}
+	if report.GetButtons() != 0 || report.GetLeftTrigger() != 0 || report.GetRightTrigger() != 255 || report.Buttons != StadiaButtonB {
+		t.Errorf("partial report was decoded to %+v", report)
+	}
+
+	if err := json.Unmarshal([]byte(`{"stadia_buttons":"a|start"}`), &report); err == nil {
+		t.Errorf("unknown Stadia button was decoded")
+	}
+}
+
+func TestReportJSONAllocations(t *testing.T) {
+	var report Xbox360ControllerReport
+
+	if err := ParseReport([]byte{0x03, 0x00, 0xf3, 0x7f, 0x00, 0xff, 0xff, 0x00, 0xff, 0xff}, &report); err != nil {
+		t.Fatal(err)
+	}
+
+	// Reports are encoded one by one by -output json and recordings, so
+	// encoding one must only allocate a few times, with every button pressed.
+	allocs := testing.AllocsPerRun(100, func() {
+		if _, err := json.Marshal(report); err != nil {
+			t.Fatal(err)
+		}
+	})
+
+	if allocs > 16 {
+		t.Errorf("encoding a report allocates %v times", allocs)
+	}
+}
+
+func BenchmarkReportMarshalJSON(b *testing.B) {
+	var report Xbox360ControllerReport
+
+	if err := ParseReport([]byte{0x03, 0x00, 0xf3, 0x7f, 0x00, 0xff, 0xff, 0x00, 0xff, 0xff}, &report); err != nil {
+		b.Fatal(err)
+	}
+
+	b.ReportAllocs()
+
+	for i := 0; i < b.N; i++ {
+		if _, err := json.Marshal(report); err != nil {
+			b.Fatal(err)
+		}
+	}
+}
@@ -0,0 +1,247 @@
+package stadiacontroller
+
+import (
+	"errors"
+	"fmt"
+	"time"
+)
+
+// StadiaControllerOptions configures how a StadiaController finds and
+// acquires a controller. The zero value selects the first connected Stadia
+// controller, like NewStadiaController.
+type StadiaControllerOptions struct {
+	// VendorID is the vendor ID of the device to acquire. Zero selects the
+	// vendor ID of the Stadia controller.
+	VendorID uint16
+
+	// ProductIDs are the accepted product IDs of the device to acquire. When
+	// empty, only the product ID of the Stadia controller is accepted.
+	ProductIDs []uint16
+
+	// DevicePath is the path of the device to acquire, bypassing enumeration
+	// and vendor and product ID checks.
+	DevicePath string
+
+	// Serial is the serial number of the device to acquire.
+	Serial string
+
+	// DeviceIndex is the index of the device to acquire among all connected
+	// devices matching the other options.
+	DeviceIndex int
+
+	// Transport is the transport of the device to acquire. TransportUnknown
+	// accepts all transports.
+	Transport Transport
+
+	// ReconnectInterval is the time to wait between two attempts to find a
+	// device when device arrivals cannot be watched, and before trying again
+	// to open a device which could not be opened. Zero selects one second.
+	ReconnectInterval time.Duration
+
+	// HandOffIdle enables hand-off between two controllers when non-zero: a
+	// second matching controller is acquired as a standby, and takes over
+	// without disconnecting when the active one is disconnected, or when one of
+	// its buttons is pressed while no button of the active one was pressed for
+	// HandOffIdle. Until then, the inputs of the standby controller are
+	// ignored.
+	HandOffIdle time.Duration
+
+	// FlushOnAcquire discards the reports buffered by the system when a device
+	// is acquired, so that the first report returned is a fresh one.
+	FlushOnAcquire bool
+
+	// Backend is used to find and open devices. Nil selects HIDBackend.
+	Backend Backend
+
+	// ReportMaps describe the reports of the devices they match, e.g. clones
+	// of the controller. The first matching map is used; devices matching
+	// none are parsed according to their firmware revision. Devices with the
+	// product ID of a map are accepted in addition to ProductIDs.
+	ReportMaps []ReportMap
+
+	// OnRawReport, if set, is called with each input report read from the
+	// device, the report it was parsed into, and the error which prevented
+	// parsing it, if any. It is called by GetReport, and must not retain data.
+	OnRawReport func(data []byte, report *Xbox360ControllerReport, err error)
+}
+
+func (o *StadiaControllerOptions) validate() error {
+	if o.DevicePath != "" && o.Serial != "" {
+		return errors.New("cannot select a device by both path and serial number")
+	}
+	if o.DevicePath != "" && o.DeviceIndex != 0 {
+		return errors.New("cannot select a device by both path and index")
+	}
+	if o.DevicePath != "" && o.Transport != TransportUnknown {
+		return errors.New("cannot select a device by both path and transport")
+	}
+	if o.DeviceIndex < 0 {
+		return errors.New("device index cannot be negative")
+	}
+	if o.HandOffIdle < 0 {
+		return errors.New("hand-off idle time cannot be negative")
+	}
+	if o.HandOffIdle != 0 && (o.DevicePath != "" || o.Serial != "" || o.DeviceIndex != 0) {
+		return errors.New("cannot hand off between controllers selected by path, serial number or index")
+	}
+	if o.ReconnectInterval < 0 {
+		return errors.New("reconnect interval cannot be negative")
+	}
+
+	for i := range o.ReportMaps {
+		if err := o.ReportMaps[i].Validate(); err != nil {
+			return fmt.Errorf("invalid report map %d: %w", i, err)
+		}
+	}
+
+	return nil
+}
+
+func (o *StadiaControllerOptions) reconnectInterval() time.Duration {
+	if o.ReconnectInterval == 0 {
+		return 1 * time.Second
+	}
+
+	return o.ReconnectInterval
+}
+
+func (o *StadiaControllerOptions) backend() Backend {
+	if o.Backend == nil {
+		return HIDBackend
+	}
+
+	return o.Backend
+}
+
+// matches returns whether the given device matches the vendor ID, product IDs,
+// serial number and transport in the options.
+func (o *StadiaControllerOptions) matches(device *DeviceInfo) bool {
+	vendorID := o.VendorID
+	if vendorID == 0 {
+		vendorID = stadiaControllerVid
+	}
+
+	if device.VendorID != vendorID {
+		return false
+	}
+	if o.Serial != "" && device.SerialNumber != o.Serial {
+		return false
+	}
+	if o.Transport != TransportUnknown && device.Transport() != o.Transport {
+		return false
+	}
+
+	for _, m := range o.ReportMaps {
+		if m.ProductID != 0 && device.ProductID == m.ProductID {
+			return true
+		}
+	}
+	if len(o.ProductIDs) == 0 {
+		return device.ProductID == stadiaControllerPid
+	}
+
+	for _, productID := range o.ProductIDs {
+		if device.ProductID == productID {
+			return true
+		}
+	}
+
+	return false
+}
+
+// FindDevice returns the device that a StadiaController created with the
+// options would acquire, or ErrDeviceNotFound if it is not connected.
+func (o StadiaControllerOptions) FindDevice() (*DeviceInfo, error) {
+	if err := o.validate(); err != nil {
+		return nil, err
+	}
+
+	device, err := o.findDevice()
+
+	if err != nil {
+		return nil, err
+	}
+	if device == nil {
+		return nil, ErrDeviceNotFound
+	}
+
+	return device, nil
+}
+
+// FindDevices returns all the connected devices matching the vendor ID,
+// product IDs, serial number and transport in the options.
+func (o StadiaControllerOptions) FindDevices() ([]*DeviceInfo, error) {
+	if err := o.validate(); err != nil {
+		return nil, err
+	}
+
+	devices, err := o.backend().Devices()
+
+	if err != nil {
+		return nil, err
+	}
+
+	var matching []*DeviceInfo
+
+	for _, device := range devices {
+		if o.matches(device) {
+			matching = append(matching, device)
+		}
+	}
+
+	return matching, nil
+}
+
+// findDevice returns the device selected by the options, or nil if it is not
+// connected.
+func (o *StadiaControllerOptions) findDevice() (*DeviceInfo, error) {
+	if o.DevicePath != "" {
+		device, err := o.backend().ByPath(o.DevicePath)
+
+		if err != nil {
+			// The device is not connected (yet).
+			return nil, nil
+		}
+
+		return device, nil
+	}
+
+	devices, err := o.backend().Devices()
+
+	if err != nil {
+		return nil, err
+	}
+
+	index := 0
+
+	for _, device := range devices {
+		if !o.matches(device) {
+			continue
+		}
+		if index == o.DeviceIndex {
+			return device, nil
+		}
+
+		index++
+	}
+
+	return nil, nil
+}
+
+// findStandbyDevice returns a device matching the options other than the
+// device at the given path, or nil if none is connected.
+func (o *StadiaControllerOptions) findStandbyDevice(activePath string) (*DeviceInfo, error) {
+	devices, err := o.backend().Devices()
+
+	if err != nil {
+		return nil, err
+	}
+
+	for _, device := range devices {
+		if device.Path != activePath && o.matches(device) {
+			return device, nil
+		}
+	}
+
+	return nil, nil
+}
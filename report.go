@@ -0,0 +1,120 @@
+package stadiacontroller
+
+/*
+#include <stdint.h>
+
+typedef struct
+{
+	uint16_t wButtons;
+	uint8_t bLeftTrigger;
+	uint8_t bRightTrigger;
+	int16_t sThumbLX;
+	int16_t sThumbLY;
+	int16_t sThumbRX;
+	int16_t sThumbRY;
+} xusb_report;
+*/
+import "C"
+
+// Xbox360ControllerReport is the state of an Xbox 360 controller, along with
+// the state of the Stadia buttons that have no Xbox 360 equivalent.
+type Xbox360ControllerReport struct {
+	native    C.xusb_report
+	Capture   bool
+	Assistant bool
+
+	// Buttons is the set of Stadia buttons pressed in the report this report
+	// was parsed from, independently of their Xbox 360 equivalents.
+	Buttons StadiaButtons
+}
+
+// Bits that correspond to the Xbox 360 controller buttons.
+const (
+	Xbox360ControllerButtonUp            = 0
+	Xbox360ControllerButtonDown          = 1
+	Xbox360ControllerButtonLeft          = 2
+	Xbox360ControllerButtonRight         = 3
+	Xbox360ControllerButtonStart         = 4
+	Xbox360ControllerButtonBack          = 5
+	Xbox360ControllerButtonLeftThumb     = 6
+	Xbox360ControllerButtonRightThumb    = 7
+	Xbox360ControllerButtonLeftShoulder  = 8
+	Xbox360ControllerButtonRightShoulder = 9
+	Xbox360ControllerButtonGuide         = 10
+	Xbox360ControllerButtonA             = 12
+	Xbox360ControllerButtonB             = 13
+	Xbox360ControllerButtonX             = 14
+	Xbox360ControllerButtonY             = 15
+)
+
+// NewXbox360ControllerReport returns a report with no button pressed, centered
+// thumbsticks and released triggers.
+func NewXbox360ControllerReport() Xbox360ControllerReport {
+	return Xbox360ControllerReport{}
+}
+
+// GetButtons returns the pressed buttons, one bit per button as given by the
+// Xbox360ControllerButton constants.
+func (r *Xbox360ControllerReport) GetButtons() uint16 {
+	return uint16(r.native.wButtons)
+}
+
+// SetButtons sets the pressed buttons, one bit per button as given by the
+// Xbox360ControllerButton constants.
+func (r *Xbox360ControllerReport) SetButtons(buttons uint16) {
+	r.native.wButtons = C.uint16_t(buttons)
+}
+
+// MaybeSetButton marks the given button as pressed if isSet is true.
+func (r *Xbox360ControllerReport) MaybeSetButton(shiftBy int, isSet bool) {
+	if isSet {
+		r.SetButton(shiftBy)
+	}
+}
+
+// SetButton marks the given button as pressed.
+func (r *Xbox360ControllerReport) SetButton(shiftBy int) {
+	r.native.wButtons |= 1 << shiftBy
+}
+
+// GetLeftTrigger returns the value of the left trigger, from 0 to 255.
+func (r *Xbox360ControllerReport) GetLeftTrigger() byte {
+	return byte(r.native.bLeftTrigger)
+}
+
+// SetLeftTrigger sets the value of the left trigger, from 0 to 255.
+func (r *Xbox360ControllerReport) SetLeftTrigger(value byte) {
+	r.native.bLeftTrigger = C.uint8_t(value)
+}
+
+// GetRightTrigger returns the value of the right trigger, from 0 to 255.
+func (r *Xbox360ControllerReport) GetRightTrigger() byte {
+	return byte(r.native.bRightTrigger)
+}
+
+// SetRightTrigger sets the value of the right trigger, from 0 to 255.
+func (r *Xbox360ControllerReport) SetRightTrigger(value byte) {
+	r.native.bRightTrigger = C.uint8_t(value)
+}
+
+// GetLeftThumb returns the position of the left thumbstick.
+func (r *Xbox360ControllerReport) GetLeftThumb() (x, y int16) {
+	return int16(r.native.sThumbLX), int16(r.native.sThumbLY)
+}
+
+// SetLeftThumb sets the position of the left thumbstick.
+func (r *Xbox360ControllerReport) SetLeftThumb(x, y int16) {
+	r.native.sThumbLX = C.int16_t(x)
+	r.native.sThumbLY = C.int16_t(y)
+}
+
+// GetRightThumb returns the position of the right thumbstick.
+func (r *Xbox360ControllerReport) GetRightThumb() (x, y int16) {
+	return int16(r.native.sThumbRX), int16(r.native.sThumbRY)
+}
+
+// SetRightThumb sets the position of the right thumbstick.
+func (r *Xbox360ControllerReport) SetRightThumb(x, y int16) {
+	r.native.sThumbRX = C.int16_t(x)
+	r.native.sThumbRY = C.int16_t(y)
+}
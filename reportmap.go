@@ -0,0 +1,305 @@
+package stadiacontroller
+
+import (
+	"encoding/base64"
+	"fmt"
+	"sort"
+)
+
+// Maximum length of an input report described by a ReportMap.
+const maxReportLength = 64
+
+// A ReportMap describes where the inputs of a controller are in its input
+// reports, so that clones and firmwares laying out the same data differently
+// can be supported without code changes. DefaultReportMap describes the Stadia
+// controller.
+//
+// Offsets are given in bytes from the start of the report, including its ID.
+type ReportMap struct {
+	// ProductID and Firmware select the devices whose reports are parsed with
+	// the map; zero matches all product IDs or firmware revisions.
+	ProductID uint16 `json:"product_id,omitempty"`
+	Firmware  uint16 `json:"firmware,omitempty"`
+
+	// ReportID is the first byte of the input reports.
+	ReportID byte `json:"report_id"`
+
+	// Buttons gives the bit set while each button is pressed, by button name
+	// (see StadiaButtons.String). Buttons located after the other inputs are
+	// only read from reports long enough to hold them.
+	Buttons map[string]ReportBit `json:"buttons"`
+
+	// Hat is the offset of the D-pad, whose values 0 to 7 are directions
+	// clockwise from up, and whose other values mean it is released. -1 means
+	// that the D-pad is given as dpad_* buttons instead.
+	Hat int `json:"hat"`
+
+	// Offsets of the axes of the sticks (0 is left or up and 255 right or
+	// down) and of the triggers (0 is released).
+	LeftStickX   int `json:"left_stick_x"`
+	LeftStickY   int `json:"left_stick_y"`
+	RightStickX  int `json:"right_stick_x"`
+	RightStickY  int `json:"right_stick_y"`
+	LeftTrigger  int `json:"left_trigger"`
+	RightTrigger int `json:"right_trigger"`
+}
+
+// A ReportBit is the location of a bit in a report: Bit 0 is the least
+// significant bit of the byte at offset Byte.
+type ReportBit struct {
+	Byte int  `json:"byte"`
+	Bit  uint `json:"bit"`
+}
+
+// DefaultReportMap returns the map of the reports of the Stadia controller, as
+// parsed by ParseReport.
+func DefaultReportMap() ReportMap {
+	return ReportMap{
+		ReportID: 0x03,
+		Buttons: map[string]ReportBit{
+			"a":         {3, 6},
+			"b":         {3, 5},
+			"x":         {3, 4},
+			"y":         {3, 3},
+			"l1":        {3, 2},
+			"r1":        {3, 1},
+			"l3":        {3, 0},
+			"r3":        {2, 7},
+			"options":   {2, 6},
+			"menu":      {2, 5},
+			"stadia":    {2, 4},
+			"assistant": {2, 1},
+			"capture":   {2, 0},
+
+			// The volume buttons of a headset are reported as consumer
+			// controls after the triggers, by firmware whose reports are long
+			// enough.
+			"volume_up":   {10, 0},
+			"volume_down": {10, 1},
+		},
+		Hat:          1,
+		LeftStickX:   4,
+		LeftStickY:   5,
+		RightStickX:  6,
+		RightStickY:  7,
+		LeftTrigger:  8,
+		RightTrigger: 9,
+	}
+}
+
+// matches returns whether the map applies to the given device.
+func (m *ReportMap) matches(device *DeviceInfo) bool {
+	return (m.ProductID == 0 || m.ProductID == device.ProductID) &&
+		(m.Firmware == 0 || m.Firmware == device.VersionNumber)
+}
+
+// parserFor returns the parser of the reports of the given device: the first
+// report map matching it, or the parser of its firmware revision.
+func (c *StadiaController) parserFor(device *DeviceInfo) reportParser {
+	for i := range c.options.ReportMaps {
+		if c.options.ReportMaps[i].matches(device) {
+			return c.layouts[i].parse
+		}
+	}
+
+	return parserForRevision(device.VersionNumber)
+}
+
+// Validate returns an error if an input is out of the bounds of a report, or
+// if two inputs overlap.
+func (m *ReportMap) Validate() error {
+	_, err := m.layout()
+
+	return err
+}
+
+type reportButtonBit struct {
+	offset int
+	mask   byte
+	button StadiaButtons
+}
+
+// reportLayout is a validated ReportMap, ready to parse reports.
+type reportLayout struct {
+	id        byte
+	minLength int
+	buttons   []reportButtonBit
+	hat       int
+	sticks    [4]int
+	triggers  [2]int
+}
+
+func (m *ReportMap) layout() (*reportLayout, error) {
+	l := &reportLayout{
+		id:       m.ReportID,
+		hat:      m.Hat,
+		sticks:   [4]int{m.LeftStickX, m.LeftStickY, m.RightStickX, m.RightStickY},
+		triggers: [2]int{m.LeftTrigger, m.RightTrigger},
+	}
+
+	// Inputs using a whole byte, by offset.
+	bytes := map[int]string{}
+	addByte := func(name string, offset int) error {
+		if offset < 1 || offset >= maxReportLength {
+			return fmt.Errorf("%s: offset %d is out of the report", name, offset)
+		}
+		if other, ok := bytes[offset]; ok {
+			return fmt.Errorf("%s and %s are both at offset %d", name, other, offset)
+		}
+
+		bytes[offset] = name
+
+		if offset >= l.minLength {
+			l.minLength = offset + 1
+		}
+
+		return nil
+	}
+
+	if m.Hat != -1 {
+		if err := addByte("hat", m.Hat); err != nil {
+			return nil, err
+		}
+	}
+
+	for i, name := range [...]string{"left_stick_x", "left_stick_y", "right_stick_x", "right_stick_y"} {
+		if err := addByte(name, l.sticks[i]); err != nil {
+			return nil, err
+		}
+	}
+	for i, name := range [...]string{"left_trigger", "right_trigger"} {
+		if err := addByte(name, l.triggers[i]); err != nil {
+			return nil, err
+		}
+	}
+
+	// Sort buttons so that errors do not depend on the order of the map.
+	names := make([]string, 0, len(m.Buttons))
+
+	for name := range m.Buttons {
+		names = append(names, name)
+	}
+
+	sort.Strings(names)
+
+	bits := map[ReportBit]string{}
+
+	for _, name := range names {
+		bit := m.Buttons[name]
+		button, err := parseStadiaButton(name)
+
+		if err != nil {
+			return nil, err
+		}
+		if bit.Byte < 1 || bit.Byte >= maxReportLength || bit.Bit > 7 {
+			return nil, fmt.Errorf("%s: bit %d of offset %d is out of the report", name, bit.Bit, bit.Byte)
+		}
+		if other, ok := bytes[bit.Byte]; ok {
+			return nil, fmt.Errorf("%s is in %s, at offset %d", name, other, bit.Byte)
+		}
+		if other, ok := bits[bit]; ok {
+			return nil, fmt.Errorf("%s and %s are both at bit %d of offset %d", name, other, bit.Bit, bit.Byte)
+		}
+		if m.Hat != -1 && button&(StadiaButtonDpadUp|StadiaButtonDpadDown|StadiaButtonDpadLeft|StadiaButtonDpadRight) != 0 {
+			return nil, fmt.Errorf("%s cannot be given along with the hat", name)
+		}
+
+		bits[bit] = name
+		l.buttons = append(l.buttons, reportButtonBit{bit.Byte, 1 << bit.Bit, button})
+	}
+
+	return l, nil
+}
+
+// Pressed D-pad directions, indexed by the D-pad value of a report. Other
+// values mean that the D-pad is released.
+var dpadDirections = [...]StadiaButtons{
+	StadiaButtonDpadUp,
+	StadiaButtonDpadUp | StadiaButtonDpadRight,
+	StadiaButtonDpadRight,
+	StadiaButtonDpadRight | StadiaButtonDpadDown,
+	StadiaButtonDpadDown,
+	StadiaButtonDpadDown | StadiaButtonDpadLeft,
+	StadiaButtonDpadLeft,
+	StadiaButtonDpadLeft | StadiaButtonDpadUp,
+}
+
+// otherReportError is returned when parsing a report whose ID is not that of
+// the input reports of the controller. It is an ErrMalformedReport according
+// to errors.Is.
+type otherReportError struct {
+	id byte
+}
+
+func (e *otherReportError) Error() string {
+	return fmt.Sprintf("%v: report %02x is not an input report of the controller", ErrMalformedReport, e.id)
+}
+
+func (e *otherReportError) Is(target error) bool {
+	return target == ErrMalformedReport
+}
+
+// parse parses a raw input report laid out as described by the layout, like
+// ParseReport.
+func (l *reportLayout) parse(data []byte, report *Xbox360ControllerReport) error {
+	if len(data) == 0 {
+		return fmt.Errorf("%w: empty report", ErrMalformedReport)
+	}
+
+	if data[0] != l.id {
+		return &otherReportError{data[0]}
+	}
+	if len(data) < l.minLength {
+		return fmt.Errorf("%w: unknown report format; raw report was %s", ErrMalformedReport, base64.StdEncoding.EncodeToString(data))
+	}
+
+	// Update buttons.
+	var buttons StadiaButtons
+
+	for _, bit := range l.buttons {
+		if bit.offset < len(data) && data[bit.offset]&bit.mask != 0 {
+			buttons |= bit.button
+		}
+	}
+
+	if l.hat != -1 {
+		if dpad := int(data[l.hat]); dpad < len(dpadDirections) {
+			buttons |= dpadDirections[dpad]
+		}
+	}
+
+	report.Buttons = buttons
+	report.Assistant = buttons.Has(StadiaButtonAssistant)
+	report.Capture = buttons.Has(StadiaButtonCapture)
+
+	for _, button := range xbox360Buttons {
+		report.MaybeSetButton(button.xbox360, buttons.Has(button.stadia))
+	}
+
+	// Set axes values.
+	lThumbX := axisValue(data[l.sticks[0]], false)
+	lThumbY := axisValue(data[l.sticks[1]], true)
+	rThumbX := axisValue(data[l.sticks[2]], false)
+	rThumbY := axisValue(data[l.sticks[3]], true)
+
+	report.SetLeftThumb(lThumbX, lThumbY)
+	report.SetRightThumb(rThumbX, rThumbY)
+
+	// Set triggers.
+	report.SetLeftTrigger(data[l.triggers[0]])
+	report.SetRightTrigger(data[l.triggers[1]])
+
+	return nil
+}
+
+// defaultLayout is the layout of DefaultReportMap, used by ParseReport.
+var defaultLayout = func() *reportLayout {
+	m := DefaultReportMap()
+	l, err := m.layout()
+
+	if err != nil {
+		panic(err)
+	}
+
+	return l
+}()
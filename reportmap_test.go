@@ -0,0 +1,160 @@
+package stadiacontroller
+
+import (
+	"errors"
+	"math/rand"
+	"testing"
+)
+
+// hardCodedParse is ParseReport as it was before report maps, with the current
+// axis conversion.
+func hardCodedParse(data []byte, report *Xbox360ControllerReport) error {
+	if len(data) == 0 || data[0] != 0x03 || len(data) < 10 {
+		return ErrMalformedReport
+	}
+
+	bits := [...]struct {
+		offset int
+		mask   byte
+		button StadiaButtons
+	}{
+		{3, 0b0100_0000, StadiaButtonA},
+		{3, 0b0010_0000, StadiaButtonB},
+		{3, 0b0001_0000, StadiaButtonX},
+		{3, 0b0000_1000, StadiaButtonY},
+		{3, 0b0000_0100, StadiaButtonL1},
+		{3, 0b0000_0010, StadiaButtonR1},
+		{3, 0b0000_0001, StadiaButtonL3},
+		{2, 0b1000_0000, StadiaButtonR3},
+		{2, 0b0100_0000, StadiaButtonOptions},
+		{2, 0b0010_0000, StadiaButtonMenu},
+		{2, 0b0001_0000, StadiaButtonStadia},
+		{2, 0b0000_0010, StadiaButtonAssistant},
+		{2, 0b0000_0001, StadiaButtonCapture},
+	}
+
+	var buttons StadiaButtons
+
+	for _, bit := range bits {
+		if data[bit.offset]&bit.mask != 0 {
+			buttons |= bit.button
+		}
+	}
+
+	if dpad := int(data[1]); dpad < len(dpadDirections) {
+		buttons |= dpadDirections[dpad]
+	}
+
+	if len(data) >= 11 {
+		if data[10]&0b0000_0001 != 0 {
+			buttons |= StadiaButtonVolumeUp
+		}
+		if data[10]&0b0000_0010 != 0 {
+			buttons |= StadiaButtonVolumeDown
+		}
+	}
+
+	report.Buttons = buttons
+	report.Assistant = buttons.Has(StadiaButtonAssistant)
+	report.Capture = buttons.Has(StadiaButtonCapture)
+
+	for _, button := range xbox360Buttons {
+		report.MaybeSetButton(button.xbox360, buttons.Has(button.stadia))
+	}
+
+	report.SetLeftThumb(axisValue(data[4], false), axisValue(data[5], true))
+	report.SetRightThumb(axisValue(data[6], false), axisValue(data[7], true))
+	report.SetLeftTrigger(data[8])
+	report.SetRightTrigger(data[9])
+
+	return nil
+}
+
+func TestDefaultReportMapMatchesHardCodedParser(t *testing.T) {
+	reports := [][]byte{
+		// At rest.
+		{0x03, 0x08, 0x00, 0x00, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00},
+		// A held, left stick up-left, D-pad down-left.
+		{0x03, 0x05, 0x00, 0x40, 0x12, 0x07, 0x80, 0x80, 0x00, 0x00},
+		// Every button, the sticks at their extremes and both triggers.
+		{0x03, 0x00, 0xf3, 0x7f, 0x00, 0xff, 0xff, 0x00, 0xff, 0xff},
+		// Stadia and Capture, with the triggers half pressed.
+		{0x03, 0x0f, 0x11, 0x00, 0x7f, 0x81, 0x80, 0x7f, 0x7a, 0x83},
+		// Unused bits set, which no input is assigned to.
+		{0x03, 0x08, 0x0c, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00},
+		// Headset volume buttons, reported by firmware with 11 byte reports.
+		{0x03, 0x08, 0x00, 0x00, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00, 0x01},
+		{0x03, 0x02, 0x00, 0x08, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00, 0xfe},
+		// Longer reports, whose extra bytes are ignored.
+		{0x03, 0x08, 0x00, 0x00, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00, 0x03, 0xff, 0xff},
+	}
+
+	random := rand.New(rand.NewSource(1))
+
+	for i := 0; i < 10000; i++ {
+		data := make([]byte, 10+random.Intn(3))
+		random.Read(data)
+		data[0] = 0x03
+
+		reports = append(reports, data)
+	}
+
+	for _, data := range reports {
+		var expected, report Xbox360ControllerReport
+
+		hardCodedParse(data, &expected)
+
+		if err := ParseReport(data, &report); err != nil {
+			t.Errorf("ParseReport(%x) returned %v", data, err)
+		} else if report != expected {
+			t.Errorf("ParseReport(%x) = %+v, expected %+v", data, report, expected)
+		}
+	}
+
+	// Both parsers reject the same reports.
+	for _, data := range [][]byte{{}, {0x03}, {0x03, 0x08, 0x00, 0x00, 0x80, 0x80, 0x80, 0x80, 0x00}, {0x01, 0x08, 0x00, 0x00, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00}} {
+		var report Xbox360ControllerReport
+
+		if err := ParseReport(data, &report); !errors.Is(err, ErrMalformedReport) {
+			t.Errorf("ParseReport(%x) returned %v", data, err)
+		}
+	}
+}
+
+func TestReportMapValidate(t *testing.T) {
+	if m := DefaultReportMap(); m.Validate() != nil {
+		t.Fatalf("default report map is invalid: %v", m.Validate())
+	}
+
+	tests := []struct {
+		name   string
+		change func(m *ReportMap)
+		valid  bool
+	}{
+		{"moved trigger", func(m *ReportMap) { m.RightTrigger = 11 }, true},
+		{"moved button", func(m *ReportMap) { m.Buttons["a"] = ReportBit{2, 3} }, true},
+		{"D-pad buttons without hat", func(m *ReportMap) {
+			m.Hat = -1
+			m.Buttons["dpad_up"] = ReportBit{1, 0}
+			m.Buttons["dpad_down"] = ReportBit{1, 1}
+		}, true},
+		{"D-pad buttons along with hat", func(m *ReportMap) { m.Buttons["dpad_up"] = ReportBit{11, 0} }, false},
+		{"two axes at one offset", func(m *ReportMap) { m.LeftStickY = m.LeftStickX }, false},
+		{"axis on the hat", func(m *ReportMap) { m.RightStickX = m.Hat }, false},
+		{"button in an axis", func(m *ReportMap) { m.Buttons["b"] = ReportBit{m.LeftTrigger, 0} }, false},
+		{"two buttons at one bit", func(m *ReportMap) { m.Buttons["y"] = m.Buttons["x"] }, false},
+		{"axis on the report ID", func(m *ReportMap) { m.LeftStickX = 0 }, false},
+		{"axis past the report", func(m *ReportMap) { m.LeftStickX = maxReportLength }, false},
+		{"button past its byte", func(m *ReportMap) { m.Buttons["a"] = ReportBit{3, 8} }, false},
+		{"unknown button", func(m *ReportMap) { m.Buttons["start"] = ReportBit{11, 0} }, false},
+	}
+
+	for _, test := range tests {
+		m := DefaultReportMap()
+		test.change(&m)
+
+		if err := m.Validate(); (err == nil) != test.valid {
+			t.Errorf("%s: Validate returned %v", test.name, err)
+		}
+	}
+}
@@ -1,207 +1,697 @@
+// Package stadiacontroller reads input from a Stadia controller connected to
+// a Windows machine, drives its vibration motors, and forwards its state to an
+// Xbox 360 controller emulated with ViGEm.
 package stadiacontroller
 
 import (
-	"encoding/base64"
 	"errors"
 	"fmt"
 	"log"
+	"sync"
+	"sync/atomic"
 	"time"
 )
 
+// Vendor and product IDs of the Stadia controller.
 const (
 	stadiaControllerVid = 0x18D1
 	stadiaControllerPid = 0x9400
 )
 
+// Interval between two attempts to find a device when device arrivals are
+// watched, in case a notification is missed.
+const arrivalFallbackInterval = 30 * time.Second
+
+// Number of consecutive reports which could not be parsed after which failures
+// are no longer logged, and after which the controller is acquired again.
+const (
+	maxLoggedParseFailures = 5
+	maxParseFailures       = 50
+)
+
+// StadiaController is a Stadia controller which is acquired in the background
+// as soon as it is connected, and re-acquired after being disconnected.
 type StadiaController struct {
+	options StadiaControllerOptions
+	ticker  *time.Ticker
+
+	// retries wakes up the goroutine acquiring devices, e.g. when a device
+	// arrives.
+	retries  chan struct{}
+	arrivals *deviceArrivals
+
+	// vibrateMu ensures that no request is sent to vibrations once done is
+	// closed, so that every request is answered.
+	vibrateMu  sync.Mutex
+	vibrations chan vibrateRequest
+	done       chan struct{}
+
+	// vibrateStopped is closed once vibrateLoop wrote its last frame.
+	vibrateStopped chan struct{}
+
+	eventsMu     sync.RWMutex
+	events       chan ControllerEvent
+	eventsClosed bool
+
+	// layouts are the validated options.ReportMaps.
+	layouts []*reportLayout
+
+	// parseFailures is the number of consecutive reports which could not be
+	// parsed.
+	parseFailures uint32
+
+	// openErr is the last error which prevented opening a device, which is
+	// only logged when it changes. It is only used by acquire.
+	openErr error
+
+	mu     sync.Mutex
 	device *Device
-	ticker *time.Ticker
+	info   *DeviceInfo
+	parse  reportParser
 	err    error
+	closed bool
+
+	// lastInput is when a button of the active controller was last pressed,
+	// and standby the controller which takes over from it with HandOffIdle.
+	lastInput time.Time
+	standby   *standbyController
 }
 
+// NewStadiaController returns a StadiaController which looks for a connected
+// controller whenever a device arrives until one is found.
 func NewStadiaController() *StadiaController {
-	ticker := time.NewTicker(1 * time.Second)
-	controller := &StadiaController{nil, ticker, nil}
+	controller, _ := NewStadiaControllerWithOptions(StadiaControllerOptions{})
 
-	go func() {
-		for range ticker.C {
-			if controller.device != nil || controller.err != nil {
-				continue
-			}
+	return controller
+}
 
-			devices, err := Devices()
+// NewStadiaControllerWithOptions returns a StadiaController which looks for the
+// controller selected by the given options until it is found. An error is
+// returned if the options are invalid or conflicting.
+func NewStadiaControllerWithOptions(options StadiaControllerOptions) (*StadiaController, error) {
+	if err := options.validate(); err != nil {
+		return nil, err
+	}
 
-			if err != nil {
-				controller.err = err
+	layouts := make([]*reportLayout, len(options.ReportMaps))
 
-				break
-			}
+	for i := range options.ReportMaps {
+		layouts[i], _ = options.ReportMaps[i].layout()
+	}
+
+	controller := &StadiaController{
+		options:        options,
+		layouts:        layouts,
+		retries:        make(chan struct{}, 1),
+		vibrations:     make(chan vibrateRequest, 8),
+		done:           make(chan struct{}),
+		vibrateStopped: make(chan struct{}),
+	}
 
-			for _, device := range devices {
-				if device.VendorID == stadiaControllerVid && device.ProductID == stadiaControllerPid {
-					openDevice, err := device.Open()
+	// Devices are only looked for when one arrives, falling back to polling if
+	// arrivals cannot be watched.
+	interval := options.reconnectInterval()
+	arrivals, err := watchDeviceArrivals(controller.retry)
 
-					if err != nil {
-						log.Printf("cannot open device %s: %v", device.Path, err)
+	if err != nil {
+		log.Printf("%v, looking for devices every %v", err, interval)
+	} else {
+		controller.arrivals = arrivals
+		interval = arrivalFallbackInterval
+	}
 
-						break
-					}
+	controller.ticker = time.NewTicker(interval)
+	controller.retry()
 
-					log.Printf("opened device %s", device.Path)
-					controller.device = &openDevice
+	go func() {
+		for {
+			select {
+			case <-controller.ticker.C:
+			case <-controller.retries:
+			case <-controller.done:
+				return
+			}
 
-					break
-				}
+			if !controller.acquire() {
+				return
 			}
 		}
 	}()
 
-	return controller
+	go controller.vibrateLoop()
+
+	return controller, nil
+}
+
+// acquire looks for a device and opens it if no device is acquired yet. It
+// returns false if looking for devices failed and should no longer be tried.
+func (c *StadiaController) acquire() bool {
+	c.mu.Lock()
+	busy := c.device != nil || c.err != nil || c.closed
+	needsStandby := c.device != nil && c.standby == nil && !c.closed && c.options.HandOffIdle != 0
+	info := c.info
+	c.mu.Unlock()
+
+	if needsStandby {
+		c.acquireStandby(info.Path)
+	}
+	if busy {
+		return true
+	}
+
+	device, err := c.options.findDevice()
+
+	if err != nil {
+		c.mu.Lock()
+		c.err = err
+		c.mu.Unlock()
+
+		return false
+	}
+	if device == nil {
+		return true
+	}
+
+	openDevice, err := c.options.backend().Open(device)
+
+	if err != nil {
+		// Another program may hold the device for a long time: only log the
+		// first attempt.
+		if c.openErr == nil || c.openErr.Error() != err.Error() {
+			log.Printf("cannot open device %s: %v", device.Path, err)
+		}
+
+		c.openErr = err
+		c.emit(ErrorEvent{&retryError{err}})
+
+		// The device will not arrive again.
+		time.AfterFunc(c.options.reconnectInterval(), c.retry)
+
+		return true
+	}
+
+	c.openErr = nil
+
+	if c.options.FlushOnAcquire {
+		if err := openDevice.Flush(); err != nil {
+			log.Printf("cannot flush device %s: %v", device.Path, err)
+		}
+	}
+
+	// Send the event before storing the device, so that it is received before
+	// any report of the device.
+	c.emit(ConnectedEvent{
+		Path:      device.Path,
+		Serial:    device.SerialNumber,
+		Firmware:  device.VersionNumber,
+		Transport: device.Transport(),
+	})
+
+	c.mu.Lock()
+	defer c.mu.Unlock()
+
+	if c.closed {
+		openDevice.Close()
+
+		return false
+	}
+
+	log.Printf("opened device %s over %v: %s", device.Path, device.Transport(), describeDevice(device))
+	c.device = &openDevice
+	c.info = device
+	c.parse = c.parserFor(device)
+	c.lastInput = time.Now()
+	atomic.StoreUint32(&c.parseFailures, 0)
+
+	return true
+}
+
+// describeDevice returns the product, serial number, firmware revision and
+// report lengths of the given device, for bug reports. Strings which could not
+// be read are given as "unknown".
+func describeDevice(device *DeviceInfo) string {
+	orUnknown := func(s string) string {
+		if s == "" {
+			return "unknown"
+		}
+		return s
+	}
+
+	return fmt.Sprintf("product %s, serial %s, firmware %04x, reports of %d (input), %d (output) and %d (feature) bytes",
+		orUnknown(device.Product), orUnknown(device.SerialNumber), device.VersionNumber,
+		device.InputReportLength, device.OutputReportLength, device.FeatureReportLength)
+}
+
+// Info returns the information read from the acquired controller when it was
+// opened, or nil if no controller is acquired.
+func (c *StadiaController) Info() *DeviceInfo {
+	c.mu.Lock()
+	defer c.mu.Unlock()
+
+	if c.device == nil {
+		return nil
+	}
+
+	info := *c.info
+
+	return &info
 }
 
+// retry makes the controller look for a device right away.
+func (c *StadiaController) retry() {
+	select {
+	case c.retries <- struct{}{}:
+	default:
+	}
+}
+
+// Close stops looking for a controller, stops the motors of the acquired one,
+// if any, and releases it.
 func (c *StadiaController) Close() {
 	c.ticker.Stop()
 
-	if c.device == nil {
-		return
+	if c.arrivals != nil {
+		c.arrivals.Close()
+	}
+
+	c.mu.Lock()
+	c.closed = true
+	c.mu.Unlock()
+
+	c.vibrateMu.Lock()
+	select {
+	case <-c.done:
+	default:
+		close(c.done)
+	}
+	c.vibrateMu.Unlock()
+
+	// Games may have left the motors running, and the controller keeps
+	// vibrating until told otherwise. Wait for pending writes so that the
+	// motors are stopped last.
+	<-c.vibrateStopped
+
+	if err := c.VibrateRaw(StopVibration); err != nil && !errors.Is(err, ErrDeviceNotFound) {
+		log.Printf("cannot stop the vibration of the controller: %v", err)
 	}
 
-	(*c.device).Close()
+	c.closeEvents()
+	c.Release()
 }
 
+// Release releases the acquired controller, if any: its pending reads and
+// writes are cancelled, and Release returns once they completed and its
+// handles are closed. The standby controller is released as well. Unless Close
+// was called, the controller is acquired again as soon as it is found.
+//
+// A GetReport call waiting for a report of the released controller returns a
+// RetryError. No report is lost in the process, since only reports which were
+// not read from the device yet are discarded.
+func (c *StadiaController) Release() {
+	c.mu.Lock()
+	device := c.device
+	standby := c.standby
+	c.device = nil
+	c.standby = nil
+	c.mu.Unlock()
+
+	if standby != nil {
+		standby.device.Close()
+	}
+	if device != nil {
+		(*device).Close()
+		c.emit(DisconnectedEvent{})
+	}
+
+	// The released device may still be connected.
+	c.retry()
+}
+
+// Vibrate sets the intensity of the large and small vibration motors of the
+// controller. When no controller is acquired, it does nothing and returns
+// ErrDeviceNotFound, or the error that prevented finding one.
 func (c *StadiaController) Vibrate(largeMotor, smallMotor byte) error {
-	if c.device == nil {
-		return c.err
+	return c.VibrateRaw(NewVibrationFrame(largeMotor, smallMotor))
+}
+
+// VibrateRaw sends the given frame to the vibration motors of the controller.
+// When no controller is acquired, it does nothing and returns
+// ErrDeviceNotFound, or the error that prevented finding one.
+func (c *StadiaController) VibrateRaw(frame VibrationFrame) error {
+	c.mu.Lock()
+	device, err := c.device, c.err
+	c.mu.Unlock()
+
+	if device == nil {
+		if err != nil {
+			return err
+		}
+		return ErrDeviceNotFound
 	}
 
-	return (*c.device).Write([]byte{0x05, largeMotor, largeMotor, smallMotor, smallMotor})
+	return (*device).Write([]byte{0x05, frame[0], frame[1], frame[2], frame[3]})
+}
+
+type vibrateRequest struct {
+	frame VibrationFrame
+	done  chan error
+}
+
+// VibrateAsync is like Vibrate, but returns immediately. The returned channel
+// receives the result of the write once the controller completed it.
+func (c *StadiaController) VibrateAsync(largeMotor, smallMotor byte) <-chan error {
+	return c.VibrateRawAsync(NewVibrationFrame(largeMotor, smallMotor))
+}
+
+// VibrateRawAsync is like VibrateRaw, but returns immediately. The returned
+// channel receives the result of the write once the controller completed it.
+//
+// Writes are sent to the controller one at a time, in the order in which they
+// were requested. Frames requested while a write is pending are coalesced:
+// only the latest one is written, and the channels of the frames it
+// superseded receive the result of its write. A frame identical to the last
+// one written is not written again, unless it stops the motors. After Close,
+// the channel receives ErrDeviceNotFound.
+func (c *StadiaController) VibrateRawAsync(frame VibrationFrame) <-chan error {
+	request := vibrateRequest{frame: frame, done: make(chan error, 1)}
+
+	c.vibrateMu.Lock()
+	defer c.vibrateMu.Unlock()
+
+	select {
+	case <-c.done:
+		request.done <- ErrDeviceNotFound
+	default:
+		c.vibrations <- request
+	}
+
+	return request.done
+}
+
+// vibrateLoop performs the writes requested by VibrateRawAsync until the
+// controller is closed.
+func (c *StadiaController) vibrateLoop() {
+	defer close(c.vibrateStopped)
+
+	var last VibrationFrame
+	var lastDevice *Device
+
+	for {
+		select {
+		case request := <-c.vibrations:
+			// Games may request vibrations faster than they are written, so
+			// skip to the latest one instead of replaying a backlog of
+			// vibrations which are already over.
+			var superseded []vibrateRequest
+
+		coalesce:
+			for {
+				select {
+				case next := <-c.vibrations:
+					superseded = append(superseded, request)
+					request = next
+				default:
+					break coalesce
+				}
+			}
+
+			c.mu.Lock()
+			device := c.device
+			c.mu.Unlock()
+
+			var err error
+
+			if device == nil || device != lastDevice || request.frame != last || request.frame == StopVibration {
+				err = c.VibrateRaw(request.frame)
+
+				if err == nil {
+					last, lastDevice = request.frame, device
+				}
+			}
+
+			for _, r := range superseded {
+				r.done <- err
+			}
+
+			request.done <- err
+		case <-c.done:
+			for {
+				select {
+				case request := <-c.vibrations:
+					request.done <- ErrDeviceNotFound
+				default:
+					return
+				}
+			}
+		}
+	}
 }
 
+// RetryError is returned by GetReport when no report is available yet,
+// because no controller is acquired or because the last report could not be
+// parsed. GetReport should be called again after a short delay.
+//
+// Errors returned by GetReport are RetryError according to errors.Is, and
+// wrap their cause (e.g. ErrDeviceNotFound or ErrMalformedReport).
 var RetryError = errors.New("retry")
 
+// GetReport waits for the next report of the controller, and returns it.
 func (c *StadiaController) GetReport() (Xbox360ControllerReport, error) {
 	report := Xbox360ControllerReport{}
 
-	if c.device == nil {
-		err := c.err
+	c.mu.Lock()
+	device, parse, err := c.device, c.parse, c.err
+	c.mu.Unlock()
+
+	if device == nil {
 		if err == nil {
-			err = RetryError
+			err = &retryError{ErrDeviceNotFound}
 		}
 		return report, err
 	}
 
-	buf, ok := <-(*c.device).ReadCh()
+	buf, ok := <-(*device).ReadCh()
+
+	// Other kinds of reports may be interleaved with the input reports of the
+	// controller, e.g. by some firmware revisions: skip them.
+	for ok && !c.replaced(device) {
+		var other *otherReportError
+
+		if err = parse(buf, &report); !errors.As(err, &other) {
+			break
+		}
+
+		noticeOtherReport(other.id)
+		buf, ok = <-(*device).ReadCh()
+	}
+
+	if c.replaced(device) {
+		// The standby controller took over while waiting.
+		return c.GetReport()
+	}
+
+	if ok && c.options.OnRawReport != nil {
+		c.options.OnRawReport(buf, &report, err)
+	}
 
 	if !ok {
-		err := (*c.device).ReadError()
+		err := (*device).ReadError()
 		log.Printf("unable to read from controller: %v", err)
+		(*device).Close()
+
+		if c.handOff(device, true) {
+			return c.GetReport()
+		}
+
 		log.Printf("waiting for new controller")
-		(*c.device).Close()
-		c.device = nil
-		return report, RetryError
-	}
 
-	err := ParseReport(buf, &report)
+		c.mu.Lock()
+		released := c.device == device
+		if released {
+			c.device = nil
+		}
+		c.mu.Unlock()
+
+		if released {
+			c.emit(DisconnectedEvent{})
+
+			// Another matching device may already be connected.
+			c.retry()
+		}
+
+		return report, &retryError{err}
+	}
 
 	if err != nil {
-		log.Printf("unable to parse controller report: %v", err)
-		return report, RetryError
+		failures := atomic.AddUint32(&c.parseFailures, 1)
+
+		// A device sending reports in another format would flood the log.
+		if failures <= maxLoggedParseFailures {
+			log.Printf("unable to parse controller report: %v", err)
+		}
+		if failures == maxParseFailures {
+			log.Printf("%d reports in a row could not be parsed, acquiring the controller again", failures)
+
+			// Release waits for GetReport to return.
+			go c.Release()
+		}
+
+		return report, &retryError{err}
+	}
+
+	atomic.StoreUint32(&c.parseFailures, 0)
+
+	if report.Buttons != 0 && c.options.HandOffIdle != 0 {
+		c.mu.Lock()
+		c.lastInput = time.Now()
+		c.mu.Unlock()
 	}
 
 	return report, nil
 }
 
-func ParseReport(data []byte, report *Xbox360ControllerReport) error {
-	if len(data) == 0 {
-		return errors.New("cannot parse empty report")
-	}
-
-	if data[0] == 0x03 && len(data) >= 10 {
-		a := data[1]
-		b := data[2]
-		c := data[3]
-
-		// Update common buttons.
-		report.MaybeSetButton(Xbox360ControllerButtonA, (c&0b0100_0000) != 0)
-		report.MaybeSetButton(Xbox360ControllerButtonB, (c&0b0010_0000) != 0)
-		report.MaybeSetButton(Xbox360ControllerButtonX, (c&0b0001_0000) != 0)
-		report.MaybeSetButton(Xbox360ControllerButtonY, (c&0b0000_1000) != 0)
-		report.MaybeSetButton(Xbox360ControllerButtonLeftShoulder, (c&0b0000_0100) != 0)
-		report.MaybeSetButton(Xbox360ControllerButtonRightShoulder, (c&0b0000_0010) != 0)
-		report.MaybeSetButton(Xbox360ControllerButtonLeftThumb, (c&0b0000_0001) != 0)
-		report.MaybeSetButton(Xbox360ControllerButtonRightThumb, (b&0b1000_0000) != 0)
-		report.MaybeSetButton(Xbox360ControllerButtonBack, (b&0b0100_0000) != 0)
-		report.MaybeSetButton(Xbox360ControllerButtonStart, (b&0b0010_0000) != 0)
-		report.MaybeSetButton(Xbox360ControllerButtonGuide, (b&0b0001_0000) != 0)
-
-		report.Assistant = (b & 0b0000_0010) != 0
-		report.Capture = (b & 0b0000_0001) != 0
-
-		// Update DPad buttons.
-		switch a {
-		case 0:
-			report.SetButton(Xbox360ControllerButtonUp)
-		case 1:
-			report.SetButton(Xbox360ControllerButtonUp)
-			report.SetButton(Xbox360ControllerButtonRight)
-		case 2:
-			report.SetButton(Xbox360ControllerButtonRight)
-		case 3:
-			report.SetButton(Xbox360ControllerButtonRight)
-			report.SetButton(Xbox360ControllerButtonDown)
-		case 4:
-			report.SetButton(Xbox360ControllerButtonDown)
-		case 5:
-			report.SetButton(Xbox360ControllerButtonDown)
-			report.SetButton(Xbox360ControllerButtonLeft)
-		case 6:
-			report.SetButton(Xbox360ControllerButtonLeft)
-		case 7:
-			report.SetButton(Xbox360ControllerButtonLeft)
-			report.SetButton(Xbox360ControllerButtonUp)
-		}
-
-		// Normalize axes values.
-		// Port of https://github.com/MWisBest/StadiEm.
-		for i := 4; i < 8; i++ {
-			if data[i] <= 0x7F && data[i] > 0x00 {
-				data[i]--
-			}
-		}
+// A reportParser parses a raw input report into the given report, like
+// ParseReport.
+type reportParser func(data []byte, report *Xbox360ControllerReport) error
 
-		// Set axes values.
-		lThumbX := convertAxisValue(data[4]) - 0x8000
-		lThumbY := -convertAxisValue(data[5]) + 0x7fff
-		rThumbX := convertAxisValue(data[6]) - 0x8000
-		rThumbY := -convertAxisValue(data[7]) + 0x7fff
+// firmwareRevision describes a known firmware revision of the controller.
+type firmwareRevision struct {
+	// parse parses the reports of the revision, or is nil if ParseReport
+	// does.
+	parse reportParser
+}
 
-		if lThumbY == -1 {
-			lThumbY = 0
-		}
-		if rThumbY == -1 {
-			rThumbY = 0
+// Firmware revisions known to work, by version number of the device. Revisions
+// whose reports are laid out differently select their own parser here.
+//
+// Revisions are added as they are reported working (with the output of the
+// controller-info command); no revision has been confirmed yet.
+var firmwareRevisions = map[uint16]firmwareRevision{}
+
+var (
+	noticedRevisionsMu sync.Mutex
+	noticedRevisions   = map[uint16]bool{}
+)
+
+var (
+	noticedReportIDsMu sync.Mutex
+	noticedReportIDs   = map[byte]bool{}
+)
+
+// noticeOtherReport logs that reports with the given ID are skipped, once per
+// ID.
+func noticeOtherReport(id byte) {
+	noticedReportIDsMu.Lock()
+	noticed := noticedReportIDs[id]
+	noticedReportIDs[id] = true
+	noticedReportIDsMu.Unlock()
+
+	if !noticed {
+		log.Printf("skipping reports %02x of the controller, which are not input reports", id)
+	}
+}
+
+// parserForRevision returns the parser of the reports of the given firmware
+// revision. Unknown revisions use ParseReport, and are noticed once unless no
+// revision is known at all.
+func parserForRevision(version uint16) reportParser {
+	if revision, ok := firmwareRevisions[version]; ok {
+		if revision.parse != nil {
+			return revision.parse
 		}
 
-		report.SetLeftThumb(int16(lThumbX), int16(lThumbY))
-		report.SetRightThumb(int16(rThumbX), int16(rThumbY))
+		return ParseReport
+	}
 
-		// Set triggers.
-		report.SetLeftTrigger(data[8])
-		report.SetRightTrigger(data[9])
+	// Noticing every revision would only be noise.
+	if len(firmwareRevisions) == 0 {
+		return ParseReport
+	}
 
-		return nil
+	noticedRevisionsMu.Lock()
+	noticed := noticedRevisions[version]
+	noticedRevisions[version] = true
+	noticedRevisionsMu.Unlock()
+
+	if !noticed {
+		log.Printf("firmware revision %04x of the controller is not known to work; "+
+			"if inputs behave strangely, please file an issue with the output of "+
+			"`stadiacontroller controller-info`", version)
+	}
+
+	return ParseReport
+}
+
+// Xbox 360 equivalent of each Stadia button that has one.
+var xbox360Buttons = [...]struct {
+	stadia  StadiaButtons
+	xbox360 int
+}{
+	{StadiaButtonA, Xbox360ControllerButtonA},
+	{StadiaButtonB, Xbox360ControllerButtonB},
+	{StadiaButtonX, Xbox360ControllerButtonX},
+	{StadiaButtonY, Xbox360ControllerButtonY},
+	{StadiaButtonL1, Xbox360ControllerButtonLeftShoulder},
+	{StadiaButtonR1, Xbox360ControllerButtonRightShoulder},
+	{StadiaButtonL3, Xbox360ControllerButtonLeftThumb},
+	{StadiaButtonR3, Xbox360ControllerButtonRightThumb},
+	{StadiaButtonOptions, Xbox360ControllerButtonBack},
+	{StadiaButtonMenu, Xbox360ControllerButtonStart},
+	{StadiaButtonStadia, Xbox360ControllerButtonGuide},
+	{StadiaButtonDpadUp, Xbox360ControllerButtonUp},
+	{StadiaButtonDpadDown, Xbox360ControllerButtonDown},
+	{StadiaButtonDpadLeft, Xbox360ControllerButtonLeft},
+	{StadiaButtonDpadRight, Xbox360ControllerButtonRight},
+}
+
+// Xbox360Buttons returns the Xbox 360 equivalents of the buttons in the set,
+// one bit per button as given by the Xbox360ControllerButton constants.
+func (b StadiaButtons) Xbox360Buttons() uint16 {
+	var buttons uint16
+
+	for _, button := range xbox360Buttons {
+		if b.Has(button.stadia) {
+			buttons |= 1 << button.xbox360
+		}
 	}
 
-	return fmt.Errorf("unknown report format; raw report was %s", base64.StdEncoding.EncodeToString(data))
+	return buttons
 }
 
-func convertAxisValue(byteValue byte) int32 {
-	value := int32(byteValue)
-	value = value<<8 | ((value << 1) & 0b1111)
+// ParseReport parses a raw input report of the controller into the given
+// report, laid out as described by DefaultReportMap. The Xbox 360 buttons set
+// in data are added to those already set in report, whereas Buttons, Capture
+// and Assistant are replaced by those of data.
+func ParseReport(data []byte, report *Xbox360ControllerReport) error {
+	return defaultLayout.parse(data, report)
+}
+
+// axisValue converts the raw value of a stick axis, centered on 0x80, to the
+// range of the axes of the Xbox 360 controller. The conversion is monotonic,
+// and maps both extremes and the center exactly to -32768, 32767 and 0.
+// Inverted axes, i.e. the Y axes whose raw values grow downwards, map 0x00 to
+// 32767 instead.
+//
+// Port of https://github.com/MWisBest/StadiEm, which stops at 32526 above the
+// center and maps the center of inverted axes to -1. Values below the center
+// are unchanged, and those above it are stretched by less than 1%.
+func axisValue(raw byte, inverted bool) int16 {
+	var value int32
+
+	if raw >= 0x80 {
+		value = (int32(raw) - 0x80) * 0x7fff / 0x7f
+	} else {
+		// Raw values from 0x01 to 0x7F are one step too high.
+		if raw > 0 {
+			raw--
+		}
+
+		value = (int32(raw)<<8 | (int32(raw)<<1)&0b1111) - 0x8000
+	}
 
-	if value == 0xfffe {
-		return 0xffff
+	if inverted && value != 0 {
+		value = -value - 1
 	}
 
-	return value
+	return int16(value)
 }
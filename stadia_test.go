@@ -0,0 +1,345 @@
+package stadiacontroller
+
+import (
+	"encoding/hex"
+	"errors"
+	"io/ioutil"
+	"strings"
+	"testing"
+)
+
+// stadiEmAxisValue is the previous conversion, ported from StadiEm, without
+// the special case of the center of inverted axes.
+func stadiEmAxisValue(raw byte) int32 {
+	value := int32(raw)
+
+	if value > 0 && value <= 0x7f {
+		value--
+	}
+
+	value = value<<8 | (value<<1)&0b1111
+
+	if value == 0xfffe {
+		value = 0xffff
+	}
+
+	return value - 0x8000
+}
+
+func TestAxisValue(t *testing.T) {
+	for _, inverted := range []bool{false, true} {
+		extremes := map[byte]int16{0x00: -32768, 0x80: 0, 0xff: 32767}
+
+		if inverted {
+			extremes = map[byte]int16{0x00: 32767, 0x80: 0, 0xff: -32768}
+		}
+
+		for raw, expected := range extremes {
+			if value := axisValue(raw, inverted); value != expected {
+				t.Errorf("axisValue(%#04x, %v) = %d, expected %d", raw, inverted, value, expected)
+			}
+		}
+
+		previous := axisValue(0, inverted)
+
+		for i := 1; i < 256; i++ {
+			raw := byte(i)
+			value := axisValue(raw, inverted)
+
+			if (!inverted && value < previous) || (inverted && value > previous) {
+				t.Errorf("axisValue(%#04x, %v) = %d is not monotonic after %d", raw, inverted, value, previous)
+			}
+
+			previous = value
+
+			// Values stay within 1% of StadiEm, and are the same below the
+			// center.
+			expected := stadiEmAxisValue(raw)
+
+			if inverted && raw != 0x80 {
+				expected = -expected - 1
+			}
+
+			difference := int32(value) - expected
+
+			if difference < 0 {
+				difference = -difference
+			}
+			if difference > 0x7fff/100 || (raw < 0x80 && difference != 0) {
+				t.Errorf("axisValue(%#04x, %v) = %d, expected about %d", raw, inverted, value, expected)
+			}
+		}
+	}
+}
+
+func TestParseReportMalformed(t *testing.T) {
+	valid := []byte{0x03, 0x08, 0x00, 0x00, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00}
+
+	for n := 0; n < len(valid); n++ {
+		var report Xbox360ControllerReport
+
+		if err := ParseReport(valid[:n], &report); !errors.Is(err, ErrMalformedReport) {
+			t.Errorf("ParseReport of %d bytes returned %v", n, err)
+		}
+	}
+
+	for id := 0; id < 256; id++ {
+		if id == 0x03 {
+			continue
+		}
+
+		data := append([]byte{byte(id)}, valid[1:]...)
+
+		var report Xbox360ControllerReport
+		var other *otherReportError
+
+		if err := ParseReport(data, &report); !errors.As(err, &other) || other.id != byte(id) || !errors.Is(err, ErrMalformedReport) {
+			t.Errorf("ParseReport of report %02x returned %v", id, err)
+		}
+	}
+}
+
+func TestGetReportParseFailures(t *testing.T) {
+	device := newFakeDevice(`\\?\hid#vid_18d1&pid_9400#1`, "")
+
+	backend := &fakeBackend{}
+	backend.plug(nil, device)
+
+	c := newFakeController(t, backend, StadiaControllerOptions{})
+	defer c.Close()
+
+	waitUntil(t, "the controller is acquired", acquired(c, device))
+
+	getReport := func(reports ...[]byte) (Xbox360ControllerReport, error) {
+		go func() {
+			for _, report := range reports {
+				device.send(report)
+			}
+		}()
+
+		return c.GetReport()
+	}
+
+	truncated := []byte{0x03, 0x08, 0x00, 0x00, 0x80}
+	valid := []byte{0x03, 0x08, 0x00, 0x40, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00}
+
+	// Reports with other IDs are skipped.
+	if report, err := getReport([]byte{0x01, 0xff}, []byte{0x05, 0, 0, 0, 0}, valid); err != nil || report.Buttons != StadiaButtonA {
+		t.Errorf("GetReport after other reports returned %q, %v", report.Buttons, err)
+	}
+
+	// D-pad values past the directions mean that the D-pad is released.
+	for _, dpad := range []byte{0x08, 0x09, 0x0f, 0xff} {
+		released := append([]byte(nil), valid...)
+		released[1] = dpad
+
+		if report, err := getReport(released); err != nil || report.Buttons != StadiaButtonA {
+			t.Errorf("GetReport with D-pad %#04x returned %q, %v", dpad, report.Buttons, err)
+		}
+	}
+
+	// Truncated reports are skipped, until too many in a row suggest that the
+	// device is not a controller, or one which must be opened again.
+	for round := 0; round < 2; round++ {
+		for i := 1; i < maxParseFailures; i++ {
+			if _, err := getReport(truncated); !errors.Is(err, RetryError) || !errors.Is(err, ErrMalformedReport) {
+				t.Fatalf("GetReport of truncated report %d returned %v", i, err)
+			}
+		}
+
+		if round == 0 {
+			if _, err := getReport(valid); err != nil {
+				t.Fatalf("GetReport returned %v", err)
+			}
+		}
+	}
+
+	if opened, _ := device.openCount(); opened != 1 {
+		t.Fatalf("device was opened again before %d parse failures in a row", maxParseFailures)
+	}
+	if _, err := getReport(truncated); !errors.Is(err, ErrMalformedReport) {
+		t.Fatalf("GetReport of truncated report %d returned %v", maxParseFailures, err)
+	}
+
+	waitUntil(t, "the controller is opened again", func() bool {
+		opened, closed := device.openCount()
+		return opened == 2 && closed == 1
+	})
+
+	if report, err := getReport(valid); err != nil || report.Buttons != StadiaButtonA {
+		t.Errorf("GetReport after opening the controller again returned %q, %v", report.Buttons, err)
+	}
+}
+
+func TestNoReportLostDuringVibration(t *testing.T) {
+	device := newFakeDevice(`\\?\hid#vid_18d1&pid_9400#1`, "")
+	device.readBufs = 32
+
+	backend := &fakeBackend{}
+	backend.plug(nil, device)
+
+	c := newFakeController(t, backend, StadiaControllerOptions{})
+	defer c.Close()
+
+	waitUntil(t, "the controller is acquired", acquired(c, device))
+
+	events := c.Events()
+
+	// Reports are numbered by their triggers, and sent while vibrations are
+	// requested as fast as they are written.
+	const reports = 20000
+
+	stop := make(chan struct{})
+	vibrations := make(chan int)
+
+	go func() {
+		n := 0
+
+		for {
+			select {
+			case <-stop:
+				vibrations <- n
+				return
+			default:
+			}
+
+			if err := <-c.VibrateAsync(byte(n), byte(n>>8)); err != nil {
+				t.Errorf("VibrateAsync returned %v", err)
+			}
+
+			n++
+		}
+	}()
+
+	go func() {
+		for i := 0; i < reports; i++ {
+			device.send([]byte{0x03, 0x08, 0x00, 0x00, 0x80, 0x80, 0x80, 0x80, byte(i), byte(i >> 8)})
+		}
+	}()
+
+	for i := 0; i < reports; i++ {
+		event := nextEvent(t, events)
+		report, ok := event.(ReportEvent)
+
+		if !ok {
+			t.Fatalf("received %#v, expected report %d", event, i)
+		}
+		if n := int(report.Report.GetLeftTrigger()) | int(report.Report.GetRightTrigger())<<8; n != i {
+			t.Fatalf("received report %d, expected report %d", n, i)
+		}
+
+		// Synchronous writes are interleaved with the asynchronous ones.
+		if i%100 == 0 {
+			if err := c.Vibrate(0, byte(i)); err != nil {
+				t.Fatalf("Vibrate returned %v", err)
+			}
+		}
+	}
+
+	close(stop)
+
+	if n := <-vibrations; n == 0 || len(device.written()) == 0 {
+		t.Errorf("%d vibrations were requested and %d written during the reports", n, len(device.written()))
+	}
+}
+
+func TestParserForRevision(t *testing.T) {
+	defer func(revisions map[uint16]firmwareRevision) {
+		firmwareRevisions = revisions
+	}(firmwareRevisions)
+
+	shifted := func(data []byte, report *Xbox360ControllerReport) error {
+		return ParseReport(append([]byte{0x03}, data[2:]...), report)
+	}
+
+	firmwareRevisions = map[uint16]firmwareRevision{
+		0x0100: {},
+		0x0200: {parse: shifted},
+	}
+
+	data := []byte{0x03, 0x00, 0x08, 0x00, 0x40, 0x80, 0x80, 0x80, 0x80, 0x00, 0x00}
+
+	for _, test := range []struct {
+		version  uint16
+		expected StadiaButtons
+	}{
+		{0x0100, StadiaButtonDpadUp},
+		{0x0200, StadiaButtonA},
+		{0x0300, StadiaButtonDpadUp},
+	} {
+		var report Xbox360ControllerReport
+
+		if err := parserForRevision(test.version)(data, &report); err != nil || report.Buttons != test.expected {
+			t.Errorf("parser of revision %04x returned %q, %v, expected %q", test.version, report.Buttons, err, test.expected)
+		}
+	}
+}
+
+// readReportFixtures returns the raw reports of the file at the given path,
+// written one per line as hexadecimal bytes. Empty lines and lines starting
+// with # are ignored.
+func readReportFixtures(tb testing.TB, path string) [][]byte {
+	tb.Helper()
+
+	data, err := ioutil.ReadFile(path)
+
+	if err != nil {
+		tb.Fatal(err)
+	}
+
+	var reports [][]byte
+
+	for i, line := range strings.Split(string(data), "\n") {
+		if line = strings.TrimSpace(line); line == "" || strings.HasPrefix(line, "#") {
+			continue
+		}
+
+		report, err := hex.DecodeString(strings.Replace(line, " ", "", -1))
+
+		if err != nil {
+			tb.Fatalf("%s:%d: %v", path, i+1, err)
+		}
+
+		reports = append(reports, report)
+	}
+
+	if len(reports) == 0 {
+		tb.Fatalf("%s has no report", path)
+	}
+
+	return reports
+}
+
+// Parsing is part of the budget of each report of the input loop, checked by
+// BenchmarkHandleReportAllTransforms in cmd.
+func BenchmarkParseReport(b *testing.B) {
+	reports := readReportFixtures(b, "testdata/reports.hex")
+
+	for _, data := range reports {
+		var report Xbox360ControllerReport
+
+		if err := ParseReport(data, &report); err != nil {
+			b.Fatalf("ParseReport(% x) returned %v", data, err)
+		}
+	}
+
+	b.ReportAllocs()
+	b.ResetTimer()
+
+	for i := 0; i < b.N; i++ {
+		var report Xbox360ControllerReport
+
+		ParseReport(reports[i%len(reports)], &report)
+	}
+}
+
+// axisSink keeps the conversions of BenchmarkAxisValue from being optimized
+// away.
+var axisSink int16
+
+func BenchmarkAxisValue(b *testing.B) {
+	for i := 0; i < b.N; i++ {
+		// Every raw value, in both directions.
+		axisSink = axisValue(byte(i), i&0x100 != 0)
+	}
+}
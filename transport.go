@@ -0,0 +1,63 @@
+package stadiacontroller
+
+import (
+	"fmt"
+	"strings"
+)
+
+// Transport is the way a device is connected to the computer.
+type Transport int
+
+// Transports of devices.
+const (
+	TransportUnknown Transport = iota
+	TransportUSB
+	TransportBluetooth
+)
+
+var transportNames = [...]string{
+	TransportUnknown:   "unknown",
+	TransportUSB:       "usb",
+	TransportBluetooth: "bluetooth",
+}
+
+// String returns "usb", "bluetooth" or "unknown".
+func (t Transport) String() string {
+	if t < 0 || int(t) >= len(transportNames) {
+		return transportNames[TransportUnknown]
+	}
+
+	return transportNames[t]
+}
+
+// ParseTransport parses a transport as returned by String. "bt" is accepted as
+// an alias of "bluetooth", and "any" as an alias of "unknown".
+func ParseTransport(s string) (Transport, error) {
+	switch strings.ToLower(s) {
+	case "usb":
+		return TransportUSB, nil
+	case "bluetooth", "bt":
+		return TransportBluetooth, nil
+	case "unknown", "any", "":
+		return TransportUnknown, nil
+	default:
+		return TransportUnknown, fmt.Errorf("unknown transport %q", s)
+	}
+}
+
+// Transport returns the way the device is connected, as indicated by its path:
+// the paths of Bluetooth HID devices contain the UUID of the HID service
+// instead of the vendor and product IDs of USB devices.
+func (di *DeviceInfo) Transport() Transport {
+	path := strings.ToLower(di.Path)
+
+	switch {
+	case strings.Contains(path, "{00001812-0000-1000-8000-00805f9b34fb}"),
+		strings.Contains(path, "{00001124-0000-1000-8000-00805f9b34fb}"):
+		return TransportBluetooth
+	case strings.Contains(path, `hid#vid_`):
+		return TransportUSB
+	default:
+		return TransportUnknown
+	}
+}
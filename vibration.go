@@ -0,0 +1,29 @@
+package stadiacontroller
+
+// VibrationFrame is the payload of the output report which drives the
+// vibration motors of the controller.
+//
+// Bytes 0 and 1 hold the intensity of the large, low-frequency motor, and
+// bytes 2 and 3 the intensity of the small, high-frequency motor, each as a
+// little-endian 16-bit value. This is the layout used by SDL's Stadia driver;
+// the controller does not seem to treat the low byte of each intensity
+// differently from its high byte.
+type VibrationFrame [4]byte
+
+// StopVibration is the frame which stops both motors.
+var StopVibration = VibrationFrame{}
+
+// NewVibrationFrame returns the frame setting the large and small motors to
+// the given 8-bit intensities, as sent by Vibrate.
+func NewVibrationFrame(largeMotor, smallMotor byte) VibrationFrame {
+	return VibrationFrame{largeMotor, largeMotor, smallMotor, smallMotor}
+}
+
+// NewVibrationFrame16 returns the frame setting the large and small motors to
+// the given 16-bit intensities.
+func NewVibrationFrame16(largeMotor, smallMotor uint16) VibrationFrame {
+	return VibrationFrame{
+		byte(largeMotor), byte(largeMotor >> 8),
+		byte(smallMotor), byte(smallMotor >> 8),
+	}
+}
@@ -1,20 +1,6 @@
-package stadiacontroller
+// +build !novigem
 
-/*
-#include <stdint.h>
-
-typedef struct
-{
-	uint16_t wButtons;
-	uint8_t bLeftTrigger;
-	uint8_t bRightTrigger;
-	int16_t sThumbLX;
-	int16_t sThumbLY;
-	int16_t sThumbRX;
-	int16_t sThumbRY;
-} xusb_report;
-*/
-import "C"
+package stadiacontroller
 
 import (
 	"errors"
@@ -23,6 +9,7 @@ import (
 	"golang.org/x/sys/windows"
 )
 
+// Error codes returned by the ViGEm client library.
 const (
 	VIGEM_ERROR_NONE                        = 0x20000000
 	VIGEM_ERROR_BUS_NOT_FOUND               = 0xE0000001
@@ -57,12 +44,36 @@ var (
 	procTargetX360RegisterNotification   = client.NewProc("vigem_target_x360_register_notification")
 	procTargetX360UnregisterNotification = client.NewProc("vigem_target_x360_unregister_notification")
 	procTargetX360Update                 = client.NewProc("vigem_target_x360_update")
+	procTargetX360GetUserIndex           = client.NewProc("vigem_target_x360_get_user_index")
 )
 
+// ErrBusUnavailable indicates that the ViGEm bus cannot be used anymore, e.g.
+// because its driver was restarted. VigemErrors caused by the bus are
+// ErrBusUnavailable according to errors.Is.
+var ErrBusUnavailable = errors.New("ViGEm bus unavailable")
+
+// VigemError is an error code returned by the ViGEm client library.
 type VigemError struct {
 	code uint
 }
 
+// Is returns whether the error was caused by the bus when target is
+// ErrBusUnavailable.
+func (err *VigemError) Is(target error) bool {
+	if target != ErrBusUnavailable {
+		return false
+	}
+
+	switch err.code {
+	case VIGEM_ERROR_BUS_NOT_FOUND, VIGEM_ERROR_BUS_ACCESS_FAILED, VIGEM_ERROR_BUS_INVALID_HANDLE, VIGEM_ERROR_TARGET_NOT_PLUGGED_IN:
+		return true
+	default:
+		return false
+	}
+}
+
+// NewVigemError returns the error corresponding to the given code returned by
+// ViGEm, or nil if the code indicates a success.
 func NewVigemError(rawCode uintptr) *VigemError {
 	code := uint(rawCode)
 
@@ -108,16 +119,25 @@ func (err *VigemError) Error() string {
 	}
 }
 
+// Emulator is a client of the ViGEm bus, used to create emulated controllers.
 type Emulator struct {
 	handle      uintptr
 	onVibration func(vibration Vibration)
 }
 
+// Vibration is a vibration request sent by a game to an emulated controller.
 type Vibration struct {
 	LargeMotor byte
 	SmallMotor byte
+
+	// Player is the player number (1 to 4) assigned to an emulated Xbox 360
+	// controller, as shown by its LEDs, or 0 if unknown. It is sent along
+	// with vibrations, and may change without them.
+	Player int
 }
 
+// NewEmulator connects to the ViGEm bus. onVibration is called whenever a game
+// requests a vibration of a controller created by the emulator.
 func NewEmulator(onVibration func(vibration Vibration)) (*Emulator, error) {
 	handle, _, err := procAlloc.Call()
 
@@ -137,6 +157,7 @@ func NewEmulator(onVibration func(vibration Vibration)) (*Emulator, error) {
 	return &Emulator{handle, onVibration}, nil
 }
 
+// Close disconnects from the ViGEm bus.
 func (e *Emulator) Close() error {
 	procDisconnect.Call(e.handle)
 	_, _, err := procFree.Call(e.handle)
@@ -144,6 +165,35 @@ func (e *Emulator) Close() error {
 	return err
 }
 
+// EmulatedController is a controller emulated with ViGEm: an
+// Xbox360Controller or a DS4Controller.
+type EmulatedController interface {
+	// Connect plugs the controller into the ViGEm bus, making it visible to
+	// games.
+	Connect() error
+
+	// Disconnect unplugs the controller from the ViGEm bus.
+	Disconnect() error
+
+	// Close releases the controller.
+	Close() error
+
+	// Send updates the state of the controller seen by games.
+	Send(report *Xbox360ControllerReport) error
+}
+
+// BatteryReporter is an EmulatedController which shows the battery of the
+// controller to games, such as a DS4Controller.
+type BatteryReporter interface {
+	EmulatedController
+
+	// SetBattery shows the given status to games, or the battery shown by
+	// default if it is unknown.
+	SetBattery(status BatteryStatus) error
+}
+
+// CreateXbox360Controller creates an emulated Xbox 360 controller, which must be
+// connected using Connect before games can see it.
 func (e *Emulator) CreateXbox360Controller() (*Xbox360Controller, error) {
 	handle, _, err := procTargetX360Alloc.Call()
 
@@ -152,30 +202,41 @@ func (e *Emulator) CreateXbox360Controller() (*Xbox360Controller, error) {
 	}
 
 	notificationHandler := func(client, target uintptr, largeMotor, smallMotor, ledNumber byte) uintptr {
-		e.onVibration(Vibration{largeMotor, smallMotor})
+		vibration := Vibration{LargeMotor: largeMotor, SmallMotor: smallMotor}
+
+		// ledNumber is the 0-based user index of the controller.
+		if ledNumber < 4 {
+			vibration.Player = int(ledNumber) + 1
+		}
+
+		e.onVibration(vibration)
 
 		return 0
 	}
 	callback := windows.NewCallback(notificationHandler)
 
-	return &Xbox360Controller{e, handle, false, callback}, nil
+	return &Xbox360Controller{emulator: e, handle: handle, notificationHandler: callback}, nil
 }
 
 type x360NotificationHandler func(client, target uintptr, largeMotor, smallMotor, ledNumber byte) uintptr
 
+// Xbox360Controller is an Xbox 360 controller emulated with ViGEm.
 type Xbox360Controller struct {
 	emulator            *Emulator
 	handle              uintptr
 	connected           bool
 	notificationHandler uintptr
+	report              Xbox360ControllerReport
 }
 
+// Close releases the controller.
 func (c *Xbox360Controller) Close() error {
 	_, _, err := procTargetFree.Call(c.handle)
 
 	return err
 }
 
+// Connect plugs the controller into the ViGEm bus, making it visible to games.
 func (c *Xbox360Controller) Connect() error {
 	libErr, _, err := procTargetAdd.Call(c.emulator.handle, c.handle)
 
@@ -200,6 +261,7 @@ func (c *Xbox360Controller) Connect() error {
 	return nil
 }
 
+// Disconnect unplugs the controller from the ViGEm bus.
 func (c *Xbox360Controller) Disconnect() error {
 	libErr, _, err := procTargetX360UnregisterNotification.Call(c.handle)
 
@@ -224,8 +286,14 @@ func (c *Xbox360Controller) Disconnect() error {
 	return nil
 }
 
+// Send updates the state of the controller seen by games.
 func (c *Xbox360Controller) Send(report *Xbox360ControllerReport) error {
-	libErr, _, err := procTargetX360Update.Call(c.emulator.handle, c.handle, uintptr(unsafe.Pointer(&report.native)))
+	// Pointers given to a DLL call escape to the heap, so the report is copied
+	// into memory owned by the controller rather than passing the caller's
+	// report, which would otherwise be allocated for every report sent.
+	c.report = *report
+
+	libErr, _, err := procTargetX360Update.Call(c.emulator.handle, c.handle, uintptr(unsafe.Pointer(&c.report.native)))
 
 	if !errors.Is(err, windows.ERROR_SUCCESS) {
 		return err
@@ -237,83 +305,20 @@ func (c *Xbox360Controller) Send(report *Xbox360ControllerReport) error {
 	return nil
 }
 
-type Xbox360ControllerReport struct {
-	native    C.xusb_report
-	Capture   bool
-	Assistant bool
-}
+// UserIndex returns the XInput user index (0 to 3) assigned to the connected
+// controller. Windows assigns it shortly after Connect returns, until which
+// an error is returned.
+func (c *Xbox360Controller) UserIndex() (uint32, error) {
+	var index uint32
 
-// Bits that correspond to the Xbox 360 controller buttons.
-const (
-	Xbox360ControllerButtonUp            = 0
-	Xbox360ControllerButtonDown          = 1
-	Xbox360ControllerButtonLeft          = 2
-	Xbox360ControllerButtonRight         = 3
-	Xbox360ControllerButtonStart         = 4
-	Xbox360ControllerButtonBack          = 5
-	Xbox360ControllerButtonLeftThumb     = 6
-	Xbox360ControllerButtonRightThumb    = 7
-	Xbox360ControllerButtonLeftShoulder  = 8
-	Xbox360ControllerButtonRightShoulder = 9
-	Xbox360ControllerButtonGuide         = 10
-	Xbox360ControllerButtonA             = 12
-	Xbox360ControllerButtonB             = 13
-	Xbox360ControllerButtonX             = 14
-	Xbox360ControllerButtonY             = 15
-)
+	libErr, _, err := procTargetX360GetUserIndex.Call(c.emulator.handle, c.handle, uintptr(unsafe.Pointer(&index)))
 
-func NewXbox360ControllerReport() Xbox360ControllerReport {
-	return Xbox360ControllerReport{}
-}
-
-func (r *Xbox360ControllerReport) GetButtons() uint16 {
-	return uint16(r.native.wButtons)
-}
-
-func (r *Xbox360ControllerReport) SetButtons(buttons uint16) {
-	r.native.wButtons = C.uint16_t(buttons)
-}
-
-func (r *Xbox360ControllerReport) MaybeSetButton(shiftBy int, isSet bool) {
-	if isSet {
-		r.SetButton(shiftBy)
+	if !errors.Is(err, windows.ERROR_SUCCESS) {
+		return 0, err
+	}
+	if err := NewVigemError(libErr); err != nil {
+		return 0, err
 	}
-}
-
-func (r *Xbox360ControllerReport) SetButton(shiftBy int) {
-	r.native.wButtons |= 1 << shiftBy
-}
-
-func (r *Xbox360ControllerReport) GetLeftTrigger() byte {
-	return byte(r.native.bLeftTrigger)
-}
-
-func (r *Xbox360ControllerReport) SetLeftTrigger(value byte) {
-	r.native.bLeftTrigger = C.uint8_t(value)
-}
-
-func (r *Xbox360ControllerReport) GetRightTrigger() byte {
-	return byte(r.native.bRightTrigger)
-}
-
-func (r *Xbox360ControllerReport) SetRightTrigger(value byte) {
-	r.native.bRightTrigger = C.uint8_t(value)
-}
-
-func (r *Xbox360ControllerReport) GetLeftThumb() (x, y int16) {
-	return int16(r.native.sThumbLX), int16(r.native.sThumbLY)
-}
-
-func (r *Xbox360ControllerReport) SetLeftThumb(x, y int16) {
-	r.native.sThumbLX = C.int16_t(x)
-	r.native.sThumbLY = C.int16_t(y)
-}
-
-func (r *Xbox360ControllerReport) GetRightThumb() (x, y int16) {
-	return int16(r.native.sThumbRX), int16(r.native.sThumbRY)
-}
 
-func (r *Xbox360ControllerReport) SetRightThumb(x, y int16) {
-	r.native.sThumbRX = C.int16_t(x)
-	r.native.sThumbRY = C.int16_t(y)
+	return index, nil
 }